@@ -0,0 +1,73 @@
+/* Generated by cbindgen from src/lib.rs, do not edit. */
+
+#ifndef BULBA_H
+#define BULBA_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+typedef enum BulbaType {
+  BulbaType_String,
+  BulbaType_Number,
+  BulbaType_Bool,
+  BulbaType_Array,
+  BulbaType_Map,
+  BulbaType_Null,
+  BulbaType_Secret,
+  BulbaType_Regex,
+  BulbaType_Encrypted,
+} BulbaType;
+
+// A parsed document or any value inside one.
+typedef struct BulbaValue BulbaValue;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Parses a NUL-terminated UTF-8 document. Returns NULL on failure and, when
+// `error` is not NULL, stores a message there to free with
+// `bulba_string_free`.
+BulbaValue *bulba_parse(const char *input, char **error);
+
+// Reads and parses the file at `path`, see `bulba_parse`.
+BulbaValue *bulba_parse_file(const char *path, char **error);
+
+// The value at a dotted path such as `database.port`, or NULL when there
+// is none. An empty path gives `value` itself. The result lives as long as
+// the document it came from.
+const BulbaValue *bulba_get(const BulbaValue *value, const char *path);
+
+BulbaType bulba_type(const BulbaValue *value);
+
+// Stores the number in `out` and returns true, or returns false when the
+// value is not a number.
+bool bulba_as_number(const BulbaValue *value, double *out);
+
+// Stores the flag in `out` and returns true, or returns false when the
+// value is not a bool.
+bool bulba_as_bool(const BulbaValue *value, bool *out);
+
+// A copy of a string, date, secret or regex pattern, to free with `bulba_string_free`, or NULL
+// for any other type.
+char *bulba_as_string(const BulbaValue *value);
+
+// The number of elements of a list or keys of a section, 0 otherwise.
+size_t bulba_len(const BulbaValue *value);
+
+// The element of a list at `index`, or NULL when out of range or not a
+// list. Borrows from the document like `bulba_get`.
+const BulbaValue *bulba_index(const BulbaValue *value, size_t index);
+
+// Frees a document from `bulba_parse` or `bulba_parse_file`. NULL is
+// ignored.
+void bulba_free(BulbaValue *value);
+
+// Frees a string handed out by this library. NULL is ignored.
+void bulba_string_free(char *text);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BULBA_H */
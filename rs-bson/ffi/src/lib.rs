@@ -0,0 +1,241 @@
+//! C interface to rs-bson: parse a document once, then look values up by
+//! dotted path. Every pointer handed out is owned by the caller and freed
+//! with `bulba_free` (documents) or `bulba_string_free` (strings); values
+//! returned by `bulba_get` and `bulba_index` borrow from their document.
+
+use std::ffi::{CStr, CString, c_char};
+use std::fs;
+use std::ptr;
+
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+
+/// A parsed document or any value inside one.
+#[repr(transparent)]
+pub struct BulbaValue(BsonValue<'static>);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BulbaType {
+    String,
+    Number,
+    Bool,
+    Array,
+    Map,
+    Null,
+    Secret,
+    Regex,
+    Encrypted,
+}
+
+// Messages never hold a NUL, but a path or file name echoed back might
+fn c_string(text: &str) -> *mut c_char {
+    CString::new(text.replace('\0', ""))
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
+
+unsafe fn set_error(error: *mut *mut c_char, message: &str) {
+    if !error.is_null() {
+        unsafe { *error = c_string(message) };
+    }
+}
+
+unsafe fn read_str<'a>(text: *const c_char) -> Option<&'a str> {
+    if text.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(text) }.to_str().ok()
+}
+
+fn parse_str(input: &str) -> Result<BulbaValue, &'static str> {
+    let tokens = lexer::lex_str(input)?;
+    Ok(BulbaValue(parser::parse(&tokens)?.into_owned()))
+}
+
+fn boxed(result: Result<BulbaValue, String>, error: *mut *mut c_char) -> *mut BulbaValue {
+    match result {
+        Ok(value) => Box::into_raw(Box::new(value)),
+        Err(message) => {
+            unsafe { set_error(error, &message) };
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Parses a NUL-terminated UTF-8 document. Returns NULL on failure and, when
+/// `error` is not NULL, stores a message there to free with
+/// `bulba_string_free`.
+///
+/// # Safety
+/// `input` must be NULL or a valid C string, `error` NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_parse(
+    input: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut BulbaValue {
+    let result = match unsafe { read_str(input) } {
+        Some(input) => parse_str(input).map_err(String::from),
+        None => Err(String::from("It hurt itself in its confusion!")),
+    };
+    boxed(result, error)
+}
+
+/// Reads and parses the file at `path`, see `bulba_parse`.
+///
+/// # Safety
+/// `path` must be NULL or a valid C string, `error` NULL or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_parse_file(
+    path: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut BulbaValue {
+    let result = match unsafe { read_str(path) } {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|err| format!("{path}: {err}"))
+            .and_then(|text| parse_str(&text).map_err(|err| format!("{path}: {err}"))),
+        None => Err(String::from("It hurt itself in its confusion!")),
+    };
+    boxed(result, error)
+}
+
+/// The value at a dotted path such as `database.port`, or NULL when there
+/// is none. An empty path gives `value` itself. The result lives as long as
+/// the document it came from.
+///
+/// # Safety
+/// `value` must be NULL or a live value, `path` NULL or a valid C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_get(
+    value: *const BulbaValue,
+    path: *const c_char,
+) -> *const BulbaValue {
+    let (Some(value), Some(path)) = (unsafe { value.as_ref() }, unsafe { read_str(path) }) else {
+        return ptr::null();
+    };
+    match value.0.get(path) {
+        Some(found) => ptr::from_ref(found).cast(),
+        None => ptr::null(),
+    }
+}
+
+/// # Safety
+/// `value` must be a live value.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_type(value: *const BulbaValue) -> BulbaType {
+    match unsafe { &(*value).0 } {
+        BsonValue::BString(_) | BsonValue::Date(_) => BulbaType::String,
+        BsonValue::Bool(_) => BulbaType::Bool,
+        BsonValue::Array(_) => BulbaType::Array,
+        BsonValue::Map(_) => BulbaType::Map,
+        BsonValue::Null(()) => BulbaType::Null,
+        BsonValue::Secret(_) => BulbaType::Secret,
+        BsonValue::Regex(_) => BulbaType::Regex,
+        BsonValue::Encrypted(_) => BulbaType::Encrypted,
+        // Every kind of number
+        _ => BulbaType::Number,
+    }
+}
+
+/// Stores the number in `out` and returns true, or returns false when the
+/// value is not a number.
+///
+/// # Safety
+/// `value` must be NULL or a live value, `out` writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_as_number(value: *const BulbaValue, out: *mut f64) -> bool {
+    match unsafe { value.as_ref() }.and_then(|value| value.0.as_f64()) {
+        Some(n) => {
+            unsafe { *out = n };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Stores the flag in `out` and returns true, or returns false when the
+/// value is not a bool.
+///
+/// # Safety
+/// `value` must be NULL or a live value, `out` writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_as_bool(value: *const BulbaValue, out: *mut bool) -> bool {
+    match unsafe { value.as_ref() } {
+        Some(BulbaValue(BsonValue::Bool(b))) => {
+            unsafe { *out = *b };
+            true
+        }
+        _ => false,
+    }
+}
+
+/// A copy of a string, date, secret or regex pattern, to free with `bulba_string_free`, or NULL
+/// for any other type.
+///
+/// # Safety
+/// `value` must be NULL or a live value.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_as_string(value: *const BulbaValue) -> *mut c_char {
+    match unsafe { value.as_ref() } {
+        Some(BulbaValue(
+            BsonValue::BString(s)
+            | BsonValue::Secret(s)
+            | BsonValue::Regex(s)
+            | BsonValue::Date(s),
+        )) => c_string(s),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// The number of elements of a list or keys of a section, 0 otherwise.
+///
+/// # Safety
+/// `value` must be NULL or a live value.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_len(value: *const BulbaValue) -> usize {
+    match unsafe { value.as_ref() } {
+        Some(BulbaValue(BsonValue::Array(items))) => items.len(),
+        Some(BulbaValue(BsonValue::Map(m))) => m.len(),
+        _ => 0,
+    }
+}
+
+/// The element of a list at `index`, or NULL when out of range or not a
+/// list. Borrows from the document like `bulba_get`.
+///
+/// # Safety
+/// `value` must be NULL or a live value.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_index(value: *const BulbaValue, index: usize) -> *const BulbaValue {
+    match unsafe { value.as_ref() } {
+        Some(BulbaValue(BsonValue::Array(items))) => match items.get(index) {
+            Some(item) => ptr::from_ref(item).cast(),
+            None => ptr::null(),
+        },
+        _ => ptr::null(),
+    }
+}
+
+/// Frees a document from `bulba_parse` or `bulba_parse_file`. NULL is
+/// ignored.
+///
+/// # Safety
+/// `value` must be NULL or a document not freed before, never a value from
+/// `bulba_get` or `bulba_index`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_free(value: *mut BulbaValue) {
+    if !value.is_null() {
+        drop(unsafe { Box::from_raw(value) });
+    }
+}
+
+/// Frees a string handed out by this library. NULL is ignored.
+///
+/// # Safety
+/// `text` must be NULL or a string from this library not freed before.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bulba_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(unsafe { CString::from_raw(text) });
+    }
+}
@@ -0,0 +1,62 @@
+use std::ffi::{CStr, CString, c_char};
+use std::ptr;
+
+use bulba::*;
+
+#[cfg(test)]
+pub mod ffi_tests {
+    use crate::*;
+
+    fn c(text: &str) -> CString {
+        CString::new(text).unwrap()
+    }
+
+    fn take(text: *mut c_char) -> String {
+        let owned = unsafe { CStr::from_ptr(text) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { bulba_string_free(text) };
+        owned
+    }
+
+    #[test]
+    fn read_values_by_path() {
+        let path = c("../tests/test_data/valid.bson");
+        let mut error = ptr::null_mut();
+        let doc = unsafe { bulba_parse_file(path.as_ptr(), &mut error) };
+        assert!(!doc.is_null());
+        assert!(error.is_null());
+
+        let host = unsafe { bulba_get(doc, c("database.host").as_ptr()) };
+        assert_eq!(unsafe { bulba_type(host) }, BulbaType::String);
+        assert_eq!(take(unsafe { bulba_as_string(host) }), "127.0.0.1");
+
+        let mut port = 0.0;
+        let found = unsafe { bulba_get(doc, c("database.pool.max_connections").as_ptr()) };
+        assert!(unsafe { bulba_as_number(found, &mut port) });
+        assert_eq!(port, 100.0);
+        assert!(unsafe { bulba_get(doc, c("database.missing").as_ptr()) }.is_null());
+
+        unsafe { bulba_free(doc) };
+    }
+
+    #[test]
+    fn report_errors() {
+        let mut error = ptr::null_mut();
+        let doc = unsafe { bulba_parse(c("IVYSAUR!\n").as_ptr(), &mut error) };
+        assert!(doc.is_null());
+        assert_eq!(take(error), "Status: Fainted");
+
+        let input = c("BULBA!\nfriends ~> <| \"Mom\", \"Oak\" |>\n");
+        let doc = unsafe { bulba_parse(input.as_ptr(), ptr::null_mut()) };
+        let friends = unsafe { bulba_get(doc, c("friends").as_ptr()) };
+        assert_eq!(unsafe { bulba_len(friends) }, 2);
+        assert_eq!(
+            take(unsafe { bulba_as_string(bulba_index(friends, 1)) }),
+            "Oak"
+        );
+        assert!(unsafe { bulba_index(friends, 2) }.is_null());
+        unsafe { bulba_free(doc) };
+    }
+}
@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rs_bson::emit;
+use rs_bson::parser::BsonValue;
+use rs_bson::{lexer, parser};
+
+// Every document BSON can spell is emitted and read back unchanged, and
+// emitting what was read back gives the same text
+fuzz_target!(|value: BsonValue| {
+    let text = emit(&value).expect("arbitrary documents can be emitted");
+    let tokens = lexer::lex_str(&text).expect("emitted documents lex");
+    let parsed = parser::parse(&tokens).expect("emitted documents parse");
+    assert_eq!(parsed, value, "{text}");
+    assert_eq!(emit(&parsed), Ok(text));
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rs_bson::document::Document;
+use rs_bson::{lexer, parser};
+
+// Arbitrary text must come back as an error, never as a panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(tokens) = lexer::lex_str(input) {
+        let _ = parser::parse(&tokens);
+        let _ = parser::parse_all(&tokens);
+    }
+    if let Ok(doc) = Document::parse(input) {
+        assert_eq!(doc.to_string(), input);
+        let _ = doc.comments();
+    }
+});
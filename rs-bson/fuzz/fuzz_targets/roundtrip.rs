@@ -0,0 +1,103 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rs_bson::format::format;
+use rs_bson::parser::BsonValue;
+use rs_bson::{lexer, parser};
+
+// Lines that are close to valid BSON, so the fuzzer spends its time past
+// the header check and deep in the parser
+#[derive(Arbitrary, Debug)]
+enum Line {
+    Blank,
+    Comment(String),
+    Section {
+        indent: u8,
+        stage: u8,
+        key: String,
+    },
+    Pair {
+        indent: u8,
+        key: String,
+        arrow: u8,
+        value: Value,
+        comment: Option<String>,
+    },
+    Raw(String),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Value {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    Null,
+    Secret(String),
+    Array(Vec<Value>),
+    Raw(String),
+}
+
+fn render_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("\"{s}\""),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(true) => String::from("SuperEffective"),
+        Value::Bool(false) => String::from("NotVeryEffective"),
+        Value::Null => String::from("MissingNo"),
+        Value::Secret(s) => format!("secret(\"{s}\")"),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(render_value).collect();
+            format!("<| {} |>", items.join(", "))
+        }
+        Value::Raw(s) => s.clone(),
+    }
+}
+
+fn render(lines: &[Line]) -> String {
+    let mut text = String::from("BULBA!\n");
+    for line in lines {
+        match line {
+            Line::Blank => {}
+            Line::Comment(s) => text += &format!("zZz {s}"),
+            Line::Section { indent, stage, key } => {
+                let stage = ["(o)", "(O)", "(@)"][*stage as usize % 3];
+                let indent = " ".repeat(*indent as usize % 16);
+                text += &format!("{indent}{stage} {key} {stage}");
+            }
+            Line::Pair {
+                indent,
+                key,
+                arrow,
+                value,
+                comment,
+            } => {
+                let indent = " ".repeat(*indent as usize % 16);
+                let arrow = "~".repeat(*arrow as usize % 8 + 1);
+                text += &format!("{indent}{key} {arrow}> {}", render_value(value));
+                if let Some(comment) = comment {
+                    text += &format!(" zZz {comment}");
+                }
+            }
+            Line::Raw(s) => text += s,
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn parse(input: &str) -> Option<BsonValue<'static>> {
+    let tokens = lexer::lex_str(input).ok()?;
+    parser::parse(&tokens).ok().map(BsonValue::into_owned)
+}
+
+// Formatting a valid document keeps its value and is idempotent
+fuzz_target!(|lines: Vec<Line>| {
+    let input = render(&lines);
+    let Some(value) = parse(&input) else {
+        return;
+    };
+    let formatted = format(&input).expect("valid documents format");
+    assert_eq!(parse(&formatted), Some(value));
+    assert_eq!(format(&formatted), Ok(formatted));
+});
@@ -0,0 +1,91 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::lexer::STAGES;
+use crate::parser::BsonValue;
+
+// Quotes, arrows, leaves and keywords are all fine inside a string
+const TEXT: &[&str] = &[
+    "a",
+    " ",
+    "\"",
+    "'",
+    "~>",
+    "<|",
+    "|>",
+    "(o)",
+    "é",
+    "MissingNo",
+    ".",
+];
+const DATES: &[&str] = &[
+    "2024-02-29",
+    "1999-12-31T23:59:59Z",
+    "2024-01-01T12:30:00+02:00",
+];
+// None of them spells a reserved key
+const KEY_START: &[&str] = &["a", "Z", "_", "pool"];
+const KEY_REST: &[&str] = &["a", "B", "0", "9", "_", "ash"];
+
+fn text(u: &mut Unstructured, alphabet: &[&str], max: usize) -> Result<String> {
+    let len = u.int_in_range(0..=max)?;
+    let mut s = String::new();
+    for _ in 0..len {
+        s += u.choose(alphabet)?;
+    }
+    Ok(s)
+}
+
+fn key(u: &mut Unstructured) -> Result<String> {
+    Ok(u.choose(KEY_START)?.to_string() + &text(u, KEY_REST, 6)?)
+}
+
+fn scalar<'a>(u: &mut Unstructured, in_array: bool) -> Result<BsonValue<'a>> {
+    // Inside a Razor Leaf list a comma would split the element in two
+    let alphabet = match in_array {
+        true => TEXT.to_vec(),
+        false => [TEXT, &[","]].concat(),
+    };
+    Ok(match u.int_in_range(0..=11)? {
+        0 => BsonValue::BString(Cow::Owned(text(u, &alphabet, 12)?)),
+        1 => BsonValue::Number(f64::from(u.arbitrary::<i32>()?) / 1024.0),
+        2 => BsonValue::Number(f64::from(u.int_in_range(-1000..=1000)?)),
+        3 => BsonValue::Bool(u.arbitrary()?),
+        4 => BsonValue::Null(()),
+        5 => BsonValue::Secret(Cow::Owned(format!("env:{}", key(u)?))),
+        6 => BsonValue::Regex(Cow::Owned(format!("^{}[0-9]+$", key(u)?))),
+        7 => BsonValue::Encrypted(Cow::Owned(format!("{}+/==", key(u)?))),
+        8 => BsonValue::Integer(i128::from(u.arbitrary::<i64>()?)),
+        9 => BsonValue::Float(f64::from(u.arbitrary::<i32>()?) / 1024.0),
+        10 => BsonValue::Date(Cow::Borrowed(u.choose(DATES)?)),
+        _ if in_array => BsonValue::Array(vec![]),
+        _ => {
+            let len = u.int_in_range(0..=3)?;
+            BsonValue::Array((0..len).map(|_| scalar(u, true)).collect::<Result<_>>()?)
+        }
+    })
+}
+
+fn map<'a>(u: &mut Unstructured, level: usize) -> Result<BsonValue<'a>> {
+    let mut m = BTreeMap::new();
+    for _ in 0..u.int_in_range(0..=5)? {
+        let value = if level < STAGES.len() && u.ratio(1, 4)? {
+            map(u, level + 1)?
+        } else {
+            scalar(u, false)?
+        };
+        m.insert(Cow::Owned(key(u)?), value);
+    }
+    Ok(BsonValue::Map(m))
+}
+
+/// Documents BSON can spell: keys, strings and nesting stay within what
+/// `emit` writes and the lexer reads back, so `parse(emit(value))` gives
+/// every one of them back unchanged.
+impl<'a> Arbitrary<'a> for BsonValue<'a> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<BsonValue<'a>> {
+        map(u, 0)
+    }
+}
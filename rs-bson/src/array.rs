@@ -0,0 +1,65 @@
+use std::cmp::Ordering;
+
+use crate::parser::BsonValue;
+
+// Editing Razor Leaf lists in place. Every method fails with Target is
+// immune! on a value that is not a list and leaves it untouched.
+impl<'a> BsonValue<'a> {
+    fn items_mut(&mut self) -> Result<&mut Vec<BsonValue<'a>>, &'static str> {
+        match self {
+            BsonValue::Array(items) => Ok(items),
+            _ => Err("Target is immune!"),
+        }
+    }
+
+    pub fn push(&mut self, value: impl Into<BsonValue<'a>>) -> Result<(), &'static str> {
+        self.items_mut()?.push(value.into());
+        Ok(())
+    }
+
+    pub fn extend<V: Into<BsonValue<'a>>>(
+        &mut self,
+        values: impl IntoIterator<Item = V>,
+    ) -> Result<(), &'static str> {
+        self.items_mut()?.extend(values.into_iter().map(Into::into));
+        Ok(())
+    }
+
+    /// Inserts before the element at `index`; The attack missed! when the
+    /// list is shorter than `index`.
+    pub fn insert(
+        &mut self,
+        index: usize,
+        value: impl Into<BsonValue<'a>>,
+    ) -> Result<(), &'static str> {
+        let items = self.items_mut()?;
+        if index > items.len() {
+            return Err("The attack missed!");
+        }
+        items.insert(index, value.into());
+        Ok(())
+    }
+
+    /// Takes out the element at `index`; The attack missed! past the end.
+    pub fn remove(&mut self, index: usize) -> Result<BsonValue<'a>, &'static str> {
+        let items = self.items_mut()?;
+        if index >= items.len() {
+            return Err("The attack missed!");
+        }
+        Ok(items.remove(index))
+    }
+
+    pub fn retain(&mut self, keep: impl FnMut(&BsonValue<'a>) -> bool) -> Result<(), &'static str> {
+        self.items_mut()?.retain(keep);
+        Ok(())
+    }
+
+    /// Sorts the elements with `compare`, keeping equal ones in order.
+    pub fn sort_by(
+        &mut self,
+        compare: impl FnMut(&BsonValue<'a>, &BsonValue<'a>) -> Ordering,
+    ) -> Result<(), &'static str> {
+        self.items_mut()?.sort_by(compare);
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use sha2::{Digest, Sha256};
+
+use crate::lexer::{COMMENT, FOOTER_MARKER, strip_footer};
+
+/// Lowercase hex, as footers spell digests.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// The digest a footer records for `text`: SHA-256 of its lines, each
+/// ended by a line feed whatever the file used, so a checkout that turns
+/// LF into CRLF does not break it.
+pub fn digest(text: &str) -> String {
+    let mut sha = Sha256::new();
+    for line in text.lines() {
+        sha.update(line.as_bytes());
+        sha.update(b"\n");
+    }
+    hex(&sha.finalize())
+}
+
+/// The footer line for `text`, without a line break.
+pub fn footer(text: &str) -> String {
+    format!("{COMMENT} {FOOTER_MARKER} {}", digest(text))
+}
+
+/// `text` with a footer line recording its digest, in place of the one it
+/// had.
+pub fn seal(text: &str) -> String {
+    let mut body = strip_footer(text).to_string();
+    if !body.ends_with('\n') {
+        body.push('\n');
+    }
+    let footer = footer(&body);
+    body + &footer + "\n"
+}
@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+
+use crate::json::JsonValue;
+use crate::parser::{BsonValue, join_path};
+use crate::schema::{Schema, Type};
+
+/// What an editor needs to suggest one key: where it goes, what it holds
+/// and what the schema says about it. Elements of a Razor Leaf list are
+/// addressed with a `*` segment, e.g. `servers.*.host`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub path: String,
+    /// Type as spelled in a `.bulbaschema`, e.g. `int` or `array<string>`
+    pub ty: String,
+    pub required: bool,
+    pub doc: Option<String>,
+    pub allowed: Vec<BsonValue<'static>>,
+    pub default: Option<BsonValue<'static>>,
+    /// Replacement hint, empty when there is none
+    pub deprecated: Option<String>,
+}
+
+fn type_spec(schema: &Schema) -> String {
+    match (&schema.ty, &schema.items) {
+        (Type::Array, Some(items)) => format!("array<{}>", type_spec(items)),
+        (ty, _) => ty.to_string(),
+    }
+}
+
+fn collect(schema: &Schema, path: &str, completions: &mut Vec<Completion>) {
+    for (key, field) in &schema.fields {
+        let path = join_path(path, key);
+        completions.push(Completion {
+            path: path.clone(),
+            ty: type_spec(field),
+            required: schema.required.contains(key),
+            doc: field.doc.clone(),
+            allowed: field.one_of.clone(),
+            default: field.default.clone(),
+            deprecated: field.deprecated.clone(),
+        });
+        collect(field, &path, completions);
+    }
+    if let Some(items) = &schema.items {
+        collect(items, &join_path(path, "*"), completions);
+    }
+}
+
+/// Every key the schema declares, parents before their children.
+pub fn completions(schema: &Schema) -> Vec<Completion> {
+    let mut completions = vec![];
+    collect(schema, "", &mut completions);
+    completions
+}
+
+impl From<&Completion> for JsonValue {
+    fn from(completion: &Completion) -> JsonValue {
+        let mut members = BTreeMap::new();
+        members.insert(
+            "path".to_string(),
+            JsonValue::String(completion.path.clone()),
+        );
+        members.insert("type".to_string(), JsonValue::String(completion.ty.clone()));
+        members.insert("required".to_string(), JsonValue::Bool(completion.required));
+        if let Some(doc) = &completion.doc {
+            members.insert("doc".to_string(), JsonValue::String(doc.clone()));
+        }
+        if !completion.allowed.is_empty() {
+            let allowed = completion.allowed.iter().map(JsonValue::from).collect();
+            members.insert("allowed".to_string(), JsonValue::Array(allowed));
+        }
+        if let Some(default) = &completion.default {
+            members.insert("default".to_string(), JsonValue::from(default));
+        }
+        if let Some(replacement) = &completion.deprecated {
+            members.insert(
+                "deprecated".to_string(),
+                JsonValue::String(replacement.clone()),
+            );
+        }
+        JsonValue::Object(members)
+    }
+}
+
+/// The completion manifest of a schema as JSON: `{"keys": [...]}` with one
+/// object per key holding `path`, `type` and `required`, plus `doc`,
+/// `allowed`, `default` and `deprecated` when the schema sets them.
+pub fn manifest(schema: &Schema) -> JsonValue {
+    let keys = completions(schema).iter().map(JsonValue::from).collect();
+    JsonValue::Object(BTreeMap::from([(
+        "keys".to_string(),
+        JsonValue::Array(keys),
+    )]))
+}
@@ -0,0 +1,109 @@
+use std::borrow::Cow;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+use std::io::Read;
+
+use crate::parser::ParseOptions;
+
+/// What a file that unpacks to more than the limit of `ParseOptions` fails
+/// with
+pub const TOO_LARGE: &str = "Your bag is full!";
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+const CORRUPT: &str = "It hurt itself in its confusion!";
+
+/// How the bytes of a file are packed, told apart by their magic numbers
+/// rather than the file name, so `config.bson.gz` and a gzip stream saved
+/// as `config.bson` both load.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+pub fn detect(data: &[u8]) -> Compression {
+    match data {
+        [0x1f, 0x8b, ..] => Compression::Gzip,
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Compression::Zstd,
+        _ => Compression::None,
+    }
+}
+
+/// The contents of a `.bson.gz` or `.bson.zst` file, or `data` itself when
+/// it is not compressed. Each format needs its cargo feature, `gzip` and
+/// `zstd`, both on by default. Files that unpack to more than
+/// `parser::DEFAULT_MAX_DECOMPRESSED` bytes fail with `TOO_LARGE`.
+pub fn decompress(data: &[u8]) -> Result<Cow<'_, [u8]>, &'static str> {
+    decompress_with(data, &ParseOptions::default())
+}
+
+/// `decompress` with the limit set by `ParseOptions::max_decompressed`.
+pub fn decompress_with<'d>(
+    data: &'d [u8],
+    options: &ParseOptions,
+) -> Result<Cow<'d, [u8]>, &'static str> {
+    #[cfg_attr(not(any(feature = "gzip", feature = "zstd")), allow(unused_variables))]
+    let limit = options.decompressed_limit();
+    match detect(data) {
+        Compression::None => Ok(Cow::Borrowed(data)),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => gunzip(data, limit).map(Cow::Owned),
+        #[cfg(not(feature = "gzip"))]
+        Compression::Gzip => Err("gzip input needs the gzip feature"),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => unzstd(data, limit).map(Cow::Owned),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => Err("zstd input needs the zstd feature"),
+    }
+}
+
+// Appends what `reader` unpacks to `out`, reading one byte past the room
+// left so that anything over `limit` shows
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn read_limited(reader: impl Read, limit: usize, out: &mut Vec<u8>) -> Result<(), &'static str> {
+    let room = limit.saturating_sub(out.len()) as u64;
+    reader
+        .take(room + 1)
+        .read_to_end(out)
+        .map_err(|_| CORRUPT)?;
+    match out.len() > limit {
+        true => Err(TOO_LARGE),
+        false => Ok(()),
+    }
+}
+
+/// Every member of a gzip file, each checked against its CRC, in at most
+/// `limit` bytes.
+#[cfg(feature = "gzip")]
+fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>, &'static str> {
+    let mut out = vec![];
+    read_limited(flate2::read::MultiGzDecoder::new(data), limit, &mut out)?;
+    Ok(out)
+}
+
+/// Every frame of a Zstandard file, skipping skippable frames, in at most
+/// `limit` bytes.
+#[cfg(feature = "zstd")]
+fn unzstd(mut data: &[u8], limit: usize) -> Result<Vec<u8>, &'static str> {
+    use std::io;
+
+    use ruzstd::decoding::StreamingDecoder;
+    use ruzstd::decoding::errors::{FrameDecoderError, ReadFrameHeaderError};
+
+    let mut out = vec![];
+    // The decoder reads one frame and leaves the rest of `data` unread
+    while !data.is_empty() {
+        match StreamingDecoder::new(&mut data) {
+            Ok(frame) => read_limited(frame, limit, &mut out)?,
+            Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::SkipFrame {
+                length,
+                ..
+            })) => {
+                io::copy(&mut (&mut data).take(u64::from(length)), &mut io::sink())
+                    .map_err(|_| CORRUPT)?;
+            }
+            Err(_) => return Err(CORRUPT),
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,93 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use ::config::{FileStoredFormat, Format, Map, Value, ValueKind};
+#[cfg(feature = "decimal")]
+use rust_decimal::prelude::ToPrimitive;
+
+use crate::parser::{self, BsonValue, ParseOptions};
+
+/// BSON as a format of the `config` crate, so `.bson` and `.bulba` files
+/// join the sources of a `config::Config::builder()`:
+///
+/// ```no_run
+/// use config::{Config, File};
+/// use rs_bson::config::Bson;
+///
+/// let settings = Config::builder()
+///     .add_source(File::new("App.bulba", Bson))
+///     .build()?;
+/// # Ok::<(), config::ConfigError>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bson;
+
+// Whole numbers become integers, which `config` converts more readily
+fn kind(value: &BsonValue, uri: Option<&String>) -> Result<ValueKind, String> {
+    Ok(match value {
+        BsonValue::BString(s) | BsonValue::Regex(s) | BsonValue::Date(s) => {
+            ValueKind::String(s.to_string())
+        }
+        BsonValue::Number(n)
+            if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 =>
+        {
+            ValueKind::I64(*n as i64)
+        }
+        BsonValue::Number(n) | BsonValue::Float(n) => ValueKind::Float(*n),
+        BsonValue::Integer(n) => match (i64::try_from(*n), u64::try_from(*n)) {
+            (Ok(n), _) => ValueKind::I64(n),
+            (_, Ok(n)) => ValueKind::U64(n),
+            _ => ValueKind::I128(*n),
+        },
+        // Fractions stay exact as a string
+        #[cfg(feature = "decimal")]
+        BsonValue::Decimal(d) if d.fract().is_zero() => match d.to_i64() {
+            Some(n) => ValueKind::I64(n),
+            None => ValueKind::String(d.to_string()),
+        },
+        #[cfg(feature = "decimal")]
+        BsonValue::Decimal(d) => ValueKind::String(d.to_string()),
+        BsonValue::Bool(b) => ValueKind::Boolean(*b),
+        BsonValue::Null(()) => ValueKind::Nil,
+        BsonValue::Array(items) => ValueKind::Array(
+            items
+                .iter()
+                .map(|item| Ok(Value::new(uri, kind(item, uri)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        BsonValue::Map(m) => ValueKind::Table(table(m, uri)?),
+        BsonValue::Secret(reference) => {
+            return Err(format!("Unresolved secret reference {reference}"));
+        }
+        BsonValue::Encrypted(_) => return Err(String::from("Encrypted values need a key")),
+    })
+}
+
+fn table(
+    m: &BTreeMap<Cow<'_, str>, BsonValue<'_>>,
+    uri: Option<&String>,
+) -> Result<Map<String, Value>, String> {
+    m.iter()
+        .map(|(key, value)| Ok((key.to_string(), Value::new(uri, kind(value, uri)?))))
+        .collect()
+}
+
+impl Format for Bson {
+    fn parse(
+        &self,
+        uri: Option<&String>,
+        text: &str,
+    ) -> Result<Map<String, Value>, Box<dyn Error + Send + Sync>> {
+        match parser::parse_str(text, &ParseOptions::default())?.into_map() {
+            Ok(m) => Ok(table(&m, uri)?),
+            Err(_) => Ok(Map::new()),
+        }
+    }
+}
+
+impl FileStoredFormat for Bson {
+    fn file_extensions(&self) -> &'static [&'static str] {
+        &["bson", "bulba"]
+    }
+}
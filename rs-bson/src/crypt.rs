@@ -0,0 +1,277 @@
+use std::cmp::Reverse;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+#[cfg(feature = "checksum")]
+use crate::checksum;
+use crate::emit;
+use crate::lexer::{self, ENCRYPTED_CLOSE};
+use crate::parser::{self, BsonValue, ParseError, ParseOptions};
+use crate::spanned;
+use crate::visit::{VisitMut, walk_value_mut};
+
+/// Turns values into the ciphertext stored in `ENC[...]` and back, e.g.
+/// with a key kept in a KMS. Ciphertext must fit on one line and hold no
+/// comma or `]`.
+pub trait KeyProvider {
+    fn encrypt(&self, plaintext: &str) -> Result<String, String>;
+
+    fn decrypt(&self, ciphertext: &str) -> Result<String, String>;
+}
+
+/// Pipes values through shell commands, such as `age -r <recipient>` and
+/// `age -d -i key.txt`. What the encrypting command prints is stored in
+/// base64, so it may be binary.
+#[derive(Debug, Clone)]
+pub struct CommandKeys {
+    encrypt: Option<String>,
+    decrypt: Option<String>,
+}
+
+impl CommandKeys {
+    pub fn new(encrypt: &str, decrypt: &str) -> CommandKeys {
+        CommandKeys {
+            encrypt: Some(encrypt.to_string()),
+            decrypt: Some(decrypt.to_string()),
+        }
+    }
+
+    /// The commands in `BULBA_ENCRYPT_COMMAND` and `BULBA_DECRYPT_COMMAND`.
+    /// Only the one for the direction in use has to be set.
+    pub fn from_env() -> CommandKeys {
+        CommandKeys {
+            encrypt: env::var("BULBA_ENCRYPT_COMMAND").ok(),
+            decrypt: env::var("BULBA_DECRYPT_COMMAND").ok(),
+        }
+    }
+}
+
+fn run(command: Option<&String>, name: &str, input: &[u8]) -> Result<Vec<u8>, String> {
+    let command = command.ok_or_else(|| format!("No {name} command set"))?;
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("{command}: {err}"))?;
+    // Fed from a thread of its own, so a command that prints before it
+    // has read all of its input cannot stall on a full pipe
+    let mut stdin = child.stdin.take().unwrap();
+    let (written, output) = thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(input));
+        let output = child.wait_with_output();
+        (writer.join().unwrap(), output)
+    });
+    let output = output.map_err(|err| format!("{command}: {err}"))?;
+    if !output.status.success() {
+        return Err(format!("{command}: {}", output.status));
+    }
+    written.map_err(|err| format!("{command}: {err}"))?;
+    Ok(output.stdout)
+}
+
+impl KeyProvider for CommandKeys {
+    fn encrypt(&self, plaintext: &str) -> Result<String, String> {
+        run(self.encrypt.as_ref(), "encrypt", plaintext.as_bytes()).map(|out| BASE64.encode(out))
+    }
+
+    fn decrypt(&self, ciphertext: &str) -> Result<String, String> {
+        let bytes = BASE64
+            .decode(ciphertext)
+            .map_err(|_| "Ciphertext is not base64")?;
+        let out = run(self.decrypt.as_ref(), "decrypt", &bytes)?;
+        String::from_utf8(out).map_err(|err| err.to_string())
+    }
+}
+
+/// A value that could not be encrypted or decrypted, or a document that
+/// could not be read. `path` is empty for the latter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CryptError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for CryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_str() {
+            "" => f.write_str(&self.message),
+            path => write!(f, "{path}: {}", self.message),
+        }
+    }
+}
+
+impl Error for CryptError {}
+
+// Offers every scalar to `replace` and swaps in what it returns, until the
+// first failure. Remembers the paths it changed.
+struct Rewrite<F> {
+    replace: F,
+    changed: Vec<(String, BsonValue<'static>)>,
+    error: Option<CryptError>,
+}
+
+impl<F> VisitMut for Rewrite<F>
+where
+    F: FnMut(&str, &BsonValue) -> Result<Option<BsonValue<'static>>, String>,
+{
+    fn visit_value_mut(&mut self, path: &str, value: &mut BsonValue) {
+        if self.error.is_some() {
+            return;
+        }
+        if matches!(value, BsonValue::Map(_) | BsonValue::Array(_)) {
+            return walk_value_mut(self, path, value);
+        }
+        match (self.replace)(path, value) {
+            Ok(Some(new)) => {
+                *value = new.clone();
+                self.changed.push((path.to_string(), new));
+            }
+            Ok(None) => {}
+            Err(message) => {
+                self.error = Some(CryptError {
+                    path: path.to_string(),
+                    message,
+                })
+            }
+        }
+    }
+}
+
+fn rewrite(
+    value: &mut BsonValue,
+    replace: impl FnMut(&str, &BsonValue) -> Result<Option<BsonValue<'static>>, String>,
+) -> Result<Vec<(String, BsonValue<'static>)>, CryptError> {
+    let mut rewrite = Rewrite {
+        replace,
+        changed: vec![],
+        error: None,
+    };
+    value.visit_mut(&mut rewrite);
+    rewrite.error.map_or(Ok(rewrite.changed), Err)
+}
+
+// Rewrites the values of a document in its text, so comments and layout
+// stay as they were
+fn rewrite_text(
+    input: &str,
+    replace: impl FnMut(&str, &BsonValue) -> Result<Option<BsonValue<'static>>, String>,
+) -> Result<String, CryptError> {
+    let unreadable = |message: &str| CryptError {
+        path: String::new(),
+        message: message.to_string(),
+    };
+    let unparsed = |err: ParseError| unreadable(&err.to_string());
+    let tokens = lexer::lex_str(input).map_err(unparsed)?;
+    let tree = spanned::parse_spanned(&tokens, &ParseOptions::new()).map_err(unparsed)?;
+    let mut expected = tree.to_value();
+    let changed = rewrite(&mut expected, replace)?;
+
+    let mut lines: Vec<String> = input.split('\n').map(String::from).collect();
+    let mut edits = vec![];
+    for (path, value) in changed {
+        let span = &tree.get(&path).unwrap().span;
+        let text = emit::fragment(&value).map_err(|err| CryptError {
+            path: path.clone(),
+            message: err.to_string(),
+        })?;
+        edits.push((span.line, span.columns.start, span.columns.end, text));
+    }
+    // Right to left, so earlier columns of a line stay put
+    edits.sort_by_key(|edit| Reverse((edit.0, edit.1)));
+    for (line, start, end, text) in edits {
+        let line = &mut lines[line - 1];
+        if line.get(start..end).is_none() {
+            return Err(unreadable("It hurt itself in its confusion!"));
+        }
+        line.replace_range(start..end, &text);
+    }
+    let output = lines.join("\n");
+    #[cfg(feature = "checksum")]
+    let output = match lexer::is_sealed(input) {
+        true => checksum::seal(&output),
+        false => output,
+    };
+
+    // Values continued over several lines have no single place to edit
+    let reread =
+        lexer::lex_str(&output).and_then(|tokens| parser::parse(&tokens).map(|v| v.into_owned()));
+    if reread != Ok(expected) {
+        return Err(unreadable("It hurt itself in its confusion!"));
+    }
+    Ok(output)
+}
+
+fn encrypt_one(
+    keys: &dyn KeyProvider,
+    select: &impl Fn(&str) -> bool,
+    path: &str,
+    value: &BsonValue,
+) -> Result<Option<BsonValue<'static>>, String> {
+    let BsonValue::BString(plaintext) = value else {
+        return Ok(None);
+    };
+    if !select(path) {
+        return Ok(None);
+    }
+    let ciphertext = keys.encrypt(plaintext)?;
+    if ciphertext.contains([',', '\n', '\r']) || ciphertext.contains(ENCRYPTED_CLOSE) {
+        return Err(String::from("Ciphertext cannot be written as ENC[...]"));
+    }
+    Ok(Some(BsonValue::Encrypted(ciphertext.into())))
+}
+
+fn decrypt_one(
+    keys: &dyn KeyProvider,
+    value: &BsonValue,
+) -> Result<Option<BsonValue<'static>>, String> {
+    match value {
+        BsonValue::Encrypted(ciphertext) => {
+            Ok(Some(BsonValue::BString(keys.decrypt(ciphertext)?.into())))
+        }
+        _ => Ok(None),
+    }
+}
+
+impl BsonValue<'_> {
+    /// Replaces every `ENC[...]` value with the string `keys` decrypts it
+    /// to. Stops at the first value that fails.
+    pub fn decrypt(&mut self, keys: &dyn KeyProvider) -> Result<(), CryptError> {
+        rewrite(self, |_, value| decrypt_one(keys, value)).map(|_| ())
+    }
+
+    /// Encrypts every string whose path `select` picks and returns how many
+    /// there were. `lint::sensitive_key` picks the ones that look like
+    /// credentials.
+    pub fn encrypt(
+        &mut self,
+        keys: &dyn KeyProvider,
+        select: impl Fn(&str) -> bool,
+    ) -> Result<usize, CryptError> {
+        let changed = rewrite(self, |path, value| encrypt_one(keys, &select, path, value))?;
+        Ok(changed.len())
+    }
+}
+
+/// `BsonValue::encrypt` on the text of a document. Only the encrypted
+/// values change; comments and layout are kept.
+pub fn encrypt_text(
+    input: &str,
+    keys: &dyn KeyProvider,
+    select: impl Fn(&str) -> bool,
+) -> Result<String, CryptError> {
+    rewrite_text(input, |path, value| encrypt_one(keys, &select, path, value))
+}
+
+/// `BsonValue::decrypt` on the text of a document, keeping comments and
+/// layout.
+pub fn decrypt_text(input: &str, keys: &dyn KeyProvider) -> Result<String, CryptError> {
+    rewrite_text(input, |_, value| decrypt_one(keys, value))
+}
@@ -0,0 +1,353 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "regex")]
+use regex::Regex;
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+#[cfg(feature = "decimal")]
+use rust_decimal::prelude::ToPrimitive;
+use serde::de::value::{BorrowedStrDeserializer, MapAccessDeserializer};
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess, SeqAccess,
+    Unexpected, Visitor,
+};
+
+use crate::parser::BsonValue;
+
+/// Why a value does not fit the type it is read into: the dotted path of
+/// the key, empty for the value itself, and serde's words for what is
+/// wrong with it, such as `invalid type: string "80", expected u16`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertError {
+    pub path: String,
+    pub message: String,
+}
+
+impl ConvertError {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> ConvertError {
+        ConvertError {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    // The same error one level further out, under `segment`
+    pub(crate) fn within(mut self, segment: &str) -> ConvertError {
+        self.path = match self.path.is_empty() {
+            true => segment.to_string(),
+            false => format!("{segment}.{}", self.path),
+        };
+        self
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for ConvertError {}
+
+impl de::Error for ConvertError {
+    fn custom<T: fmt::Display>(message: T) -> ConvertError {
+        ConvertError::new("", message.to_string())
+    }
+}
+
+/// Reads `value` into any type that implements `Deserialize`. Strings and
+/// keys are borrowed from the value where the type allows it.
+pub fn from_value<'de, T: Deserialize<'de>>(value: &'de BsonValue<'_>) -> Result<T, ConvertError> {
+    T::deserialize(value)
+}
+
+/// Reads an `Option` field that has to be written, for
+/// `#[serde(deserialize_with = "rs_bson::de::nullable")]`: `MissingNo` is
+/// `None`, while a key left out is a `missing field` error unless the field
+/// also has `#[serde(default)]`. A plain `Option` field reads both as `None`.
+pub fn nullable<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    Option::<T>::deserialize(deserializer)
+}
+
+/// Compiles a regex literal, or a plain string such as an environment
+/// override, for `#[serde(deserialize_with = "rs_bson::de::regex")]` on a
+/// `Regex` field.
+#[cfg(feature = "regex")]
+pub fn regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = Cow::<str>::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(de::Error::custom)
+}
+
+// Whole numbers go to the visitor as integers, so integer fields take them
+fn visit_number<'de, V: Visitor<'de>>(n: f64, visitor: V) -> Result<V::Value, ConvertError> {
+    if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+        visitor.visit_i64(n as i64)
+    } else if n.fract() == 0.0 && n >= 0.0 && n < u64::MAX as f64 {
+        visitor.visit_u64(n as u64)
+    } else {
+        visitor.visit_f64(n)
+    }
+}
+
+fn visit_integer<'de, V: Visitor<'de>>(n: i128, visitor: V) -> Result<V::Value, ConvertError> {
+    if let Ok(n) = i64::try_from(n) {
+        visitor.visit_i64(n)
+    } else if let Ok(n) = u64::try_from(n) {
+        visitor.visit_u64(n)
+    } else {
+        visitor.visit_i128(n)
+    }
+}
+
+// Whole decimals go as integers too, the others as the nearest `f64`
+#[cfg(feature = "decimal")]
+fn visit_decimal<'de, V: Visitor<'de>>(d: Decimal, visitor: V) -> Result<V::Value, ConvertError> {
+    match (d.fract().is_zero(), d.to_i128(), d.to_f64()) {
+        (true, Some(n), _) => visit_integer(n, visitor),
+        (_, _, Some(n)) => visitor.visit_f64(n),
+        _ => visitor.visit_string(d.to_string()),
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for &'de BsonValue<'a> {
+    type Error = ConvertError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        match self {
+            BsonValue::BString(s) | BsonValue::Regex(s) | BsonValue::Date(s) => {
+                visitor.visit_borrowed_str(s)
+            }
+            BsonValue::Number(n) => visit_number(*n, visitor),
+            BsonValue::Float(n) => visitor.visit_f64(*n),
+            BsonValue::Integer(n) => visit_integer(*n, visitor),
+            #[cfg(feature = "decimal")]
+            BsonValue::Decimal(d) => visit_decimal(*d, visitor),
+            BsonValue::Bool(b) => visitor.visit_bool(*b),
+            BsonValue::Null(()) => visitor.visit_unit(),
+            BsonValue::Array(items) => visitor.visit_seq(Elements(items.iter().enumerate())),
+            BsonValue::Map(m) => visitor.visit_map(Entries {
+                entries: m.iter(),
+                value: None,
+            }),
+            // Resolved and decrypted values are plain strings by now
+            BsonValue::Secret(_) => Err(de::Error::invalid_type(
+                Unexpected::Other("secret reference"),
+                &visitor,
+            )),
+            BsonValue::Encrypted(_) => Err(de::Error::invalid_type(
+                Unexpected::Other("encrypted value"),
+                &visitor,
+            )),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        match self {
+            BsonValue::Null(()) => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    // A string field keeps an `Integer` or `Decimal` digit for digit
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        match self {
+            BsonValue::Integer(n) => visitor.visit_string(n.to_string()),
+            #[cfg(feature = "decimal")]
+            BsonValue::Decimal(d) => visitor.visit_string(d.to_string()),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        match self.as_f64() {
+            Some(n) => visitor.visit_f64(n),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ConvertError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // Unit variants are written as strings, the others as a section with
+    // the variant as its only key
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ConvertError> {
+        match self {
+            BsonValue::BString(s) => visitor.visit_enum(s.as_ref().into_deserializer()),
+            BsonValue::Map(m) if m.len() == 1 => {
+                visitor.visit_enum(MapAccessDeserializer::new(Entries {
+                    entries: m.iter(),
+                    value: None,
+                }))
+            }
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, ConvertError> for &'de BsonValue<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct Elements<I>(I);
+
+impl<'de, 'a: 'de, I: Iterator<Item = (usize, &'de BsonValue<'a>)>> SeqAccess<'de> for Elements<I> {
+    type Error = ConvertError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, ConvertError> {
+        self.0
+            .next()
+            .map(|(i, item)| {
+                seed.deserialize(item)
+                    .map_err(|err| err.within(&i.to_string()))
+            })
+            .transpose()
+    }
+}
+
+struct Entries<'de, 'a> {
+    entries: std::collections::btree_map::Iter<'de, Cow<'a, str>, BsonValue<'a>>,
+    // The key handed out last and its value
+    value: Option<(&'de str, &'de BsonValue<'a>)>,
+}
+
+impl<'de, 'a> MapAccess<'de> for Entries<'de, 'a> {
+    type Error = ConvertError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ConvertError> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some((key, value));
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ConvertError> {
+        match self.value.take() {
+            Some((key, value)) => seed.deserialize(value).map_err(|err| err.within(key)),
+            None => Err(de::Error::custom("value asked for before its key")),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = BsonValue<'static>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a BSON value")
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<BsonValue<'static>, E> {
+        Ok(BsonValue::Bool(b))
+    }
+
+    // Integers `f64` would round stay exact, as the parser keeps them
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<BsonValue<'static>, E> {
+        self.visit_i128(n.into())
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<BsonValue<'static>, E> {
+        self.visit_i128(n.into())
+    }
+
+    fn visit_i128<E: de::Error>(self, n: i128) -> Result<BsonValue<'static>, E> {
+        match n as f64 as i128 == n {
+            true => Ok(BsonValue::Number(n as f64)),
+            false => Ok(BsonValue::Integer(n)),
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, n: f64) -> Result<BsonValue<'static>, E> {
+        Ok(BsonValue::Number(n))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<BsonValue<'static>, E> {
+        Ok(BsonValue::from(s.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<BsonValue<'static>, E> {
+        Ok(BsonValue::from(s))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<BsonValue<'static>, E> {
+        Ok(BsonValue::Null(()))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<BsonValue<'static>, E> {
+        Ok(BsonValue::Null(()))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<BsonValue<'static>, D::Error> {
+        BsonValue::deserialize(d)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<BsonValue<'static>, A::Error> {
+        let mut items = vec![];
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(BsonValue::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<BsonValue<'static>, A::Error> {
+        let mut entries = BTreeMap::new();
+        while let Some((key, value)) = map.next_entry::<String, BsonValue<'static>>()? {
+            entries.insert(Cow::Owned(key), value);
+        }
+        Ok(BsonValue::Map(entries))
+    }
+}
+
+/// Any self-describing format read into a document, such as a JSON file
+/// through `serde_json`. Integers `f64` cannot hold become an `Integer`.
+impl<'de> Deserialize<'de> for BsonValue<'static> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<BsonValue<'static>, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, HashMap};
+
+use regex::Regex;
+
+use crate::parser::BsonValue;
+use crate::schema::{Schema, Type};
+
+/// Types that know the schema of their own config representation. Structs
+/// get an implementation from `bulba_schema!`, so the schema is always the
+/// one the consuming code actually expects.
+pub trait Describe {
+    /// False for types that may be left out, i.e. `Option<T>`
+    const REQUIRED: bool = true;
+
+    fn schema() -> Schema;
+}
+
+impl Describe for String {
+    fn schema() -> Schema {
+        Schema::from(Type::String)
+    }
+}
+
+impl Describe for Regex {
+    fn schema() -> Schema {
+        Schema::from(Type::String)
+    }
+}
+
+impl Describe for bool {
+    fn schema() -> Schema {
+        Schema::from(Type::Bool)
+    }
+}
+
+impl Describe for f32 {
+    fn schema() -> Schema {
+        Schema::from(Type::Number)
+    }
+}
+
+impl Describe for f64 {
+    fn schema() -> Schema {
+        Schema::from(Type::Number)
+    }
+}
+
+macro_rules! describe_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Describe for $ty {
+                fn schema() -> Schema {
+                    Type::Int.range((<$ty>::MIN as f64)..=(<$ty>::MAX as f64))
+                }
+            }
+        )*
+    };
+}
+
+describe_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl<T: Describe> Describe for Option<T> {
+    const REQUIRED: bool = false;
+
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T: Describe> Describe for Vec<T> {
+    fn schema() -> Schema {
+        Schema::array(T::schema())
+    }
+}
+
+impl<T> Describe for BTreeMap<String, T> {
+    fn schema() -> Schema {
+        Schema::from(Type::Map)
+    }
+}
+
+impl<T, S> Describe for HashMap<String, T, S> {
+    fn schema() -> Schema {
+        Schema::from(Type::Map)
+    }
+}
+
+impl Describe for BsonValue<'static> {
+    fn schema() -> Schema {
+        Schema::from(Type::Any)
+    }
+}
+
+/// Declares a config struct and derives its schema from the field names and
+/// types. Attributes, derives included, are passed through untouched, so
+/// the same definition derives the `Deserialize` that `load` reads it with.
+///
+/// ```
+/// rs_bson::bulba_schema! {
+///     #[derive(Debug, serde::Deserialize)]
+///     pub struct Database {
+///         pub host: String,
+///         pub port: Option<u16>,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! bulba_schema {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident : $ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $crate::describe::Describe for $name {
+            fn schema() -> $crate::schema::Schema {
+                let schema = $crate::schema::Schema::map();
+                $(
+                    let schema = schema.key(
+                        stringify!($field),
+                        <$ty as $crate::describe::Describe>::schema(),
+                    );
+                    let schema = if <$ty as $crate::describe::Describe>::REQUIRED {
+                        schema.required(stringify!($field))
+                    } else {
+                        schema
+                    };
+                )*
+                schema
+            }
+        }
+    };
+}
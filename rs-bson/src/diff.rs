@@ -0,0 +1,116 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::parser::{BsonValue, join_path, join_pointer};
+use crate::patch::{Operation, Patch};
+use crate::visit::{Visit, walk_value};
+
+// Compares every value of the new version with the one at the same place
+// in the old
+struct Diff<'o, 'a> {
+    // The counterpart of the value being visited and where both are
+    old: Option<&'o BsonValue<'a>>,
+    pointer: String,
+    patch: Patch<'a>,
+}
+
+impl Visit for Diff<'_, '_> {
+    fn visit_value(&mut self, path: &str, new: &BsonValue) {
+        match self.old {
+            None => self.patch.operations.push(Operation::Add {
+                path: self.pointer.clone(),
+                value: new.clone().into_owned(),
+            }),
+            Some(BsonValue::Map(_)) if matches!(new, BsonValue::Map(_)) => {
+                walk_value(self, path, new)
+            }
+            // Arrays are replaced as a whole, a single operation is easier
+            // to read in an audit log than a string of index shuffles
+            Some(old) if old != new => self.patch.operations.push(Operation::Replace {
+                path: self.pointer.clone(),
+                value: new.clone().into_owned(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    fn visit_map(&mut self, path: &str, new_map: &BTreeMap<Cow<'_, str>, BsonValue<'_>>) {
+        let (old, pointer) = (self.old, std::mem::take(&mut self.pointer));
+        let Some(BsonValue::Map(old_map)) = old else {
+            return;
+        };
+        for key in old_map.keys() {
+            if !new_map.contains_key(key.as_ref()) {
+                self.patch.operations.push(Operation::Remove {
+                    path: join_pointer(&pointer, key),
+                });
+            }
+        }
+        for (key, value) in new_map {
+            self.old = old_map.get(key.as_ref());
+            self.pointer = join_pointer(&pointer, key);
+            self.visit_value(&join_path(path, key), value);
+        }
+        (self.old, self.pointer) = (old, pointer);
+    }
+}
+
+/// Computes the operations turning `old` into `new`, addressed by JSON
+/// Pointers. Sections are compared key by key, anything else that differs
+/// is replaced.
+pub fn diff<'a>(old: &BsonValue<'a>, new: &BsonValue<'a>) -> Patch<'a> {
+    let mut diff = Diff {
+        old: Some(old),
+        pointer: String::new(),
+        patch: Patch::new(),
+    };
+    new.visit(&mut diff);
+    diff.patch
+}
+
+/// The JSON Pointers that differ between two versions of a document, see
+/// `changed_paths`. A section that was added or removed is one path.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChangedPaths {
+    paths: BTreeSet<String>,
+}
+
+impl ChangedPaths {
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.paths.iter().map(String::as_str)
+    }
+
+    /// Whether anything at the pointer `prefix` or below it changed, such as
+    /// `/database/pool/size` for `/database`, or the section around it was
+    /// added or removed. The empty prefix matches every change.
+    pub fn touches(&self, prefix: &str) -> bool {
+        let below = |outer: &str, inner: &str| {
+            outer.is_empty()
+                || inner == outer
+                || inner
+                    .strip_prefix(outer)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        };
+        self.iter()
+            .any(|path| below(prefix, path) || below(path, prefix))
+    }
+}
+
+/// The paths of every operation of `diff(old, new)`.
+pub fn changed_paths(old: &BsonValue, new: &BsonValue) -> ChangedPaths {
+    let paths = diff(old, new)
+        .operations
+        .into_iter()
+        .map(|operation| match operation {
+            Operation::Add { path, .. }
+            | Operation::Remove { path }
+            | Operation::Replace { path, .. }
+            | Operation::Move { path, .. } => path,
+        })
+        .collect();
+    ChangedPaths { paths }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use crate::completion::{Completion, completions};
+use crate::document::Document;
+use crate::emit::fragment;
+use crate::parser::{BsonValue, join_path};
+use crate::schema::{Schema, Type};
+
+const COLUMNS: [&str; 5] = ["Key", "Type", "Required", "Default", "Description"];
+
+fn literal(value: &BsonValue) -> String {
+    fragment(value).unwrap_or_default()
+}
+
+// Doc comment, allowed values and deprecation folded into one cell
+fn description(completion: &Completion) -> String {
+    let mut parts = vec![];
+    if let Some(replacement) = &completion.deprecated {
+        match replacement.as_str() {
+            "" => parts.push(String::from("Deprecated.")),
+            _ => parts.push(format!("Deprecated, use `{replacement}`.")),
+        }
+    }
+    if let Some(doc) = &completion.doc {
+        let end = if doc.ends_with(['.', '!', '?']) {
+            ""
+        } else {
+            "."
+        };
+        parts.push(format!("{doc}{end}"));
+    }
+    if !completion.allowed.is_empty() {
+        let allowed: Vec<String> = completion
+            .allowed
+            .iter()
+            .map(|value| format!("`{}`", literal(value)))
+            .collect();
+        parts.push(format!("One of {}.", allowed.join(", ")));
+    }
+    parts.join(" ")
+}
+
+fn rows(schema: &Schema) -> Vec<[String; 5]> {
+    completions(schema)
+        .iter()
+        .map(|completion| {
+            [
+                format!("`{}`", completion.path),
+                completion.ty.clone(),
+                String::from(if completion.required { "yes" } else { "no" }),
+                completion
+                    .default
+                    .as_ref()
+                    .map(|value| format!("`{}`", literal(value)))
+                    .unwrap_or_default(),
+                description(completion),
+            ]
+        })
+        .collect()
+}
+
+/// Reference documentation for every key of a schema as a Markdown table:
+/// key path, type, whether it is required, default and description.
+pub fn markdown(schema: &Schema) -> String {
+    let mut out = String::from("# Configuration reference\n\n");
+    out += &format!("| {} |\n", COLUMNS.join(" | "));
+    out += &format!("|{}\n", "---|".repeat(COLUMNS.len()));
+    for row in rows(schema) {
+        let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        out += &format!("| {} |\n", cells.join(" | "));
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Backtick spans of a cell become <code>
+fn inline_html(cell: &str) -> String {
+    let mut out = String::new();
+    for (i, part) in escape_html(cell).split('`').enumerate() {
+        match i % 2 {
+            0 => out += part,
+            _ => out += &format!("<code>{part}</code>"),
+        }
+    }
+    out
+}
+
+/// The same reference as `markdown`, as an HTML fragment with one table.
+pub fn html(schema: &Schema) -> String {
+    let mut out = String::from("<h1>Configuration reference</h1>\n<table>\n  <tr>");
+    for column in COLUMNS {
+        out += &format!("<th>{column}</th>");
+    }
+    out += "</tr>\n";
+    for row in rows(schema) {
+        out += "  <tr>";
+        for cell in row {
+            out += &format!("<td>{}</td>", inline_html(&cell));
+        }
+        out += "</tr>\n";
+    }
+    out + "</table>\n"
+}
+
+fn type_of(value: &BsonValue) -> Schema {
+    match value {
+        BsonValue::BString(_)
+        | BsonValue::Secret(_)
+        | BsonValue::Regex(_)
+        | BsonValue::Encrypted(_) => Schema::from(Type::String),
+        BsonValue::Bool(_) => Schema::from(Type::Bool),
+        BsonValue::Null(()) => Schema::from(Type::Any),
+        BsonValue::Array(items) => Schema::array(items.first().map_or(Type::Any.into(), type_of)),
+        BsonValue::Map(_) => Schema::map(),
+        BsonValue::Date(_) => Schema::from(Type::Date),
+        BsonValue::Float(_) => Schema::from(Type::Number),
+        // Every kind of number
+        _ => match value.as_f64() {
+            Some(n) if n.fract() == 0.0 => Schema::from(Type::Int),
+            _ => Schema::from(Type::Number),
+        },
+    }
+}
+
+fn describe_example(value: &BsonValue, path: &str, comments: &BTreeMap<String, String>) -> Schema {
+    let BsonValue::Map(m) = value else {
+        return type_of(value);
+    };
+    let mut schema = Schema::map();
+    for (key, child) in m {
+        let child_path = join_path(path, key);
+        let mut field = match child {
+            BsonValue::Map(_) => describe_example(child, &child_path, comments),
+            BsonValue::Null(()) => type_of(child),
+            _ => type_of(child).default(child.clone().into_owned()),
+        };
+        if let Some(text) = comments.get(&child_path) {
+            field = field.doc(text);
+        }
+        schema = schema.key(key, field);
+    }
+    schema
+}
+
+/// Reads a schema off an annotated example document: each key gets the
+/// type and value of the example as its default, and the comment above it
+/// as its description. Nothing is marked required.
+pub fn from_example(doc: &Document) -> Result<Schema, &'static str> {
+    Ok(describe_example(&doc.value()?, "", &doc.comments()))
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+
+use crate::document::Document;
+use crate::lexer::{COMMENT, FALSE, KEY, NULL, QUOTES, TRUE};
+use crate::parser::{BsonValue, join_path};
+
+/// A likely mistake in a document and how to fix it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnosis {
+    pub line: usize,
+    pub message: String,
+    pub suggestion: String,
+}
+
+fn diagnosis(line: usize, message: String, suggestion: String) -> Diagnosis {
+    Diagnosis {
+        line,
+        message,
+        suggestion,
+    }
+}
+
+// Spellings other formats use for the BSON keywords
+fn keyword_for(word: &str) -> Option<&'static str> {
+    match word.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(TRUE),
+        "false" | "no" | "off" => Some(FALSE),
+        "null" | "nil" | "none" => Some(NULL),
+        _ => None,
+    }
+}
+
+fn check_value(line: usize, key: &str, value: &str, found: &mut Vec<Diagnosis>) {
+    let bare = Regex::new(r"^[A-Za-z_][A-Za-z0-9_.:/-]*$").unwrap();
+    let quoted = (value.chars().next())
+        .filter(|ch| QUOTES.contains(ch))
+        .and_then(|quote| value[1..].strip_suffix(quote));
+    if let Some(text) = quoted {
+        let looks_like = if text.parse::<f64>().is_ok_and(f64::is_finite) {
+            Some("number")
+        } else if [TRUE, FALSE].contains(&text) {
+            Some("bool")
+        } else if text == NULL {
+            Some("MissingNo")
+        } else {
+            None
+        };
+        if let Some(kind) = looks_like {
+            found.push(diagnosis(
+                line,
+                format!("`{key}` holds the string \"{text}\", not a {kind}"),
+                format!("Drop the quotes: {key} ~> {text}"),
+            ));
+        }
+    } else if bare.is_match(value) && ![TRUE, FALSE, NULL].contains(&value) {
+        found.push(match keyword_for(value) {
+            Some(keyword) => diagnosis(
+                line,
+                format!("`{value}` is not a BSON keyword"),
+                format!("Write {key} ~> {keyword}"),
+            ),
+            None => diagnosis(
+                line,
+                format!("`{key}` looks like an unquoted string"),
+                format!("Quote it: {key} ~> \"{value}\""),
+            ),
+        });
+    }
+}
+
+fn check_lines(input: &str, found: &mut Vec<Diagnosis>) {
+    let pair = Regex::new(&format!(r"^\s*({KEY}(?:\.{KEY})*)\s*~+>\s*(.*)$")).unwrap();
+    let mut crlf = None;
+    for (i, raw) in input.split_inclusive('\n').enumerate().skip(1) {
+        let line = i + 1;
+        if raw.ends_with('\n') {
+            let is_crlf = raw.ends_with("\r\n");
+            match crlf {
+                None => crlf = Some(is_crlf),
+                Some(first) if first != is_crlf => {
+                    found.push(diagnosis(
+                        line,
+                        String::from("Line endings switch between LF and CRLF"),
+                        String::from("Save the file with LF line endings"),
+                    ));
+                    crlf = Some(is_crlf);
+                }
+                Some(_) => {}
+            }
+        }
+        let text = raw.trim_end_matches(['\n', '\r']);
+
+        if let Some(comment_idx) = text.find(COMMENT)
+            && text[..comment_idx].matches('"').count() % 2 == 1
+        {
+            found.push(diagnosis(
+                line,
+                format!("`{COMMENT}` inside a string starts a comment"),
+                format!("Spell it another way, the line ends at the first {COMMENT}"),
+            ));
+            continue;
+        }
+        let code = text
+            .find(COMMENT)
+            .map_or(text, |idx| &text[..idx])
+            .trim_end();
+        if code.is_empty() {
+            continue;
+        }
+
+        let indent = &code[..code.len() - code.trim_start().len()];
+        if indent.chars().any(|ch| ch != ' ') {
+            found.push(diagnosis(
+                line,
+                String::from("Indentation mixes spaces with other whitespace"),
+                String::from("Indent with spaces only, 4 per level"),
+            ));
+        } else if indent.len() % 4 != 0 {
+            found.push(diagnosis(
+                line,
+                format!("Indented by {} spaces", indent.len()),
+                format!(
+                    "Indent by a multiple of 4, e.g. {}",
+                    indent.len().div_ceil(4) * 4
+                ),
+            ));
+        }
+
+        if let Some(matches) = pair.captures(code) {
+            check_value(line, &matches[1], matches[2].trim(), found);
+        }
+    }
+}
+
+fn check_case(value: &BsonValue, path: &str, doc: &Document, found: &mut Vec<Diagnosis>) {
+    let BsonValue::Map(m) = value else {
+        return;
+    };
+    let mut seen: BTreeMap<String, &str> = BTreeMap::new();
+    for (key, child) in m {
+        if let Some(other) = seen.insert(key.to_ascii_lowercase(), key) {
+            // Reported where the second spelling appears
+            let line_of = |key: &str| doc.line_of(&join_path(path, key)).unwrap_or_default();
+            let (first, second) = if line_of(other) < line_of(key) {
+                (other, key.as_ref())
+            } else {
+                (key.as_ref(), other)
+            };
+            found.push(diagnosis(
+                line_of(second),
+                format!("`{second}` differs from `{first}` only by case"),
+                String::from("Keys are case-sensitive, merge them under one spelling"),
+            ));
+        }
+        check_case(child, &join_path(path, key), doc, found);
+    }
+}
+
+/// Looks for mistakes that parse but likely do not mean what the author
+/// thinks, and for the usual causes of the hard errors: indentation that
+/// is not a multiple of 4 spaces or mixes in other whitespace, line
+/// endings that switch style, keys differing only by case, quoted numbers
+/// and keywords, bare words BSON reads as neither, and `zZz` inside
+/// strings. Findings come sorted by line.
+pub fn examine(input: &str) -> Vec<Diagnosis> {
+    let mut found = vec![];
+    check_lines(input, &mut found);
+    if let Ok(doc) = Document::parse(input)
+        && let Ok(value) = doc.value()
+    {
+        check_case(&value, "", &doc, &mut found);
+    }
+    found.sort_by_key(|d| d.line);
+    found
+}
@@ -0,0 +1,572 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[cfg(feature = "checksum")]
+use crate::checksum;
+use crate::expr::Expression;
+use crate::lexer;
+use crate::parser::{self, BsonValue, ParseError, ParseOptions, join_path};
+
+/// Lossless view of a BSON file. Every comment, blank line and the exact
+/// spelling of each line is kept, so the document can be rearranged and
+/// written back without disturbing what the author wrote around the edit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    header: String,
+    root: Block,
+    trailing_newline: bool,
+    // Ends in a checksum footer, written afresh for the text as it reads
+    sealed: bool,
+    // The rules the text was read by, for every later parse of it
+    options: ParseOptions,
+}
+
+/// What `Document::extend_from` does when both documents define the same
+/// value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy {
+    /// Refuse the whole extension
+    Error,
+    /// Keep the entry already in the document
+    KeepExisting,
+    /// Swap in the incoming entry where the existing one stood
+    Overwrite,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Block {
+    entries: Vec<Entry>,
+    // Comments closing the block that belong to neither neighbour
+    trailing: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    // Comments and blank lines directly above the entry
+    leading: Vec<String>,
+    // The whole key, dotted as in `database.pool`
+    key: String,
+    // Where the key starts in `line`
+    key_at: usize,
+    // A section written `key[]`, one of a list of them. `key` is without
+    // the marker.
+    repeated: bool,
+    // With the lines a trailing `\` continues it onto, joined by `\n`
+    line: String,
+    // Children of an evolution stage, None for key-value pairs
+    body: Option<Block>,
+}
+
+enum Line<'s> {
+    Trivia,
+    Section {
+        level: usize,
+        key: &'s str,
+        key_at: usize,
+    },
+    Pair {
+        level: usize,
+        key: &'s str,
+        key_at: usize,
+    },
+}
+
+// Whether the next line carries on `line`, see `Lexer::push_line`
+fn continues(line: &str) -> bool {
+    let code = match line.find(lexer::COMMENT) {
+        Some(comment_idx) => &line[..comment_idx],
+        None => line,
+    };
+    code.trim_end().ends_with(lexer::CONTINUATION)
+}
+
+fn classify(line: &str) -> Line<'_> {
+    let code = match line.find(lexer::COMMENT) {
+        Some(comment_idx) => &line[..comment_idx],
+        None => line,
+    };
+    let code = code.trim_end();
+    let trimmed = code.trim_start();
+    if trimmed.is_empty() {
+        return Line::Trivia;
+    }
+
+    let indent = code.len() - trimmed.len();
+    for (i, stage) in lexer::STAGES.iter().enumerate() {
+        let inner = trimmed
+            .strip_prefix(stage)
+            .and_then(|rest| rest.strip_prefix(' '))
+            .and_then(|rest| rest.strip_suffix(stage))
+            .and_then(|rest| rest.strip_suffix(' '));
+        if let Some(inner) = inner {
+            let key_at = indent + stage.len() + 1 + inner.len() - inner.trim_start().len();
+            return Line::Section {
+                level: i + 1,
+                key: inner.trim(),
+                key_at,
+            };
+        }
+    }
+
+    let key_len = match lexer::split_pair(trimmed) {
+        Some((key, _)) => key.end,
+        None => 0,
+    };
+    Line::Pair {
+        level: indent / 4,
+        key: &trimmed[..key_len],
+        key_at: indent,
+    }
+}
+
+// Comments indented at least as deep as the block being closed stay with it,
+// everything after them is left for the entry that follows.
+fn take_trailing(pending: &mut Vec<String>, depth: usize) -> Vec<String> {
+    let mut end = 0;
+    for (i, line) in pending.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.trim_end().is_empty() {
+            continue;
+        }
+        if line.len() - trimmed.len() < depth * 4 {
+            break;
+        }
+        end = i + 1;
+    }
+    pending.drain(..end).collect()
+}
+
+fn close_blocks(
+    stack: &mut Vec<Block>,
+    open: &mut Vec<Entry>,
+    pending: &mut Vec<String>,
+    depth: usize,
+) {
+    while stack.len() > depth {
+        let mut block = stack.pop().unwrap();
+        block.trailing = take_trailing(pending, stack.len());
+        let mut section = open.pop().unwrap();
+        section.body = Some(block);
+        stack.last_mut().unwrap().entries.push(section);
+    }
+}
+
+fn sort_block(block: &mut Block) {
+    // A conditional section overrides the keys above it, so it stays put
+    // and only the keys between such sections are sorted
+    let conditional = |entry: &Entry| lexer::condition(&entry.key).is_some();
+    for run in block.entries.split_mut(conditional) {
+        run.sort_by(|a, b| a.key.cmp(&b.key));
+    }
+    for entry in block.entries.iter_mut() {
+        if let Some(body) = &mut entry.body {
+            sort_block(body);
+        }
+    }
+}
+
+fn line_count(entry: &Entry) -> usize {
+    let body = match &entry.body {
+        Some(block) => block.entries.iter().map(line_count).sum::<usize>() + block.trailing.len(),
+        None => 0,
+    };
+    entry.leading.len() + entry.line.split('\n').count() + body
+}
+
+// The path each entry of `block` stands for: its key, split at the dots,
+// and for a repeated section which one of the list it is
+fn entry_paths(block: &Block) -> Vec<Vec<String>> {
+    let mut seen: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut paths = vec![];
+    for entry in &block.entries {
+        let mut path: Vec<String> = entry.key.split('.').map(String::from).collect();
+        if entry.repeated {
+            let index = seen.entry(&entry.key).or_default();
+            path.push(index.to_string());
+            *index += 1;
+        }
+        paths.push(path);
+    }
+    paths
+}
+
+// How many of `segments` the path of an entry spells out. A dotted key may
+// cover several, or all of them and more.
+fn shared(path: &[String], segments: &[&str]) -> usize {
+    path.iter()
+        .zip(segments)
+        .take_while(|(part, segment)| part == *segment)
+        .count()
+}
+
+// `before` is the number of lines rendered ahead of the block's first line
+fn find_line(block: &Block, segments: &[&str], before: usize) -> Option<usize> {
+    let mut found = None;
+    let mut line = before;
+    for (entry, path) in block.entries.iter().zip(entry_paths(block)) {
+        let entry_line = line + entry.leading.len() + 1;
+        let shared = shared(&path, segments);
+        if shared == segments.len() {
+            found = Some(entry_line);
+        } else if shared == path.len() {
+            found = match &entry.body {
+                Some(body) => find_line(body, &segments[shared..], entry_line).or(found),
+                // Array elements live on the line of their key
+                None => Some(entry_line),
+            };
+        }
+        line += line_count(entry);
+    }
+    found
+}
+
+// Renames the key part naming the last of `segments` wherever a key spells
+// it out, and notes the lines that changed
+fn rename_in(
+    block: &mut Block,
+    segments: &[&str],
+    new_key: &str,
+    before: usize,
+    renamed: &mut Vec<usize>,
+) {
+    let paths = entry_paths(block);
+    let mut line = before;
+    for (entry, path) in block.entries.iter_mut().zip(paths) {
+        let entry_line = line + entry.leading.len() + 1;
+        line += line_count(entry);
+        let shared = shared(&path, segments);
+        let mut parts: Vec<&str> = entry.key.split('.').collect();
+        // The index of a repeated section has nothing to rename
+        if shared == segments.len() && shared <= parts.len() {
+            let start = entry.key_at
+                + parts[..shared - 1]
+                    .iter()
+                    .map(|part| part.len() + 1)
+                    .sum::<usize>();
+            let end = start + parts[shared - 1].len();
+            parts[shared - 1] = new_key;
+            let key = parts.join(".");
+            entry.line.replace_range(start..end, new_key);
+            entry.key = key;
+            renamed.push(entry_line);
+        } else if shared < segments.len()
+            && shared == path.len()
+            && let Some(body) = &mut entry.body
+        {
+            rename_in(body, &segments[shared..], new_key, entry_line, renamed);
+        }
+    }
+}
+
+// Whether an expression below `block` names the value at `path` or one
+// inside it. `expressions` holds the text of each by the line it starts on;
+// names resolve the way the parser resolves them, in the same section first.
+fn references(
+    block: &Block,
+    prefix: &str,
+    before: usize,
+    expressions: &BTreeMap<usize, String>,
+    value: &BsonValue,
+    path: &str,
+) -> bool {
+    let mut line = before;
+    for (entry, entry_path) in block.entries.iter().zip(entry_paths(block)) {
+        let entry_line = line + entry.leading.len() + 1;
+        line += line_count(entry);
+        let expression = match &entry.body {
+            Some(body) => {
+                let section = join_path(prefix, &entry_path.join("."));
+                if references(body, &section, entry_line, expressions, value, path) {
+                    return true;
+                }
+                continue;
+            }
+            None => match expressions
+                .get(&entry_line)
+                .map(|text| Expression::parse(text))
+            {
+                Some(Ok(expression)) => expression,
+                _ => continue,
+            },
+        };
+        let named = expression.names().into_iter().any(|name| {
+            let local = join_path(prefix, name);
+            let target = if value.get(&local).is_some() {
+                local.as_str()
+            } else {
+                name
+            };
+            target == path
+                || target
+                    .strip_prefix(path)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        });
+        if named {
+            return true;
+        }
+    }
+    false
+}
+
+fn collect_comments(block: &Block, prefix: &str, comments: &mut BTreeMap<String, String>) {
+    for (entry, path) in block.entries.iter().zip(entry_paths(block)) {
+        let path = join_path(prefix, &path.join("."));
+        let mut text: Vec<&str> = entry
+            .leading
+            .iter()
+            .rev()
+            .map_while(|line| line.trim().strip_prefix(lexer::COMMENT))
+            .map(str::trim)
+            .collect();
+        if !text.is_empty() {
+            text.reverse();
+            comments.insert(path.clone(), text.join("\n"));
+        }
+        if let Some(body) = &entry.body {
+            collect_comments(body, &path, comments);
+        }
+    }
+}
+
+fn push_lines<'d>(block: &'d Block, lines: &mut Vec<&'d str>) {
+    for entry in &block.entries {
+        lines.extend(entry.leading.iter().map(String::as_str));
+        lines.push(&entry.line);
+        if let Some(body) = &entry.body {
+            push_lines(body, lines);
+        }
+    }
+    lines.extend(block.trailing.iter().map(String::as_str));
+}
+
+impl Document {
+    pub fn parse(input: &str) -> Result<Document, ParseError> {
+        Document::parse_with(input, &ParseOptions::default())
+    }
+
+    /// `parse` under the rules of `options`, which `value` and every edit
+    /// go on checking the document against.
+    pub fn parse_with(input: &str, options: &ParseOptions) -> Result<Document, ParseError> {
+        // Let the real lexer and parser reject anything malformed first, so
+        // the line walk only ever sees valid BSON.
+        let tokens = lexer::lex_str_with(input, options)?;
+        parser::parse_with(&tokens, options)?;
+        Ok(Document::walk(input, options)?)
+    }
+
+    fn walk(input: &str, options: &ParseOptions) -> Result<Document, &'static str> {
+        let sealed = lexer::is_sealed(input);
+        let input = lexer::strip_footer(input);
+        let trailing_newline = input.ends_with('\n');
+        let mut lines = input.strip_suffix('\n').unwrap_or(input).split('\n');
+        let header = lines.next().unwrap_or_default().to_string();
+
+        let mut stack = vec![Block::default()];
+        let mut open: Vec<Entry> = vec![];
+        let mut pending: Vec<String> = vec![];
+        // A value that goes on over the lines after it is one entry
+        let mut continued = false;
+        let mut opened = false;
+        for line in lines {
+            if continued {
+                // The section just opened has no entries of its own yet
+                let entry = match stack.last_mut().unwrap().entries.last_mut() {
+                    Some(entry) if !opened => entry,
+                    _ => open.last_mut().ok_or("It hurt itself in its confusion!")?,
+                };
+                entry.line.push('\n');
+                entry.line.push_str(line);
+                continued = continues(line);
+                continue;
+            }
+            continued = continues(line);
+            let (depth, key, key_at, is_section) = match classify(line) {
+                Line::Trivia => {
+                    pending.push(line.to_string());
+                    continue;
+                }
+                Line::Section { level, key, key_at } => (level - 1, key, key_at, true),
+                Line::Pair { level, key, key_at } => (level, key, key_at, false),
+            };
+            close_blocks(&mut stack, &mut open, &mut pending, depth + 1);
+
+            let entry = Entry {
+                leading: std::mem::take(&mut pending),
+                key: key.strip_suffix("[]").unwrap_or(key).to_string(),
+                key_at,
+                repeated: is_section && key.ends_with("[]"),
+                line: line.to_string(),
+                body: None,
+            };
+            opened = is_section;
+            if is_section {
+                open.push(entry);
+                stack.push(Block::default());
+            } else {
+                stack.last_mut().unwrap().entries.push(entry);
+            }
+        }
+        close_blocks(&mut stack, &mut open, &mut pending, 1);
+
+        let mut root = stack.pop().unwrap();
+        root.trailing = pending;
+        Ok(Document {
+            header,
+            root,
+            trailing_newline,
+            sealed,
+            options: options.clone(),
+        })
+    }
+
+    /// The parsed value of the document as it currently reads.
+    pub fn value(&self) -> Result<BsonValue<'static>, ParseError> {
+        let tokens = lexer::lex_str_with(&self.to_string(), &self.options)?;
+        Ok(parser::parse_with(&tokens, &self.options)?.into_owned())
+    }
+
+    /// Sorts the keys of every section into the same order `BsonValue` uses.
+    /// Each entry takes its leading comments and blank lines along with it.
+    pub fn sort_keys(&mut self) {
+        sort_block(&mut self.root);
+    }
+
+    /// Line number of the key at a dotted `path` such as `database.pool.max_connections`.
+    pub fn line_of(&self, path: &str) -> Option<usize> {
+        let segments: Vec<&str> = path.split('.').collect();
+        find_line(&self.root, &segments, 1)
+    }
+
+    /// The comment lines directly above each key, without their `zZz`
+    /// markers, keyed by the dotted path of the key they describe. A blank
+    /// line between a comment and the key detaches it.
+    pub fn comments(&self) -> BTreeMap<String, String> {
+        let mut comments = BTreeMap::new();
+        collect_comments(&self.root, "", &mut comments);
+        comments
+    }
+
+    /// Renames the key at `path` to `new_key` and returns the lines that were
+    /// rewritten: the lines defining the key, its own or each dotted key such
+    /// as `db.host` and `db.port` that spells it out. A key that an
+    /// expression refers to, see `ParseOptions::expressions`, is not renamed.
+    pub fn rename_path(&mut self, path: &str, new_key: &str) -> Result<Vec<usize>, &'static str> {
+        if !lexer::is_key(new_key) {
+            return Err("It hurt itself in its confusion!");
+        }
+        self.line_of(path).ok_or("But it failed!")?;
+
+        let segments: Vec<&str> = path.split('.').collect();
+        let (_, parents) = segments.split_last().unwrap();
+        let value = self.value()?;
+        let siblings = match parents {
+            [] => Some(&value),
+            _ => value.get(&parents.join(".")),
+        };
+        if let Some(BsonValue::Map(siblings)) = siblings
+            && siblings.contains_key(new_key)
+        {
+            return Err("But it failed!");
+        }
+        if self.options.allows_expressions() {
+            let tokens = lexer::lex_str_with(&self.to_string(), &self.options)?;
+            let expressions = tokens
+                .into_iter()
+                .filter(|token| token.ttype == lexer::TokenType::Expression)
+                .map(|token| (token.line, token.literal))
+                .collect();
+            if references(&self.root, "", 1, &expressions, &value, path) {
+                return Err("But it failed!");
+            }
+        }
+        let mut renamed = self.clone();
+        let mut lines = vec![];
+        rename_in(&mut renamed.root, &segments, new_key, 1, &mut lines);
+        if lines.is_empty() {
+            return Err("But it failed!");
+        }
+
+        // Reserved keys and the like are the parser's call
+        Document::parse_with(&renamed.to_string(), &self.options)?;
+        *self = renamed;
+        Ok(lines)
+    }
+
+    /// Appends the top-level entries of `other`, comments included. Entries
+    /// of both documents that define the same value, such as `database` and
+    /// `database.host`, are resolved by `policy`.
+    pub fn extend_from(
+        &mut self,
+        other: &Document,
+        policy: ConflictPolicy,
+    ) -> Result<(), &'static str> {
+        let incoming = &other.root.entries;
+        // Incoming entries already kept, swapped in or dropped
+        let mut settled = vec![false; incoming.len()];
+        let mut entries = vec![];
+        for existing in &self.root.entries {
+            let clashes: Vec<usize> = (0..incoming.len())
+                .filter(|&i| overlaps(&existing.key, &incoming[i].key))
+                .collect();
+            match policy {
+                _ if clashes.is_empty() => entries.push(existing.clone()),
+                ConflictPolicy::Error => return Err("But it failed!"),
+                ConflictPolicy::KeepExisting => entries.push(existing.clone()),
+                // Where the first existing entry they cover stood
+                ConflictPolicy::Overwrite => entries.extend(
+                    clashes
+                        .iter()
+                        .filter(|&&i| !settled[i])
+                        .map(|&i| incoming[i].clone()),
+                ),
+            }
+            for i in clashes {
+                settled[i] = true;
+            }
+        }
+        let mut appended: Vec<Entry> = incoming
+            .iter()
+            .zip(&settled)
+            .filter(|(_, settled)| !**settled)
+            .map(|(entry, _)| entry.clone())
+            .collect();
+
+        let mut extended = self.clone();
+        // Our closing comments now sit between the old and the new entries
+        if let Some(first) = appended.first_mut() {
+            let mut leading = std::mem::take(&mut extended.root.trailing);
+            leading.append(&mut first.leading);
+            first.leading = leading;
+            extended.root.trailing = other.root.trailing.clone();
+        }
+        entries.append(&mut appended);
+        extended.root.entries = entries;
+        // `other` may have been read by laxer options, such as ones that
+        // allow a key ours reserve
+        Document::parse_with(&extended.to_string(), &self.options)?;
+        *self = extended;
+        Ok(())
+    }
+}
+
+// Whether two top-level keys define the same value, one of them holding
+// the other as `database` holds `database.host`
+fn overlaps(a: &str, b: &str) -> bool {
+    a.split('.').zip(b.split('.')).all(|(a, b)| a == b)
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines = vec![self.header.as_str()];
+        push_lines(&self.root, &mut lines);
+        let mut text = lines.join("\n");
+        if self.trailing_newline {
+            text.push('\n');
+        }
+        #[cfg(feature = "checksum")]
+        if self.sealed {
+            text = checksum::seal(&text);
+        }
+        f.write_str(&text)
+    }
+}
@@ -0,0 +1,212 @@
+use std::cmp::Ordering;
+
+#[cfg(feature = "checksum")]
+use crate::checksum;
+use crate::document::Document;
+use crate::format::{self, FormatOptions};
+use crate::lexer::{
+    self, ARRAY_CLOSE, ARRAY_OPEN, COMMENT, ENCRYPTED_CLOSE, ENCRYPTED_OPEN, FALSE, HEADER, NULL,
+    REGEX_DELIMITER, STAGES, TRUE,
+};
+use crate::parser::{BsonValue, ParseOptions};
+
+// Text that survives between quotes: no line breaks, no tabs and nothing the
+// lexer would take for a comment. Inside a Razor Leaf list a comma would
+// split the element in two.
+fn check_text(text: &str, in_array: bool) -> Result<(), &'static str> {
+    if text.contains(['\n', '\t']) || text.contains(COMMENT) || (in_array && text.contains(',')) {
+        return Err("Target is immune!");
+    }
+    Ok(())
+}
+
+// `compact` leaves out the spaces inside Razor Leaf lists
+fn scalar(value: &BsonValue, in_array: bool, compact: bool) -> Result<String, &'static str> {
+    match value {
+        BsonValue::BString(s) => {
+            check_text(s, in_array)?;
+            // Single quotes only where they spare a double quote inside
+            if s.contains('"') && !s.contains('\'') {
+                return Ok(format!("'{s}'"));
+            }
+            Ok(format!("\"{s}\""))
+        }
+        BsonValue::Number(n) if n.is_finite() => Ok(n.to_string()),
+        BsonValue::Number(_) => Err("Target is immune!"),
+        // Typed values keep their suffix
+        BsonValue::Integer(n) => Ok(format!("{n}i")),
+        BsonValue::Float(n) if n.is_finite() => Ok(format!("{n}f")),
+        BsonValue::Float(_) => Err("Target is immune!"),
+        BsonValue::Date(d) => {
+            check_text(d, in_array)?;
+            Ok(format!("\"{d}\"d"))
+        }
+        // Written back digit for digit, trailing zeros included
+        #[cfg(feature = "decimal")]
+        BsonValue::Decimal(d) => Ok(d.to_string()),
+        BsonValue::Bool(true) => Ok(TRUE.to_string()),
+        BsonValue::Bool(false) => Ok(FALSE.to_string()),
+        BsonValue::Null(()) => Ok(NULL.to_string()),
+        BsonValue::Secret(s) => {
+            check_text(s, in_array)?;
+            Ok(format!("secret(\"{s}\")"))
+        }
+        BsonValue::Regex(s) => {
+            check_text(s, in_array)?;
+            Ok(format!("{REGEX_DELIMITER}{s}{REGEX_DELIMITER}"))
+        }
+        BsonValue::Encrypted(s) => {
+            check_text(s, in_array)?;
+            Ok(format!("{ENCRYPTED_OPEN}{s}{ENCRYPTED_CLOSE}"))
+        }
+        BsonValue::Array(items) if in_array && !items.is_empty() => Err("Target is immune!"),
+        BsonValue::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| scalar(item, true, compact))
+                .collect::<Result<Vec<_>, _>>()?;
+            if compact {
+                return Ok(format!("{ARRAY_OPEN}{}{ARRAY_CLOSE}", items.join(",")));
+            }
+            Ok(format!("{ARRAY_OPEN} {} {ARRAY_CLOSE}", items.join(", ")))
+        }
+        // BSON has no spelling for a section inside a value
+        BsonValue::Map(_) => Err("Target is immune!"),
+    }
+}
+
+type Compare<'c> = &'c dyn Fn(&str, &str) -> Ordering;
+
+fn emit_block(
+    value: &BsonValue,
+    level: usize,
+    compare: Compare,
+    compact: bool,
+    options: &ParseOptions,
+    out: &mut Vec<String>,
+) -> Result<(), &'static str> {
+    let BsonValue::Map(m) = value else {
+        return Err("Target is immune!");
+    };
+    let indent = "    ".repeat(level);
+    let mut entries: Vec<_> = m.iter().collect();
+    entries.sort_by(|(a, _), (b, _)| compare(a, b));
+    for (name, child) in entries {
+        if !lexer::is_dotted_key(name) {
+            return Err("It hurt itself in its confusion!");
+        }
+        options.check_key(name)?;
+        match child {
+            BsonValue::Map(_) => {
+                let stage = STAGES.get(level).ok_or("Not enough badges!")?;
+                out.push(format!("{indent}{stage} {name} {stage}"));
+                emit_block(child, level + 1, compare, compact, options, out)?;
+            }
+            BsonValue::Array(entries)
+                if !entries.is_empty()
+                    && entries
+                        .iter()
+                        .all(|entry| matches!(entry, BsonValue::Map(_))) =>
+            {
+                let stage = STAGES.get(level).ok_or("Not enough badges!")?;
+                for entry in entries {
+                    out.push(format!("{indent}{stage} {name}[] {stage}"));
+                    emit_block(entry, level + 1, compare, compact, options, out)?;
+                }
+            }
+            _ if name.contains('.') => return Err("It hurt itself in its confusion!"),
+            _ if compact => out.push(format!("{indent}{name}~>{}", scalar(child, false, true)?)),
+            _ => out.push(format!(
+                "{indent}{name} ~> {}",
+                scalar(child, false, false)?
+            )),
+        }
+    }
+    Ok(())
+}
+
+/// Writes a value as a BSON document in the layout `format::format`
+/// produces, so that parsing the text gives the value back. Values BSON
+/// cannot spell are refused: sections deeper than the third stage, lists
+/// mixing sections with other values, nested lists, non-finite numbers,
+/// invalid keys and strings holding line breaks, tabs or `zZz`.
+pub fn emit(value: &BsonValue) -> Result<String, &'static str> {
+    emit_by(value, |a, b| a.cmp(b))
+}
+
+/// `emit` closed with a `zZz bulba-sha256:` footer, so readers can tell the
+/// file arrived whole. See `checksum`.
+#[cfg(feature = "checksum")]
+pub fn emit_sealed(value: &BsonValue) -> Result<String, &'static str> {
+    emit(value).map(|text| checksum::seal(&text))
+}
+
+/// `emit` with the keys of every section in the order of `compare` rather
+/// than alphabetically. Keys it finds equal keep their alphabetical order.
+pub fn emit_by(
+    value: &BsonValue,
+    compare: impl Fn(&str, &str) -> Ordering,
+) -> Result<String, &'static str> {
+    emit_inner(value, &compare, &FormatOptions::default())
+}
+
+/// `emit` in the layout of `options`, such as CRLF line endings.
+pub fn emit_with(value: &BsonValue, options: &FormatOptions) -> Result<String, &'static str> {
+    emit_inner(value, &|a, b| a.cmp(b), options)
+}
+
+fn emit_inner(
+    value: &BsonValue,
+    compare: Compare,
+    options: &FormatOptions,
+) -> Result<String, &'static str> {
+    let mut lines = vec![HEADER.to_string()];
+    emit_block(
+        value,
+        0,
+        compare,
+        false,
+        &ParseOptions::default(),
+        &mut lines,
+    )?;
+    format::format_with(&(lines.join("\n") + "\n"), options)
+}
+
+/// `emit` in as few bytes as BSON allows, for configs shipped through
+/// environment variables or user data: no space around arrows or inside
+/// Razor Leaf lists. The indentation and stage markers are part of the
+/// grammar and stay.
+pub fn emit_compact(value: &BsonValue) -> Result<String, &'static str> {
+    emit_compact_with(value, &ParseOptions::default())
+}
+
+/// `emit_compact` for readers that parse with `options`, such as ones that
+/// allow or reserve keys of their own.
+pub fn emit_compact_with(
+    value: &BsonValue,
+    options: &ParseOptions,
+) -> Result<String, &'static str> {
+    let mut lines = vec![HEADER.to_string()];
+    emit_block(value, 0, &|a, b| a.cmp(b), true, options, &mut lines)?;
+    let text = lines.join("\n") + "\n";
+    // The same check `format` makes on the regular layout
+    Document::parse_with(&text, options)?;
+    Ok(text)
+}
+
+/// `emit` with the keys listed in `first`, such as `app_name` and
+/// `version`, ahead of the rest in that order, in every section.
+pub fn emit_ordered(value: &BsonValue, first: &[&str]) -> Result<String, &'static str> {
+    let rank = |key: &str| first.iter().position(|f| *f == key).unwrap_or(first.len());
+    emit_by(value, |a, b| rank(a).cmp(&rank(b)).then(a.cmp(b)))
+}
+
+/// A value the way it reads inside a document: the literal for scalars and
+/// Razor Leaf lists, the body of the section without a cry for maps.
+pub fn fragment(value: &BsonValue) -> Result<String, &'static str> {
+    let text = match value {
+        BsonValue::Map(_) => emit(value)?.replacen(&format!("{HEADER}\n"), "", 1),
+        _ => scalar(value, false, false)?,
+    };
+    Ok(text.trim_end().to_string())
+}
@@ -0,0 +1,61 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+
+use crate::parser::BsonValue;
+
+/// A key of a section that may or may not be set yet, see `BsonValue::entry`.
+pub struct Entry<'e, 'a>(btree_map::Entry<'e, Cow<'a, str>, BsonValue<'a>>);
+
+impl<'e, 'a> Entry<'e, 'a> {
+    pub fn key(&self) -> &str {
+        self.0.key()
+    }
+
+    /// The value of the key, set to `default` first when it is missing.
+    pub fn or_insert(self, default: impl Into<BsonValue<'a>>) -> &'e mut BsonValue<'a> {
+        self.0.or_insert_with(|| default.into())
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> BsonValue<'a>) -> &'e mut BsonValue<'a> {
+        self.0.or_insert_with(default)
+    }
+
+    /// The value of the key, an empty section when it is missing, so that
+    /// `entry` can be chained to build nested sections.
+    pub fn or_insert_map(self) -> &'e mut BsonValue<'a> {
+        self.0.or_insert_with(|| BsonValue::Map(BTreeMap::new()))
+    }
+
+    /// The value of the key, an empty Razor Leaf list when it is missing.
+    pub fn or_insert_array(self) -> &'e mut BsonValue<'a> {
+        self.0.or_insert_with(|| BsonValue::Array(vec![]))
+    }
+
+    /// Runs `f` on the value when the key is set.
+    pub fn and_modify(self, f: impl FnOnce(&mut BsonValue<'a>)) -> Entry<'e, 'a> {
+        Entry(self.0.and_modify(f))
+    }
+}
+
+impl<'a> BsonValue<'a> {
+    /// The entry of `key` in this section, for setting keys in place the
+    /// way `BTreeMap::entry` does:
+    ///
+    /// ```
+    /// # use rs_bson::parser::BsonValue;
+    /// let mut doc = BsonValue::Map(Default::default());
+    /// doc.entry("database").or_insert_map().entry("host").or_insert("localhost");
+    /// assert_eq!(doc.get("database.host"), Some(&BsonValue::from("localhost")));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// When the value is not a section.
+    pub fn entry(&mut self, key: impl Into<Cow<'a, str>>) -> Entry<'_, 'a> {
+        match self {
+            BsonValue::Map(m) => Entry(m.entry(key.into())),
+            _ => panic!("entry called on a value that is not a section"),
+        }
+    }
+}
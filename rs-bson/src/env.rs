@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::env;
+
+use crate::lexer;
+use crate::parser::{self, BsonValue, PathConflict};
+use crate::template;
+#[cfg(feature = "schema")]
+use crate::visit::{VisitMut, walk_value_mut};
+
+/// `database.pool.max_connections` with prefix `APP_` is
+/// `APP_DATABASE_POOL_MAX_CONNECTIONS`.
+pub(crate) fn var_name(prefix: &str, path: &str) -> String {
+    format!("{prefix}{}", path.replace('.', "_").to_uppercase())
+}
+
+// Reads a variable as a BSON value, so `100` stays a number and
+// `SuperEffective` a bool. Whatever does not parse is taken as a string.
+pub(crate) fn parse_literal(raw: &str) -> BsonValue<'static> {
+    let literal = || -> Option<BsonValue<'static>> {
+        if raw.contains('\n') {
+            return None;
+        }
+        let tokens = lexer::lex_str(&format!("{}\nvalue ~> {raw}\n", lexer::HEADER)).ok()?;
+        let value = parser::parse(&tokens).ok()?.into_owned();
+        value.into_map().ok()?.remove("value")
+    };
+    literal().unwrap_or_else(|| BsonValue::BString(raw.to_string().into()))
+}
+
+// Swaps every value below a section for the variable named after its path
+#[cfg(feature = "schema")]
+struct Overrides<'p> {
+    prefix: &'p str,
+    applied: Vec<(String, String)>,
+}
+
+#[cfg(feature = "schema")]
+impl VisitMut for Overrides<'_> {
+    fn visit_value_mut(&mut self, path: &str, value: &mut BsonValue) {
+        if let BsonValue::Map(_) = value {
+            return walk_value_mut(self, path, value);
+        }
+        let name = var_name(self.prefix, path);
+        if let Ok(raw) = env::var(&name) {
+            *value = parse_literal(&raw);
+            self.applied.push((path.to_string(), name));
+        }
+    }
+}
+
+/// Replaces every value that has a variable named after its path, and
+/// returns the path and variable of each.
+#[cfg(feature = "schema")]
+pub(crate) fn apply_overrides(
+    value: &mut BsonValue<'static>,
+    prefix: &str,
+) -> Vec<(String, String)> {
+    let mut overrides = Overrides {
+        prefix,
+        applied: vec![],
+    };
+    value.visit_mut(&mut overrides);
+    overrides.applied
+}
+
+impl BsonValue<'_> {
+    /// One variable per value, named after its path, e.g.
+    /// `APP_DATABASE_POOL_MAX_CONNECTIONS=100`. Strings are passed as they
+    /// are and everything else in its BSON spelling, so `Layers::env_prefix`
+    /// reads the variables back as the same values. Arrays stay whole, and
+    /// values BSON cannot spell are left out, as in `template::vars_from`.
+    pub fn to_env_map(&self, prefix: &str) -> BTreeMap<String, String> {
+        template::vars_from(self)
+            .into_iter()
+            .map(|(path, text)| (var_name(prefix, &path), text))
+            .collect()
+    }
+}
+
+/// Builds a document out of `vars` whose names start with `prefix`. The rest
+/// of each name is split on `separator` into a path, lowercased, so with
+/// prefix `APP_` and separator `__` the variable `APP_DATABASE__HOST` sets
+/// `database.host`. Values are read like `Layers::env_prefix` reads them,
+/// and keys counting up from `0` make a list, as in `APP_WHITELIST__0`.
+/// Fails when one variable names a value and another a key inside it.
+pub fn from_vars(
+    prefix: &str,
+    separator: &str,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<BsonValue<'static>, PathConflict> {
+    let vars: BTreeMap<String, String> = vars.into_iter().collect();
+    let mut entries = vec![];
+    for (name, raw) in &vars {
+        let Some(path) = name.strip_prefix(prefix) else {
+            continue;
+        };
+        let segments: Vec<String> = path.split(separator).map(str::to_lowercase).collect();
+        if segments.iter().any(String::is_empty) {
+            continue;
+        }
+        entries.push((segments, parse_literal(raw)));
+    }
+    parser::unflatten_segments(entries)
+}
+
+/// `from_vars` over the environment of the process, nesting on `__`.
+pub fn from_env(prefix: &str) -> Result<BsonValue<'static>, PathConflict> {
+    from_vars(prefix, "__", env::vars())
+}
+
+/// `from_env` with a different nesting separator.
+pub fn from_env_with_separator(
+    prefix: &str,
+    separator: &str,
+) -> Result<BsonValue<'static>, PathConflict> {
+    from_vars(prefix, separator, env::vars())
+}
@@ -0,0 +1,218 @@
+use crate::lexer::QUOTES;
+use crate::parser::{BsonValue, DEFAULT_MAX_DEPTH};
+
+// Small enough to read at a glance: numbers, strings, names of other keys,
+// `+ - * / %`, unary minus and parentheses. Nothing can call out of it.
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Text(String),
+    Name(String),
+    Negate(Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+}
+
+/// A constant expression from a value such as `30 * 1000` or
+/// `host + ":" + port`, see `ParseOptions::expressions`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression(Node);
+
+struct Reader<'t> {
+    text: &'t str,
+    pos: usize,
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.pos += self.text[self.pos..].len() - self.text[self.pos..].trim_start().len();
+        self.text[self.pos..].chars().next()
+    }
+
+    fn sum(&mut self) -> Result<Node, &'static str> {
+        let mut node = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.product()?));
+        }
+        Ok(node)
+    }
+
+    fn product(&mut self) -> Result<Node, &'static str> {
+        let mut node = self.unary()?;
+        while let Some(op @ ('*' | '/' | '%')) = self.peek() {
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, &'static str> {
+        self.depth += 1;
+        if self.depth > DEFAULT_MAX_DEPTH {
+            return Err("Not enough badges!");
+        }
+        let node = match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Node::Negate(Box::new(self.unary()?))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.sum()?;
+                if self.peek() != Some(')') {
+                    return Err("It hurt itself in its confusion!");
+                }
+                self.pos += 1;
+                inner
+            }
+            Some(quote) if QUOTES.contains(&quote) => {
+                let rest = &self.text[self.pos + 1..];
+                let end = rest.find(quote).ok_or("It hurt itself in its confusion!")?;
+                self.pos += end + 2;
+                Node::Text(rest[..end].to_string())
+            }
+            Some(ch) if ch.is_ascii_digit() || ch == '.' => {
+                let rest = &self.text[self.pos..];
+                let len = rest
+                    .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '.'))
+                    .unwrap_or(rest.len());
+                let number = rest[..len]
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|n| n.is_finite())
+                    .ok_or("It hurt itself in its confusion!")?;
+                self.pos += len;
+                Node::Number(number)
+            }
+            Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {
+                let rest = &self.text[self.pos..];
+                let len = rest
+                    .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_' || ch == '.'))
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                Node::Name(rest[..len].to_string())
+            }
+            _ => return Err("It hurt itself in its confusion!"),
+        };
+        self.depth -= 1;
+        Ok(node)
+    }
+}
+
+fn names<'n>(node: &'n Node, out: &mut Vec<&'n str>) {
+    match node {
+        Node::Name(name) => out.push(name),
+        Node::Negate(inner) => names(inner, out),
+        Node::Binary(_, left, right) => {
+            names(left, out);
+            names(right, out);
+        }
+        Node::Number(_) | Node::Text(_) => {}
+    }
+}
+
+// Decimals on either side keep the sum exact, `0.1 + 0.2` is `0.3`
+#[cfg(feature = "decimal")]
+fn exact(
+    op: char,
+    left: &BsonValue,
+    right: &BsonValue,
+) -> Result<rust_decimal::Decimal, &'static str> {
+    let (a, b) = match (left.as_decimal(), right.as_decimal()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err("Target is immune!"),
+    };
+    match op {
+        '+' => a.checked_add(b),
+        '-' => a.checked_sub(b),
+        '*' => a.checked_mul(b),
+        '/' => a.checked_div(b),
+        '%' => a.checked_rem(b),
+        _ => None,
+    }
+    .ok_or("Target is immune!")
+}
+
+fn evaluate(
+    node: &Node,
+    resolve: &dyn Fn(&str) -> Option<BsonValue<'static>>,
+) -> Result<BsonValue<'static>, &'static str> {
+    let number = |value: &BsonValue| value.as_f64().ok_or("Target is immune!");
+    let value = match node {
+        Node::Number(n) => BsonValue::Number(*n),
+        Node::Text(text) => BsonValue::from(text.clone()),
+        Node::Name(name) => resolve(name).ok_or("The attack missed!")?,
+        Node::Negate(inner) => match evaluate(inner, resolve)? {
+            #[cfg(feature = "decimal")]
+            BsonValue::Decimal(d) => BsonValue::Decimal(-d),
+            other => BsonValue::Number(-number(&other)?),
+        },
+        Node::Binary(op, left, right) => {
+            let (left, right) = (evaluate(left, resolve)?, evaluate(right, resolve)?);
+            let text = |value: &BsonValue| match value {
+                BsonValue::BString(s) => Ok(s.to_string()),
+                other => number(other).map(|n| n.to_string()),
+            };
+            // `+` joins as soon as one side is a string
+            if *op == '+'
+                && (matches!(left, BsonValue::BString(_)) || matches!(right, BsonValue::BString(_)))
+            {
+                return Ok(BsonValue::from(text(&left)? + &text(&right)?));
+            }
+            #[cfg(feature = "decimal")]
+            if matches!(left, BsonValue::Decimal(_)) || matches!(right, BsonValue::Decimal(_)) {
+                return exact(*op, &left, &right).map(BsonValue::Decimal);
+            }
+            let (a, b) = (number(&left)?, number(&right)?);
+            let result = match op {
+                '+' => a + b,
+                '-' => a - b,
+                '*' => a * b,
+                '/' if b != 0.0 => a / b,
+                '%' if b != 0.0 => a % b,
+                _ => return Err("Target is immune!"),
+            };
+            if !result.is_finite() {
+                return Err("Target is immune!");
+            }
+            BsonValue::Number(result)
+        }
+    };
+    Ok(value)
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, &'static str> {
+        let mut reader = Reader {
+            text,
+            pos: 0,
+            depth: 0,
+        };
+        let node = reader.sum()?;
+        if reader.peek().is_some() {
+            return Err("It hurt itself in its confusion!");
+        }
+        Ok(Expression(node))
+    }
+
+    /// The keys the expression refers to, dotted as written.
+    pub fn names(&self) -> Vec<&str> {
+        let mut out = vec![];
+        names(&self.0, &mut out);
+        out
+    }
+
+    /// Works the expression out, looking names up with `resolve`. `+`
+    /// joins when either side is a string, numbers written in their
+    /// shortest form; every other operator takes numbers only. Unknown
+    /// names miss, division by zero and other type mismatches leave the
+    /// target immune.
+    pub fn evaluate(
+        &self,
+        resolve: impl Fn(&str) -> Option<BsonValue<'static>>,
+    ) -> Result<BsonValue<'static>, &'static str> {
+        evaluate(&self.0, &resolve)
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+
+use ::figment::value::{Dict, Map};
+use ::figment::{Error, Metadata, Profile, Provider};
+
+use crate::de;
+use crate::layers::PROFILE;
+use crate::lexer;
+use crate::load;
+use crate::parser::{self, BsonValue};
+
+#[derive(Debug, Clone)]
+enum Source {
+    File(PathBuf),
+    Text(String),
+}
+
+/// A BSON document as a figment `Provider`, to layer it with environment
+/// variables, defaults and other formats:
+///
+/// ```
+/// use figment::Figment;
+/// use figment::providers::Env;
+/// use rs_bson::figment::Bson;
+///
+/// let figment = Figment::new()
+///     .merge(Bson::file("App.bson"))
+///     .merge(Env::prefixed("APP_"));
+/// ```
+///
+/// The keys outside of profiles go to figment's default profile and every
+/// `(o) profile.<name> (o)` section to the profile `<name>`, so selecting a
+/// profile reads the way `Layers::profile` does.
+#[derive(Debug, Clone)]
+pub struct Bson {
+    source: Source,
+    required: bool,
+}
+
+impl Bson {
+    /// A file, read when the figment is extracted. Compressed files are
+    /// unpacked first, see `load::read`. A file that does not exist adds
+    /// nothing unless it is `required`.
+    pub fn file(path: impl AsRef<Path>) -> Bson {
+        Bson {
+            source: Source::File(path.as_ref().to_path_buf()),
+            required: false,
+        }
+    }
+
+    /// A document held in memory.
+    pub fn string(text: &str) -> Bson {
+        Bson {
+            source: Source::Text(text.to_string()),
+            required: false,
+        }
+    }
+
+    /// Whether a file that does not exist fails the extraction.
+    pub fn required(mut self, required: bool) -> Bson {
+        self.required = required;
+        self
+    }
+}
+
+fn dict(value: &BsonValue) -> Result<Dict, String> {
+    de::from_value(value).map_err(|err| err.to_string())
+}
+
+impl Provider for Bson {
+    fn metadata(&self) -> Metadata {
+        match &self.source {
+            Source::File(path) => Metadata::from("BSON file", path.as_path()),
+            Source::Text(_) => Metadata::named("BSON source string"),
+        }
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let text = match &self.source {
+            Source::File(path) if !self.required && !path.exists() => return Ok(Map::new()),
+            Source::File(path) => load::read(path).map_err(|err| err.to_string())?,
+            Source::Text(text) => text.clone(),
+        };
+        let tokens = lexer::lex_str(&text).map_err(|err| err.to_string())?;
+        let value = parser::parse(&tokens).map_err(|err| err.to_string())?;
+        let Ok(mut base) = value.into_map() else {
+            return Ok(Map::new());
+        };
+        let profiles = base
+            .remove(PROFILE)
+            .and_then(|profiles| profiles.into_map().ok())
+            .unwrap_or_default();
+        let mut data = Map::new();
+        data.insert(Profile::Default, dict(&BsonValue::Map(base))?);
+        for (name, section) in profiles {
+            data.insert(Profile::new(&name), dict(&section)?);
+        }
+        Ok(data)
+    }
+}
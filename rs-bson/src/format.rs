@@ -0,0 +1,311 @@
+#[cfg(feature = "schema")]
+use std::path::Path;
+
+#[cfg(feature = "schema")]
+use crate::bulba_schema;
+#[cfg(feature = "checksum")]
+use crate::checksum;
+#[cfg(feature = "schema")]
+use crate::de::ConvertError;
+use crate::document::Document;
+use crate::lexer;
+#[cfg(feature = "schema")]
+use crate::load::{self, FileError, LoadError};
+
+/// Name of the file `FormatOptions::discover` looks for.
+pub const CONFIG_FILE: &str = ".bulbafmt.bson";
+
+/// Line ending written after every line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Newline {
+    Lf,
+    /// `\r\n`, for tools on Windows that insist on it
+    CrLf,
+}
+
+/// Layout choices of the formatter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    /// Spaces per level. The Solar Beam rule only allows 4 for now.
+    pub indent_width: usize,
+    /// Stretch the arrows of neighbouring keys to end in the same column
+    pub align_arrows: bool,
+    /// Tildes in the shortest arrow, 1 for `~>`
+    pub min_arrow: usize,
+    /// Column past which Razor Leaf lists should wrap. Lists cannot span
+    /// lines yet, so this is accepted but has no effect.
+    pub array_wrap: Option<usize>,
+    /// Longest run of blank lines kept
+    pub max_blank_lines: usize,
+    /// Put a blank line before every top-level section
+    pub blank_before_sections: bool,
+    /// Line ending of the output. Input may use either.
+    pub newline: Newline,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            indent_width: 4,
+            align_arrows: true,
+            min_arrow: 1,
+            array_wrap: None,
+            max_blank_lines: 1,
+            blank_before_sections: false,
+            newline: Newline::Lf,
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+bulba_schema! {
+    // `.bulbafmt.bson`, every key optional
+    #[derive(serde::Deserialize)]
+    struct ConfigFile {
+        indent_width: Option<usize>,
+        align_arrows: Option<bool>,
+        min_arrow: Option<usize>,
+        array_wrap: Option<usize>,
+        max_blank_lines: Option<usize>,
+        blank_before_sections: Option<bool>,
+        newline: Option<String>,
+    }
+}
+
+#[cfg(feature = "schema")]
+impl FormatOptions {
+    /// Reads a `.bulbafmt.bson`. Keys left out keep their defaults.
+    pub fn parse(input: &str) -> Result<FormatOptions, LoadError> {
+        let file: ConfigFile = load::from_str(input)?;
+        let refuse =
+            |key: &str, message: &str| Err(LoadError::Convert(ConvertError::new(key, message)));
+        let defaults = FormatOptions::default();
+        let options = FormatOptions {
+            indent_width: file.indent_width.unwrap_or(defaults.indent_width),
+            align_arrows: file.align_arrows.unwrap_or(defaults.align_arrows),
+            min_arrow: file.min_arrow.unwrap_or(defaults.min_arrow),
+            array_wrap: file.array_wrap.or(defaults.array_wrap),
+            max_blank_lines: file.max_blank_lines.unwrap_or(defaults.max_blank_lines),
+            blank_before_sections: file
+                .blank_before_sections
+                .unwrap_or(defaults.blank_before_sections),
+            newline: match file.newline.as_deref() {
+                None => defaults.newline,
+                Some("lf") => Newline::Lf,
+                Some("crlf") => Newline::CrLf,
+                Some(_) => return refuse("newline", "Target is immune!"),
+            },
+        };
+        if options.indent_width != 4 {
+            return refuse("indent_width", "The attack missed!");
+        }
+        if options.min_arrow == 0 {
+            return refuse("min_arrow", "Target is immune!");
+        }
+        Ok(options)
+    }
+
+    /// The options for formatting `path`: the nearest `.bulbafmt.bson` in
+    /// the directory of the file or any directory above it, or the defaults
+    /// when there is none.
+    pub fn discover(path: &Path) -> Result<FormatOptions, LoadError> {
+        let start = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => std::env::current_dir().map_err(|err| LoadError::Io(FileError::new(".", err)))?,
+        };
+        for dir in start.ancestors() {
+            let candidate = dir.join(CONFIG_FILE);
+            if candidate.is_file() {
+                return FormatOptions::parse(&load::read(&candidate)?);
+            }
+        }
+        Ok(FormatOptions::default())
+    }
+}
+
+enum Line<'s> {
+    Blank,
+    // Comments and further cries are written back as they are
+    Verbatim(&'s str),
+    Section {
+        level: usize,
+        key: &'s str,
+        comment: Option<&'s str>,
+    },
+    Pair {
+        level: usize,
+        key: &'s str,
+        value: String,
+        comment: Option<&'s str>,
+    },
+}
+
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    match line.find(lexer::COMMENT) {
+        Some(comment_idx) => (&line[..comment_idx], Some(line[comment_idx..].trim_end())),
+        None => (line, None),
+    }
+}
+
+// Razor Leaf lists get one space inside the leaves and after each comma
+fn format_value(value: &str) -> String {
+    match value
+        .strip_prefix("<|")
+        .and_then(|rest| rest.strip_suffix("|>"))
+    {
+        Some(content) if content.trim().is_empty() => String::from("<| |>"),
+        Some(content) => {
+            let items: Vec<&str> = content.split(',').map(str::trim).collect();
+            format!("<| {} |>", items.join(", "))
+        }
+        None => value.to_string(),
+    }
+}
+
+fn classify(line: &str) -> Line<'_> {
+    let (code, comment) = split_comment(line);
+    let code = code.trim_end();
+    let trimmed = code.trim_start();
+    if trimmed.is_empty() {
+        return match comment {
+            Some(_) => Line::Verbatim(line.trim_end()),
+            None => Line::Blank,
+        };
+    }
+    for (i, stage) in lexer::STAGES.iter().enumerate() {
+        let key = trimmed
+            .strip_prefix(stage)
+            .and_then(|rest| rest.strip_suffix(stage))
+            .map(str::trim);
+        if let Some(key) = key {
+            return Line::Section {
+                level: i + 1,
+                key,
+                comment,
+            };
+        }
+    }
+    match lexer::split_pair(trimmed) {
+        // Dotted keys are kept as they were written
+        Some((key, arrow)) if !trimmed[key.clone()].contains('.') => Line::Pair {
+            level: (code.len() - trimmed.len()) / 4,
+            key: &trimmed[key],
+            value: format_value(trimmed[arrow.end..].trim()),
+            comment,
+        },
+        _ => Line::Verbatim(line.trim_end()),
+    }
+}
+
+fn with_comment(mut line: String, comment: Option<&str>) -> String {
+    if let Some(comment) = comment {
+        line.push(' ');
+        line.push_str(comment);
+    }
+    line
+}
+
+/// Rewrites a document in the canonical layout: four spaces per level,
+/// arrows of neighbouring keys stretched to end in the same column, one
+/// space around arrows and inside Razor Leaf lists, no trailing whitespace,
+/// no runs of blank lines and a single newline at the end. Comments stay
+/// where they are. Only valid documents are formatted.
+pub fn format(input: &str) -> Result<String, &'static str> {
+    format_with(input, &FormatOptions::default())
+}
+
+/// `format` with the layout tuned by `options`.
+pub fn format_with(input: &str, options: &FormatOptions) -> Result<String, &'static str> {
+    Document::parse(input)?;
+
+    // A footer is written afresh for the new layout
+    #[cfg(feature = "checksum")]
+    let sealed = lexer::is_sealed(input);
+    let mut lines = lexer::strip_footer(input).lines();
+    let header = lines.next().unwrap_or_default().trim_end();
+    let lines: Vec<Line> = lines.map(classify).collect();
+
+    let indent = " ".repeat(options.indent_width);
+    let mut output = vec![header.to_string()];
+    let mut blank_run = 0;
+    let mut i = 0;
+    while i < lines.len() {
+        if !matches!(lines[i], Line::Blank) {
+            blank_run = 0;
+        }
+        match &lines[i] {
+            Line::Blank => {
+                blank_run += 1;
+                if blank_run <= options.max_blank_lines {
+                    output.push(String::new());
+                }
+            }
+            Line::Verbatim(line) => output.push(line.to_string()),
+            Line::Section {
+                level,
+                key,
+                comment,
+            } => {
+                let stage = lexer::STAGES[level - 1];
+                if *level == 1 && options.blank_before_sections {
+                    // The section's own comments stay directly above it
+                    let comments = output
+                        .iter()
+                        .rev()
+                        .take_while(|line| line.trim_start().starts_with(lexer::COMMENT))
+                        .count();
+                    let at = output.len() - comments;
+                    if at > 1 && !output[at - 1].is_empty() {
+                        output.insert(at, String::new());
+                    }
+                }
+                let line = format!("{}{stage} {key} {stage}", indent.repeat(level - 1));
+                output.push(with_comment(line, *comment));
+            }
+            Line::Pair { level, .. } => {
+                // Neighbouring keys of one block share an arrow column
+                let group_end = lines[i..]
+                    .iter()
+                    .position(|line| !matches!(line, Line::Pair { level: l, .. } if l == level))
+                    .map_or(lines.len(), |len| i + len);
+                let width = lines[i..group_end]
+                    .iter()
+                    .map(|line| match line {
+                        Line::Pair { key, .. } if options.align_arrows => key.len(),
+                        _ => 0,
+                    })
+                    .max()
+                    .unwrap_or_default();
+                for line in &lines[i..group_end] {
+                    if let Line::Pair {
+                        level,
+                        key,
+                        value,
+                        comment,
+                    } = line
+                    {
+                        let arrow = "~".repeat(width.saturating_sub(key.len()) + options.min_arrow);
+                        let line = format!("{}{key} {arrow}> {value}", indent.repeat(*level));
+                        output.push(with_comment(line, *comment));
+                    }
+                }
+                i = group_end;
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    while output.len() > 1 && output.last().unwrap().is_empty() {
+        output.pop();
+    }
+    #[cfg(feature = "checksum")]
+    if sealed {
+        output.push(checksum::footer(&(output.join("\n") + "\n")));
+    }
+    let newline = match options.newline {
+        Newline::Lf => "\n",
+        Newline::CrLf => "\r\n",
+    };
+    Ok(output.join(newline) + newline)
+}
@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+
+use crate::json::JsonValue;
+use crate::lexer::{
+    ARRAY_CLOSE, ARRAY_OPEN, COMMENT, ENCRYPTED_CLOSE, ENCRYPTED_OPEN, FALSE, HEADER, KEY, NULL,
+    REGEX_DELIMITER, SECRET_CLOSE, SECRET_OPEN, STAGES, TRUE, VINE_WHIP,
+};
+
+// Escapes what regexes read specially, as `regex::escape` does
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for ch in text.chars() {
+        if r"\.+*?()|[]{}^$#&-~".contains(ch) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+fn rule(scope: &str, pattern: &str) -> JsonValue {
+    JsonValue::object(vec![
+        ("name", JsonValue::from(scope)),
+        ("match", JsonValue::from(pattern)),
+    ])
+}
+
+fn include(name: &str) -> JsonValue {
+    JsonValue::object(vec![("include", JsonValue::from(format!("#{name}")))])
+}
+
+// Scopes for the numbered groups of a pattern
+fn captures(scopes: &[&str]) -> JsonValue {
+    JsonValue::Object(
+        scopes
+            .iter()
+            .enumerate()
+            .map(|(i, scope)| {
+                (
+                    (i + 1).to_string(),
+                    JsonValue::object(vec![("name", JsonValue::from(*scope))]),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// A TextMate grammar for BSON, the format VS Code, Sublime Text and most
+/// other editors highlight with. It is assembled from the lexer's own
+/// spellings, so highlighting follows the language as it changes.
+pub fn textmate() -> JsonValue {
+    let stage = STAGES
+        .iter()
+        .map(|stage| escape(stage))
+        .collect::<Vec<_>>()
+        .join("|");
+
+    let mut repository = BTreeMap::new();
+    repository.insert(
+        "comment".to_string(),
+        rule("comment.line.bson", &format!("{}.*$", escape(COMMENT))),
+    );
+    repository.insert(
+        "header".to_string(),
+        rule(
+            "keyword.control.header.bson",
+            &format!("^{}", escape(HEADER)),
+        ),
+    );
+    repository.insert(
+        "section".to_string(),
+        JsonValue::object(vec![
+            (
+                "match",
+                JsonValue::from(format!(r"^\s*({stage})\s+(.+?)\s+({stage})")),
+            ),
+            (
+                "captures",
+                captures(&[
+                    "punctuation.definition.section.begin.bson",
+                    "entity.name.section.bson",
+                    "punctuation.definition.section.end.bson",
+                ]),
+            ),
+        ]),
+    );
+    repository.insert(
+        "pair".to_string(),
+        JsonValue::object(vec![
+            (
+                "begin",
+                JsonValue::from(format!(r"^\s*({KEY}(?:\.{KEY})*)\s*({VINE_WHIP})")),
+            ),
+            (
+                "beginCaptures",
+                captures(&[
+                    "variable.other.key.bson",
+                    "keyword.operator.assignment.bson",
+                ]),
+            ),
+            ("end", JsonValue::from("$")),
+            (
+                "patterns",
+                JsonValue::Array(vec![include("comment"), include("value")]),
+            ),
+        ]),
+    );
+    repository.insert(
+        "value".to_string(),
+        JsonValue::object(vec![(
+            "patterns",
+            JsonValue::Array(
+                [
+                    "secret",
+                    "encrypted",
+                    "string",
+                    "regex",
+                    "constant",
+                    "number",
+                    "array",
+                ]
+                .iter()
+                .map(|name| include(name))
+                .collect(),
+            ),
+        )]),
+    );
+    repository.insert(
+        "secret".to_string(),
+        JsonValue::object(vec![
+            (
+                "match",
+                JsonValue::from(format!(
+                    "({})([^\"]*)({})",
+                    escape(SECRET_OPEN),
+                    escape(SECRET_CLOSE)
+                )),
+            ),
+            (
+                "captures",
+                captures(&[
+                    "support.function.secret.bson",
+                    "string.other.reference.bson",
+                    "support.function.secret.bson",
+                ]),
+            ),
+        ]),
+    );
+    repository.insert(
+        "encrypted".to_string(),
+        JsonValue::object(vec![
+            (
+                "match",
+                JsonValue::from(format!(
+                    "({})([^{}]*)({})",
+                    escape(ENCRYPTED_OPEN),
+                    escape(ENCRYPTED_CLOSE),
+                    escape(ENCRYPTED_CLOSE)
+                )),
+            ),
+            (
+                "captures",
+                captures(&[
+                    "support.function.encrypted.bson",
+                    "string.other.ciphertext.bson",
+                    "support.function.encrypted.bson",
+                ]),
+            ),
+        ]),
+    );
+    repository.insert(
+        "string".to_string(),
+        JsonValue::object(vec![(
+            "patterns",
+            JsonValue::Array(vec![
+                rule("string.quoted.double.bson", "\"[^\"]*\""),
+                rule("string.quoted.single.bson", "'[^']*'"),
+            ]),
+        )]),
+    );
+    repository.insert(
+        "regex".to_string(),
+        rule(
+            "string.regexp.bson",
+            &format!("{REGEX_DELIMITER}[^{REGEX_DELIMITER}]*{REGEX_DELIMITER}"),
+        ),
+    );
+    repository.insert(
+        "constant".to_string(),
+        rule(
+            "constant.language.bson",
+            &format!(r"\b({}|{}|{})\b", escape(TRUE), escape(FALSE), escape(NULL)),
+        ),
+    );
+    repository.insert(
+        "number".to_string(),
+        rule(
+            "constant.numeric.bson",
+            r"-?\b\d+(\.\d+)?([eE][+-]?\d+)?[if]?\b",
+        ),
+    );
+    repository.insert(
+        "array".to_string(),
+        JsonValue::object(vec![
+            ("begin", JsonValue::from(escape(ARRAY_OPEN))),
+            ("end", JsonValue::from(escape(ARRAY_CLOSE))),
+            (
+                "beginCaptures",
+                captures(&["punctuation.definition.array.begin.bson"]),
+            ),
+            (
+                "endCaptures",
+                captures(&["punctuation.definition.array.end.bson"]),
+            ),
+            (
+                "patterns",
+                JsonValue::Array(vec![
+                    include("value"),
+                    rule("punctuation.separator.array.bson", ","),
+                ]),
+            ),
+        ]),
+    );
+
+    JsonValue::object(vec![
+        ("name", JsonValue::from("BulbaSaur Object Notation")),
+        ("scopeName", JsonValue::from("source.bson")),
+        (
+            "fileTypes",
+            JsonValue::Array(vec![
+                JsonValue::from("bson"),
+                JsonValue::from("bulbaschema"),
+            ]),
+        ),
+        (
+            "patterns",
+            JsonValue::Array(vec![
+                include("comment"),
+                include("header"),
+                include("section"),
+                include("pair"),
+            ]),
+        ),
+        ("repository", JsonValue::Object(repository)),
+    ])
+}
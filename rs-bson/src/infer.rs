@@ -0,0 +1,149 @@
+use std::collections::BTreeSet;
+
+use crate::format;
+use crate::lexer::{HEADER, STAGES, TRUE};
+use crate::parser::BsonValue;
+use crate::schema::{OPTIONAL, Schema, Type};
+
+fn type_of(value: &BsonValue) -> Type {
+    match value {
+        BsonValue::BString(_)
+        | BsonValue::Secret(_)
+        | BsonValue::Regex(_)
+        | BsonValue::Encrypted(_) => Type::String,
+        BsonValue::Bool(_) => Type::Bool,
+        BsonValue::Null(()) => Type::Null,
+        BsonValue::Array(_) => Type::Array,
+        BsonValue::Map(_) => Type::Map,
+        BsonValue::Date(_) => Type::Date,
+        BsonValue::Float(_) => Type::Number,
+        // Every kind of number
+        _ => match value.as_f64() {
+            Some(n) if n.fract() == 0.0 => Type::Int,
+            _ => Type::Number,
+        },
+    }
+}
+
+// The one type every sample fits, `Any` when they disagree
+fn common_type(samples: &[&BsonValue]) -> Type {
+    let mut types: Vec<Type> = vec![];
+    for ty in samples.iter().map(|value| type_of(value)) {
+        if !types.contains(&ty) {
+            types.push(ty);
+        }
+    }
+    match types[..] {
+        [] => Type::Any,
+        [ty] => ty,
+        [Type::Int, Type::Number] | [Type::Number, Type::Int] => Type::Number,
+        _ => Type::Any,
+    }
+}
+
+fn infer_values(samples: &[&BsonValue]) -> Schema {
+    match common_type(samples) {
+        Type::Map => infer_maps(samples),
+        Type::Array => {
+            let items: Vec<&BsonValue> = samples
+                .iter()
+                .filter_map(|value| match value {
+                    BsonValue::Array(items) => Some(items),
+                    _ => None,
+                })
+                .flatten()
+                .filter(|item| !matches!(item, BsonValue::Null(())))
+                .collect();
+            Schema::array(infer_values(&items))
+        }
+        ty => Schema::from(ty),
+    }
+}
+
+fn infer_maps(samples: &[&BsonValue]) -> Schema {
+    let maps: Vec<_> = samples
+        .iter()
+        .filter_map(|value| match value {
+            BsonValue::Map(m) => Some(m),
+            _ => None,
+        })
+        .collect();
+    let keys: BTreeSet<&str> = maps
+        .iter()
+        .flat_map(|m| m.keys())
+        .map(|k| k.as_ref())
+        .collect();
+    let mut schema = Schema::map();
+    for key in keys {
+        let values: Vec<&BsonValue> = maps.iter().filter_map(|m| m.get(key)).collect();
+        let set: Vec<&BsonValue> = values
+            .iter()
+            .copied()
+            .filter(|v| !matches!(v, BsonValue::Null(())))
+            .collect();
+        schema = schema.key(key, infer_values(&set));
+        if set.len() == maps.len() {
+            schema = schema.required(key);
+        }
+    }
+    schema
+}
+
+/// Guesses a starting schema from example documents. Every key gets the
+/// type its values share, `number` where integers and fractions mix and
+/// `any` where nothing else fits. A key is optional when some example
+/// leaves it out or sets it to `MissingNo`.
+pub fn infer(examples: &[BsonValue]) -> Schema {
+    let samples: Vec<&BsonValue> = examples.iter().collect();
+    infer_maps(&samples)
+}
+
+fn descriptor(schema: &Schema) -> String {
+    match (&schema.ty, &schema.items) {
+        (Type::Array, Some(items)) => format!("array<{}>", descriptor(items)),
+        (ty, _) => ty.to_string(),
+    }
+}
+
+fn write_section(schema: &Schema, level: usize, lines: &mut Vec<String>) {
+    let indent = "    ".repeat(level);
+    let (sections, keys): (Vec<_>, Vec<_>) = schema
+        .fields
+        .iter()
+        .partition(|(_, field)| field.ty == Type::Map && !field.fields.is_empty());
+    for (key, field) in keys {
+        let optional = if schema.required.contains(key) {
+            ""
+        } else {
+            "?"
+        };
+        lines.push(format!(
+            "{indent}{key} ~> \"{}{optional}\"",
+            descriptor(field)
+        ));
+    }
+    for (key, field) in sections {
+        let optional = !schema.required.contains(key);
+        match STAGES.get(level) {
+            Some(stage) => {
+                lines.push(format!("{indent}{stage} {key} {stage}"));
+                if optional {
+                    lines.push(format!("{indent}    {OPTIONAL} ~> {TRUE}"));
+                }
+                write_section(field, level + 1, lines);
+            }
+            None if optional => lines.push(format!("{indent}{key} ~> \"map?\"")),
+            None => lines.push(format!("{indent}{key} ~> \"map\"")),
+        }
+    }
+}
+
+/// Writes the types and optionality of `schema` as a `.bulbaschema` file
+/// that `Schema::parse` reads back. Constraints, defaults and
+/// documentation are left out, this is a starting point to edit.
+pub fn to_bulbaschema(schema: &Schema) -> String {
+    let mut lines = vec![HEADER.to_string()];
+    write_section(schema, 0, &mut lines);
+    let text = lines.join("\n") + "\n";
+    format::format(&text).unwrap_or(text)
+}
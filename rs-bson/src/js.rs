@@ -0,0 +1,83 @@
+use crate::document::Document;
+use crate::format;
+use crate::json::JsonValue;
+use crate::lsp::error_line;
+use crate::parser::ParseError;
+use crate::schema::{self, Schema};
+
+// Entry points shaped for JavaScript hosts: plain strings in, one JSON
+// object out, so the wasm bindings in `wasm/` only have to hand the result
+// to `JSON.parse`. Lines are 1-based, as editors show them.
+
+fn failure(text: &str, err: impl Into<ParseError>) -> JsonValue {
+    let err = err.into();
+    let line = err.line().unwrap_or_else(|| error_line(text) + 1);
+    JsonValue::object(vec![
+        ("ok", JsonValue::Bool(false)),
+        ("error", JsonValue::from(err.message())),
+        ("line", JsonValue::Number(line as f64)),
+    ])
+}
+
+/// `{"ok": true, "value": {...}}` with the document as JSON, or
+/// `{"ok": false, "error": "...", "line": n}`.
+pub fn parse(input: &str) -> JsonValue {
+    match Document::parse(input).and_then(|doc| doc.value()) {
+        Ok(value) => JsonValue::object(vec![
+            ("ok", JsonValue::Bool(true)),
+            ("value", JsonValue::from(&value)),
+        ]),
+        Err(err) => failure(input, err),
+    }
+}
+
+/// `{"ok": true, "text": "..."}` with the formatted document, or the same
+/// error object as `parse`.
+pub fn format(input: &str) -> JsonValue {
+    match format::format(input) {
+        Ok(text) => JsonValue::object(vec![
+            ("ok", JsonValue::Bool(true)),
+            ("text", JsonValue::from(text)),
+        ]),
+        Err(err) => failure(input, err),
+    }
+}
+
+/// `{"diagnostics": [...]}` for a document checked against a
+/// `.bulbaschema`, each with `line`, `severity`, `path` and `message`. A
+/// document that does not parse gets a single error without a path, a
+/// schema that does not parse an error object as from `parse`.
+pub fn validate(input: &str, schema: &str) -> JsonValue {
+    let schema = match Schema::parse(schema) {
+        Ok(schema) => schema,
+        Err(err) => return failure(schema, err),
+    };
+    let diagnostics = match Document::parse(input) {
+        Ok(doc) => schema::validate(&doc, &schema)
+            .iter()
+            .map(|violation| {
+                JsonValue::object(vec![
+                    (
+                        "line",
+                        violation
+                            .line
+                            .map_or(JsonValue::Null, |line| JsonValue::Number(line as f64)),
+                    ),
+                    ("severity", JsonValue::from(violation.severity.to_string())),
+                    ("path", JsonValue::from(violation.path.as_str())),
+                    ("message", JsonValue::from(violation.message.as_str())),
+                ])
+            })
+            .collect(),
+        Err(err) => vec![JsonValue::object(vec![
+            (
+                "line",
+                JsonValue::Number(err.line().unwrap_or_else(|| error_line(input) + 1) as f64),
+            ),
+            ("severity", JsonValue::from("error")),
+            ("path", JsonValue::Null),
+            ("message", JsonValue::from(err.message())),
+        ])],
+    };
+    JsonValue::object(vec![("diagnostics", JsonValue::Array(diagnostics))])
+}
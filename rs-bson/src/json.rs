@@ -0,0 +1,312 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::parser::BsonValue;
+
+/// Just enough JSON to read schemas and talk to tools, without pulling in a
+/// serialization framework.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(BTreeMap<String, JsonValue>),
+}
+
+struct Reader<'s> {
+    input: &'s str,
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(ch) = self.peek() {
+            if !ch.is_ascii_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), &'static str> {
+        if self.input[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err("It hurt itself in its confusion!")
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, &'static str> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.expect("null").map(|_| JsonValue::Null),
+            Some('t') => self.expect("true").map(|_| JsonValue::Bool(true)),
+            Some('f') => self.expect("false").map(|_| JsonValue::Bool(false)),
+            Some('"') => self.string().map(JsonValue::String),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(_) => self.number(),
+            None => Err("It hurt itself in its confusion!"),
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonValue, &'static str> {
+        let len = self.input[self.pos..]
+            .find(|ch: char| !(ch.is_ascii_digit() || "+-.eE".contains(ch)))
+            .unwrap_or(self.input.len() - self.pos);
+        let literal = &self.input[self.pos..self.pos + len];
+        self.pos += len;
+        literal
+            .parse::<f64>()
+            .map(JsonValue::Number)
+            .map_err(|_| "It hurt itself in its confusion!")
+    }
+
+    fn hex4(&mut self) -> Result<u32, &'static str> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .ok_or("It hurt itself in its confusion!")?;
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).map_err(|_| "It hurt itself in its confusion!")
+    }
+
+    fn string(&mut self) -> Result<String, &'static str> {
+        self.expect("\"")?;
+        let mut result = String::new();
+        loop {
+            let ch = self.peek().ok_or("It hurt itself in its confusion!")?;
+            self.pos += ch.len_utf8();
+            match ch {
+                '"' => return Ok(result),
+                '\\' => {
+                    let escaped = self.peek().ok_or("It hurt itself in its confusion!")?;
+                    self.pos += 1;
+                    match escaped {
+                        '"' | '\\' | '/' => result.push(escaped),
+                        'b' => result.push('\u{8}'),
+                        'f' => result.push('\u{c}'),
+                        'n' => result.push('\n'),
+                        'r' => result.push('\r'),
+                        't' => result.push('\t'),
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            // Characters outside the BMP come as surrogate pairs
+                            if (0xD800..0xDC00).contains(&code) {
+                                self.expect("\\u")?;
+                                let low = self.hex4()?;
+                                code =
+                                    0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00));
+                            }
+                            result.push(
+                                char::from_u32(code).ok_or("It hurt itself in its confusion!")?,
+                            );
+                        }
+                        _ => return Err("It hurt itself in its confusion!"),
+                    }
+                }
+                _ => result.push(ch),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonValue, &'static str> {
+        self.expect("[")?;
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                _ => return Err("It hurt itself in its confusion!"),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<JsonValue, &'static str> {
+        self.expect("{")?;
+        let mut members = BTreeMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            members.insert(key, self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                _ => return Err("It hurt itself in its confusion!"),
+            }
+        }
+    }
+}
+
+impl JsonValue {
+    pub fn parse(input: &str) -> Result<JsonValue, &'static str> {
+        let mut reader = Reader { input, pos: 0 };
+        let value = reader.value()?;
+        reader.skip_whitespace();
+        if reader.pos != input.len() {
+            return Err("It hurt itself in its confusion!");
+        }
+        Ok(value)
+    }
+
+    /// An object with `members`, for building messages by hand.
+    pub fn object(members: Vec<(&str, JsonValue)>) -> JsonValue {
+        JsonValue::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(s: &str) -> JsonValue {
+        JsonValue::String(s.to_string())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(s: String) -> JsonValue {
+        JsonValue::String(s)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for ch in s.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            ch if (ch as u32) < 0x20 => write!(f, "\\u{:04x}", ch as u32)?,
+            ch => write!(f, "{ch}")?,
+        }
+    }
+    f.write_str("\"")
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Bool(b) => write!(f, "{b}"),
+            JsonValue::Number(n) if n.is_finite() => write!(f, "{n}"),
+            JsonValue::Number(_) => f.write_str("null"),
+            JsonValue::String(s) => write_string(f, s),
+            JsonValue::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str("]")
+            }
+            JsonValue::Object(members) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl From<&JsonValue> for BsonValue<'static> {
+    fn from(json: &JsonValue) -> BsonValue<'static> {
+        match json {
+            JsonValue::Null => BsonValue::Null(()),
+            JsonValue::Bool(b) => BsonValue::Bool(*b),
+            JsonValue::Number(n) => BsonValue::Number(*n),
+            JsonValue::String(s) => BsonValue::BString(Cow::Owned(s.clone())),
+            JsonValue::Array(items) => {
+                BsonValue::Array(items.iter().map(BsonValue::from).collect())
+            }
+            JsonValue::Object(members) => BsonValue::Map(
+                members
+                    .iter()
+                    .map(|(key, value)| (Cow::Owned(key.clone()), BsonValue::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<&BsonValue<'_>> for JsonValue {
+    fn from(bson: &BsonValue<'_>) -> JsonValue {
+        match bson {
+            BsonValue::Null(()) => JsonValue::Null,
+            BsonValue::Bool(b) => JsonValue::Bool(*b),
+            BsonValue::Number(n) => JsonValue::Number(*n),
+            // Unresolved references are written out as the reference itself
+            BsonValue::BString(s) | BsonValue::Secret(s) => JsonValue::String(s.to_string()),
+            // JSON has no patterns, only their text
+            BsonValue::Regex(s) | BsonValue::Date(s) => JsonValue::String(s.to_string()),
+            // Ciphertext stays recognisable as such
+            BsonValue::Encrypted(s) => JsonValue::String(format!("ENC[{s}]")),
+            BsonValue::Array(items) => {
+                JsonValue::Array(items.iter().map(JsonValue::from).collect())
+            }
+            BsonValue::Map(members) => JsonValue::Object(
+                members
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), JsonValue::from(value)))
+                    .collect(),
+            ),
+            // Every kind of number; JSON readers take them as doubles anyway
+            _ => JsonValue::Number(bson.as_f64().unwrap_or(f64::NAN)),
+        }
+    }
+}
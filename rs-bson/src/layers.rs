@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "crypt")]
+use crate::crypt::KeyProvider;
+use crate::describe::Describe;
+use crate::env;
+use crate::lexer;
+use crate::load::{self, LoadError};
+use crate::parser::{BsonValue, ParseOptions};
+use crate::schema;
+use crate::secret::Secrets;
+use crate::spanned;
+use crate::visit::{Visit, walk_value};
+
+#[derive(Debug, Clone)]
+enum Layer {
+    File { path: PathBuf, optional: bool },
+    Value(BsonValue<'static>),
+    Env(String),
+    EnvNested { prefix: String, separator: String },
+}
+
+/// Where a key of the merged configuration got its value, see
+/// `Layers::explain`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    /// A file layer, with the line the key is on
+    File { path: PathBuf, line: Option<usize> },
+    /// A `Layers::value` layer, numbered by its position among all layers
+    Value { layer: usize },
+    /// An environment variable of `env_prefix` or `env_nested`
+    Env { name: String },
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::File {
+                path,
+                line: Some(line),
+            } => write!(f, "{}:{line}", path.display()),
+            Origin::File { path, line: None } => write!(f, "{}", path.display()),
+            Origin::Value { layer } => write!(f, "in-memory layer {layer}"),
+            Origin::Env { name } => write!(f, "environment variable {name}"),
+        }
+    }
+}
+
+// Notes the origin of every value a layer sets, sections included
+struct Record<'o, F> {
+    origins: &'o mut BTreeMap<String, Origin>,
+    origin: F,
+}
+
+impl<F: Fn(&str) -> Origin> Visit for Record<'_, F> {
+    fn visit_value(&mut self, path: &str, value: &BsonValue) {
+        if !path.is_empty() {
+            self.origins.insert(path.to_string(), (self.origin)(path));
+        }
+        walk_value(self, path, value);
+    }
+}
+
+fn record(
+    origins: &mut BTreeMap<String, Origin>,
+    value: &BsonValue,
+    origin: impl Fn(&str) -> Origin,
+) {
+    value.visit(&mut Record { origins, origin });
+}
+
+/// Builds one configuration out of several sources. Layers apply in the
+/// order they were added, each one over everything before it: sections are
+/// combined key by key, any other value of a later layer wins outright.
+/// Put `env_prefix` last for the usual defaults < files < environment order.
+#[derive(Debug, Clone, Default)]
+pub struct Layers {
+    layers: Vec<Layer>,
+    profile: Option<String>,
+    options: ParseOptions,
+}
+
+impl Layers {
+    pub fn new() -> Layers {
+        Layers {
+            layers: vec![],
+            profile: None,
+            options: ParseOptions::default(),
+        }
+    }
+
+    /// A BSON file that has to exist.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Layers {
+        self.layers.push(Layer::File {
+            path: path.into(),
+            optional: false,
+        });
+        self
+    }
+
+    /// A BSON file that is skipped when it does not exist, such as a local
+    /// override most deployments do not have.
+    pub fn optional_file(mut self, path: impl Into<PathBuf>) -> Layers {
+        self.layers.push(Layer::File {
+            path: path.into(),
+            optional: true,
+        });
+        self
+    }
+
+    /// An in-memory layer, typically defaults compiled into the program.
+    pub fn value(mut self, value: BsonValue<'static>) -> Layers {
+        self.layers.push(Layer::Value(value));
+        self
+    }
+
+    /// Environment variables named after the keys of the earlier layers:
+    /// `database.pool.max_connections` is overridden by
+    /// `APP_DATABASE_POOL_MAX_CONNECTIONS`. Only keys that already exist can
+    /// be overridden, since a flat name cannot tell an underscore inside a
+    /// key from a section boundary.
+    pub fn env_prefix(mut self, prefix: &str) -> Layers {
+        self.layers.push(Layer::Env(prefix.to_string()));
+        self
+    }
+
+    /// Environment variables spelling out their own nesting, see
+    /// `env::from_env`. Unlike `env_prefix` these can add keys no earlier
+    /// layer defines.
+    pub fn env_nested(mut self, prefix: &str, separator: &str) -> Layers {
+        self.layers.push(Layer::EnvNested {
+            prefix: prefix.to_string(),
+            separator: separator.to_string(),
+        });
+        self
+    }
+
+    /// Selects a profile of the merged result, see `BsonValue::select_profile`.
+    pub fn profile(mut self, name: &str) -> Layers {
+        self.profile = Some(name.to_string());
+        self
+    }
+
+    /// The rules every file layer is read by.
+    pub fn options(mut self, options: ParseOptions) -> Layers {
+        self.options = options;
+        self
+    }
+
+    /// Reads every layer and merges them.
+    pub fn load(&self) -> Result<BsonValue<'static>, LoadError> {
+        self.merge(&mut BTreeMap::new())
+    }
+
+    /// Merges the layers like `load`, then validates the result against
+    /// `T::schema()`, resolves secret references with `Secrets` and converts
+    /// it into `T`.
+    pub fn load_as<T: Describe + DeserializeOwned>(&self) -> Result<T, LoadError> {
+        let value = self.merge(&mut BTreeMap::new())?;
+        let violations = schema::validate_value(&value, &T::schema());
+        load::convert(value, violations, &Secrets::new())
+    }
+
+    /// Merges the layers like `load` and decrypts every `ENC[...]` value of
+    /// the result with `keys`.
+    #[cfg(feature = "crypt")]
+    pub fn load_decrypted(&self, keys: &dyn KeyProvider) -> Result<BsonValue<'static>, LoadError> {
+        let mut value = self.merge(&mut BTreeMap::new())?;
+        value.decrypt(keys).map_err(LoadError::Decrypt)?;
+        Ok(value)
+    }
+
+    /// `load_as` on the decrypted values, see `load_decrypted`. The schema
+    /// sees the plaintext.
+    #[cfg(feature = "crypt")]
+    pub fn load_as_decrypted<T: Describe + DeserializeOwned>(
+        &self,
+        keys: &dyn KeyProvider,
+    ) -> Result<T, LoadError> {
+        let value = self.load_decrypted(keys)?;
+        let violations = schema::validate_value(&value, &T::schema());
+        load::convert(value, violations, &Secrets::new())
+    }
+
+    /// Reads every layer and merges them like `load`, then tells for every
+    /// path of the result which layer supplied it. A section counts as
+    /// coming from the last layer that set anything inside it.
+    pub fn explain(&self) -> Result<BTreeMap<String, Origin>, LoadError> {
+        let mut origins = BTreeMap::new();
+        self.merge(&mut origins)?;
+        Ok(origins)
+    }
+
+    /// Which layer supplied the value at `path`, see `explain`. `None` when
+    /// the merged configuration has no such key.
+    pub fn explain_path(&self, path: &str) -> Result<Option<Origin>, LoadError> {
+        Ok(self.explain()?.remove(path))
+    }
+
+    fn merge(
+        &self,
+        origins: &mut BTreeMap<String, Origin>,
+    ) -> Result<BsonValue<'static>, LoadError> {
+        let mut merged = BsonValue::Map(Default::default());
+        for (i, layer) in self.layers.iter().enumerate() {
+            match layer {
+                Layer::File { path, optional } => {
+                    if *optional && !path.exists() {
+                        continue;
+                    }
+                    let input = load::read(path)?;
+                    let tokens = lexer::lex_str_with(&input, &self.options)?;
+                    let tree = spanned::parse_spanned(&tokens, &self.options)?;
+                    let value = tree.to_value();
+                    record(origins, &value, |key| Origin::File {
+                        path: path.clone(),
+                        line: tree.get(key).map(|node| node.span.line),
+                    });
+                    merged.overlay(value);
+                }
+                Layer::Value(value) => {
+                    record(origins, value, |_| Origin::Value { layer: i });
+                    merged.overlay(value.clone());
+                }
+                Layer::Env(prefix) => {
+                    for (path, name) in env::apply_overrides(&mut merged, prefix) {
+                        origins.insert(path, Origin::Env { name });
+                    }
+                }
+                Layer::EnvNested { prefix, separator } => {
+                    let nested = env::from_env_with_separator(prefix, separator)
+                        .map_err(LoadError::Conflict)?;
+                    record(origins, &nested, |path| {
+                        let parts: Vec<String> = path.split('.').map(str::to_uppercase).collect();
+                        Origin::Env {
+                            name: format!("{prefix}{}", parts.join(separator)),
+                        }
+                    });
+                    merged.overlay(nested)
+                }
+            }
+        }
+        let merged = match &self.profile {
+            Some(name) => {
+                let merged = merged
+                    .select_profile(name)
+                    .map_err(|_| LoadError::Profile(name.clone()))?;
+                // Keys of the selected profile now live at the top level
+                let selected = format!("{PROFILE}.{name}.");
+                let from_profile: Vec<(String, Origin)> = origins
+                    .iter()
+                    .filter_map(|(path, origin)| {
+                        let path = path.strip_prefix(&selected)?;
+                        Some((path.to_string(), origin.clone()))
+                    })
+                    .collect();
+                origins.extend(from_profile);
+                merged
+            }
+            None => merged,
+        };
+        // Keys a later layer replaced along with the value around them
+        origins.retain(|path, _| merged.get(path).is_some());
+        Ok(merged)
+    }
+}
+
+// `(o) profile.<name> (o)` opens `<name>` inside this section
+pub(crate) const PROFILE: &str = "profile";
+
+impl<'a> BsonValue<'a> {
+    /// Names of the profiles the document defines, i.e. of its
+    /// `(o) profile.<name> (o)` sections.
+    pub fn profiles(&self) -> Vec<&str> {
+        match self.get(PROFILE) {
+            Some(BsonValue::Map(m)) => m.keys().map(|key| key.as_ref()).collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The document as seen from one profile: the base keys with the
+    /// `profile.<name>` section laid over them. Every profile section is
+    /// dropped from the result. Naming a profile the document does not
+    /// define is refused, so a typo does not silently fall back to the base.
+    pub fn select_profile(self, name: &str) -> Result<BsonValue<'a>, &'static str> {
+        let Ok(mut base) = self.into_map() else {
+            return Err("But it failed!");
+        };
+        let Some(Ok(mut profiles)) = base.remove(PROFILE).map(BsonValue::into_map) else {
+            return Err("But it failed!");
+        };
+        let profile = profiles.remove(name).ok_or("But it failed!")?;
+        let mut selected = BsonValue::Map(base);
+        selected.overlay(profile);
+        Ok(selected)
+    }
+}
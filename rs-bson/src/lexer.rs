@@ -1,6 +1,53 @@
-use regex::Regex;
+use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Lines};
+use std::ops::Range;
+
+#[cfg(feature = "checksum")]
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "checksum")]
+use crate::checksum;
+use crate::expr::Expression;
+use crate::parser::{ParseError, ParseOptions, Utf8Policy};
+
+// The spellings of the language. `grammar` builds editor highlighting from
+// the same constants, so the two cannot drift apart.
+pub const HEADER: &str = "BULBA!";
+pub const COMMENT: &str = "zZz";
+/// Section markers of the three evolution stages, outermost first
+pub const STAGES: [&str; 3] = ["(o)", "(O)", "(@)"];
+pub const TRUE: &str = "SuperEffective";
+pub const FALSE: &str = "NotVeryEffective";
+pub const NULL: &str = "MissingNo";
+pub const ARRAY_OPEN: &str = "<|";
+pub const ARRAY_CLOSE: &str = "|>";
+/// Either quote opens a string, which ends at the same quote
+pub const QUOTES: [char; 2] = ['"', '\''];
+pub const SECRET_OPEN: &str = "secret(\"";
+pub const SECRET_CLOSE: &str = "\")";
+/// Wrap a value encrypted by a `crypt::KeyProvider`, as in `ENC[...]`
+pub const ENCRYPTED_OPEN: &str = "ENC[";
+pub const ENCRYPTED_CLOSE: &str = "]";
+/// Opens and closes a regex literal such as `/^poke[0-9]+$/`
+pub const REGEX_DELIMITER: char = '/';
+/// Ends a line that goes on in the next one, see `Lexer::push_line`
+pub const CONTINUATION: char = '\\';
+/// Byte order mark some editors write at the start of UTF-8 files
+pub const BOM: char = '\u{feff}';
+/// Follows the comment marker on a checksum footer line,
+/// `zZz bulba-sha256: <hex>`
+pub const FOOTER_MARKER: &str = "bulba-sha256:";
+/// What a footer that does not match its document fails with
+pub const CORRUPTED: &str = "The save file is corrupted!";
+/// Regex for keys
+pub const KEY: &str = "[a-zA-Z_][a-zA-Z0-9_]*";
+/// Regex for the condition of a conditional section, `when env == "prod"`
+pub const CONDITION: &str =
+    r#"^when\s+([a-zA-Z_][a-zA-Z0-9_]*)\s*(==|!=)\s*(?:"([^"]*)"|'([^']*)')$"#;
+/// Regex for the assignment arrow
+pub const VINE_WHIP: &str = "~{1,}>";
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
@@ -14,19 +61,30 @@ pub enum TokenType {
     Number,
     Bool,
     Null,
+    Secret,
+    /// A date or date-time from a `d` suffixed string, without the quotes
+    Date,
+    /// The ciphertext of an encrypted value, without `ENC[` and `]`
+    Encrypted,
+    /// The pattern of a regex literal, without the slashes
+    Regex,
     ArrayStart,
     ArrayEnd,
     Comma,
+    /// A value worked out after parsing, see `ParseOptions::expressions`
+    Expression,
     Eof,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub ttype: TokenType,
     pub literal: String,
-    line: usize,
+    /// 1-based line the token was read from
+    pub line: usize,
     pub level: usize,
+    /// Bytes of the line the token covers, after tabs were read as spaces
+    pub span: Range<usize>,
 }
 
 fn count_whitespaces_at_start(input: &str) -> usize {
@@ -37,79 +95,295 @@ fn count_whitespaces_at_start(input: &str) -> usize {
         .sum()
 }
 
+/// Whether `key` matches `KEY`
+pub fn is_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// Whether `key` is keys joined by dots, as in `database.pool`
+pub fn is_dotted_key(key: &str) -> bool {
+    key.split('.').all(is_key)
+}
+
+/// The key and the arrow of a `key ~~~> value` line, where the key may be
+/// dotted. The value is whatever follows the arrow.
+pub fn split_pair(line: &str) -> Option<(Range<usize>, Range<usize>)> {
+    let key_end = line
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_' || ch == '.'))
+        .unwrap_or(line.len());
+    if !is_dotted_key(&line[..key_end]) {
+        return None;
+    }
+    let rest = &line[key_end..];
+    let arrow_start = line.len() - rest.trim_start().len();
+    let tildes = line[arrow_start..].len() - line[arrow_start..].trim_start_matches('~').len();
+    let arrow_end = arrow_start + tildes + 1;
+    if tildes == 0 || line.get(arrow_end - 1..arrow_end) != Some(">") {
+        return None;
+    }
+    Some((0..key_end, arrow_start..arrow_end))
+}
+
+/// The context name, whether it must equal the value, and the value of a
+/// conditional section name such as `when env == "production"`
+pub fn condition(name: &str) -> Option<(&str, bool, &str)> {
+    let rest = name.strip_prefix("when")?;
+    let rest = rest.strip_prefix(char::is_whitespace)?.trim_start();
+    let context_end = rest
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+        .unwrap_or(rest.len());
+    let (context, rest) = rest.split_at(context_end);
+    let rest = rest.trim_start();
+    let (equal, rest) = match rest.get(..2)? {
+        "==" => (true, &rest[2..]),
+        "!=" => (false, &rest[2..]),
+        _ => return None,
+    };
+    let rest = rest.trim_start();
+    let quote = rest.chars().next().filter(|ch| QUOTES.contains(ch))?;
+    let value = rest[1..].strip_suffix(quote)?;
+    if !is_key(context) || value.contains(quote) {
+        return None;
+    }
+    Some((context, equal, value))
+}
+
+fn is_number(value: &str) -> bool {
+    value.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+// `YYYY-MM-DD`, optionally followed by an RFC 3339 time of day
+/// The digest on a checksum footer line, or `None` for any other line.
+pub fn parse_footer(line: &str) -> Option<&str> {
+    let digest = line
+        .strip_prefix(COMMENT)?
+        .trim_start()
+        .strip_prefix(FOOTER_MARKER)?;
+    Some(digest.trim())
+}
+
+/// `text` without its checksum footer and the blank lines after it.
+pub fn strip_footer(text: &str) -> &str {
+    let trimmed = text.trim_end();
+    match trimmed.rsplit_once('\n') {
+        Some((body, last)) if parse_footer(last.trim_end()).is_some() => &text[..body.len() + 1],
+        _ => text,
+    }
+}
+
+/// Whether `text` ends in a checksum footer. The lexer checks it matches,
+/// and refuses it without the `checksum` feature.
+pub fn is_sealed(text: &str) -> bool {
+    strip_footer(text).len() != text.len()
+}
+
+pub(crate) fn is_date(text: &str) -> bool {
+    // Digits in `text[at..at + n]`, as a number
+    let field = |at: usize, n: usize| {
+        text.get(at..at + n)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+            .map(|digits| digits.parse::<u32>().unwrap())
+    };
+    let punct = |at: usize, ch: u8| text.as_bytes().get(at) == Some(&ch);
+    let (Some(year), Some(month), Some(day)) = (field(0, 4), field(5, 2), field(8, 2)) else {
+        return false;
+    };
+    if !punct(4, b'-') || !punct(7, b'-') {
+        return false;
+    }
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=12).contains(&month) || !(1..=days).contains(&day) {
+        return false;
+    }
+    if text.len() == 10 {
+        return true;
+    }
+
+    let (Some(hour), Some(minute), Some(second)) = (field(11, 2), field(14, 2), field(17, 2))
+    else {
+        return false;
+    };
+    if !punct(10, b'T') || !punct(13, b':') || !punct(16, b':') {
+        return false;
+    }
+    let mut rest = &text[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction.len()
+            - fraction
+                .trim_start_matches(|ch: char| ch.is_ascii_digit())
+                .len();
+        if digits == 0 {
+            return false;
+        }
+        rest = &fraction[digits..];
+    }
+    let (offset_hour, offset_minute) = match rest.as_bytes() {
+        [] | [b'Z'] => (0, 0),
+        [b'+' | b'-', h1, h2, b':', m1, m2]
+            if [h1, h2, m1, m2].iter().all(|b| b.is_ascii_digit()) =>
+        {
+            (
+                (h1 - b'0') * 10 + (h2 - b'0'),
+                (m1 - b'0') * 10 + (m2 - b'0'),
+            )
+        }
+        _ => return false,
+    };
+    // 60 seconds for a leap second
+    hour < 24 && minute < 60 && second <= 60 && offset_hour < 24 && offset_minute < 60
+}
+
+// `start` is where `value` begins in its line
 fn tokenize_value(
     value: &str,
+    start: usize,
     line_num: usize,
+    options: &ParseOptions,
     tokens: &mut Vec<Token>,
 ) -> Result<(), &'static str> {
     if value.is_empty() {
         return Ok(());
     }
+    let span = start..start + value.len();
 
-    // String literal
-    if value.starts_with("\"") && value.ends_with("\"") {
+    // String literal, in either quotes; there are no escapes, so the other
+    // quote can appear inside as it is
+    if value.len() >= 2
+        && let Some(quote) = value.chars().next().filter(|ch| QUOTES.contains(ch))
+        && value.ends_with(quote)
+    {
         tokens.push(Token {
             ttype: TokenType::TString,
             literal: value[1..value.len() - 1].to_string(),
             line: line_num,
             level: 0,
+            span,
         });
         return Ok(());
     }
 
+    // Secret reference: secret("scheme:location"), resolved at load time
+    if let Some(reference) = value
+        .strip_prefix(SECRET_OPEN)
+        .and_then(|rest| rest.strip_suffix(SECRET_CLOSE))
+    {
+        tokens.push(Token {
+            ttype: TokenType::Secret,
+            literal: reference.to_string(),
+            line: line_num,
+            level: 0,
+            span,
+        });
+        return Ok(());
+    }
+
+    // Encrypted value: ENC[ciphertext], decrypted at load time
+    if let Some(ciphertext) = value
+        .strip_prefix(ENCRYPTED_OPEN)
+        .and_then(|rest| rest.strip_suffix(ENCRYPTED_CLOSE))
+    {
+        tokens.push(Token {
+            ttype: TokenType::Encrypted,
+            literal: ciphertext.to_string(),
+            line: line_num,
+            level: 0,
+            span,
+        });
+        return Ok(());
+    }
+
+    // Regex literal: /pattern/, kept as written
+    if value.len() >= 2
+        && let Some(pattern) = value
+            .strip_prefix(REGEX_DELIMITER)
+            .and_then(|rest| rest.strip_suffix(REGEX_DELIMITER))
+    {
+        tokens.push(Token {
+            ttype: TokenType::Regex,
+            literal: pattern.to_string(),
+            line: line_num,
+            level: 0,
+            span,
+        });
+        return Ok(());
+    }
+
+    // Extra spellings of the keywords count as the keywords themselves
+    let keyword = options.keyword_for(value).unwrap_or(value);
+
     // Bool true
-    if value == "SuperEffective" {
+    if keyword == TRUE {
         tokens.push(Token {
             ttype: TokenType::Bool,
             literal: String::from("true"),
             line: line_num,
             level: 0,
+            span,
         });
         return Ok(());
     }
     // Bool false
-    if value == "NotVeryEffective" {
+    if keyword == FALSE {
         tokens.push(Token {
             ttype: TokenType::Bool,
             literal: String::from("false"),
             line: line_num,
             level: 0,
+            span,
         });
         return Ok(());
     }
 
     // Null
-    if value == "MissingNo" {
+    if keyword == NULL {
         tokens.push(Token {
             ttype: TokenType::Null,
             literal: String::from(""),
             line: line_num,
             level: 0,
+            span,
         });
         return Ok(());
     }
 
     // Array <| ... |>
-    if value.starts_with("<|") && value.ends_with("|>") {
+    if value.len() >= ARRAY_OPEN.len() + ARRAY_CLOSE.len()
+        && value.starts_with(ARRAY_OPEN)
+        && value.ends_with(ARRAY_CLOSE)
+    {
         tokens.push(Token {
             ttype: TokenType::ArrayStart,
             literal: String::from(""),
             line: line_num,
             level: 0,
+            span: start..start + ARRAY_OPEN.len(),
         });
-        let array_content = value[2..value.len() - 2].trim();
-        if !array_content.is_empty() {
-            let elements = array_content.split(',');
-            for (i, elem) in elements.enumerate() {
+        let array_content = &value[ARRAY_OPEN.len()..value.len() - ARRAY_CLOSE.len()];
+        if !array_content.trim().is_empty() {
+            let mut elem_start = start + ARRAY_OPEN.len();
+            for (i, elem) in array_content.split(',').enumerate() {
                 if i > 0 {
                     tokens.push(Token {
                         ttype: TokenType::Comma,
                         literal: String::from(""),
                         line: line_num,
                         level: 0,
+                        span: elem_start - 1..elem_start,
                     });
                 }
-                tokenize_value(elem.trim(), line_num, tokens)?;
+                let lead = elem.len() - elem.trim_start().len();
+                tokenize_value(elem.trim(), elem_start + lead, line_num, options, tokens)?;
+                elem_start += elem.len() + 1;
             }
         }
         tokens.push(Token {
@@ -117,155 +391,510 @@ fn tokenize_value(
             literal: String::from(""),
             line: line_num,
             level: 0,
+            span: span.end - ARRAY_CLOSE.len()..span.end,
         });
         return Ok(());
     }
 
-    // Number
-    if value.parse::<f64>().is_ok() {
+    // Type suffixes: 8080i, 1.5f and "2024-01-01"d. Numbers keep theirs so
+    // the parser can tell them from plain ones.
+    if let Some(digits) = value.strip_suffix('i')
+        && is_number(digits)
+    {
+        if digits.parse::<i128>().is_err() {
+            return Err("Target is immune!");
+        }
         tokens.push(Token {
             ttype: TokenType::Number,
             literal: value.to_string(),
             line: line_num,
             level: 0,
+            span,
         });
         return Ok(());
     }
-
-    Err("Target is immune!")
-}
-
-fn tokenize_line(
-    line: &mut str,
-    line_num: usize,
-    tokens: &mut Vec<Token>,
-) -> Result<(), &'static str> {
-    // Evolution stage: (o) key (o)
-    if line.starts_with("(o) ") && line.ends_with(" (o)") {
+    if value.strip_suffix('f').is_some_and(is_number) {
         tokens.push(Token {
-            ttype: TokenType::SectionOpen,
-            literal: String::from(""),
-            line: line_num,
-            level: 1,
-        });
-        tokens.push(Token {
-            ttype: TokenType::Identifier,
-            literal: line[4..line.len() - 4].to_string(),
-            line: line_num,
-            level: 1,
-        });
-        tokens.push(Token {
-            ttype: TokenType::SectionClose,
-            literal: String::from(""),
+            ttype: TokenType::Number,
+            literal: value.to_string(),
             line: line_num,
-            level: 1,
+            level: 0,
+            span,
         });
         return Ok(());
     }
-    if line.starts_with("(O) ") && line.ends_with(" (O)") {
-        tokens.push(Token {
-            ttype: TokenType::SectionOpen,
-            literal: String::from(""),
-            line: line_num,
-            level: 2,
-        });
+    if let Some(quoted) = value.strip_suffix('d')
+        && quoted.len() >= 2
+        && let Some(quote) = quoted.chars().next().filter(|ch| QUOTES.contains(ch))
+        && quoted.ends_with(quote)
+    {
+        let date = &quoted[1..quoted.len() - 1];
+        if !is_date(date) {
+            return Err("Target is immune!");
+        }
         tokens.push(Token {
-            ttype: TokenType::Identifier,
-            literal: line[4..line.len() - 4].to_string(),
+            ttype: TokenType::Date,
+            literal: date.to_string(),
             line: line_num,
-            level: 2,
-        });
-        tokens.push(Token {
-            ttype: TokenType::SectionClose,
-            literal: String::from(""),
-            line: line_num,
-            level: 2,
+            level: 0,
+            span,
         });
         return Ok(());
     }
-    if line.starts_with("(@) ") && line.ends_with(" (@)") {
-        tokens.push(Token {
-            ttype: TokenType::SectionOpen,
-            literal: String::from(""),
-            line: line_num,
-            level: 3,
-        });
+
+    // Number, but not the NaN or inf spellings Rust would accept
+    if is_number(value) {
         tokens.push(Token {
-            ttype: TokenType::Identifier,
-            literal: line[4..line.len() - 4].to_string(),
+            ttype: TokenType::Number,
+            literal: value.to_string(),
             line: line_num,
-            level: 3,
+            level: 0,
+            span,
         });
+        return Ok(());
+    }
+
+    if options.allows_expressions() && Expression::parse(value).is_ok() {
         tokens.push(Token {
-            ttype: TokenType::SectionClose,
-            literal: String::from(""),
+            ttype: TokenType::Expression,
+            literal: value.to_string(),
             line: line_num,
-            level: 3,
+            level: 0,
+            span,
         });
         return Ok(());
     }
 
-    // Vine whip: key ~~~> value
-    let re = Regex::new(r"^([a-zA-Z_][a-zA-Z0-9_]*)\s*(~{1,}>)\s*(.*)$").unwrap();
-    match re.captures(line) {
-        Some(matches) => {
+    Err("Target is immune!")
+}
+
+// `line` comes without its indentation, which is `offset` bytes long
+fn tokenize_line(
+    line: &mut str,
+    offset: usize,
+    line_num: usize,
+    options: &ParseOptions,
+    tokens: &mut Vec<Token>,
+) -> Result<(), &'static str> {
+    // Evolution stage: (o) key (o)
+    for (i, stage) in STAGES.iter().enumerate() {
+        let level = i + 1;
+        if line.starts_with(&format!("{stage} ")) && line.ends_with(&format!(" {stage}")) {
+            let inner = &line[stage.len()..line.len() - stage.len()];
+            let key = inner.trim();
+            let key_start = offset + stage.len() + inner.len() - inner.trim_start().len();
+            // A dotted name such as `profile.test` opens nested sections, and
+            // a trailing `[]` repeats a section into a list of them
+            let plain = key.strip_suffix("[]").unwrap_or(key);
+            if !is_dotted_key(plain) && condition(key).is_none() {
+                return Err("It hurt itself in its confusion!");
+            }
+            tokens.push(Token {
+                ttype: TokenType::SectionOpen,
+                literal: String::from(""),
+                line: line_num,
+                level,
+                span: offset..offset + stage.len(),
+            });
             tokens.push(Token {
                 ttype: TokenType::Identifier,
-                literal: matches.get(1).unwrap().as_str().to_string(),
+                literal: key.to_string(),
                 line: line_num,
-                level: 0,
+                level,
+                span: key_start..key_start + key.len(),
             });
             tokens.push(Token {
-                ttype: TokenType::VineWhip,
+                ttype: TokenType::SectionClose,
                 literal: String::from(""),
                 line: line_num,
-                level: 0,
+                level,
+                span: offset + line.len() - stage.len()..offset + line.len(),
             });
+            return Ok(());
+        }
+    }
+
+    // Vine whip: key ~~~> value, where a dotted key nests, `a.b ~> 1`
+    let Some((key, arrow)) = split_pair(line) else {
+        return Err("It hurt itself in its confusion!");
+    };
+    let shift = |range: Range<usize>| offset + range.start..offset + range.end;
+    tokens.push(Token {
+        ttype: TokenType::Identifier,
+        literal: line[key.clone()].to_string(),
+        line: line_num,
+        level: 0,
+        span: shift(key),
+    });
+    tokens.push(Token {
+        ttype: TokenType::VineWhip,
+        literal: String::from(""),
+        line: line_num,
+        level: 0,
+        span: shift(arrow.clone()),
+    });
+
+    let value = &line[arrow.end..];
+    let lead = value.len() - value.trim_start().len();
+    tokenize_value(
+        value.trim(),
+        offset + arrow.end + lead,
+        line_num,
+        options,
+        tokens,
+    )
+}
+
+/// Something a lenient `ParseOptions` let through that strict BSON refuses
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub message: String,
+}
+
+pub fn lex(file: File) -> Result<Vec<Token>, ParseError> {
+    lex_reader(BufReader::new(file), &ParseOptions::default())
+}
+
+pub fn lex_str(input: &str) -> Result<Vec<Token>, ParseError> {
+    lex_str_with(input, &ParseOptions::default())
+}
+
+/// `lex_str` with the rules of `options`, such as a header of your own.
+pub fn lex_str_with(input: &str, options: &ParseOptions) -> Result<Vec<Token>, ParseError> {
+    lex_reader(input.as_bytes(), options)
+}
+
+/// `lex_str_with` that also hands back the warnings of lenient options, such
+/// as every line whose tabs were read as spaces.
+pub fn lex_str_with_warnings(
+    input: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<Token>, Vec<Warning>), ParseError> {
+    #[cfg(feature = "trace")]
+    let span = crate::trace::lexing();
+    let lexed = lex_lines(input.lines().map(|line| Ok(Cow::Borrowed(line))), options)?;
+    #[cfg(feature = "trace")]
+    crate::trace::lexed(&span, &lexed.0);
+    Ok(lexed)
+}
+
+/// `lex_str_with` on raw bytes, for machine-written files whose string
+/// values may hold bytes that are not UTF-8. What becomes of those is up to
+/// `ParseOptions::invalid_utf8`.
+pub fn lex_bytes(bytes: &[u8], options: &ParseOptions) -> Result<Vec<Token>, ParseError> {
+    check_encoding(bytes)?;
+    #[cfg(feature = "trace")]
+    let span = crate::trace::lexing();
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    // The quote a string continued from the line before is still in
+    let mut quote = None;
+    let lines = bytes
+        .split(|byte| *byte == b'\n')
+        .filter(|_| !bytes.is_empty())
+        .map(|line| decode_line(line, options.utf8_policy(), &mut quote).map(Cow::Owned));
+    let (tokens, _) = lex_lines(lines, options)?;
+    #[cfg(feature = "trace")]
+    crate::trace::lexed(&span, &tokens);
+    Ok(tokens)
+}
+
+// Pushes every line to a lexer, noting where the one that fails stopped
+fn lex_lines<'l>(
+    lines: impl Iterator<Item = Result<Cow<'l, str>, &'static str>>,
+    options: &ParseOptions,
+) -> Result<(Vec<Token>, Vec<Warning>), ParseError> {
+    let mut lexer = Lexer::with_options(options);
+    for line in lines {
+        line.and_then(|line| lexer.push_line(&line))
+            .map_err(|message| lexer.locate(message))?;
+    }
+    let warnings = lexer.warnings().to_vec();
+    let end = lexer.position();
+    let tokens = lexer
+        .finish()
+        .map_err(|message| ParseError::at(message, end))?;
+    Ok((tokens, warnings))
+}
 
-            let value = matches.get(3).unwrap().as_str().trim();
-            tokenize_value(value, line_num, tokens)
+// A line of `lex_bytes` as text. Bytes that are not UTF-8 are only let
+// through inside quotes and comments.
+fn decode_line(
+    line: &[u8],
+    policy: Utf8Policy,
+    quote: &mut Option<char>,
+) -> Result<String, &'static str> {
+    let mut text = String::new();
+    for chunk in line.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            match *quote {
+                None if QUOTES.contains(&ch) => *quote = Some(ch),
+                Some(open) if open == ch => *quote = None,
+                _ => {}
+            }
+        }
+        text.push_str(chunk.valid());
+        let invalid = chunk.invalid();
+        if invalid.is_empty() {
+            continue;
+        }
+        if quote.is_none() && !text.contains(COMMENT) {
+            return Err("It hurt itself in its confusion!");
         }
-        None => Err("It hurt itself in its confusion!"),
+        match policy {
+            Utf8Policy::Error => return Err("It hurt itself in its confusion!"),
+            Utf8Policy::Replace => text.push(char::REPLACEMENT_CHARACTER),
+            Utf8Policy::PassThrough => text.extend(invalid.iter().map(|byte| char::from(*byte))),
+        }
+    }
+    if !text.trim_end().ends_with(CONTINUATION) {
+        *quote = None;
     }
+    Ok(text)
+}
+
+/// Refuses UTF-16 before it lexes as garbage. It is recognised by its byte
+/// order mark, or by the NUL byte next to every ASCII character without one.
+pub fn check_encoding(bytes: &[u8]) -> Result<(), &'static str> {
+    let utf16 = bytes.starts_with(&[0xff, 0xfe])
+        || bytes.starts_with(&[0xfe, 0xff])
+        || (bytes.len() >= 2 && (bytes[0] == 0) != (bytes[1] == 0));
+    if utf16 {
+        return Err("Wrong region: BSON is UTF-8 only");
+    }
+    Ok(())
+}
+
+fn lex_reader<R: BufRead>(reader: R, options: &ParseOptions) -> Result<Vec<Token>, ParseError> {
+    #[cfg(feature = "trace")]
+    let span = crate::trace::lexing();
+    let tokens: Vec<Token> = Tokens::new(reader, options).collect::<Result<_, _>>()?;
+    #[cfg(feature = "trace")]
+    crate::trace::lexed(&span, &tokens);
+    Ok(tokens)
+}
+
+/// The tokens of a document, read a line at a time as they are asked for.
+/// Hand it to `parser::parse_stream` to parse a big file without holding
+/// all of its tokens. It ends after the first error.
+pub struct Tokens<R> {
+    lines: Lines<R>,
+    // None once the tokens ran out or failed
+    lexer: Option<Lexer>,
+    ready: VecDeque<Token>,
+    failed: Option<ParseError>,
+}
+
+impl<R: BufRead> Tokens<R> {
+    pub fn new(mut reader: R, options: &ParseOptions) -> Tokens<R> {
+        let failed = match reader.fill_buf() {
+            Ok(start) => check_encoding(start).err(),
+            Err(_) => Some("It hurt itself in its confusion!"),
+        };
+        let failed = failed.map(ParseError::Document);
+        Tokens {
+            lines: reader.lines(),
+            lexer: Some(Lexer::with_options(options)),
+            ready: VecDeque::new(),
+            failed,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Tokens<R> {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(token) = self.ready.pop_front() {
+                return Some(Ok(token));
+            }
+            let lexer = self.lexer.as_mut()?;
+            let pushed = match self.failed.take() {
+                Some(err) => Err(err),
+                None => match self.lines.next() {
+                    Some(Ok(line)) => lexer
+                        .push_line(&line)
+                        .map_err(|message| lexer.locate(message)),
+                    // Anything but UTF-8 confuses the parser, see the spec
+                    Some(Err(_)) => Err(lexer.locate("It hurt itself in its confusion!")),
+                    None => {
+                        let lexer = self.lexer.take()?;
+                        let end = lexer.position();
+                        return match lexer.finish() {
+                            Ok(tokens) => {
+                                self.ready.extend(tokens);
+                                self.ready.pop_front().map(Ok)
+                            }
+                            Err(message) => Some(Err(ParseError::at(message, end))),
+                        };
+                    }
+                },
+            };
+            if let Err(err) = pushed {
+                self.lexer = None;
+                return Some(Err(err));
+            }
+            self.ready.extend(lexer.tokens.drain(..));
+        }
+    }
+}
+
+/// Tokenizes a document one line at a time, for sources `lex` cannot read
+/// from such as async streams. Feed it every line without its terminator,
+/// then take the tokens with `finish`.
+#[derive(Debug, Default)]
+pub struct Lexer {
+    tokens: Vec<Token>,
+    line_num: usize,
+    options: ParseOptions,
+    warnings: Vec<Warning>,
+    // A line ending in the continuation marker, with its number, waiting
+    // for the next one
+    continued: Option<(usize, String)>,
+    // Every line so far, for a checksum footer
+    #[cfg(feature = "checksum")]
+    checksum: Sha256,
+    sealed: bool,
+    // Lines pushed so far, continued ones included
+    lines: usize,
+    // The line and column the last line that failed stopped at
+    failed: Option<(usize, usize)>,
 }
 
-pub fn lex(file: File) -> Result<Vec<Token>, &'static str> {
-    let mut tokens: Vec<Token> = vec![];
-    let mut line_num = 0;
-    let reader = BufReader::new(file);
+impl Lexer {
+    pub fn new() -> Lexer {
+        Lexer::default()
+    }
+
+    pub fn with_options(options: &ParseOptions) -> Lexer {
+        Lexer {
+            options: options.clone(),
+            ..Lexer::default()
+        }
+    }
+
+    #[cfg(feature = "checksum")]
+    fn digest_line(&mut self, line: &str) {
+        self.checksum.update(line.as_bytes());
+        self.checksum.update(b"\n");
+    }
+
+    #[cfg(not(feature = "checksum"))]
+    fn digest_line(&mut self, _line: &str) {}
+
+    #[cfg(feature = "checksum")]
+    fn footer_matches(&self, digest: &str) -> bool {
+        checksum::hex(&self.checksum.clone().finalize()) == digest.to_lowercase()
+    }
+
+    // Nothing to check a footer against, and an unchecked one proves nothing
+    #[cfg(not(feature = "checksum"))]
+    fn footer_matches(&self, _digest: &str) -> bool {
+        false
+    }
+
+    pub fn push_line(&mut self, line: &str) -> Result<(), &'static str> {
+        self.lines += 1;
+        let pushed = self.tokens.len();
+        let result = self.read_line(line);
+        if result.is_err() {
+            // Just past the last token the line got to
+            let column = self.tokens[pushed..]
+                .last()
+                .map_or(0, |token| token.span.end);
+            self.failed = Some((self.lines, column + 1));
+        }
+        result
+    }
 
-    for line_r in reader.lines() {
-        let mut line = line_r.unwrap();
+    fn read_line(&mut self, line: &str) -> Result<(), &'static str> {
+        // A line of a CRLF file reads the same as its LF twin
+        let line = line.strip_suffix('\r').unwrap_or(line);
 
-        // First line: check header
-        if line_num == 0 {
-            if line != "BULBA!" {
+        // Nothing but blank lines may follow the footer
+        if self.sealed {
+            return match line.trim() {
+                "" => Ok(()),
+                _ => Err(CORRUPTED),
+            };
+        }
+        if self.line_num > 0
+            && let Some(digest) = parse_footer(line)
+        {
+            if !self.footer_matches(digest) {
+                return Err(CORRUPTED);
+            }
+            self.sealed = true;
+            return Ok(());
+        }
+        self.digest_line(line);
+        let tokens = &mut self.tokens;
+
+        // First line: check header, past the mark of editors that add one
+        if self.line_num == 0 {
+            let line = line.strip_prefix(BOM).unwrap_or(line);
+            if !self.options.is_header(line) {
                 return Err("Status: Fainted");
             }
             tokens.push(Token {
                 ttype: TokenType::Header,
-                literal: line.clone(),
+                literal: line.to_string(),
                 line: 1,
                 level: 0,
+                span: 0..line.len(),
             });
-            line_num += 1;
-            continue;
+            self.line_num += 1;
+            return Ok(());
         }
-        line_num += 1;
+        self.line_num += 1;
+        let mut line_num = self.line_num;
+        let mut line = line.to_string();
 
         // Sleep powder: ignore comments
-        if let Some(comment_idx) = line.find("zZz") {
+        if let Some(comment_idx) = line.find(COMMENT) {
             line.truncate(comment_idx);
         }
 
         // Poison powder: tab character not allowed!
         if line.contains("\t") {
-            return Err("Poison Type: Tab character detected");
+            let Some(width) = self.options.tab_width() else {
+                return Err("Poison Type: Tab character detected");
+            };
+            line = line.replace('\t', &" ".repeat(width));
+            self.warnings.push(Warning {
+                line: line_num,
+                message: format!("Poison Type: Tab character read as {width} spaces"),
+            });
         }
 
         line = line.trim_end().to_string();
+
+        // Long values: a trailing `\` joins the next line on, without its
+        // indentation. Tokens carry the number of the first line.
+        if let Some((first, head)) = self.continued.take() {
+            line = head + line.trim_start();
+            line_num = first;
+        }
+        if let Some(head) = line.strip_suffix(CONTINUATION) {
+            self.continued = Some((line_num, head.to_string()));
+            return Ok(());
+        }
+
         if line.is_empty() {
-            continue;
+            return Ok(());
+        }
+
+        // Another cry starts the next document of a stream
+        if self.options.is_header(&line) {
+            tokens.push(Token {
+                ttype: TokenType::Header,
+                literal: line.clone(),
+                line: line_num,
+                level: 0,
+                span: 0..line.len(),
+            });
+            return Ok(());
         }
 
         // Solar beam: check indentation is multiple of 4
@@ -279,17 +908,55 @@ pub fn lex(file: File) -> Result<Vec<Token>, &'static str> {
             literal: String::from(""),
             line: line_num,
             level,
+            span: 0..indent,
         });
 
         line = line.trim().to_string();
-        tokenize_line(&mut line, line_num, &mut tokens)?;
+        tokenize_line(&mut line, indent, line_num, &self.options, tokens)
     }
 
-    tokens.push(Token {
-        ttype: TokenType::Eof,
-        literal: String::from(""),
-        line: line_num,
-        level: 0,
-    });
-    Ok(tokens)
+    /// The tokens of the lines pushed so far, also after a line failed.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// The line and column, both counted from 1, where the last line that
+    /// failed stopped.
+    pub fn failed_at(&self) -> Option<(usize, usize)> {
+        self.failed
+    }
+
+    // The line that failed, or else the start of the line after the last
+    // one pushed
+    fn position(&self) -> (usize, usize) {
+        self.failed.unwrap_or((self.lines + 1, 1))
+    }
+
+    fn locate(&self, message: &'static str) -> ParseError {
+        ParseError::at(message, self.position())
+    }
+
+    /// What lenient options let through so far.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// The tokens of every line pushed so far, closed with `Eof`. Fails
+    /// when the last line asked to be continued.
+    pub fn finish(mut self) -> Result<Vec<Token>, &'static str> {
+        if self.continued.is_some() {
+            return Err("It hurt itself in its confusion!");
+        }
+        if self.options.requires_checksum() && !self.sealed {
+            return Err(CORRUPTED);
+        }
+        self.tokens.push(Token {
+            ttype: TokenType::Eof,
+            literal: String::from(""),
+            line: self.line_num,
+            level: 0,
+            span: 0..0,
+        });
+        Ok(self.tokens)
+    }
 }
@@ -1,2 +1,84 @@
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+pub mod array;
+#[cfg(feature = "checksum")]
+pub mod checksum;
+#[cfg(feature = "schema")]
+pub mod completion;
+pub mod compress;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "crypt")]
+pub mod crypt;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "schema")]
+pub mod describe;
+pub mod diff;
+#[cfg(feature = "schema")]
+pub mod docs;
+#[cfg(feature = "doctor")]
+pub mod doctor;
+pub mod document;
+pub mod emit;
+pub mod entry;
+#[cfg(feature = "json")]
+pub mod env;
+pub mod expr;
+#[cfg(feature = "figment")]
+pub mod figment;
+pub mod format;
+#[cfg(feature = "json")]
+pub mod grammar;
+#[cfg(feature = "schema")]
+pub mod infer;
+#[cfg(feature = "schema")]
+pub mod js;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "schema")]
+pub mod layers;
 pub mod lexer;
+#[cfg(feature = "schema")]
+pub mod lint;
+#[cfg(feature = "schema")]
+pub mod load;
+#[cfg(feature = "schema")]
+pub mod lsp;
+pub mod measure;
+pub mod merge;
+pub mod migrate;
+#[cfg(feature = "schema")]
+pub mod output;
 pub mod parser;
+pub mod patch;
+#[cfg(feature = "schema")]
+pub mod query;
+#[cfg(feature = "schema")]
+pub mod repl;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod secret;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "schema")]
+pub mod shared;
+#[cfg(feature = "signature")]
+pub mod signature;
+pub mod spanned;
+pub mod stream;
+pub mod template;
+pub mod testing;
+#[cfg(feature = "trace")]
+mod trace;
+pub mod visit;
+#[cfg(feature = "watch")]
+pub mod watch;
+
+pub use diff::diff;
+pub use emit::emit;
+#[cfg(feature = "schema")]
+pub use load::load;
+pub use merge::merge3;
+#[cfg(feature = "schema")]
+pub use shared::SharedDocument;
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use regex::Regex;
+
+use crate::document::Document;
+use crate::lexer;
+use crate::parser::BsonValue;
+use crate::schema::{Severity, Violation};
+use crate::visit::{Visit, walk_value};
+
+/// A check the linter runs on every key of a document, e.g. "no plaintext
+/// passwords". `name` is what severity configs and `bulba-allow` comments
+/// refer to, so it should stay stable once published.
+pub trait Rule {
+    fn name(&self) -> &str;
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, path: &str, value: &BsonValue) -> Result<(), String>;
+}
+
+/// Whether the last key of `path` names a credential, such as
+/// `database.password` or `api_key`.
+pub fn sensitive_key(path: &str) -> bool {
+    let key = path.rsplit('.').next().unwrap_or(path).to_lowercase();
+    ["password", "passwd", "secret", "token", "api_key"]
+        .iter()
+        .any(|word| key.contains(word))
+}
+
+/// Keys that look like credentials but hold a plain string instead of a
+/// `secret("...")` reference.
+pub struct NoPlaintextSecrets;
+
+impl Rule for NoPlaintextSecrets {
+    fn name(&self) -> &str {
+        "no_plaintext_secrets"
+    }
+
+    fn check(&self, path: &str, value: &BsonValue) -> Result<(), String> {
+        match value {
+            BsonValue::BString(s) if sensitive_key(path) && !s.is_empty() => {
+                Err(String::from("Plaintext credential, use secret(\"...\")"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Sections without a single key.
+pub struct EmptySection;
+
+impl Rule for EmptySection {
+    fn name(&self) -> &str {
+        "empty_section"
+    }
+
+    fn check(&self, path: &str, value: &BsonValue) -> Result<(), String> {
+        match value {
+            BsonValue::Map(m) if m.is_empty() && !path.is_empty() => {
+                Err(String::from("Section has no keys"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The rules to run and how loudly each one reports. Findings are ordinary
+/// violations whose message ends in the rule name, e.g.
+/// `Section has no keys [empty_section]`.
+///
+/// A `zZz bulba-allow(rule_name)` comment on a line of its own turns a rule
+/// off for the whole file; after a key it only silences that line. Several
+/// rules can be listed, separated by commas.
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    // None switches a rule off
+    severities: BTreeMap<String, Option<Severity>>,
+}
+
+impl Default for Linter {
+    fn default() -> Linter {
+        Linter::new().rule(NoPlaintextSecrets).rule(EmptySection)
+    }
+}
+
+impl Linter {
+    /// A linter without any rules, not even the built-in ones.
+    pub fn new() -> Linter {
+        Linter {
+            rules: vec![],
+            severities: BTreeMap::new(),
+        }
+    }
+
+    pub fn rule(mut self, rule: impl Rule + 'static) -> Linter {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Overrides the severity of a rule, `None` switches it off.
+    pub fn severity(mut self, name: &str, severity: Option<Severity>) -> Linter {
+        self.severities.insert(name.to_string(), severity);
+        self
+    }
+
+    /// Reads severities from a config document with one key per rule under
+    /// `(o) rules (o)`, each set to `"error"`, `"warning"` or `"off"`.
+    pub fn configure(mut self, config: &BsonValue) -> Result<Linter, &'static str> {
+        let Some(rules) = config.get("rules") else {
+            return Ok(self);
+        };
+        let BsonValue::Map(rules) = rules else {
+            return Err("Target is immune!");
+        };
+        for (name, level) in rules {
+            let severity = match level {
+                BsonValue::BString(s) if s == "error" => Some(Severity::Error),
+                BsonValue::BString(s) if s == "warning" => Some(Severity::Warning),
+                BsonValue::BString(s) if s == "off" => None,
+                _ => return Err("Target is immune!"),
+            };
+            self = self.severity(name, severity);
+        }
+        Ok(self)
+    }
+
+    pub fn lint(&self, doc: &Document) -> Vec<Violation> {
+        let (file_wide, by_line) = allowed(&doc.to_string());
+        let mut checks = Checks {
+            rules: &self.rules,
+            findings: vec![],
+        };
+        if let Ok(value) = doc.value() {
+            value.visit(&mut checks);
+        }
+        let findings = checks.findings;
+
+        let mut violations = vec![];
+        for (i, path, message) in findings {
+            let rule = &self.rules[i];
+            let name = rule.name();
+            let severity = match self.severities.get(name) {
+                Some(severity) => *severity,
+                None => Some(rule.severity()),
+            };
+            // Array elements are reported on the line of their key
+            let line = path
+                .match_indices('.')
+                .map(|(idx, _)| &path[..idx])
+                .chain([path.as_str()])
+                .filter_map(|prefix| doc.line_of(prefix))
+                .next_back();
+            let suppressed = file_wide.contains(name)
+                || line.is_some_and(|line| {
+                    by_line.get(&line).is_some_and(|names| names.contains(name))
+                });
+            if let Some(severity) = severity
+                && !suppressed
+            {
+                violations.push(Violation {
+                    path,
+                    line,
+                    severity,
+                    message: format!("{message} [{name}]"),
+                    related: vec![],
+                });
+            }
+        }
+        violations
+    }
+}
+
+// Runs every rule on every key and element below the root
+struct Checks<'r> {
+    rules: &'r [Box<dyn Rule>],
+    findings: Vec<(usize, String, String)>,
+}
+
+impl Visit for Checks<'_> {
+    fn visit_value(&mut self, path: &str, value: &BsonValue) {
+        if !path.is_empty() {
+            for (i, rule) in self.rules.iter().enumerate() {
+                if let Err(message) = rule.check(path, value) {
+                    self.findings.push((i, path.to_string(), message));
+                }
+            }
+        }
+        walk_value(self, path, value);
+    }
+}
+
+// Rules named in `bulba-allow(...)` comments: the file-wide ones, and the
+// ones silenced on a single line
+fn allowed(text: &str) -> (BTreeSet<String>, BTreeMap<usize, BTreeSet<String>>) {
+    let allow = Regex::new(r"bulba-allow\(([^)]*)\)").unwrap();
+    let mut file_wide = BTreeSet::new();
+    let mut by_line: BTreeMap<usize, BTreeSet<String>> = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let Some(comment_idx) = line.find(lexer::COMMENT) else {
+            continue;
+        };
+        for matches in allow.captures_iter(&line[comment_idx..]) {
+            let names = matches[1].split(',').map(|name| name.trim().to_string());
+            if line[..comment_idx].trim().is_empty() {
+                file_wide.extend(names);
+            } else {
+                by_line.entry(i + 1).or_default().extend(names);
+            }
+        }
+    }
+    (file_wide, by_line)
+}
@@ -0,0 +1,197 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+
+use crate::compress;
+#[cfg(feature = "crypt")]
+use crate::crypt::CryptError;
+use crate::de::{self, ConvertError};
+use crate::describe::Describe;
+use crate::document::Document;
+use crate::layers::Layers;
+use crate::lexer;
+use crate::parser::{BsonValue, ParseError, PathConflict};
+use crate::schema::{self, Severity, Violation};
+use crate::secret::{SecretError, SecretResolver, Secrets};
+
+/// A file that could not be read or decoded. What went wrong underneath,
+/// such as the `io::Error`, is its `source`.
+#[derive(Debug, Clone)]
+pub struct FileError {
+    pub path: PathBuf,
+    source: Arc<dyn Error + Send + Sync>,
+}
+
+impl FileError {
+    pub fn new(
+        path: impl Into<PathBuf>,
+        source: impl Into<Box<dyn Error + Send + Sync>>,
+    ) -> FileError {
+        FileError {
+            path: path.into(),
+            source: Arc::from(source.into()),
+        }
+    }
+}
+
+// Errors underneath rarely compare, their messages do
+impl PartialEq for FileError {
+    fn eq(&self, other: &FileError) -> bool {
+        self.path == other.path && self.source.to_string() == other.source.to_string()
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.source)
+    }
+}
+
+impl Error for FileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Why a typed config could not be loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    /// The file could not be read
+    Io(FileError),
+    /// The text is not valid BSON
+    Parse(ParseError),
+    /// Valid BSON the schema of the target type rejects, every error at once
+    Invalid(Vec<Violation>),
+    /// The selected profile is not defined
+    Profile(String),
+    /// A `secret("...")` reference could not be resolved
+    Secret(SecretError),
+    /// The detached signature of the file was refused, see `signature`
+    Signature(String),
+    /// An `ENC[...]` value could not be decrypted
+    #[cfg(feature = "crypt")]
+    Decrypt(CryptError),
+    /// The value passed the schema but still does not fit the type
+    Convert(ConvertError),
+    /// Environment variables that set both a value and a key inside it
+    Conflict(PathConflict),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{err}"),
+            LoadError::Parse(err) => write!(f, "{err}"),
+            LoadError::Convert(err) => write!(f, "{err}"),
+            LoadError::Profile(name) => write!(f, "No profile named {name}"),
+            LoadError::Secret(err) => write!(f, "{err}"),
+            #[cfg(feature = "crypt")]
+            LoadError::Decrypt(err) => write!(f, "{err}"),
+            LoadError::Signature(err) => write!(f, "Bad signature: {err}"),
+            LoadError::Conflict(err) => write!(f, "{err}"),
+            LoadError::Invalid(violations) => {
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("\n")?;
+                    }
+                    if let Some(line) = violation.line {
+                        write!(f, "{line}: ")?;
+                    }
+                    write!(f, "{}: {}", violation.path, violation.message)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(err) => Some(err),
+            LoadError::Parse(err) => Some(err),
+            LoadError::Secret(err) => Some(err),
+            #[cfg(feature = "crypt")]
+            LoadError::Decrypt(err) => Some(err),
+            LoadError::Conflict(err) => Some(err),
+            LoadError::Convert(err) => Some(err),
+            LoadError::Invalid(_) | LoadError::Profile(_) | LoadError::Signature(_) => None,
+        }
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(err: ParseError) -> LoadError {
+        LoadError::Parse(err)
+    }
+}
+
+/// Parses `input`, validates it against `T::schema()`, resolves secret
+/// references with the `env:` and `file:` schemes of `Secrets` and converts
+/// it. Warnings such as deprecated keys do not stop the load.
+pub fn from_str<T: Describe + DeserializeOwned>(input: &str) -> Result<T, LoadError> {
+    from_str_with_secrets(input, &Secrets::new())
+}
+
+/// `from_str` with secret references going to `resolver`.
+pub fn from_str_with_secrets<T: Describe + DeserializeOwned>(
+    input: &str,
+    resolver: &dyn SecretResolver,
+) -> Result<T, LoadError> {
+    let doc = Document::parse(input)?;
+    let violations = schema::validate(&doc, &T::schema());
+    convert(doc.value()?, violations, resolver)
+}
+
+// The steps after validation shared by every way of loading
+pub(crate) fn convert<T: DeserializeOwned>(
+    mut value: BsonValue<'static>,
+    violations: Vec<Violation>,
+    resolver: &dyn SecretResolver,
+) -> Result<T, LoadError> {
+    let errors: Vec<Violation> = violations
+        .into_iter()
+        .filter(|violation| violation.severity == Severity::Error)
+        .collect();
+    if !errors.is_empty() {
+        return Err(LoadError::Invalid(errors));
+    }
+    value.resolve_secrets(resolver).map_err(LoadError::Secret)?;
+    de::from_value(&value).map_err(LoadError::Convert)
+}
+
+/// `from_str` on the contents of a file.
+pub fn from_file<T: Describe + DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, LoadError> {
+    from_str(&read(path.as_ref())?)
+}
+
+/// The text of a config file, decompressed first when it is gzip or
+/// Zstandard, see `compress::decompress`.
+pub fn read(path: &Path) -> Result<String, LoadError> {
+    let bytes = fs::read(path).map_err(|err| LoadError::Io(FileError::new(path, err)))?;
+    decode(path, bytes)
+}
+
+// The text of the bytes of a file, see `read`
+pub(crate) fn decode(path: &Path, bytes: Vec<u8>) -> Result<String, LoadError> {
+    let fail = |err: Box<dyn Error + Send + Sync>| LoadError::Io(FileError::new(path, err));
+    let bytes = compress::decompress(&bytes).map_err(|err| fail(ParseError::from(err).into()))?;
+    lexer::check_encoding(&bytes).map_err(|err| fail(ParseError::from(err).into()))?;
+    String::from_utf8(bytes.into_owned()).map_err(|err| fail(err.into()))
+}
+
+/// Loads the config of an application in one go: reads the file at `path`,
+/// lets `BULBA_`-prefixed environment variables override its keys (see
+/// `Layers::env_prefix`), validates the result against `T::schema()`,
+/// resolves secret references and converts it. Every schema error is
+/// reported at once. Use `Layers::load_as` to pick the sources yourself.
+pub fn load<T: Describe + DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, LoadError> {
+    Layers::new()
+        .file(path.as_ref())
+        .env_prefix("BULBA_")
+        .load_as()
+}
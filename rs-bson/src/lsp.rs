@@ -0,0 +1,406 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::completion;
+use crate::document::Document;
+use crate::format::{self, FormatOptions};
+use crate::json::JsonValue;
+use crate::lexer;
+use crate::parser::{self, BsonValue, ParseOptions, join_path};
+use crate::schema::{self, Schema, Severity, type_name};
+use crate::spanned::{self, Span};
+
+// LSP SymbolKind and DiagnosticSeverity values
+const SYMBOL_NAMESPACE: f64 = 3.0;
+const SYMBOL_PROPERTY: f64 = 7.0;
+const DIAGNOSTIC_ERROR: f64 = 1.0;
+const DIAGNOSTIC_WARNING: f64 = 2.0;
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<JsonValue>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "It hurt itself in its confusion!",
+        )
+    };
+    let mut body = vec![0; length.ok_or_else(invalid)?];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|_| invalid())?;
+    JsonValue::parse(&body).map(Some).map_err(|_| invalid())
+}
+
+fn write_message(writer: &mut impl Write, message: &JsonValue) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+fn position(line: usize, character: usize) -> JsonValue {
+    JsonValue::object(vec![
+        ("line", JsonValue::Number(line as f64)),
+        ("character", JsonValue::Number(character as f64)),
+    ])
+}
+
+fn range(start: usize, end: usize, end_character: usize) -> JsonValue {
+    JsonValue::object(vec![
+        ("start", position(start, 0)),
+        ("end", position(end, end_character)),
+    ])
+}
+
+// The characters `span` covers, as LSP counts them rather than bytes
+fn span_range(text: &str, span: &Span) -> JsonValue {
+    let line = span.line - 1;
+    let characters = |byte: usize| {
+        text.lines()
+            .nth(line)
+            .and_then(|line| line.get(..byte))
+            .map_or(0, |head| head.chars().count())
+    };
+    JsonValue::object(vec![
+        ("start", position(line, characters(span.columns.start))),
+        ("end", position(line, characters(span.columns.end))),
+    ])
+}
+
+fn line_length(text: &str, line: usize) -> usize {
+    text.lines()
+        .nth(line)
+        .map_or(0, |line| line.chars().count())
+}
+
+// Errors about the document as a whole do not say where they happened, so
+// find the first line after which the document stops parsing. Lines are lexed independently, so the
+// culprit is the last line of the shortest failing prefix.
+pub(crate) fn error_line(text: &str) -> usize {
+    let lines: Vec<&str> = text.split('\n').collect();
+    (1..=lines.len())
+        .find(|&n| {
+            let prefix = lines[..n].join("\n");
+            lexer::lex_str(&prefix)
+                .and_then(|tokens| parser::parse(&tokens).map(|_| ()))
+                .is_err()
+        })
+        .map_or(0, |n| n - 1)
+}
+
+fn diagnostic(range: JsonValue, severity: f64, message: &str) -> JsonValue {
+    JsonValue::object(vec![
+        ("range", range),
+        ("severity", JsonValue::Number(severity)),
+        ("source", JsonValue::from("rs-bson")),
+        ("message", JsonValue::from(message)),
+    ])
+}
+
+/// Language server for BSON files, speaking LSP over `reader` and `writer`
+/// (stdin and stdout for `rs-bson lsp`). Open documents get diagnostics for
+/// syntax errors and, when a schema is given, schema violations. Hover
+/// shows the type of a key and what the schema says about it, document
+/// symbols list sections and keys, and formatting applies `format::format`
+/// with the `.bulbafmt.bson` found above `file://` documents.
+pub struct Server {
+    schema: Option<Schema>,
+    documents: BTreeMap<String, String>,
+}
+
+impl Server {
+    pub fn new(schema: Option<Schema>) -> Server {
+        Server {
+            schema,
+            documents: BTreeMap::new(),
+        }
+    }
+
+    /// Serves requests until the client sends `exit` or closes the stream.
+    pub fn run(&mut self, mut reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        while let Some(message) = read_message(&mut reader)? {
+            let method = message.get("method").and_then(JsonValue::as_str);
+            if method == Some("exit") {
+                break;
+            }
+            let params = message.get("params").cloned().unwrap_or(JsonValue::Null);
+            let (result, notifications) = self.handle(method.unwrap_or_default(), &params);
+            for notification in notifications {
+                write_message(&mut writer, &notification)?;
+            }
+            // Notifications carry no id and get no response
+            let Some(id) = message.get("id") else {
+                continue;
+            };
+            let outcome = match result {
+                Some(result) => ("result", result),
+                None => (
+                    "error",
+                    JsonValue::object(vec![
+                        ("code", JsonValue::Number(-32601.0)),
+                        ("message", JsonValue::from("Method not found")),
+                    ]),
+                ),
+            };
+            let response = JsonValue::object(vec![
+                ("jsonrpc", JsonValue::from("2.0")),
+                ("id", id.clone()),
+                outcome,
+            ]);
+            write_message(&mut writer, &response)?;
+        }
+        Ok(())
+    }
+
+    // The result for a request, None for unknown methods, plus any
+    // notifications to send first
+    fn handle(&mut self, method: &str, params: &JsonValue) -> (Option<JsonValue>, Vec<JsonValue>) {
+        let uri = params
+            .get("textDocument")
+            .and_then(|doc| doc.get("uri"))
+            .and_then(JsonValue::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let line = match params.get("position").and_then(|p| p.get("line")) {
+            Some(JsonValue::Number(line)) => *line as usize,
+            _ => 0,
+        };
+        match method {
+            "initialize" => {
+                let capabilities = JsonValue::object(vec![
+                    ("textDocumentSync", JsonValue::Number(1.0)),
+                    ("hoverProvider", JsonValue::Bool(true)),
+                    ("documentSymbolProvider", JsonValue::Bool(true)),
+                    ("documentFormattingProvider", JsonValue::Bool(true)),
+                ]);
+                let info = JsonValue::object(vec![("name", JsonValue::from("rs-bson"))]);
+                let result =
+                    JsonValue::object(vec![("capabilities", capabilities), ("serverInfo", info)]);
+                (Some(result), vec![])
+            }
+            "shutdown" => (Some(JsonValue::Null), vec![]),
+            "textDocument/didOpen" => {
+                let text = params
+                    .get("textDocument")
+                    .and_then(|doc| doc.get("text"))
+                    .and_then(JsonValue::as_str)
+                    .unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+                (None, vec![self.publish(&uri)])
+            }
+            "textDocument/didChange" => {
+                // Full sync, the last change holds the whole text
+                if let Some(JsonValue::Array(changes)) = params.get("contentChanges")
+                    && let Some(text) = changes
+                        .last()
+                        .and_then(|change| change.get("text"))
+                        .and_then(JsonValue::as_str)
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+                (None, vec![self.publish(&uri)])
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                (None, vec![self.publish(&uri)])
+            }
+            "textDocument/hover" => (Some(self.hover(&uri, line)), vec![]),
+            "textDocument/documentSymbol" => (Some(self.symbols(&uri)), vec![]),
+            "textDocument/formatting" => (Some(self.formatting(&uri)), vec![]),
+            _ => (None, vec![]),
+        }
+    }
+
+    fn publish(&self, uri: &str) -> JsonValue {
+        let diagnostics = match self.documents.get(uri) {
+            Some(text) => self.diagnostics(text),
+            None => vec![],
+        };
+        let params = JsonValue::object(vec![
+            ("uri", JsonValue::from(uri)),
+            ("diagnostics", JsonValue::Array(diagnostics)),
+        ]);
+        JsonValue::object(vec![
+            ("jsonrpc", JsonValue::from("2.0")),
+            ("method", JsonValue::from("textDocument/publishDiagnostics")),
+            ("params", params),
+        ])
+    }
+
+    fn diagnostics(&self, text: &str) -> Vec<JsonValue> {
+        let doc = match Document::parse(text) {
+            Ok(doc) => doc,
+            Err(err) => {
+                let line = err.line().map_or_else(|| error_line(text), |line| line - 1);
+                let range = range(line, line, line_length(text, line));
+                return vec![diagnostic(range, DIAGNOSTIC_ERROR, err.message())];
+            }
+        };
+        let Some(schema) = &self.schema else {
+            return vec![];
+        };
+        // Violations of values that exist point at the value itself
+        let tree = lexer::lex_str(text)
+            .ok()
+            .and_then(|tokens| spanned::parse_spanned(&tokens, &ParseOptions::new()).ok());
+        schema::validate(&doc, schema)
+            .iter()
+            .map(|violation| {
+                let severity = match violation.severity {
+                    Severity::Error => DIAGNOSTIC_ERROR,
+                    Severity::Warning => DIAGNOSTIC_WARNING,
+                };
+                let span = tree
+                    .as_ref()
+                    .and_then(|tree| tree.get(&violation.path))
+                    .map(|node| &node.span)
+                    .filter(|span| span.line > 0);
+                let range = match span {
+                    Some(span) => span_range(text, span),
+                    None => {
+                        let line = violation.line.map_or(0, |line| line - 1);
+                        range(line, line, line_length(text, line))
+                    }
+                };
+                let message = format!("{}: {}", violation.path, violation.message);
+                diagnostic(range, severity, &message)
+            })
+            .collect()
+    }
+
+    fn hover(&self, uri: &str, line: usize) -> JsonValue {
+        let Some(doc) = self
+            .documents
+            .get(uri)
+            .and_then(|text| Document::parse(text).ok())
+        else {
+            return JsonValue::Null;
+        };
+        let Ok(value) = doc.value() else {
+            return JsonValue::Null;
+        };
+        let mut paths = vec![];
+        key_paths(&value, "", &mut paths);
+        let Some(path) = paths
+            .into_iter()
+            .find(|path| doc.line_of(path) == Some(line + 1))
+        else {
+            return JsonValue::Null;
+        };
+
+        let mut contents = format!("`{path}`: {}", type_name(value.get(&path).unwrap()));
+        let expected = self.schema.as_ref().and_then(|schema| {
+            completion::completions(schema)
+                .into_iter()
+                .find(|completion| completion.path == path)
+        });
+        if let Some(expected) = expected {
+            contents += &format!("\n\nExpected `{}`", expected.ty);
+            if !expected.required {
+                contents += " (optional)";
+            }
+            if let Some(doc) = expected.doc {
+                contents += &format!("\n\n{doc}");
+            }
+        }
+        JsonValue::object(vec![(
+            "contents",
+            JsonValue::object(vec![
+                ("kind", JsonValue::from("markdown")),
+                ("value", JsonValue::from(contents)),
+            ]),
+        )])
+    }
+
+    fn symbols(&self, uri: &str) -> JsonValue {
+        let Some(text) = self.documents.get(uri) else {
+            return JsonValue::Null;
+        };
+        let Ok(doc) = Document::parse(text) else {
+            return JsonValue::Null;
+        };
+        let symbols = match &doc.value() {
+            Ok(BsonValue::Map(m)) => section_symbols(text, &doc, m, ""),
+            _ => vec![],
+        };
+        JsonValue::Array(symbols.into_iter().map(|(symbol, _)| symbol).collect())
+    }
+
+    fn formatting(&self, uri: &str) -> JsonValue {
+        let Some(text) = self.documents.get(uri) else {
+            return JsonValue::Null;
+        };
+        let options = uri
+            .strip_prefix("file://")
+            .and_then(|path| FormatOptions::discover(Path::new(path)).ok())
+            .unwrap_or_default();
+        let Ok(formatted) = format::format_with(text, &options) else {
+            return JsonValue::Array(vec![]);
+        };
+        let edit = JsonValue::object(vec![
+            ("range", range(0, text.lines().count() + 1, 0)),
+            ("newText", JsonValue::from(formatted)),
+        ]);
+        JsonValue::Array(vec![edit])
+    }
+}
+
+fn key_paths(value: &BsonValue, path: &str, paths: &mut Vec<String>) {
+    if let BsonValue::Map(m) = value {
+        for (key, child) in m.iter() {
+            let path = join_path(path, key);
+            key_paths(child, &path, paths);
+            paths.push(path);
+        }
+    }
+}
+
+// Symbols of a section in document order, each with the last line it spans
+fn section_symbols(
+    text: &str,
+    doc: &Document,
+    section: &BTreeMap<std::borrow::Cow<str>, BsonValue>,
+    path: &str,
+) -> Vec<(JsonValue, usize)> {
+    let mut symbols = vec![];
+    for (key, value) in section.iter() {
+        let path = join_path(path, key);
+        let Some(line) = doc.line_of(&path).map(|line| line - 1) else {
+            continue;
+        };
+        let (kind, children) = match value {
+            BsonValue::Map(m) => (SYMBOL_NAMESPACE, section_symbols(text, doc, m, &path)),
+            _ => (SYMBOL_PROPERTY, vec![]),
+        };
+        let end = children.iter().map(|(_, end)| *end).max().unwrap_or(line);
+        let mut members = vec![
+            ("name", JsonValue::from(key.as_ref())),
+            ("detail", JsonValue::from(type_name(value))),
+            ("kind", JsonValue::Number(kind)),
+            ("range", range(line, end, line_length(text, end))),
+            ("selectionRange", range(line, line, line_length(text, line))),
+        ];
+        if !children.is_empty() {
+            let children = children.into_iter().map(|(symbol, _)| symbol).collect();
+            members.push(("children", JsonValue::Array(children)));
+        }
+        symbols.push((JsonValue::object(members), line, end));
+    }
+    symbols.sort_by_key(|(_, line, _)| *line);
+    symbols
+        .into_iter()
+        .map(|(symbol, _, end)| (symbol, end))
+        .collect()
+}
@@ -1,19 +1,865 @@
 use std::env;
-use std::fs::File;
-use std::path::Path;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
 
-mod lexer;
-mod parser;
+use rs_bson::checksum;
+use rs_bson::completion;
+use rs_bson::crypt::{self, CommandKeys};
+use rs_bson::docs;
+use rs_bson::doctor;
+use rs_bson::document::Document;
+use rs_bson::emit::{self, emit};
+use rs_bson::format::{self, FormatOptions};
+use rs_bson::grammar;
+use rs_bson::infer;
+use rs_bson::json::JsonValue;
+use rs_bson::lint::{self, Linter};
+use rs_bson::load;
+use rs_bson::lsp;
+use rs_bson::parser::{BsonValue, ParseError};
+use rs_bson::repl::Repl;
+use rs_bson::schema::{self, Schema, Severity, Validator, Violation};
+use rs_bson::signature::{self, CommandVerifier, HmacVerifier, Verifier};
+use rs_bson::template;
+use rs_bson::{lexer, parser};
+
+use regex::Regex;
+
+// Compressed .bson.gz and .bson.zst files read like plain ones
+fn read(path: &str) -> String {
+    load::read(Path::new(path)).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(2);
+    })
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("usage: {message}");
+    process::exit(2);
+}
+
+fn load_schema(path: &str) -> Schema {
+    let input = read(path);
+    let loaded = if path.ends_with(".json") {
+        Schema::from_json_schema(&input).map_err(ParseError::from)
+    } else {
+        Schema::parse(&input)
+    };
+    loaded.unwrap_or_else(|err| {
+        eprintln!("{path}: {err}");
+        process::exit(2);
+    })
+}
+
+fn load_lint_config(path: &str) -> Linter {
+    let input = read(path);
+    let config =
+        lexer::lex_str(&input).and_then(|tokens| parser::parse(&tokens).map(|v| v.into_owned()));
+    config
+        .and_then(|config| Ok(Linter::default().configure(&config)?))
+        .unwrap_or_else(|err| {
+            eprintln!("{path}: {err}");
+            process::exit(2);
+        })
+}
+
+fn report(file: &str, violations: &[Violation]) {
+    for violation in violations {
+        let line = violation.line.map(|l| l.to_string()).unwrap_or_default();
+        println!(
+            "{file}:{line}: {}: {}: {}",
+            violation.severity, violation.path, violation.message
+        );
+    }
+}
+
+// rs-bson lint <file> [--schema <schema>] [--strict] [--config <config>]
+fn lint(args: &[String]) {
+    const USAGE: &str = "rs-bson lint <file.bson> [--schema <file.bulbaschema|file.json>] \
+[--strict] [--config <lint.bson>]";
+    let mut file = None;
+    let mut schema_path = None;
+    let mut strict = false;
+    let mut linter = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schema" => schema_path = Some(args.next().unwrap_or_else(|| usage(USAGE))),
+            "--config" => {
+                linter = Some(load_lint_config(
+                    args.next().unwrap_or_else(|| usage(USAGE)),
+                ))
+            }
+            "--strict" => strict = true,
+            _ if file.is_none() => file = Some(arg),
+            _ => usage(USAGE),
+        }
+    }
+    let file = file.unwrap_or_else(|| usage(USAGE));
+
+    let doc = match Document::parse(&read(file)) {
+        Ok(doc) => doc,
+        Err(err) => {
+            match err.line() {
+                Some(line) => println!("{file}:{line}: error: {}", err.message()),
+                None => println!("{file}: error: {err}"),
+            }
+            process::exit(1);
+        }
+    };
+    let mut violations = linter.unwrap_or_default().lint(&doc);
+    if let Some(schema_path) = schema_path {
+        let loaded = load_schema(schema_path);
+        violations.extend(Validator::new(&loaded).strict(strict).validate(&doc));
+    }
+    report(file, &violations);
+    if violations.iter().any(|v| v.severity == Severity::Error) {
+        process::exit(1);
+    }
+}
+
+// rs-bson completions <schema>
+fn completions(args: &[String]) {
+    let [schema_path] = args else {
+        usage("rs-bson completions <file.bulbaschema|file.json>");
+    };
+    println!("{}", completion::manifest(&load_schema(schema_path)));
+}
+
+// Single quotes keep the shell from expanding anything inside
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+// rs-bson to-env <file> [--prefix <prefix>] [--format export|dotenv]
+fn to_env(args: &[String]) {
+    const USAGE: &str = "rs-bson to-env <file.bson> [--prefix <PREFIX>] [--format export|dotenv]";
+    let mut file = None;
+    let mut prefix = "";
+    let mut export = true;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--prefix" => prefix = args.next().unwrap_or_else(|| usage(USAGE)),
+            "--format" => match args.next().map(String::as_str) {
+                Some("export") => export = true,
+                Some("dotenv") => export = false,
+                _ => usage(USAGE),
+            },
+            _ if file.is_none() => file = Some(arg),
+            _ => usage(USAGE),
+        }
+    }
+    let file = file.unwrap_or_else(|| usage(USAGE));
+
+    let input = read(file);
+    let value = lexer::lex_str(&input)
+        .and_then(|tokens| parser::parse(&tokens).map(|value| value.into_owned()));
+    let value = value.unwrap_or_else(|err| {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    });
+    for (name, value) in value.to_env_map(prefix) {
+        if export {
+            println!("export {name}={}", shell_quote(&value));
+        } else {
+            println!("{name}={value}");
+        }
+    }
+}
+
+// rs-bson from-env --prefix <prefix> [--separator <separator>]
+fn from_env(args: &[String]) {
+    const USAGE: &str = "rs-bson from-env --prefix <PREFIX> [--separator <SEPARATOR>]";
+    let mut prefix = None;
+    let mut separator = "__";
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--prefix" => prefix = Some(args.next().unwrap_or_else(|| usage(USAGE))),
+            "--separator" => separator = args.next().unwrap_or_else(|| usage(USAGE)),
+            _ => usage(USAGE),
+        }
+    }
+    // Without a prefix every variable of the process would end up in it
+    let prefix = prefix
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| usage(USAGE));
+    if separator.is_empty() {
+        usage(USAGE);
+    }
+
+    let value = rs_bson::env::from_env_with_separator(prefix, separator).unwrap_or_else(|err| {
+        eprintln!("from-env: {err}");
+        process::exit(1);
+    });
+    match emit(&value) {
+        Ok(text) => print!("{text}"),
+        Err(err) => {
+            eprintln!("from-env: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+// rs-bson template <file> [--var <name=value>]... [--vars-file <vars>]...
+fn template(args: &[String]) {
+    const USAGE: &str =
+        "rs-bson template <file.bson> [--var <name=value>]... [--vars-file <vars.bson>]...";
+    let mut file = None;
+    let mut vars = std::collections::BTreeMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--var" => {
+                let var = args.next().unwrap_or_else(|| usage(USAGE));
+                let (name, value) = var.split_once('=').unwrap_or_else(|| usage(USAGE));
+                vars.insert(name.to_string(), value.to_string());
+            }
+            // Later sources win, so a --var after a file overrides it
+            "--vars-file" => {
+                let path = args.next().unwrap_or_else(|| usage(USAGE));
+                let value = lexer::lex_str(&read(path))
+                    .and_then(|tokens| parser::parse(&tokens).map(|value| value.into_owned()));
+                let value = value.unwrap_or_else(|err| {
+                    eprintln!("{path}: {err}");
+                    process::exit(1);
+                });
+                vars.extend(template::vars_from(&value));
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => usage(USAGE),
+        }
+    }
+    let file = file.unwrap_or_else(|| usage(USAGE));
+
+    let rendered = template::render(&read(file), &vars).unwrap_or_else(|err| {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    });
+    // Substituted values may still not make valid BSON
+    if let Err(err) = Document::parse(&rendered) {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    }
+    print!("{rendered}");
+}
+
+// rs-bson keys <file> [--types]
+fn keys(args: &[String]) {
+    let (file, types) = match args {
+        [file] => (file, false),
+        [file, flag] if flag == "--types" => (file, true),
+        _ => usage("rs-bson keys <file.bson> [--types]"),
+    };
+    let input = read(file);
+    let tokens = lexer::lex_str(&input);
+    let value = tokens
+        .as_ref()
+        .map_err(|err| *err)
+        .and_then(|tokens| parser::parse(tokens));
+    let value = value.unwrap_or_else(|err| {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    });
+    for (path, value) in value.paths() {
+        if types {
+            println!("{path}: {}", schema::type_name(value));
+        } else {
+            println!("{path}");
+        }
+    }
+}
+
+// Every .bson file below `path`, in a stable order
+fn bson_files(path: PathBuf, files: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        return files.push(path);
+    }
+    let entries = fs::read_dir(&path).unwrap_or_else(|err| {
+        eprintln!("{}: {err}", path.display());
+        process::exit(2);
+    });
+    let mut entries: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.to_string_lossy().ends_with(".bson") {
+            bson_files(entry, files);
+        }
+    }
+}
+
+// Values are matched as written, strings without their quotes. Every
+// element of a Razor Leaf list is a value of its own.
+fn grep_value(value: &BsonValue, path: &str, pattern: &Regex, keys: bool, hits: &mut Vec<String>) {
+    let key = path.rsplit('.').next().unwrap_or_default();
+    let key_hit = keys && !path.is_empty() && pattern.is_match(key);
+    match value {
+        BsonValue::Map(m) => {
+            if key_hit {
+                hits.push(format!("{path}: (section)"));
+            }
+            for (child_key, child) in m {
+                let child_path = if path.is_empty() {
+                    child_key.to_string()
+                } else {
+                    format!("{path}.{child_key}")
+                };
+                grep_value(child, &child_path, pattern, keys, hits);
+            }
+        }
+        BsonValue::Array(items) if !key_hit => {
+            for (i, item) in items.iter().enumerate() {
+                grep_value(item, &format!("{path}.{i}"), pattern, false, hits);
+            }
+        }
+        _ => {
+            let spelled = emit::fragment(value).unwrap_or_else(|_| value.to_string());
+            let text = match value {
+                BsonValue::BString(s)
+                | BsonValue::Secret(s)
+                | BsonValue::Regex(s)
+                | BsonValue::Encrypted(s) => s.to_string(),
+                _ => spelled.clone(),
+            };
+            if key_hit || pattern.is_match(&text) {
+                hits.push(format!("{path}: {spelled}"));
+            }
+        }
+    }
+}
+
+// rs-bson grep <pattern> <file|dir>... [--keys]
+fn grep(args: &[String]) {
+    const USAGE: &str = "rs-bson grep <pattern> <file.bson|dir>... [--keys]";
+    let keys = args.iter().any(|arg| arg == "--keys");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--keys").collect();
+    let [pattern, paths @ ..] = &args[..] else {
+        usage(USAGE);
+    };
+    if paths.is_empty() {
+        usage(USAGE);
+    }
+    let pattern = Regex::new(pattern).unwrap_or_else(|err| {
+        eprintln!("grep: {err}");
+        process::exit(2);
+    });
+
+    let mut files = vec![];
+    for path in paths {
+        bson_files(PathBuf::from(path), &mut files);
+    }
+    let mut found = false;
+    let mut failed = false;
+    for file in files {
+        let file = file.display().to_string();
+        let input = read(&file);
+        let tokens = lexer::lex_str(&input);
+        let value = tokens
+            .as_ref()
+            .map_err(|err| *err)
+            .and_then(|tokens| parser::parse(tokens));
+        let value = match value {
+            Ok(value) => value,
+            Err(err) => {
+                eprintln!("{file}: {err}");
+                failed = true;
+                continue;
+            }
+        };
+        let mut hits = vec![];
+        grep_value(&value, "", &pattern, keys, &mut hits);
+        for hit in &hits {
+            println!("{file}:{hit}");
+        }
+        found |= !hits.is_empty();
+    }
+    // Like grep: 1 when nothing matched, 2 when a file could not be read
+    if failed {
+        process::exit(2);
+    }
+    if !found {
+        process::exit(1);
+    }
+}
+
+// Lists longer than this show their first elements and a count
+const TREE_ARRAY_LIMIT: usize = 5;
+
+fn tree_label(value: &BsonValue) -> String {
+    let badge = schema::type_name(value);
+    match value {
+        BsonValue::Map(m) => format!("[{badge}]{}", if m.is_empty() { " empty" } else { "" }),
+        BsonValue::Array(items) => {
+            let mut shown: Vec<String> = items
+                .iter()
+                .take(TREE_ARRAY_LIMIT)
+                .map(|item| emit::fragment(item).unwrap_or_else(|_| item.to_string()))
+                .collect();
+            if items.len() > TREE_ARRAY_LIMIT {
+                shown.truncate(TREE_ARRAY_LIMIT - 2);
+                shown.push(format!("+{} more", items.len() - shown.len()));
+            }
+            if shown.is_empty() {
+                return format!("[{badge} 0] <| |>");
+            }
+            format!("[{badge} {}] <| {} |>", items.len(), shown.join(", "))
+        }
+        _ => format!(
+            "[{badge}] {}",
+            emit::fragment(value).unwrap_or_else(|_| value.to_string())
+        ),
+    }
+}
+
+fn tree_lines(value: &BsonValue, prefix: &str, ascii: bool, out: &mut Vec<String>) {
+    let BsonValue::Map(m) = value else {
+        return;
+    };
+    let (branch, last, pipe) = if ascii {
+        ("|-- ", "`-- ", "|   ")
+    } else {
+        ("├── ", "└── ", "│   ")
+    };
+    for (i, (key, child)) in m.iter().enumerate() {
+        let is_last = i + 1 == m.len();
+        let joint = if is_last { last } else { branch };
+        out.push(format!("{prefix}{joint}{key} {}", tree_label(child)));
+        let nested = format!("{prefix}{}", if is_last { "    " } else { pipe });
+        tree_lines(child, &nested, ascii, out);
+    }
+}
+
+// rs-bson tree <file> [--ascii]
+fn tree(args: &[String]) {
+    let (file, ascii) = match args {
+        [file] => (file, false),
+        [file, flag] if flag == "--ascii" => (file, true),
+        _ => usage("rs-bson tree <file.bson> [--ascii]"),
+    };
+    let input = read(file);
+    let tokens = lexer::lex_str(&input);
+    let value = tokens
+        .as_ref()
+        .map_err(|err| *err)
+        .and_then(|tokens| parser::parse(tokens));
+    let value = value.unwrap_or_else(|err| {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    });
+    let mut lines = vec![file.to_string()];
+    tree_lines(&value, "", ascii, &mut lines);
+    for line in lines {
+        println!("{line}");
+    }
+}
+
+fn token_json(token: &lexer::Token) -> JsonValue {
+    JsonValue::Object(
+        [
+            ("type", JsonValue::from(format!("{:?}", token.ttype))),
+            ("literal", JsonValue::from(token.literal.as_str())),
+            ("line", JsonValue::Number(token.line as f64)),
+            ("level", JsonValue::Number(token.level as f64)),
+            (
+                "span",
+                JsonValue::Array(vec![
+                    JsonValue::Number(token.span.start as f64),
+                    JsonValue::Number(token.span.end as f64),
+                ]),
+            ),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect(),
+    )
+}
+
+// rs-bson tokens <file> [--json]
+fn tokens(args: &[String]) {
+    let (file, json) = match args {
+        [file] => (file, false),
+        [file, flag] if flag == "--json" => (file, true),
+        _ => usage("rs-bson tokens <file.bson> [--json]"),
+    };
+    let input = read(file);
+    // Line by line, so the tokens before a failing line are still shown
+    let mut lexer = lexer::Lexer::new();
+    let mut failure = None;
+    for (i, line) in input.lines().enumerate() {
+        if let Err(err) = lexer.push_line(line) {
+            failure = Some((i + 1, err));
+            break;
+        }
+    }
+    let partial = lexer.tokens().to_vec();
+    let line_count = input.lines().count();
+    let (tokens, failure) = match failure {
+        Some(failure) => (partial, Some(failure)),
+        None => match lexer.finish() {
+            Ok(tokens) => (tokens, None),
+            Err(err) => (partial, Some((line_count, err))),
+        },
+    };
+
+    if json {
+        println!(
+            "{}",
+            JsonValue::Array(tokens.iter().map(token_json).collect())
+        );
+    } else {
+        println!(
+            "{:<5} {:<5} {:<8} {:<12} LITERAL",
+            "LINE", "LEVEL", "SPAN", "TYPE"
+        );
+        for token in &tokens {
+            let span = format!("{}..{}", token.span.start, token.span.end);
+            let ttype = format!("{:?}", token.ttype);
+            println!(
+                "{:<5} {:<5} {span:<8} {ttype:<12} {:?}",
+                token.line, token.level, token.literal
+            );
+        }
+    }
+    if let Some((line, err)) = failure {
+        eprintln!("{file}:{line}: {err}");
+        process::exit(1);
+    }
+}
+
+// rs-bson doctor <file>
+fn doctor(args: &[String]) {
+    let [file] = args else {
+        usage("rs-bson doctor <file.bson>");
+    };
+    let findings = doctor::examine(&read(file));
+    for finding in &findings {
+        println!("{file}:{}: {}", finding.line, finding.message);
+        println!("    fix: {}", finding.suggestion);
+    }
+    if !findings.is_empty() {
+        process::exit(1);
+    }
+}
+
+// rs-bson fmt <file> [--write]
+fn fmt(args: &[String]) {
+    const USAGE: &str = "rs-bson fmt <file.bson> [--write]";
+    let (file, write) = match args {
+        [file] => (file, false),
+        [file, flag] if flag == "--write" => (file, true),
+        _ => usage(USAGE),
+    };
+    let options = FormatOptions::discover(Path::new(file)).unwrap_or_else(|err| {
+        eprintln!("{}: {err}", format::CONFIG_FILE);
+        process::exit(2);
+    });
+    let formatted = format::format_with(&read(file), &options).unwrap_or_else(|err| {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    });
+    if write {
+        fs::write(file, formatted).unwrap_or_else(|err| {
+            eprintln!("{file}: {err}");
+            process::exit(2);
+        });
+    } else {
+        print!("{formatted}");
+    }
+}
+
+// Prints the rewritten document, or writes it back with `--write`
+fn rewrite_file(file: &str, write: bool, rewritten: Result<String, crypt::CryptError>) {
+    let rewritten = rewritten.unwrap_or_else(|err| {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    });
+    if write {
+        fs::write(file, rewritten).unwrap_or_else(|err| {
+            eprintln!("{file}: {err}");
+            process::exit(2);
+        });
+    } else {
+        print!("{rewritten}");
+    }
+}
+
+// rs-bson seal <file> [--write]
+fn seal(args: &[String]) {
+    let (file, write) = match args {
+        [file] => (file, false),
+        [file, flag] if flag == "--write" => (file, true),
+        _ => usage("rs-bson seal <file.bson> [--write]"),
+    };
+    let input = read(file);
+    // Only a valid document is worth vouching for
+    if let Err(err) =
+        lexer::lex_str(lexer::strip_footer(&input)).and_then(|t| parser::parse(&t).map(|_| ()))
+    {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    }
+    let sealed = checksum::seal(&input);
+    if write {
+        fs::write(file, sealed).unwrap_or_else(|err| {
+            eprintln!("{file}: {err}");
+            process::exit(2);
+        });
+    } else {
+        print!("{sealed}");
+    }
+}
+
+// rs-bson verify <file> [--signature <sig>] [--command <cmd> | --hmac-key <key>]
+fn verify(args: &[String]) {
+    const USAGE: &str = "rs-bson verify <file.bson> [--signature <file.sig>] \
+[--command <cmd> | --hmac-key <key file>]";
+    let mut file = None;
+    let mut signature_path = None;
+    let mut verifier: Option<Box<dyn Verifier>> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage(USAGE));
+        match arg.as_str() {
+            "--signature" => signature_path = Some(value().clone()),
+            "--command" => verifier = Some(Box::new(CommandVerifier::new(value()))),
+            "--hmac-key" => {
+                let key = fs::read(value()).unwrap_or_else(|err| {
+                    eprintln!("{err}");
+                    process::exit(2);
+                });
+                verifier = Some(Box::new(HmacVerifier::new(key.trim_ascii_end())))
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => usage(USAGE),
+        }
+    }
+    let file = file.unwrap_or_else(|| usage(USAGE));
+    let signature_path = signature_path.unwrap_or_else(|| format!("{file}.sig"));
+    // Without a flag, the command in BULBA_VERIFY_COMMAND
+    let verifier = verifier
+        .or_else(|| CommandVerifier::from_env().map(|v| Box::new(v) as Box<dyn Verifier>))
+        .unwrap_or_else(|| usage(USAGE));
+    let verified =
+        signature::read_verified(Path::new(file), Path::new(&signature_path), &*verifier).and_then(
+            |input| {
+                lexer::lex_str(&input)
+                    .and_then(|tokens| parser::parse(&tokens).map(|_| ()))
+                    .map_err(load::LoadError::Parse)
+            },
+        );
+    match verified {
+        Ok(()) => println!("{file}: OK"),
+        Err(err) => {
+            println!("{file}: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+// rs-bson encrypt <file> [--path <path>]... [--write]
+fn encrypt(args: &[String]) {
+    const USAGE: &str = "rs-bson encrypt <file.bson> [--path <path>]... [--write]";
+    let mut file = None;
+    let mut paths = vec![];
+    let mut write = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--path" => paths.push(args.next().unwrap_or_else(|| usage(USAGE)).as_str()),
+            "--write" => write = true,
+            _ if file.is_none() => file = Some(arg),
+            _ => usage(USAGE),
+        }
+    }
+    let file = file.unwrap_or_else(|| usage(USAGE));
+    // Without paths, whatever looks like a credential
+    let select = |path: &str| match paths.as_slice() {
+        [] => lint::sensitive_key(path),
+        paths => paths.iter().any(|p| {
+            path == *p
+                || path
+                    .strip_prefix(p)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        }),
+    };
+    let encrypted = crypt::encrypt_text(&read(file), &CommandKeys::from_env(), select);
+    rewrite_file(file, write, encrypted);
+}
+
+// rs-bson decrypt <file> [--write]
+fn decrypt(args: &[String]) {
+    let (file, write) = match args {
+        [file] => (file, false),
+        [file, flag] if flag == "--write" => (file, true),
+        _ => usage("rs-bson decrypt <file.bson> [--write]"),
+    };
+    let decrypted = crypt::decrypt_text(&read(file), &CommandKeys::from_env());
+    rewrite_file(file, write, decrypted);
+}
+
+// rs-bson schema infer <example>...
+fn schema(args: &[String]) {
+    const USAGE: &str = "rs-bson schema infer <example.bson>...";
+    let [command, files @ ..] = args else {
+        usage(USAGE);
+    };
+    if command != "infer" || files.is_empty() {
+        usage(USAGE);
+    }
+    let examples: Vec<_> = files
+        .iter()
+        .map(|file| {
+            let value = lexer::lex_str(&read(file))
+                .and_then(|tokens| parser::parse(&tokens).map(|value| value.into_owned()));
+            value.unwrap_or_else(|err| {
+                eprintln!("{file}: {err}");
+                process::exit(1);
+            })
+        })
+        .collect();
+    print!("{}", infer::to_bulbaschema(&infer::infer(&examples)));
+}
+
+// rs-bson minify <file>
+fn minify(args: &[String]) {
+    let [file] = args else {
+        usage("rs-bson minify <file.bson>");
+    };
+    let input = read(file);
+    let value =
+        lexer::lex_str(&input).and_then(|tokens| Ok(emit::emit_compact(&parser::parse(&tokens)?)?));
+    match value {
+        Ok(text) => print!("{text}"),
+        Err(err) => {
+            eprintln!("{file}: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+// rs-bson lsp [--schema <schema>]
+fn lsp(args: &[String]) {
+    let schema = match args {
+        [] => None,
+        [flag, path] if flag == "--schema" => Some(load_schema(path)),
+        _ => usage("rs-bson lsp [--schema <file.bulbaschema|file.json>]"),
+    };
+    let mut server = lsp::Server::new(schema);
+    if let Err(err) = server.run(io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("lsp: {err}");
+        process::exit(2);
+    }
+}
+
+// rs-bson query <filter> <file>
+fn query(args: &[String]) {
+    let [filter, file] = args else {
+        usage("rs-bson query <filter> <file.bson>");
+    };
+    let input = read(file);
+    let tokens = lexer::lex_str(&input);
+    let results = tokens
+        .as_ref()
+        .map_err(|err| *err)
+        .and_then(|tokens| Ok(parser::parse(tokens)?.query(filter)?));
+    let results = results.unwrap_or_else(|err| {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    });
+    for result in results {
+        match emit::fragment(&result) {
+            Ok(text) => println!("{text}"),
+            Err(err) => eprintln!("{file}: {err}"),
+        }
+    }
+}
+
+// rs-bson docs <schema|example> [--html]
+fn docs(args: &[String]) {
+    const USAGE: &str = "rs-bson docs <file.bulbaschema|file.json|example.bson> [--html]";
+    let (path, html) = match args {
+        [path] => (path, false),
+        [path, flag] if flag == "--html" => (path, true),
+        _ => usage(USAGE),
+    };
+    let schema = if path.ends_with(".bson") {
+        Document::parse(&read(path))
+            .and_then(|doc| Ok(docs::from_example(&doc)?))
+            .unwrap_or_else(|err| {
+                eprintln!("{path}: {err}");
+                process::exit(1);
+            })
+    } else {
+        load_schema(path)
+    };
+    if html {
+        print!("{}", docs::html(&schema));
+    } else {
+        print!("{}", docs::markdown(&schema));
+    }
+}
+
+// rs-bson repl <file> [--schema <schema>]
+fn repl(args: &[String]) {
+    let (file, schema) = match args {
+        [file] => (file, None),
+        [file, flag, path] if flag == "--schema" => (file, Some(load_schema(path))),
+        _ => usage("rs-bson repl <file.bson> [--schema <file.bulbaschema|file.json>]"),
+    };
+    let input = read(file);
+    let value = lexer::lex_str(&input)
+        .and_then(|tokens| parser::parse(&tokens).map(|value| value.into_owned()));
+    let value = value.unwrap_or_else(|err| {
+        eprintln!("{file}: {err}");
+        process::exit(1);
+    });
+    let mut session = Repl::new(value, schema);
+    if let Err(err) = session.run(io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("repl: {err}");
+        process::exit(2);
+    }
+}
 
 fn main() {
     let args: Vec<_> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("lint") => return lint(&args[2..]),
+        Some("completions") => return completions(&args[2..]),
+        Some("to-env") => return to_env(&args[2..]),
+        Some("from-env") => return from_env(&args[2..]),
+        Some("template") => return template(&args[2..]),
+        Some("fmt") => return fmt(&args[2..]),
+        Some("seal") => return seal(&args[2..]),
+        Some("verify") => return verify(&args[2..]),
+        Some("encrypt") => return encrypt(&args[2..]),
+        Some("decrypt") => return decrypt(&args[2..]),
+        Some("keys") => return keys(&args[2..]),
+        Some("grep") => return grep(&args[2..]),
+        Some("tree") => return tree(&args[2..]),
+        Some("tokens") => return tokens(&args[2..]),
+        Some("doctor") => return doctor(&args[2..]),
+        Some("minify") => return minify(&args[2..]),
+        Some("schema") => return schema(&args[2..]),
+        Some("lsp") => return lsp(&args[2..]),
+        Some("repl") => return repl(&args[2..]),
+        Some("query") => return query(&args[2..]),
+        Some("docs") => return docs(&args[2..]),
+        // rs-bson grammar > bson.tmLanguage.json
+        Some("grammar") => return println!("{}", grammar::textmate()),
+        _ => {}
+    }
+
     let input = if args.len() == 2 {
-        Path::new(&args[1])
+        read(&args[1])
     } else {
-        Path::new("tests/test_data/main_input.bson")
+        read("tests/test_data/main_input.bson")
     };
-    let file = File::open(input).unwrap();
-    let tokens = lexer::lex(file).unwrap();
+    let tokens = lexer::lex_str(&input).unwrap();
     let res = parser::parse(&tokens).unwrap();
-    print!("{}", res.to_string());
+    print!("{}", res);
 }
@@ -0,0 +1,69 @@
+use std::borrow::Cow;
+use std::mem;
+
+use crate::parser::BsonValue;
+
+// Sizing up documents. Everything walks with an explicit stack rather than
+// recursion, so even documents nested far past DEFAULT_MAX_DEPTH are safe.
+impl BsonValue<'_> {
+    // Calls `f` on every value with the number of sections and lists
+    // around it, the root excluded
+    fn for_each_value(&self, mut f: impl FnMut(&BsonValue, usize)) {
+        let mut stack = vec![(self, 0)];
+        while let Some((value, depth)) = stack.pop() {
+            f(value, depth);
+            match value {
+                BsonValue::Map(m) => stack.extend(m.values().map(|child| (child, depth + 1))),
+                BsonValue::Array(items) => stack.extend(items.iter().map(|item| (item, depth + 1))),
+                _ => {}
+            }
+        }
+    }
+
+    /// A rough count of the bytes the document takes in memory: every
+    /// value and key, and the text of strings, keys and decimals, whether
+    /// borrowed from the input or owned. Allocator and tree overhead is
+    /// not included.
+    pub fn deep_size(&self) -> usize {
+        let mut size = 0;
+        self.for_each_value(|value, _| {
+            size += mem::size_of::<BsonValue>();
+            size += match value {
+                BsonValue::BString(s)
+                | BsonValue::Secret(s)
+                | BsonValue::Regex(s)
+                | BsonValue::Encrypted(s) => s.len(),
+                BsonValue::Map(m) => m
+                    .keys()
+                    .map(|key| mem::size_of::<Cow<str>>() + key.len())
+                    .sum(),
+                _ => 0,
+            };
+        });
+        size
+    }
+
+    /// How many sections and lists nest below the root at the deepest
+    /// point, the depth `ParseOptions::max_depth` limits. 0 for a document
+    /// of top-level keys only.
+    pub fn max_depth(&self) -> usize {
+        let mut deepest = 0;
+        self.for_each_value(|value, depth| {
+            if depth > 0 && matches!(value, BsonValue::Map(_) | BsonValue::Array(_)) {
+                deepest = deepest.max(depth);
+            }
+        });
+        deepest
+    }
+
+    /// How many values are neither sections nor lists.
+    pub fn leaf_count(&self) -> usize {
+        let mut count = 0;
+        self.for_each_value(|value, _| {
+            if !matches!(value, BsonValue::Map(_) | BsonValue::Array(_)) {
+                count += 1;
+            }
+        });
+        count
+    }
+}
@@ -0,0 +1,154 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use crate::parser::{BsonValue, join_path};
+use crate::visit::{VisitMut, walk_value_mut};
+
+/// Both sides changed the value at `path` in different ways. `None` means
+/// the key is absent on that side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict<'a> {
+    pub path: String,
+    pub base: Option<BsonValue<'a>>,
+    pub ours: Option<BsonValue<'a>>,
+    pub theirs: Option<BsonValue<'a>>,
+}
+
+/// Result of a three-way merge. Conflicting paths keep our side in `value`,
+/// so a merge driver can still write the file out and report `conflicts`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merged<'a> {
+    pub value: BsonValue<'a>,
+    pub conflicts: Vec<Conflict<'a>>,
+}
+
+// Takes the changes of `theirs` into our side of a three-way merge, with
+// the values at the same place in `base` and `theirs` at hand
+struct Merge<'v, 'a> {
+    base: Option<&'v BsonValue<'a>>,
+    theirs: Option<&'v BsonValue<'a>>,
+    conflicts: Vec<Conflict<'a>>,
+}
+
+impl<'a> Merge<'_, 'a> {
+    fn conflict(&mut self, path: &str, ours: Option<&BsonValue>) {
+        self.conflicts.push(Conflict {
+            path: path.to_string(),
+            base: self.base.cloned(),
+            ours: ours.map(|ours| ours.clone().into_owned()),
+            theirs: self.theirs.cloned(),
+        });
+    }
+}
+
+impl<'v, 'a> VisitMut for Merge<'v, 'a> {
+    fn visit_value_mut(&mut self, path: &str, ours: &mut BsonValue) {
+        let (base, theirs) = (self.base, self.theirs);
+        let current: &BsonValue = ours;
+        if theirs.is_some_and(|theirs| theirs == current) || theirs == base {
+            return;
+        }
+        // Keys they removed are dropped by the section around them
+        if let (Some(base), Some(theirs)) = (base, theirs)
+            && base == current
+        {
+            *ours = theirs.clone().into_owned();
+            return;
+        }
+        if let (BsonValue::Map(_), Some(BsonValue::Map(_))) = (current, theirs) {
+            return walk_value_mut(self, path, ours);
+        }
+        self.conflict(path, Some(current));
+    }
+
+    fn visit_map_mut(&mut self, path: &str, ours: &mut BTreeMap<Cow<'_, str>, BsonValue<'_>>) {
+        let (base, theirs) = (self.base, self.theirs);
+        let section = |value: Option<&'v BsonValue<'a>>| match value {
+            Some(BsonValue::Map(m)) => Some(m),
+            _ => None,
+        };
+        let (base_map, their_map) = (section(base), section(theirs));
+        let get = |map: Option<&'v BTreeMap<Cow<'a, str>, BsonValue<'a>>>, key: &str| {
+            map.and_then(|map| map.get(key))
+        };
+
+        // Ours unchanged where they removed it
+        ours.retain(|key, value| {
+            let value: &BsonValue = value;
+            get(their_map, key).is_some() || get(base_map, key).is_none_or(|base| base != value)
+        });
+        // Theirs alone: added by them, or removed by us
+        for (key, value) in their_map.into_iter().flatten() {
+            if ours.contains_key(key.as_ref()) {
+                continue;
+            }
+            self.base = get(base_map, key);
+            self.theirs = Some(value);
+            match self.base {
+                None => {
+                    ours.insert(Cow::Owned(key.to_string()), value.clone().into_owned());
+                }
+                Some(base) if base == value => {}
+                Some(_) => self.conflict(&join_path(path, key), None),
+            }
+        }
+        for (key, value) in ours.iter_mut() {
+            self.base = get(base_map, key);
+            self.theirs = get(their_map, key);
+            self.visit_value_mut(&join_path(path, key), value);
+        }
+        (self.base, self.theirs) = (base, theirs);
+    }
+}
+
+/// Merges the changes `ours` and `theirs` each made to `base`. Sections are
+/// merged key by key; any other value changed differently on both sides is
+/// reported as a conflict.
+pub fn merge3<'a>(
+    base: &BsonValue<'a>,
+    ours: &BsonValue<'a>,
+    theirs: &BsonValue<'a>,
+) -> Merged<'a> {
+    let mut merge = Merge {
+        base: Some(base),
+        theirs: Some(theirs),
+        conflicts: vec![],
+    };
+    let mut value = ours.clone();
+    value.visit_mut(&mut merge);
+    // In the order of their paths, as the keys of each section come
+    let mut conflicts = merge.conflicts;
+    conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+    Merged { value, conflicts }
+}
+
+impl<'a> BsonValue<'a> {
+    /// Lays `top` over the value: sections present in both are combined key
+    /// by key, anything else in `top` replaces what was there. Arrays are
+    /// replaced whole.
+    pub fn overlay(&mut self, top: BsonValue<'a>) {
+        match (self, top.into_map()) {
+            (BsonValue::Map(base), Ok(top)) => {
+                for (key, value) in top {
+                    match base.get_mut(&key) {
+                        Some(existing) => existing.overlay(value),
+                        None => {
+                            base.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (base, Ok(top)) => *base = BsonValue::Map(top),
+            (base, Err(top)) => *base = top,
+        }
+    }
+
+    /// Fills in every key `defaults` has and the value lacks, at any depth,
+    /// without touching keys that are present, even when they hold
+    /// `MissingNo`. Arrays count as single values and are never combined.
+    pub fn with_defaults(self, defaults: &BsonValue<'a>) -> BsonValue<'a> {
+        let mut filled = defaults.clone();
+        filled.overlay(self);
+        filled
+    }
+}
@@ -0,0 +1,84 @@
+use crate::parser::{BsonValue, to_pointer};
+use crate::patch::Patch;
+
+/// One upgrade step between two versions of a config format.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub from: u32,
+    pub to: u32,
+    pub transform: fn(BsonValue<'static>) -> BsonValue<'static>,
+}
+
+/// Registry of upgrade steps. The version of a document is read from a key,
+/// `version` unless told otherwise; documents without it count as version 1.
+#[derive(Debug, Clone)]
+pub struct Migrations {
+    key: String,
+    migrations: Vec<Migration>,
+}
+
+impl Default for Migrations {
+    fn default() -> Migrations {
+        Migrations::new()
+    }
+}
+
+impl Migrations {
+    pub fn new() -> Migrations {
+        Migrations {
+            key: String::from("version"),
+            migrations: vec![],
+        }
+    }
+
+    /// Dotted path of the key holding the version.
+    pub fn version_key(mut self, path: &str) -> Migrations {
+        self.key = path.to_string();
+        self
+    }
+
+    pub fn register(mut self, migration: Migration) -> Migrations {
+        self.migrations.push(migration);
+        self
+    }
+
+    /// The newest version any registered step leads to.
+    pub fn latest(&self) -> u32 {
+        self.migrations.iter().map(|m| m.to).max().unwrap_or(1)
+    }
+
+    pub fn version_of(&self, value: &BsonValue) -> Result<u32, &'static str> {
+        match value.get(&self.key) {
+            None => Ok(1),
+            Some(BsonValue::Number(n)) if n.fract() == 0.0 && *n >= 0.0 => Ok(*n as u32),
+            Some(_) => Err("Target is immune!"),
+        }
+    }
+
+    /// Runs the steps from the version of `value` up to `latest`, one after
+    /// another, and records the new version in the document. A document
+    /// newer than every step is refused, as is a gap in the chain.
+    pub fn migrate_to_latest(
+        &self,
+        mut value: BsonValue<'static>,
+    ) -> Result<BsonValue<'static>, &'static str> {
+        let latest = self.latest();
+        let mut version = self.version_of(&value)?;
+        if version > latest {
+            return Err("Not enough badges!");
+        }
+        while version < latest {
+            let step = self
+                .migrations
+                .iter()
+                .find(|m| m.from == version && m.to > version)
+                .ok_or("But it failed!")?;
+            value = (step.transform)(value);
+            version = step.to;
+            value.apply_patch(
+                &Patch::new().add(&to_pointer(&self.key), BsonValue::Number(version as f64)),
+            )?;
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use crate::lexer::{self, TokenType, Warning};
+use crate::load::{self, LoadError};
+use crate::parser::{self, BsonValue, ParseError, ParseOptions};
+
+/// A parsed document with what is known about where it came from, for
+/// callers that log their config loading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseOutput {
+    pub value: BsonValue<'static>,
+    /// The file it was read from, `None` for text handed over directly
+    pub source_name: Option<String>,
+    /// The cry it opened with, which tells formats apart where the options
+    /// accept more than one, see `ParseOptions::accept_header`. `None` for
+    /// an empty input the options let through.
+    pub header_version: Option<String>,
+    pub stats: ParseStats,
+    /// What lenient options let through
+    pub warnings: Vec<Warning>,
+}
+
+/// How big a parsed document was.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ParseStats {
+    pub bytes: usize,
+    pub lines: usize,
+    pub tokens: usize,
+    /// Values that are not sections or lists, see `BsonValue::leaf_count`
+    pub leaves: usize,
+    /// See `BsonValue::max_depth`
+    pub depth: usize,
+    /// Bytes in memory, see `BsonValue::deep_size`
+    pub size: usize,
+}
+
+/// `parser::parse_with` on `input` that keeps what it learned on the way.
+pub fn parse_output(input: &str, options: &ParseOptions) -> Result<ParseOutput, ParseError> {
+    let (tokens, warnings) = lexer::lex_str_with_warnings(input, options)?;
+    let value = parser::parse_with(&tokens, options)?.into_owned();
+    let header_version = tokens
+        .first()
+        .filter(|token| token.ttype == TokenType::Header)
+        .map(|token| token.literal.clone());
+    let stats = ParseStats {
+        bytes: input.len(),
+        lines: input.lines().count(),
+        tokens: tokens.len(),
+        leaves: value.leaf_count(),
+        depth: value.max_depth(),
+        size: value.deep_size(),
+    };
+    Ok(ParseOutput {
+        value,
+        source_name: None,
+        header_version,
+        stats,
+        warnings,
+    })
+}
+
+/// `parse_output` on the file at `path`, decompressed first where needed,
+/// with the path as its source name.
+pub fn parse_file_output(
+    path: impl AsRef<Path>,
+    options: &ParseOptions,
+) -> Result<ParseOutput, LoadError> {
+    let path = path.as_ref();
+    let text = load::read(path)?;
+    let mut output = parse_output(&text, options)?;
+    output.source_name = Some(path.display().to_string());
+    Ok(output)
+}
@@ -1,143 +1,1484 @@
-use std::cell::RefCell;
-use std::collections::BTreeMap;
-use std::rc::Rc;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::Range;
 
+#[cfg(feature = "regex")]
+use regex::Regex;
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+#[cfg(feature = "decimal")]
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+
+use crate::expr::Expression;
 use crate::lexer;
+use crate::spanned::{Span, Spans};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug)]
 pub enum BsonValue<'a> {
-    BString(&'a str),
+    BString(Cow<'a, str>),
     Number(f64),
     Bool(bool),
-    Array(Vec<Rc<RefCell<BsonValue<'a>>>>),
-    Map(BTreeMap<&'a str, Rc<RefCell<BsonValue<'a>>>>),
+    Array(Vec<BsonValue<'a>>),
+    Map(BTreeMap<Cow<'a, str>, BsonValue<'a>>),
     Null(()),
+    /// Reference to a secret kept outside the file, see `secret::SecretResolver`
+    Secret(Cow<'a, str>),
+    /// A whole number written with the `i` suffix, such as `8080i`, or one
+    /// `f64` cannot hold, such as a 64-bit ID past 2^53, kept exactly
+    Integer(i128),
+    /// A number written with the `f` suffix, such as `1.5f`, which stays a
+    /// float whatever the options say
+    Float(f64),
+    /// A date or RFC 3339 date-time written with the `d` suffix, such as
+    /// `"2024-01-01"d`, without the quotes
+    Date(Cow<'a, str>),
+    /// A number kept exactly as written, so values like `0.1` add up and
+    /// compare exactly. See `ParseOptions::exact_numbers` and `as_decimal`.
+    #[cfg(feature = "decimal")]
+    Decimal(Decimal),
+    /// A matching rule written between slashes, such as `/^poke[0-9]+$/`,
+    /// without them. See `ParseOptions::check_regexes` and `to_regex`.
+    Regex(Cow<'a, str>),
+    /// A value stored encrypted as `ENC[...]`, holding the ciphertext. See
+    /// `crypt::KeyProvider`.
+    Encrypted(Cow<'a, str>),
 }
 
-impl<'a> BsonValue<'a> {
-    pub fn to_string(&self) -> String {
-        let mut result = String::new();
-        to_string_rec(&self, 0, &mut result);
-        result
+// Drop, Clone, PartialEq and into_owned keep their own stack rather than
+// recursing, so a document deep enough to pass max_depth cannot overflow the
+// call stack on the way out.
+impl Drop for BsonValue<'_> {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        take_children(self, &mut pending);
+        while let Some(mut value) = pending.pop() {
+            take_children(&mut value, &mut pending);
+        }
+    }
+}
+
+// Moves the children of a section or list that has nested ones out to `pending`
+fn take_children<'a>(value: &mut BsonValue<'a>, pending: &mut Vec<BsonValue<'a>>) {
+    let nested = |child: &BsonValue| matches!(child, BsonValue::Array(_) | BsonValue::Map(_));
+    match value {
+        BsonValue::Array(items) if items.iter().any(nested) => pending.append(items),
+        BsonValue::Map(m) if m.values().any(nested) => {
+            pending.extend(std::mem::take(m).into_values())
+        }
+        _ => {}
     }
 }
 
-fn to_string_rec<'a>(bson: &BsonValue<'a>, level: usize, result: &mut String) {
-    let indent = "    ".repeat(level);
+impl Clone for BsonValue<'_> {
+    fn clone(&self) -> Self {
+        rebuild(self, |node| match node {
+            BsonValue::Array(items) => Opened::Array(items.iter().collect()),
+            BsonValue::Map(m) => {
+                Opened::Map(m.iter().map(|(key, value)| (key.clone(), value)).collect())
+            }
+            BsonValue::BString(s) => Opened::Leaf(BsonValue::BString(s.clone())),
+            BsonValue::Secret(s) => Opened::Leaf(BsonValue::Secret(s.clone())),
+            BsonValue::Date(d) => Opened::Leaf(BsonValue::Date(d.clone())),
+            BsonValue::Regex(r) => Opened::Leaf(BsonValue::Regex(r.clone())),
+            BsonValue::Encrypted(c) => Opened::Leaf(BsonValue::Encrypted(c.clone())),
+            BsonValue::Number(n) => Opened::Leaf(BsonValue::Number(*n)),
+            BsonValue::Bool(b) => Opened::Leaf(BsonValue::Bool(*b)),
+            BsonValue::Null(()) => Opened::Leaf(BsonValue::Null(())),
+            BsonValue::Integer(n) => Opened::Leaf(BsonValue::Integer(*n)),
+            BsonValue::Float(n) => Opened::Leaf(BsonValue::Float(*n)),
+            #[cfg(feature = "decimal")]
+            BsonValue::Decimal(d) => Opened::Leaf(BsonValue::Decimal(*d)),
+        })
+    }
+}
 
-    match bson {
-        BsonValue::Array(arr) => {
-            *result += "\n";
-            for elem in arr {
-                *result += format!("{indent}-").as_str();
-                if let BsonValue::Map(ref _m) = *elem.borrow() {
-                    *result += "\n";
-                    to_string_rec(&elem.borrow(), level + 1, result);
-                } else {
-                    to_string_rec(&elem.borrow(), 0, result);
+impl PartialEq for BsonValue<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        let mut pending = vec![(self, other)];
+        while let Some(pair) = pending.pop() {
+            let equal = match pair {
+                (BsonValue::Array(a), BsonValue::Array(b)) => {
+                    pending.extend(a.iter().zip(b));
+                    a.len() == b.len()
+                }
+                (BsonValue::Map(a), BsonValue::Map(b)) => {
+                    pending.extend(a.values().zip(b.values()));
+                    a.len() == b.len() && a.keys().eq(b.keys())
+                }
+                (BsonValue::BString(a), BsonValue::BString(b))
+                | (BsonValue::Secret(a), BsonValue::Secret(b))
+                | (BsonValue::Date(a), BsonValue::Date(b))
+                | (BsonValue::Regex(a), BsonValue::Regex(b))
+                | (BsonValue::Encrypted(a), BsonValue::Encrypted(b)) => a == b,
+                (BsonValue::Number(a), BsonValue::Number(b))
+                | (BsonValue::Float(a), BsonValue::Float(b)) => a == b,
+                (BsonValue::Bool(a), BsonValue::Bool(b)) => a == b,
+                (BsonValue::Null(()), BsonValue::Null(())) => true,
+                (BsonValue::Integer(a), BsonValue::Integer(b)) => a == b,
+                #[cfg(feature = "decimal")]
+                (BsonValue::Decimal(a), BsonValue::Decimal(b)) => a == b,
+                _ => false,
+            };
+            if !equal {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// What `rebuild` makes of one node: a finished value, or the children of a
+// list or section still to be built
+enum Opened<'o, N> {
+    Leaf(BsonValue<'o>),
+    Array(Vec<N>),
+    Map(Vec<(Cow<'o, str>, N)>),
+}
+
+// A list or section whose children are being built
+enum Building<'o, N> {
+    Array(std::vec::IntoIter<N>, Vec<BsonValue<'o>>),
+    Map(
+        std::vec::IntoIter<(Cow<'o, str>, N)>,
+        BTreeMap<Cow<'o, str>, BsonValue<'o>>,
+        Option<Cow<'o, str>>,
+    ),
+}
+
+// Builds a value from `root` bottom-up with an explicit stack, `open`
+// turning each node into a leaf or the children still to build
+fn rebuild<'o, N>(root: N, open: impl Fn(N) -> Opened<'o, N>) -> BsonValue<'o> {
+    let mut stack: Vec<Building<'o, N>> = Vec::new();
+    let mut next = root;
+    loop {
+        let mut built = match open(next) {
+            Opened::Leaf(value) => Some(value),
+            Opened::Array(items) => {
+                let len = items.len();
+                stack.push(Building::Array(items.into_iter(), Vec::with_capacity(len)));
+                None
+            }
+            Opened::Map(entries) => {
+                stack.push(Building::Map(entries.into_iter(), BTreeMap::new(), None));
+                None
+            }
+        };
+        // Hands finished values to their parents until one has a child left
+        loop {
+            let Some(top) = stack.last_mut() else {
+                return built.expect("the root is built last");
+            };
+            let child = match top {
+                Building::Array(items, done) => {
+                    done.extend(built.take());
+                    items.next()
+                }
+                Building::Map(entries, done, key) => {
+                    if let (Some(key), Some(value)) = (key.take(), built.take()) {
+                        done.insert(key, value);
+                    }
+                    entries.next().map(|(name, child)| {
+                        *key = Some(name);
+                        child
+                    })
                 }
+            };
+            if let Some(child) = child {
+                next = child;
+                break;
             }
+            built = Some(match stack.pop() {
+                Some(Building::Array(_, done)) => BsonValue::Array(done),
+                Some(Building::Map(_, done, _)) => BsonValue::Map(done),
+                None => unreachable!(),
+            });
+        }
+    }
+}
+
+fn owned(text: &mut Cow<'_, str>) -> Cow<'static, str> {
+    Cow::Owned(std::mem::take(text).into_owned())
+}
+
+impl<'a> From<&'a str> for BsonValue<'a> {
+    fn from(s: &'a str) -> Self {
+        BsonValue::BString(Cow::Borrowed(s))
+    }
+}
+
+impl From<String> for BsonValue<'_> {
+    fn from(s: String) -> Self {
+        BsonValue::BString(Cow::Owned(s))
+    }
+}
+
+impl From<f64> for BsonValue<'_> {
+    fn from(n: f64) -> Self {
+        BsonValue::Number(n)
+    }
+}
+
+impl From<i32> for BsonValue<'_> {
+    fn from(n: i32) -> Self {
+        BsonValue::Number(n.into())
+    }
+}
+
+impl From<bool> for BsonValue<'_> {
+    fn from(b: bool) -> Self {
+        BsonValue::Bool(b)
+    }
+}
+
+static NULL: BsonValue<'static> = BsonValue::Null(());
+
+pub(crate) fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+impl<'a> BsonValue<'a> {
+    /// Looks up a dotted path such as `database.pool.max_connections`.
+    /// Numeric segments index into arrays and the empty path is the value itself.
+    pub fn get(&self, path: &str) -> Option<&BsonValue<'a>> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.split('.')
+            .try_fold(self, |value, segment| match value {
+                BsonValue::Map(m) => m.get(segment),
+                BsonValue::Array(arr) => arr.get(segment.parse::<usize>().ok()?),
+                _ => None,
+            })
+    }
+
+    /// Looks up a JSON Pointer (RFC 6901) such as
+    /// `/database/pool/max_connections`. Unlike dotted paths it reaches keys
+    /// containing dots; `~1` stands for `/` and `~0` for `~` inside a key.
+    /// The empty pointer is the value itself.
+    pub fn pointer(&self, pointer: &str) -> Option<&BsonValue<'a>> {
+        pointer_segments(pointer)?
+            .iter()
+            .try_fold(self, |value, segment| match value {
+                BsonValue::Map(m) => m.get(segment.as_str()),
+                BsonValue::Array(arr) => arr.get(pointer_index(segment)?),
+                _ => None,
+            })
+    }
+
+    /// `pointer` with mutable access.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut BsonValue<'a>> {
+        pointer_segments(pointer)?
+            .iter()
+            .try_fold(self, |value, segment| match value {
+                BsonValue::Map(m) => m.get_mut(segment.as_str()),
+                BsonValue::Array(arr) => arr.get_mut(pointer_index(segment)?),
+                _ => None,
+            })
+    }
+
+    /// `get` ignoring ASCII case, so `Database.HOST` finds `database.host`.
+    /// An exact match wins; otherwise the first key in order that matches.
+    /// Keys keep the casing of the file, lookups only compare differently.
+    pub fn get_ci(&self, path: &str) -> Option<&BsonValue<'a>> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.split('.')
+            .try_fold(self, |value, segment| match value {
+                BsonValue::Map(m) => m.get(segment).or_else(|| {
+                    m.iter()
+                        .find(|(key, _)| key.eq_ignore_ascii_case(segment))
+                        .map(|(_, child)| child)
+                }),
+                BsonValue::Array(arr) => arr.get(segment.parse::<usize>().ok()?),
+                _ => None,
+            })
+    }
+
+    /// Whether the key at `path` is written in the document, even as
+    /// `MissingNo`. `get` makes the same split: `None` for an absent key,
+    /// `Some(MissingNo)` for one set to nothing.
+    pub fn is_set(&self, path: &str) -> bool {
+        self.get(path).is_some()
+    }
+
+    /// Whether the key at `path` is present and explicitly `MissingNo`.
+    pub fn is_null(&self, path: &str) -> bool {
+        matches!(self.get(path), Some(BsonValue::Null(())))
+    }
+
+    /// `get` for callers that treat both alike: an absent key reads as
+    /// `MissingNo`.
+    pub fn get_or_null(&self, path: &str) -> &BsonValue<'a> {
+        self.get(path).unwrap_or(&NULL)
+    }
+
+    /// The value at `path`, `None` when it is absent or `MissingNo`.
+    pub fn get_non_null(&self, path: &str) -> Option<&BsonValue<'a>> {
+        self.get(path)
+            .filter(|value| !matches!(value, BsonValue::Null(())))
+    }
+
+    pub fn into_owned(self) -> BsonValue<'static> {
+        rebuild(self, |mut node| match &mut node {
+            BsonValue::Array(items) => Opened::Array(std::mem::take(items)),
+            BsonValue::Map(m) => Opened::Map(
+                std::mem::take(m)
+                    .into_iter()
+                    .map(|(key, value)| (Cow::Owned(key.into_owned()), value))
+                    .collect(),
+            ),
+            BsonValue::BString(s) => Opened::Leaf(BsonValue::BString(owned(s))),
+            BsonValue::Secret(s) => Opened::Leaf(BsonValue::Secret(owned(s))),
+            BsonValue::Date(d) => Opened::Leaf(BsonValue::Date(owned(d))),
+            BsonValue::Regex(r) => Opened::Leaf(BsonValue::Regex(owned(r))),
+            BsonValue::Encrypted(c) => Opened::Leaf(BsonValue::Encrypted(owned(c))),
+            BsonValue::Number(n) => Opened::Leaf(BsonValue::Number(*n)),
+            BsonValue::Bool(b) => Opened::Leaf(BsonValue::Bool(*b)),
+            BsonValue::Null(()) => Opened::Leaf(BsonValue::Null(())),
+            BsonValue::Integer(n) => Opened::Leaf(BsonValue::Integer(*n)),
+            BsonValue::Float(n) => Opened::Leaf(BsonValue::Float(*n)),
+            #[cfg(feature = "decimal")]
+            BsonValue::Decimal(d) => Opened::Leaf(BsonValue::Decimal(*d)),
+        })
+    }
+
+    /// Takes the keys of a section without copying them, or hands the value
+    /// back when it is not a section.
+    pub fn into_map(mut self) -> Result<BTreeMap<Cow<'a, str>, BsonValue<'a>>, BsonValue<'a>> {
+        match &mut self {
+            BsonValue::Map(m) => Ok(std::mem::take(m)),
+            _ => Err(self),
+        }
+    }
+
+    /// Takes the elements of a Razor Leaf list, see `into_map`.
+    pub fn into_array(mut self) -> Result<Vec<BsonValue<'a>>, BsonValue<'a>> {
+        match &mut self {
+            BsonValue::Array(items) => Ok(std::mem::take(items)),
+            _ => Err(self),
+        }
+    }
+
+    /// Takes the text of a string, see `into_map`. Secret references are
+    /// not strings until resolved.
+    pub fn into_string(mut self) -> Result<Cow<'a, str>, BsonValue<'a>> {
+        match &mut self {
+            BsonValue::BString(s) => Ok(std::mem::take(s)),
+            _ => Err(self),
+        }
+    }
+
+    /// Compiles a `Regex` value. `None` for every other type and for
+    /// patterns that do not compile, which `ParseOptions::check_regexes`
+    /// refuses up front.
+    #[cfg(feature = "regex")]
+    pub fn to_regex(&self) -> Option<Regex> {
+        match self {
+            BsonValue::Regex(pattern) => Regex::new(pattern).ok(),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is a `Number`, `Integer`, `Float` or `Decimal`.
+    pub fn is_number(&self) -> bool {
+        match self {
+            BsonValue::Number(_) | BsonValue::Integer(_) | BsonValue::Float(_) => true,
+            #[cfg(feature = "decimal")]
+            BsonValue::Decimal(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The value of a `Number` or `Float`, or of an `Integer` or `Decimal`
+    /// rounded to the nearest `f64`. `None` for every other type.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            BsonValue::Number(n) | BsonValue::Float(n) => Some(*n),
+            BsonValue::Integer(n) => Some(*n as f64),
+            #[cfg(feature = "decimal")]
+            BsonValue::Decimal(d) => d.to_f64(),
+            _ => None,
         }
-        BsonValue::Map(map) => {
-            for (key, value) in map.iter() {
-                *result += format!("{indent}{key}:").as_str();
-                if let BsonValue::Map(ref _m) = *value.borrow() {
-                    *result += "\n";
-                    to_string_rec(&value.borrow(), level + 1, result);
+    }
+
+    /// Any number as a `Decimal`, for arithmetic and comparisons that must
+    /// not round: a `Number` by its shortest spelling, so `0.1` is exactly
+    /// one tenth. `None` for other types and numbers a `Decimal` cannot hold.
+    #[cfg(feature = "decimal")]
+    pub fn as_decimal(&self) -> Option<Decimal> {
+        match self {
+            BsonValue::Number(n) | BsonValue::Float(n) => Decimal::from_f64(*n),
+            BsonValue::Integer(n) => Decimal::from_i128(*n),
+            BsonValue::Decimal(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    /// Orders two numbers by value, whatever kind each is: an `Integer`
+    /// against a whole number and a `Decimal` against any number exactly,
+    /// the rest as `f64`. `None` unless both are numbers.
+    pub fn compare_numbers(&self, other: &BsonValue) -> Option<Ordering> {
+        // Exact numbers compare exactly, `0.1 + 0.2` is not more than `0.3`
+        let whole = |value: &BsonValue| match value {
+            BsonValue::Integer(n) => Some(*n),
+            BsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e38 => Some(*n as i128),
+            _ => None,
+        };
+        if (matches!(self, BsonValue::Integer(_)) || matches!(other, BsonValue::Integer(_)))
+            && let (Some(a), Some(b)) = (whole(self), whole(other))
+        {
+            return Some(a.cmp(&b));
+        }
+        #[cfg(feature = "decimal")]
+        if (matches!(self, BsonValue::Decimal(_)) || matches!(other, BsonValue::Decimal(_)))
+            && let (Some(a), Some(b)) = (self.as_decimal(), other.as_decimal())
+        {
+            return Some(a.cmp(&b));
+        }
+        self.as_f64()?.partial_cmp(&other.as_f64()?)
+    }
+
+    /// `==` that takes numbers by value, so `80i` equals `80`.
+    pub fn same_value(&self, other: &BsonValue) -> bool {
+        match self.compare_numbers(other) {
+            Some(ordering) => ordering == Ordering::Equal,
+            None => self == other,
+        }
+    }
+
+    /// Reads a flag from a config some other tool wrote. Besides a real
+    /// `Bool` this accepts the numbers 1 and 0 and, ignoring ASCII case and
+    /// surrounding spaces, the strings `true`/`false`, `yes`/`no`, `on`/`off`,
+    /// `1`/`0` and the BSON keywords. Anything else is `None`.
+    pub fn as_bool_lenient(&self) -> Option<bool> {
+        match self {
+            BsonValue::Bool(b) => Some(*b),
+            value if value.is_number() => match value.as_f64() {
+                Some(1.0) => Some(true),
+                Some(0.0) => Some(false),
+                _ => None,
+            },
+            BsonValue::BString(s) => {
+                let s = s.trim();
+                let is = |words: [&str; 5]| words.iter().any(|w| s.eq_ignore_ascii_case(w));
+                if is(["true", "yes", "on", "1", lexer::TRUE]) {
+                    Some(true)
+                } else if is(["false", "no", "off", "0", lexer::FALSE]) {
+                    Some(false)
                 } else {
-                    to_string_rec(&value.borrow(), 0, result);
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads a number from a config some other tool wrote: a `Number`,
+    /// `Integer` or `Decimal`, a string holding a finite number such as `"100"` once
+    /// surrounding spaces are trimmed, or a `Bool` as 1 or 0.
+    pub fn as_f64_lenient(&self) -> Option<f64> {
+        match self {
+            BsonValue::BString(s) => s.trim().parse().ok().filter(|n: &f64| n.is_finite()),
+            BsonValue::Bool(b) => Some(f64::from(u8::from(*b))),
+            _ => self.as_f64(),
+        }
+    }
+
+    /// `as_f64_lenient` for whole numbers that fit an `i64`. Integers
+    /// written out in full, in an `Integer`, `Decimal` or a string, are
+    /// read exactly.
+    pub fn as_i64_lenient(&self) -> Option<i64> {
+        let digits = match self {
+            BsonValue::BString(s) => s.trim().parse().ok(),
+            BsonValue::Integer(n) => i64::try_from(*n).ok(),
+            #[cfg(feature = "decimal")]
+            BsonValue::Decimal(d) if d.fract().is_zero() => d.to_i64(),
+            _ => None,
+        };
+        digits.or_else(|| {
+            let n = self.as_f64_lenient()?;
+            let fits = n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64;
+            fits.then_some(n as i64)
+        })
+    }
+
+    /// The dotted path of every key with its value, each section ahead of
+    /// the keys inside it. Razor Leaf lists count as one value.
+    pub fn paths(&self) -> Vec<(String, &BsonValue<'a>)> {
+        fn walk<'v, 'a>(
+            value: &'v BsonValue<'a>,
+            path: &str,
+            out: &mut Vec<(String, &'v BsonValue<'a>)>,
+        ) {
+            if let BsonValue::Map(m) = value {
+                for (key, child) in m {
+                    let child_path = join_path(path, key);
+                    out.push((child_path.clone(), child));
+                    walk(child, &child_path, out);
+                }
+            }
+        }
+        let mut out = vec![];
+        walk(self, "", &mut out);
+        out
+    }
+
+    /// Every value that is not a section or list under its dotted path,
+    /// with list elements addressed by index, e.g. `whitelist.0`. Empty
+    /// sections and lists are kept as they are so nothing goes missing.
+    pub fn flatten(&self) -> BTreeMap<String, BsonValue<'a>> {
+        self.flatten_with(true)
+    }
+
+    // `flatten`, with lists kept whole unless `lists` is set
+    pub(crate) fn flatten_with(&self, lists: bool) -> BTreeMap<String, BsonValue<'a>> {
+        fn walk<'a>(
+            value: &BsonValue<'a>,
+            path: &str,
+            lists: bool,
+            out: &mut BTreeMap<String, BsonValue<'a>>,
+        ) {
+            match value {
+                BsonValue::Map(m) if !m.is_empty() => {
+                    for (key, child) in m {
+                        walk(child, &join_path(path, key), lists, out);
+                    }
+                }
+                BsonValue::Array(items) if lists && !items.is_empty() => {
+                    for (i, item) in items.iter().enumerate() {
+                        walk(item, &join_path(path, &i.to_string()), lists, out);
+                    }
+                }
+                _ => {
+                    out.insert(path.to_string(), value.clone());
                 }
             }
         }
-        _ => {
-            let value = match bson {
-                BsonValue::BString(s) => &format!(" {}", s),
-                BsonValue::Number(n) => &format!(" {}", n)[..],
-                BsonValue::Bool(b) => &format!(" {}", b)[..],
-                _ => "",
+        let mut out = BTreeMap::new();
+        walk(self, "", lists, &mut out);
+        out
+    }
+
+    /// The inverse of `flatten`: nests every value under its dotted path.
+    /// A section whose keys count up from `0` becomes a Razor Leaf list.
+    /// Fails when a path is both a value and a prefix of another path.
+    pub fn unflatten(
+        flat: impl IntoIterator<Item = (String, BsonValue<'a>)>,
+    ) -> Result<BsonValue<'a>, PathConflict> {
+        unflatten_segments(flat.into_iter().map(|(path, value)| {
+            let segments = match path.as_str() {
+                "" => vec![],
+                path => path.split('.').map(String::from).collect(),
             };
-            *result += format!("{indent}{}\n", value).as_str();
+            (segments, value)
+        }))
+    }
+
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut BsonValue<'a>> {
+        if path.is_empty() {
+            return Some(self);
         }
+        path.split('.')
+            .try_fold(self, |value, segment| match value {
+                BsonValue::Map(m) => m.get_mut(segment),
+                BsonValue::Array(arr) => arr.get_mut(segment.parse::<usize>().ok()?),
+                _ => None,
+            })
     }
 }
 
-fn validate_key(key: &str) -> Result<(), &'static str> {
-    if key == "Charizard" {
-        return Err("It burns the bulb");
+// The unescaped keys of a JSON Pointer, None when it is malformed
+pub(crate) fn pointer_segments(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(vec![]);
     }
-    Ok(())
+    pointer
+        .strip_prefix('/')?
+        .split('/')
+        .map(pointer_key)
+        .collect()
 }
 
-fn parse_value_from_tokens<'a>(
-    tokens: &'a Vec<lexer::Token>,
-    idx: usize,
-) -> Result<(BsonValue<'a>, usize), &'static str> {
-    if idx >= tokens.len() {
-        return Err("It hurt itself in its confusion!");
+// A segment of a JSON Pointer with `~1` and `~0` unescaped
+pub(crate) fn pointer_key(segment: &str) -> Option<String> {
+    let mut key = String::with_capacity(segment.len());
+    let mut chars = segment.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '~' {
+            key.push(ch);
+            continue;
+        }
+        key.push(match chars.next()? {
+            '0' => '~',
+            '1' => '/',
+            _ => return None,
+        });
     }
+    Some(key)
+}
 
-    let token = &tokens[idx];
-    match token.ttype {
-        lexer::TokenType::TString => Ok((BsonValue::BString(token.literal.as_str()), idx + 1)),
-        lexer::TokenType::Number => Ok((
-            BsonValue::Number(token.literal.parse::<f64>().unwrap()),
-            idx + 1,
-        )),
-        lexer::TokenType::Bool => Ok((BsonValue::Bool(token.literal == "true"), idx + 1)),
-        lexer::TokenType::Null => Ok((BsonValue::Null(()), idx + 1)),
-        lexer::TokenType::ArrayStart => {
-            let mut curr = idx + 1;
-            let mut arr = vec![];
-            while curr < tokens.len() {
-                if tokens[curr].ttype == lexer::TokenType::ArrayEnd {
-                    return Ok((BsonValue::Array(arr), curr + 1));
-                }
-                if tokens[curr].ttype == lexer::TokenType::Comma {
-                    curr += 1; // Consume COMMA
-                    continue;
+// Array indices in a pointer are plain digits without leading zeros
+pub(crate) fn pointer_index(segment: &str) -> Option<usize> {
+    let digits = !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+    if !digits || (segment.len() > 1 && segment.starts_with('0')) {
+        return None;
+    }
+    segment.parse().ok()
+}
+
+// `pointer` with `key` appended, escaped as a JSON Pointer needs
+pub(crate) fn join_pointer(pointer: &str, key: &str) -> String {
+    format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Writes a dotted path as a JSON Pointer, see `BsonValue::pointer`.
+pub fn to_pointer(path: &str) -> String {
+    if path.is_empty() {
+        return String::new();
+    }
+    path.split('.')
+        .fold(String::new(), |pointer, key| join_pointer(&pointer, key))
+}
+
+/// A path given a value by one entry and a key inside it by another, e.g.
+/// both `database` and `database.host`.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Two Pokemon in one Poke Ball: `{0}`")]
+pub struct PathConflict(pub String);
+
+/// Why the lexer or parser gave up: the message it failed with, such as
+/// `The attack missed!`, and where when that is known. `?` turns the plain
+/// messages into it, for functions returning `Box<dyn Error>` or
+/// `anyhow::Result`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    /// Stopped at a line of the text and a column in it, both counted from 1
+    #[error("{message} (line {line}, column {column})")]
+    At {
+        message: &'static str,
+        line: usize,
+        column: usize,
+    },
+    /// About the text as a whole, such as bytes that are not UTF-8
+    #[error("{0}")]
+    Document(&'static str),
+}
+
+impl ParseError {
+    pub(crate) fn at(message: &'static str, (line, column): (usize, usize)) -> ParseError {
+        ParseError::At {
+            message,
+            line,
+            column,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ParseError::At { message, .. } | ParseError::Document(message) => message,
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ParseError::At { line, .. } => Some(*line),
+            ParseError::Document(_) => None,
+        }
+    }
+
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            ParseError::At { column, .. } => Some(*column),
+            ParseError::Document(_) => None,
+        }
+    }
+}
+
+impl From<&'static str> for ParseError {
+    fn from(message: &'static str) -> ParseError {
+        ParseError::Document(message)
+    }
+}
+
+// `?` in functions that fail with a bare message keeps the message
+impl From<ParseError> for &'static str {
+    fn from(err: ParseError) -> &'static str {
+        err.message()
+    }
+}
+
+enum Node<'a> {
+    Leaf(BsonValue<'a>),
+    Branch(BTreeMap<String, Node<'a>>),
+}
+
+impl<'a> Node<'a> {
+    fn insert(&mut self, segments: &[String], value: BsonValue<'a>) -> Result<(), PathConflict> {
+        let mut node = self;
+        for (depth, segment) in segments.iter().enumerate() {
+            node = match node {
+                Node::Branch(children) => children
+                    .entry(segment.clone())
+                    .or_insert_with(|| Node::Branch(BTreeMap::new())),
+                Node::Leaf(_) => return Err(PathConflict(segments[..depth].join("."))),
+            };
+        }
+        match node {
+            // Anything else was set by an earlier entry
+            Node::Branch(children) if children.is_empty() => {
+                *node = Node::Leaf(value);
+                Ok(())
+            }
+            _ => Err(PathConflict(segments.join("."))),
+        }
+    }
+
+    fn into_value(self) -> BsonValue<'a> {
+        match self {
+            Node::Leaf(value) => value,
+            Node::Branch(mut children) => {
+                let count = children.len();
+                if count > 0 && (0..count).all(|i| children.contains_key(&i.to_string())) {
+                    BsonValue::Array(
+                        (0..count)
+                            .filter_map(|i| children.remove(&i.to_string()))
+                            .map(Node::into_value)
+                            .collect(),
+                    )
+                } else {
+                    BsonValue::Map(
+                        children
+                            .into_iter()
+                            .map(|(key, child)| (key.into(), child.into_value()))
+                            .collect(),
+                    )
                 }
-                match parse_value_from_tokens(tokens, curr) {
-                    Ok((value, next_idx)) => {
-                        arr.push(Rc::new(RefCell::new(value)));
-                        curr = next_idx;
+            }
+        }
+    }
+}
+
+/// `BsonValue::unflatten` for paths already split into keys, which may
+/// then contain dots themselves.
+pub(crate) fn unflatten_segments<'a>(
+    entries: impl IntoIterator<Item = (Vec<String>, BsonValue<'a>)>,
+) -> Result<BsonValue<'a>, PathConflict> {
+    let mut root = Node::Branch(BTreeMap::new());
+    for (segments, value) in entries {
+        root.insert(&segments, value)?;
+    }
+    Ok(root.into_value())
+}
+
+impl fmt::Display for BsonValue<'_> {
+    fn fmt<'v>(&'v self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Values still to write, next one last, each with the text that
+        // leads up to it and its indentation level. A stack rather than
+        // recursion, so depth is bounded by memory alone.
+        let mut pending = vec![(String::new(), self, 0)];
+        while let Some((lead, bson, level)) = pending.pop() {
+            f.write_str(&lead)?;
+            let indent = "    ".repeat(level);
+            let child = |prefix: String, value: &'v BsonValue<'_>| match value {
+                BsonValue::Map(_) => (prefix + "\n", value, level + 1),
+                _ => (prefix, value, 0),
+            };
+            match bson {
+                BsonValue::Array(arr) => {
+                    f.write_str("\n")?;
+                    for elem in arr.iter().rev() {
+                        pending.push(child(format!("{indent}-"), elem));
                     }
-                    Err(e) => return Err(e),
                 }
+                BsonValue::Map(map) => {
+                    for (key, value) in map.iter().rev() {
+                        pending.push(child(format!("{indent}{key}:"), value));
+                    }
+                }
+                BsonValue::BString(s) => writeln!(f, "{indent} {s}")?,
+                BsonValue::Number(n) => writeln!(f, "{indent} {n}")?,
+                BsonValue::Bool(b) => writeln!(f, "{indent} {b}")?,
+                BsonValue::Secret(s) => writeln!(f, "{indent} secret(\"{s}\")")?,
+                BsonValue::Integer(n) => writeln!(f, "{indent} {n}")?,
+                BsonValue::Float(n) => writeln!(f, "{indent} {n}")?,
+                BsonValue::Date(d) => writeln!(f, "{indent} {d}")?,
+                #[cfg(feature = "decimal")]
+                BsonValue::Decimal(d) => writeln!(f, "{indent} {d}")?,
+                BsonValue::Regex(r) => writeln!(f, "{indent} /{r}/")?,
+                BsonValue::Encrypted(c) => writeln!(f, "{indent} ENC[{c}]")?,
+                BsonValue::Null(()) => writeln!(f, "{indent}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How deep sections and lists may nest together unless `ParseOptions`
+/// says otherwise
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// Most bytes `compress::decompress` unpacks a file to, so a small archive
+/// cannot fill the memory of whoever opens it
+pub const DEFAULT_MAX_DECOMPRESSED: usize = 64 << 20;
+
+/// What `parse_with` makes of input without a single key, see
+/// `ParseOptions::empty_file` and `ParseOptions::header_only`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmptyPolicy {
+    /// Refuse it with Status: Fainted
+    Error,
+    /// Read it as a document without keys
+    EmptyDocument,
+}
+
+/// What `lexer::lex_bytes` makes of bytes that are not UTF-8 inside a
+/// string value, see `ParseOptions::invalid_utf8`. Anywhere else outside a
+/// comment they fail the lex whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Utf8Policy {
+    /// Refuse them, as the spec says
+    Error,
+    /// Read them as U+FFFD, as `String::from_utf8_lossy` does
+    Replace,
+    /// Read every byte as the char of the same number, U+0080 to U+00FF as
+    /// in Latin-1, so the bytes can be recovered
+    PassThrough,
+}
+
+/// How `lexer::lex_str_with` and `parse_with` read a document, for products
+/// that embed BSON with rules of their own. Hand the same options to both.
+/// The default is the language as specified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseOptions {
+    reserved: BTreeSet<String>,
+    headers: Vec<String>,
+    tab_width: Option<usize>,
+    max_depth: usize,
+    max_decompressed: usize,
+    #[cfg(feature = "decimal")]
+    exact_numbers: bool,
+    empty_file: EmptyPolicy,
+    header_only: EmptyPolicy,
+    // Extra spelling -> the keyword it stands for
+    keywords: BTreeMap<String, &'static str>,
+    context: BTreeMap<String, String>,
+    expressions: bool,
+    #[cfg(feature = "regex")]
+    check_regexes: bool,
+    invalid_utf8: Utf8Policy,
+    require_checksum: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            // Keys no document may use unless an embedder allows them
+            reserved: BTreeSet::from(["Charizard".to_string()]),
+            headers: vec![lexer::HEADER.to_string()],
+            tab_width: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_decompressed: DEFAULT_MAX_DECOMPRESSED,
+            #[cfg(feature = "decimal")]
+            exact_numbers: false,
+            empty_file: EmptyPolicy::Error,
+            header_only: EmptyPolicy::EmptyDocument,
+            keywords: BTreeMap::new(),
+            context: BTreeMap::new(),
+            expressions: false,
+            #[cfg(feature = "regex")]
+            check_regexes: false,
+            invalid_utf8: Utf8Policy::Error,
+            require_checksum: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Refuses `key` anywhere in a document, the way `Charizard` is.
+    pub fn reserve(mut self, key: impl Into<String>) -> ParseOptions {
+        self.reserved.insert(key.into());
+        self
+    }
+
+    /// Lets a reserved key through, `allow("Charizard")` included.
+    pub fn allow(mut self, key: &str) -> ParseOptions {
+        self.reserved.remove(key);
+        self
+    }
+
+    /// Expects `header` as the first line in place of `BULBA!`, for
+    /// products that brand their files.
+    pub fn header(mut self, header: impl Into<String>) -> ParseOptions {
+        self.headers = vec![header.into()];
+        self
+    }
+
+    /// Accepts `header` as well as the headers already expected, say while
+    /// files move over to a new one.
+    pub fn accept_header(mut self, header: impl Into<String>) -> ParseOptions {
+        self.headers.push(header.into());
+        self
+    }
+
+    pub(crate) fn is_header(&self, line: &str) -> bool {
+        self.headers.iter().any(|header| header == line)
+    }
+
+    /// Reads every tab as `width` spaces with a warning instead of failing
+    /// with Poison Type, for editors that insert tabs. Strict by default.
+    pub fn lenient_tabs(mut self, width: usize) -> ParseOptions {
+        self.tab_width = Some(width);
+        self
+    }
+
+    pub(crate) fn tab_width(&self) -> Option<usize> {
+        self.tab_width
+    }
+
+    /// Refuses documents nested deeper than `depth` sections and lists
+    /// with Not enough badges, before the parser recurses that far.
+    pub fn max_depth(mut self, depth: usize) -> ParseOptions {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Refuses compressed files that unpack to more than `bytes`, see
+    /// `compress::decompress_with`.
+    pub fn max_decompressed(mut self, bytes: usize) -> ParseOptions {
+        self.max_decompressed = bytes;
+        self
+    }
+
+    /// The limit set by `max_decompressed`.
+    pub fn decompressed_limit(&self) -> usize {
+        self.max_decompressed
+    }
+
+    /// Keeps every number exactly as written, as `BsonValue::Decimal`, for
+    /// money and other values `f64` would round. Numbers a `Decimal` cannot
+    /// hold, with more than 28 significant digits, are refused.
+    #[cfg(feature = "decimal")]
+    pub fn exact_numbers(mut self) -> ParseOptions {
+        self.exact_numbers = true;
+        self
+    }
+
+    /// What an empty input, without even the cry, means. It faints by
+    /// default; `EmptyDocument` treats a missing config as one left empty.
+    pub fn empty_file(mut self, policy: EmptyPolicy) -> ParseOptions {
+        self.empty_file = policy;
+        self
+    }
+
+    /// What a document of nothing but its cry, comments and blank lines
+    /// means. It is an empty document by default.
+    pub fn header_only(mut self, policy: EmptyPolicy) -> ParseOptions {
+        self.header_only = policy;
+        self
+    }
+
+    /// What `lexer::lex_bytes` does with string values that are not UTF-8.
+    /// They fail the lex by default.
+    pub fn invalid_utf8(mut self, policy: Utf8Policy) -> ParseOptions {
+        self.invalid_utf8 = policy;
+        self
+    }
+
+    pub(crate) fn utf8_policy(&self) -> Utf8Policy {
+        self.invalid_utf8
+    }
+
+    /// Refuses documents without a `zZz bulba-sha256:` footer, so a file
+    /// cut short before it fails too. A footer is checked whenever there
+    /// is one, see `checksum`.
+    pub fn require_checksum(mut self) -> ParseOptions {
+        self.require_checksum = true;
+        self
+    }
+
+    pub(crate) fn requires_checksum(&self) -> bool {
+        self.require_checksum
+    }
+
+    /// Also reads `spelling` as `keyword`, one of `lexer::TRUE`,
+    /// `lexer::FALSE` and `lexer::NULL`. The value is the same as for the
+    /// keyword itself, so emitting writes the keyword back.
+    pub fn keyword(mut self, spelling: impl Into<String>, keyword: &'static str) -> ParseOptions {
+        if [lexer::TRUE, lexer::FALSE, lexer::NULL].contains(&keyword) {
+            self.keywords.insert(spelling.into(), keyword);
+        }
+        self
+    }
+
+    /// Accepts `true`, `false` and `null` next to the keywords, for files
+    /// on their way over from JSON or YAML.
+    pub fn json_keywords(self) -> ParseOptions {
+        self.keyword("true", lexer::TRUE)
+            .keyword("false", lexer::FALSE)
+            .keyword("null", lexer::NULL)
+    }
+
+    pub(crate) fn keyword_for(&self, spelling: &str) -> Option<&'static str> {
+        self.keywords.get(spelling).copied()
+    }
+
+    /// Sets `name` for conditional sections such as
+    /// `(o) when env == "production" (o)`, whose keys are only read when
+    /// the condition holds. A name never set equals no value.
+    pub fn context(mut self, name: impl Into<String>, value: impl Into<String>) -> ParseOptions {
+        self.context.insert(name.into(), value.into());
+        self
+    }
+
+    /// Works out values written as constant expressions, such as
+    /// `30 * 1000` or `host + ":" + port`, once the document is parsed.
+    /// Names refer to other keys, in the same section first and then from
+    /// the root. See `expr::Expression` for what they may contain; Razor
+    /// Leaf lists cannot hold them.
+    pub fn expressions(mut self) -> ParseOptions {
+        self.expressions = true;
+        self
+    }
+
+    pub(crate) fn allows_expressions(&self) -> bool {
+        self.expressions
+    }
+
+    /// Compiles every regex literal while parsing, so a pattern that does
+    /// not compile fails the parse with Target is immune rather than later.
+    #[cfg(feature = "regex")]
+    pub fn check_regexes(mut self) -> ParseOptions {
+        self.check_regexes = true;
+        self
+    }
+
+    fn holds(&self, condition: (&str, bool, &str)) -> bool {
+        let (name, equal, value) = condition;
+        (self.context.get(name).map(String::as_str) == Some(value)) == equal
+    }
+
+    /// Refuses a key these options reserve.
+    pub fn check_key(&self, key: &str) -> Result<(), &'static str> {
+        if self.reserved.contains(key) {
+            return Err("It burns the bulb");
+        }
+        Ok(())
+    }
+}
+
+// `literal` as a number when it is an i64 or u64 that `f64` would round
+fn wide_integer(literal: &str) -> Option<i128> {
+    match literal.parse::<i128>() {
+        Ok(n) if n >= i128::from(i64::MIN) && n <= i128::from(u64::MAX) => {
+            (n as f64 as i128 != n).then_some(n)
+        }
+        _ => None,
+    }
+}
+
+// What the parser needs of a token, its text borrowed from a slice of
+// tokens or taken over from a stream of them
+struct Lexeme<'a> {
+    ttype: lexer::TokenType,
+    literal: Cow<'a, str>,
+    level: usize,
+    line: usize,
+    span: Range<usize>,
+}
+
+impl<'a> From<&'a lexer::Token> for Lexeme<'a> {
+    fn from(token: &'a lexer::Token) -> Lexeme<'a> {
+        Lexeme {
+            ttype: token.ttype.clone(),
+            literal: Cow::Borrowed(token.literal.as_str()),
+            level: token.level,
+            line: token.line,
+            span: token.span.clone(),
+        }
+    }
+}
+
+impl From<lexer::Token> for Lexeme<'static> {
+    fn from(token: lexer::Token) -> Lexeme<'static> {
+        Lexeme {
+            ttype: token.ttype,
+            literal: Cow::Owned(token.literal),
+            level: token.level,
+            line: token.line,
+            span: token.span,
+        }
+    }
+}
+
+// The next token, which has to be of type `ttype`
+fn expect<'a>(
+    tokens: &mut impl Iterator<Item = Result<Lexeme<'a>, &'static str>>,
+    ttype: lexer::TokenType,
+) -> Result<Lexeme<'a>, &'static str> {
+    match tokens.next() {
+        Some(Ok(token)) if token.ttype == ttype => Ok(token),
+        Some(Err(e)) => Err(e),
+        _ => Err("It hurt itself in its confusion!"),
+    }
+}
+
+// `depth` counts the sections and lists around the value. With `record`,
+// the span of the value and of every element goes in under its path.
+fn parse_value<'a>(
+    tokens: &mut impl Iterator<Item = Result<Lexeme<'a>, &'static str>>,
+    depth: usize,
+    options: &ParseOptions,
+    mut record: Option<(&str, &mut Spans)>,
+) -> Result<BsonValue<'a>, &'static str> {
+    // Razor Leaf lists still open, innermost last, with the column each
+    // opened at. A stack rather than recursion, so only `max_depth` bounds
+    // how deep lists go.
+    let mut open: Vec<(Vec<BsonValue<'a>>, usize)> = vec![];
+    loop {
+        let Some(token) = tokens.next() else {
+            return Err(match open.is_empty() {
+                true => "It hurt itself in its confusion!",
+                false => "Target is immune!",
+            });
+        };
+        let token = token?;
+        let line = token.line;
+        let (value, columns) = match token.ttype {
+            lexer::TokenType::ArrayStart => {
+                if depth + open.len() >= options.max_depth {
+                    return Err("Not enough badges!");
+                }
+                open.push((vec![], token.span.start));
+                continue;
             }
-            Err("Target is immune!")
+            lexer::TokenType::Comma if !open.is_empty() => continue,
+            lexer::TokenType::ArrayEnd if !open.is_empty() => {
+                let (items, start) = open.pop().unwrap_or_default();
+                (BsonValue::Array(items), start..token.span.end)
+            }
+            _ => {
+                let columns = token.span.clone();
+                (parse_scalar(token, options)?, columns)
+            }
+        };
+        if let Some((path, spans)) = &mut record {
+            let path = open.iter().fold(path.to_string(), |path, (items, _)| {
+                join_path(&path, &items.len().to_string())
+            });
+            spans.insert(path, Span { line, columns });
+        }
+        match open.last_mut() {
+            Some((items, _)) => items.push(value),
+            None => return Ok(value),
+        }
+    }
+}
+
+fn parse_scalar<'a>(
+    token: Lexeme<'a>,
+    #[cfg_attr(
+        not(any(feature = "decimal", feature = "regex")),
+        allow(unused_variables)
+    )]
+    options: &ParseOptions,
+) -> Result<BsonValue<'a>, &'static str> {
+    let literal = token.literal;
+    match token.ttype {
+        lexer::TokenType::TString => Ok(BsonValue::BString(literal)),
+        lexer::TokenType::Date => Ok(BsonValue::Date(literal)),
+        // The `f` suffix asks for a float whatever the options say
+        lexer::TokenType::Number if literal.ends_with('f') => literal[..literal.len() - 1]
+            .parse()
+            .map(BsonValue::Float)
+            .map_err(|_| "Target is immune!"),
+        lexer::TokenType::Number if literal.ends_with('i') => literal[..literal.len() - 1]
+            .parse()
+            .map(BsonValue::Integer)
+            .map_err(|_| "Target is immune!"),
+        #[cfg(feature = "decimal")]
+        lexer::TokenType::Number if options.exact_numbers => {
+            let decimal = match literal.contains(['e', 'E']) {
+                true => Decimal::from_scientific(&literal),
+                false => Decimal::from_str_exact(&literal),
+            };
+            decimal
+                .map(BsonValue::Decimal)
+                .map_err(|_| "Target is immune!")
+        }
+        lexer::TokenType::Number => match wide_integer(&literal) {
+            Some(n) => Ok(BsonValue::Integer(n)),
+            None => literal
+                .parse()
+                .map(BsonValue::Number)
+                .map_err(|_| "Target is immune!"),
+        },
+        lexer::TokenType::Bool => Ok(BsonValue::Bool(literal == "true")),
+        lexer::TokenType::Null => Ok(BsonValue::Null(())),
+        lexer::TokenType::Secret => Ok(BsonValue::Secret(literal)),
+        lexer::TokenType::Encrypted => Ok(BsonValue::Encrypted(literal)),
+        lexer::TokenType::Regex => {
+            #[cfg(feature = "regex")]
+            if options.check_regexes && Regex::new(&literal).is_err() {
+                return Err("Target is immune!");
+            }
+            Ok(BsonValue::Regex(literal))
         }
         _ => Err("Target is immune!"),
     }
 }
 
-pub fn parse<'a>(tokens: &'a Vec<lexer::Token>) -> Result<BsonValue<'a>, &'static str> {
-    let state = Rc::new(RefCell::new(BsonValue::Map(BTreeMap::new())));
-    let result = Rc::clone(&state);
-    let mut stack = vec![state];
+// The parts of a dotted key, still borrowed where the key was
+fn split_key(key: Cow<'_, str>) -> Vec<Cow<'_, str>> {
+    match key {
+        Cow::Borrowed(key) => key.split('.').map(Cow::Borrowed).collect(),
+        Cow::Owned(key) => key
+            .split('.')
+            .map(|part| Cow::Owned(part.to_string()))
+            .collect(),
+    }
+}
+
+// The dotted path of the innermost open section, see `section_mut`
+fn section_path(root: &BsonValue, sections: &[Vec<Cow<str>>]) -> String {
+    let mut path = String::new();
+    let mut current = root;
+    for key in sections.iter().flatten() {
+        path = join_path(&path, key);
+        current = match current {
+            BsonValue::Map(m) => &m[key],
+            _ => unreachable!(),
+        };
+        if let BsonValue::Array(entries) = current {
+            path = join_path(&path, &(entries.len() - 1).to_string());
+            current = &entries[entries.len() - 1];
+        }
+    }
+    path
+}
+
+// Works out every expression, in as many rounds as references between them
+// need. Expressions that keep waiting on each other are a cycle.
+fn evaluate_expressions(
+    result: &mut BsonValue,
+    mut pending: Vec<(String, String, String)>,
+) -> Result<(), &'static str> {
+    while !pending.is_empty() {
+        let waiting: BTreeSet<String> = pending.iter().map(|(_, path, _)| path.clone()).collect();
+        let mut later = vec![];
+        for (section, path, text) in pending.iter().cloned() {
+            let expression = Expression::parse(&text)?;
+            let locate = |name: &str| {
+                let local = join_path(&section, name);
+                if waiting.contains(&local) || result.get(&local).is_some() {
+                    local
+                } else {
+                    name.to_string()
+                }
+            };
+            if expression
+                .names()
+                .iter()
+                .any(|name| waiting.contains(&locate(name)))
+            {
+                later.push((section, path, text));
+                continue;
+            }
+            let value = expression.evaluate(|name| {
+                result
+                    .get(&locate(name))
+                    .map(|value| value.clone().into_owned())
+            })?;
+            if let Some(slot) = result.get_mut(&path) {
+                *slot = value;
+            }
+        }
+        if later.len() == pending.len() {
+            return Err("It hurt itself in its confusion!");
+        }
+        pending = later;
+    }
+    Ok(())
+}
+
+// Walks down the chain of open sections from the root, into the latest
+// entry of repeated ones
+fn section_mut<'v, 'a>(
+    root: &'v mut BsonValue<'a>,
+    sections: &[Vec<Cow<'a, str>>],
+) -> &'v mut BTreeMap<Cow<'a, str>, BsonValue<'a>> {
+    let mut current = root;
+    // Conditional sections have no parts, they add to the section around
+    // them
+    for key in sections.iter().flatten() {
+        current = match current {
+            BsonValue::Map(m) => m.get_mut(key).unwrap(),
+            _ => unreachable!(),
+        };
+        current = match current {
+            BsonValue::Array(entries) => entries.last_mut().unwrap(),
+            section => section,
+        };
+    }
+    match current {
+        BsonValue::Map(m) => m,
+        _ => unreachable!(),
+    }
+}
+
+pub fn parse<'a>(tokens: &'a [lexer::Token]) -> Result<BsonValue<'a>, ParseError> {
+    parse_with(tokens, &ParseOptions::default())
+}
+
+/// `parse` with the rules of `options` instead of the defaults.
+pub fn parse_with<'a>(
+    tokens: &'a [lexer::Token],
+    options: &ParseOptions,
+) -> Result<BsonValue<'a>, ParseError> {
+    parse_lexemes(tokens.iter().map(|token| Ok(token.into())), options, None)
+}
+
+/// Lexes and parses `input` in one go, see `lexer::lex_str_with` and
+/// `parse_with`.
+pub fn parse_str(input: &str, options: &ParseOptions) -> Result<BsonValue<'static>, ParseError> {
+    let tokens = lexer::lex_str_with(input, options)?;
+    Ok(parse_with(&tokens, options)?.into_owned())
+}
+
+// `parse_with` that also notes where every value was written, for
+// `spanned::parse_spanned`
+pub(crate) fn parse_recording<'a>(
+    tokens: &'a [lexer::Token],
+    options: &ParseOptions,
+    spans: &mut Spans,
+) -> Result<BsonValue<'a>, ParseError> {
+    parse_lexemes(
+        tokens.iter().map(|token| Ok(token.into())),
+        options,
+        Some(spans),
+    )
+}
+
+/// `parse_with` on tokens as they arrive, such as from `lexer::Tokens`,
+/// so lexing and parsing go line by line without collecting every token
+/// first. The first error in the stream ends the parse.
+pub fn parse_stream(
+    tokens: impl IntoIterator<Item = Result<lexer::Token, ParseError>>,
+    options: &ParseOptions,
+) -> Result<BsonValue<'static>, ParseError> {
+    parse_lexemes(
+        tokens.into_iter().map(|token| token.map(Lexeme::from)),
+        options,
+        None,
+    )
+}
+
+// Notes the token read last, so a failure points at it. An error of the
+// lexer already knows where it happened.
+fn parse_lexemes<'a>(
+    tokens: impl Iterator<Item = Result<Lexeme<'a>, ParseError>>,
+    options: &ParseOptions,
+    spans: Option<&mut Spans>,
+) -> Result<BsonValue<'a>, ParseError> {
+    let at = Cell::new((1, 1));
+    let lexed = Cell::new(None);
+    let tokens = tokens.map(|token| match token {
+        Ok(token) => {
+            at.set((token.line.max(1), token.span.start + 1));
+            Ok(token)
+        }
+        Err(err) => {
+            lexed.set(Some(err));
+            Err(err.message())
+        }
+    });
+    read_lexemes(tokens, options, spans)
+        .map_err(|message| lexed.get().unwrap_or(ParseError::at(message, at.get())))
+}
+
+fn read_lexemes<'a>(
+    tokens: impl Iterator<Item = Result<Lexeme<'a>, &'static str>>,
+    options: &ParseOptions,
+    mut spans: Option<&mut Spans>,
+) -> Result<BsonValue<'a>, &'static str> {
+    #[cfg(feature = "trace")]
+    let span = crate::trace::parsing();
+    let mut tokens = tokens.peekable();
+    let mut result = BsonValue::Map(BTreeMap::new());
+    // The parts of the name of every open section, none for conditional
+    // ones
+    let mut sections: Vec<Vec<Cow<'a, str>>> = vec![];
+    // Section, path and text of every expression
+    let mut expressions: Vec<(String, String, String)> = vec![];
     let mut current_level = 0;
+    // What a document without keys means, known from its first token
+    let mut policy = None;
+    let mut keyless = true;
+    // While in a conditional section whose condition fails: its level, and
+    // the document, spans and count of expressions from before it. The
+    // section is parsed like any other, into a copy that is then dropped.
+    let mut skipped: Option<(usize, BsonValue, Option<&mut Spans>, usize)> = None;
 
-    let mut i = 0;
-    while i < tokens.len() {
-        let token = &tokens[i];
+    while let Some(token) = tokens.next() {
+        let token = token?;
+        if let Some((level, ..)) = skipped
+            && (matches!(
+                token.ttype,
+                lexer::TokenType::Eof | lexer::TokenType::Header
+            ) || (token.ttype == lexer::TokenType::Indent && token.level < level))
+        {
+            let (_, kept, kept_spans, pending) = skipped.take().unwrap();
+            result = kept;
+            spans = kept_spans;
+            expressions.truncate(pending);
+        }
+        let first = policy.is_none();
+        if first {
+            policy = Some(match token.ttype {
+                lexer::TokenType::Header => options.header_only,
+                _ => options.empty_file,
+            });
+        }
         if token.ttype == lexer::TokenType::Eof {
             break;
         }
 
         if token.ttype == lexer::TokenType::Header {
-            i += 1; // Consume HEADER
+            // A second cry belongs to the next document, see parse_all
+            if !first {
+                return Err("It hurt itself in its confusion!");
+            }
+            if let Some(spans) = spans.as_deref_mut() {
+                spans.insert(
+                    String::new(),
+                    Span {
+                        line: token.line,
+                        columns: token.span,
+                    },
+                );
+            }
             continue;
         }
+        keyless = false;
 
         // Check for structure
         if token.ttype == lexer::TokenType::Indent {
-            let indent_token = &tokens[i];
-            i += 1; // Consume INDENT
-            if i >= tokens.len() {
+            let expected_level = token.level;
+            let Some(next_token) = tokens.next() else {
                 break;
-            }
-
-            let next_token = &tokens[i];
-            let expected_level = indent_token.level;
+            };
+            let next_token = next_token?;
             if next_token.ttype == lexer::TokenType::SectionOpen {
                 let header_level = next_token.level;
                 // Validate hierarchy, evolution must be sequential
@@ -145,29 +1486,97 @@ pub fn parse<'a>(tokens: &'a Vec<lexer::Token>) -> Result<BsonValue<'a>, &'stati
                     return Err("The attack missed!");
                 }
                 // Check badges: ensure we have enough parent sections to evolve
-                if stack.len() < header_level {
+                if sections.len() + 1 < header_level || header_level > options.max_depth {
                     return Err("Not enough badges!");
                 }
-                i += 1; // Consume SECTION_OPEN
-                if i >= tokens.len() || tokens[i].ttype != lexer::TokenType::Identifier {
-                    return Err("It hurt itself in its confusion!");
+                let key_token = expect(&mut tokens, lexer::TokenType::Identifier)?;
+                // A conditional section adds its keys to the section around
+                // it when the context agrees, and is skipped otherwise
+                if let Some(condition) = lexer::condition(&key_token.literal) {
+                    if let Some(Err(e)) = tokens.next() {
+                        return Err(e);
+                    }
+                    sections.truncate(header_level - 1);
+                    if !options.holds(condition) && skipped.is_none() {
+                        let copy = result.clone();
+                        let kept = std::mem::replace(&mut result, copy);
+                        skipped = Some((header_level, kept, spans.take(), expressions.len()));
+                    }
+                    sections.push(vec![]);
+                    current_level = header_level;
+                    continue;
                 }
-                let key_token = &tokens[i];
-                validate_key(key_token.literal.as_str())?;
-                i += 1; // Consume IDENTIFIER
-                if i >= tokens.len() || tokens[i].ttype != lexer::TokenType::SectionClose {
-                    return Err("It hurt itself in its confusion!");
+                // `(o) server[] (o)` adds one more section to a list of them
+                let repeated = key_token.literal.ends_with("[]");
+                let key = match key_token.literal {
+                    Cow::Borrowed(key) if repeated => Cow::Borrowed(&key[..key.len() - 2]),
+                    Cow::Owned(mut key) if repeated => {
+                        key.truncate(key.len() - 2);
+                        Cow::Owned(key)
+                    }
+                    key => key,
+                };
+                let mut keys = split_key(key);
+                for key in &keys {
+                    options.check_key(key)?;
                 }
-                i += 1; // Consume SECTION_CLOSE
-                stack = stack[0..header_level].to_vec();
+                let close = expect(&mut tokens, lexer::TokenType::SectionClose)?;
+                sections.truncate(header_level - 1);
+                if sections.iter().map(Vec::len).sum::<usize>() + keys.len() > options.max_depth {
+                    return Err("Not enough badges!");
+                }
+                let key = keys.pop().unwrap_or_default();
 
-                let new_section = Rc::new(RefCell::new(BsonValue::Map(BTreeMap::new())));
-                let nsp = Rc::clone(&new_section);
-                let parent = (*stack).last_mut().unwrap();
-                if let BsonValue::Map(ref mut m) = *(*parent).borrow_mut() {
-                    m.insert(key_token.literal.as_str(), nsp);
+                // A dotted name opens the sections before its last part, the
+                // way a dotted key does
+                let mut parent = section_mut(&mut result, &sections);
+                for part in &keys {
+                    parent = match parent
+                        .entry(part.clone())
+                        .or_insert_with(|| BsonValue::Map(BTreeMap::new()))
+                    {
+                        BsonValue::Map(m) => m,
+                        _ => return Err("It hurt itself in its confusion!"),
+                    };
+                }
+                let section = BsonValue::Map(BTreeMap::new());
+                // Sections a dotted key already opened are added to, in
+                // whichever order the two come
+                if !repeated {
+                    match parent.entry(key.clone()).or_insert(section) {
+                        BsonValue::Map(_) => {}
+                        _ => return Err("It hurt itself in its confusion!"),
+                    }
+                } else {
+                    match parent
+                        .entry(key.clone())
+                        .or_insert_with(|| BsonValue::Array(vec![]))
+                    {
+                        BsonValue::Array(entries) => entries.push(section),
+                        _ => return Err("It hurt itself in its confusion!"),
+                    }
+                }
+                if let Some(spans) = spans.as_deref_mut() {
+                    let span = Span {
+                        line: key_token.line,
+                        columns: next_token.span.start..close.span.end,
+                    };
+                    let mut parent = section_path(&result, &sections);
+                    for part in &keys {
+                        parent = join_path(&parent, part);
+                        spans.entry(parent.clone()).or_insert_with(|| span.clone());
+                    }
+                    if repeated {
+                        let list = join_path(&parent, &key);
+                        spans.entry(list).or_insert_with(|| span.clone());
+                    }
+                    keys.push(key);
+                    sections.push(keys);
+                    spans.insert(section_path(&result, &sections), span);
+                } else {
+                    keys.push(key);
+                    sections.push(keys);
                 }
-                stack.push(new_section);
                 current_level = header_level;
 
                 continue;
@@ -176,41 +1585,127 @@ pub fn parse<'a>(tokens: &'a Vec<lexer::Token>) -> Result<BsonValue<'a>, &'stati
             if next_token.ttype == lexer::TokenType::Identifier {
                 if expected_level != current_level {
                     if expected_level < current_level {
-                        stack = stack[0..expected_level + 1].to_vec();
+                        sections.truncate(expected_level);
                         current_level = expected_level;
                     } else {
                         return Err("The attack missed!");
                     }
                 }
 
-                let key_token = next_token;
-                validate_key(key_token.literal.as_str())?;
-                i += 1; // Consume IDENTIFIER
-
-                if i >= tokens.len() || tokens[i].ttype != lexer::TokenType::VineWhip {
-                    return Err("It hurt itself in its confusion!");
+                // A dotted key opens the sections before its last part
+                let key_span = Span {
+                    line: next_token.line,
+                    columns: next_token.span,
+                };
+                let mut keys = split_key(next_token.literal);
+                let key = keys.pop().unwrap_or_default();
+                for key in keys.iter().chain([&key]) {
+                    options.check_key(key)?;
                 }
-                i += 1; // Consume VINE_WHIP
+                let depth = sections.iter().map(Vec::len).sum::<usize>() + keys.len();
+                if depth > options.max_depth {
+                    return Err("Not enough badges!");
+                }
+                expect(&mut tokens, lexer::TokenType::VineWhip)?;
 
-                match parse_value_from_tokens(tokens, i) {
-                    Ok((value, next_idx)) => {
-                        i = next_idx;
+                // The section of the key and its full path
+                let locate = |result: &BsonValue| {
+                    let section = section_path(result, &sections);
+                    let path = keys
+                        .iter()
+                        .chain([&key])
+                        .fold(section.clone(), |path, key| join_path(&path, key));
+                    (section, path)
+                };
+                let mut record = spans.as_deref_mut().map(|spans| {
+                    let (section, path) = locate(&result);
+                    // The sections a dotted key opens are written at the key
+                    let mut parent = section;
+                    for part in &keys {
+                        parent = join_path(&parent, part);
+                        spans.entry(parent.clone()).or_insert(key_span.clone());
+                    }
+                    (path, spans)
+                });
 
-                        let last = (*stack).last_mut().unwrap();
-                        if let BsonValue::Map(ref mut m) = *(*last).borrow_mut() {
-                            m.insert(key_token.literal.as_str(), Rc::new(RefCell::new(value)));
+                // Expressions wait for the rest of the document
+                let value = match tokens.next_if(|token| {
+                    token
+                        .as_ref()
+                        .is_ok_and(|token| token.ttype == lexer::TokenType::Expression)
+                }) {
+                    Some(Ok(token)) => {
+                        let (section, path) = locate(&result);
+                        if let Some((_, spans)) = record {
+                            let span = Span {
+                                line: token.line,
+                                columns: token.span,
+                            };
+                            spans.insert(path.clone(), span);
                         }
+                        expressions.push((section, path, token.literal.into_owned()));
+                        BsonValue::Null(())
                     }
-                    Err(e) => return Err(e),
+                    _ => {
+                        let record = record
+                            .as_mut()
+                            .map(|(path, spans)| (path.as_str(), &mut **spans));
+                        parse_value(&mut tokens, depth, options, record)?
+                    }
+                };
+
+                let mut last = section_mut(&mut result, &sections);
+                for section in keys {
+                    last = match last
+                        .entry(section)
+                        .or_insert_with(|| BsonValue::Map(BTreeMap::new()))
+                    {
+                        BsonValue::Map(m) => m,
+                        _ => return Err("It hurt itself in its confusion!"),
+                    };
                 }
+                // A key may not take the place of a section, whichever comes
+                // first
+                if matches!(last.get(&key), Some(BsonValue::Map(_))) {
+                    return Err("It hurt itself in its confusion!");
+                }
+                last.insert(key, value);
                 continue;
             }
 
             return Err("It hurt itself in its confusion!");
         }
+    }
 
-        i += 1; // Go to next token
+    if keyless && policy.unwrap_or(options.empty_file) == EmptyPolicy::Error {
+        return Err("Status: Fainted");
     }
+    evaluate_expressions(&mut result, expressions)?;
+    #[cfg(feature = "trace")]
+    crate::trace::parsed(&span, &result);
+    Ok(result)
+}
+
+/// Parses a stream of documents, each one starting with its own `BULBA!` header.
+pub fn parse_all<'a>(tokens: &'a [lexer::Token]) -> Result<Vec<BsonValue<'a>>, ParseError> {
+    parse_all_with(tokens, &ParseOptions::default())
+}
 
-    Ok(result.borrow().clone())
+/// `parse_all` with the rules of `options`, which `lexer::lex_str_with`
+/// already split the documents by.
+pub fn parse_all_with<'a>(
+    tokens: &'a [lexer::Token],
+    options: &ParseOptions,
+) -> Result<Vec<BsonValue<'a>>, ParseError> {
+    let mut starts: Vec<usize> = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| token.ttype == lexer::TokenType::Header)
+        .map(|(i, _)| i)
+        .collect();
+    starts.push(tokens.len());
+    starts
+        .windows(2)
+        .map(|w| parse_with(&tokens[w[0]..w[1]], options))
+        .collect()
 }
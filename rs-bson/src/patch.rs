@@ -0,0 +1,142 @@
+use std::borrow::Cow;
+
+use crate::lexer;
+use crate::parser::{self, BsonValue, ParseOptions};
+
+/// A single change addressed by a JSON Pointer such as
+/// `/database/pool/max_connections`, modelled on JSON Patch. For arrays the
+/// last segment is an index, or `-` to append when adding.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation<'a> {
+    Add { path: String, value: BsonValue<'a> },
+    Remove { path: String },
+    Replace { path: String, value: BsonValue<'a> },
+    Move { from: String, path: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Patch<'a> {
+    pub operations: Vec<Operation<'a>>,
+}
+
+impl<'a> Patch<'a> {
+    pub fn new() -> Patch<'a> {
+        Patch { operations: vec![] }
+    }
+
+    pub fn add(mut self, path: &str, value: BsonValue<'a>) -> Patch<'a> {
+        self.operations.push(Operation::Add {
+            path: path.to_string(),
+            value,
+        });
+        self
+    }
+
+    pub fn remove(mut self, path: &str) -> Patch<'a> {
+        self.operations.push(Operation::Remove {
+            path: path.to_string(),
+        });
+        self
+    }
+
+    pub fn replace(mut self, path: &str, value: BsonValue<'a>) -> Patch<'a> {
+        self.operations.push(Operation::Replace {
+            path: path.to_string(),
+            value,
+        });
+        self
+    }
+
+    pub fn move_to(mut self, from: &str, path: &str) -> Patch<'a> {
+        self.operations.push(Operation::Move {
+            from: from.to_string(),
+            path: path.to_string(),
+        });
+        self
+    }
+}
+
+// The pointer to the parent and the unescaped last segment
+fn split_last(path: &str) -> Result<(&str, String), &'static str> {
+    let (parent, last) = path.rsplit_once('/').ok_or("But it failed!")?;
+    Ok((parent, parser::pointer_key(last).ok_or("But it failed!")?))
+}
+
+fn array_index(segment: &str, len: usize) -> Result<usize, &'static str> {
+    match parser::pointer_index(segment) {
+        Some(idx) if idx < len => Ok(idx),
+        _ => Err("But it failed!"),
+    }
+}
+
+fn add<'a>(doc: &mut BsonValue<'a>, path: &str, value: BsonValue<'a>) -> Result<(), &'static str> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (parent, last) = split_last(path)?;
+    match doc.pointer_mut(parent) {
+        Some(BsonValue::Map(m)) => {
+            // Only keys the emitter can write back
+            if !lexer::is_key(&last) {
+                return Err("It hurt itself in its confusion!");
+            }
+            ParseOptions::default().check_key(&last)?;
+            m.insert(Cow::Owned(last), value);
+        }
+        Some(BsonValue::Array(arr)) => {
+            let idx = match last.as_str() {
+                "-" => arr.len(),
+                _ => array_index(&last, arr.len() + 1)?,
+            };
+            arr.insert(idx, value);
+        }
+        _ => return Err("But it failed!"),
+    }
+    Ok(())
+}
+
+fn remove<'a>(doc: &mut BsonValue<'a>, path: &str) -> Result<BsonValue<'a>, &'static str> {
+    let (parent, last) = split_last(path)?;
+    match doc.pointer_mut(parent) {
+        Some(BsonValue::Map(m)) => m.remove(last.as_str()).ok_or("But it failed!"),
+        Some(BsonValue::Array(arr)) => {
+            let idx = array_index(&last, arr.len())?;
+            Ok(arr.remove(idx))
+        }
+        _ => Err("But it failed!"),
+    }
+}
+
+fn apply<'a>(doc: &mut BsonValue<'a>, operation: &Operation<'a>) -> Result<(), &'static str> {
+    match operation {
+        Operation::Add { path, value } => add(doc, path, value.clone()),
+        Operation::Remove { path } => remove(doc, path).map(|_| ()),
+        Operation::Replace { path, value } => {
+            let target = doc.pointer_mut(path).ok_or("But it failed!")?;
+            *target = value.clone();
+            Ok(())
+        }
+        Operation::Move { from, path } => {
+            // A section cannot be moved inside itself
+            if path.starts_with(&format!("{from}/")) {
+                return Err("But it failed!");
+            }
+            let value = remove(doc, from)?;
+            add(doc, path, value)
+        }
+    }
+}
+
+impl<'a> BsonValue<'a> {
+    /// Applies every operation of the patch, or none of them: if any step
+    /// fails the value is left exactly as it was.
+    pub fn apply_patch(&mut self, patch: &Patch<'a>) -> Result<(), &'static str> {
+        let mut patched = self.clone();
+        for operation in &patch.operations {
+            apply(&mut patched, operation)?;
+        }
+        *self = patched;
+        Ok(())
+    }
+}
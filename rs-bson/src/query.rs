@@ -0,0 +1,327 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::env::parse_literal;
+use crate::parser::BsonValue;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Identity,
+    Field(String),
+    Index(usize),
+    Iterate,
+    Literal(BsonValue<'static>),
+    Keys,
+    Length,
+    Type,
+    Select(Box<Filter>),
+    Pipe(Box<Filter>, Box<Filter>),
+    Compare(Box<Filter>, Op, Box<Filter>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Dot,
+    Ident(String),
+    Index(usize),
+    Iterate,
+    Literal(String),
+    Pipe,
+    Open,
+    Close,
+    Op(Op),
+}
+
+// The leading run of `text` made of characters `accept` allows
+fn word(text: &str, accept: impl Fn(char) -> bool) -> &str {
+    let len = text.find(|c: char| !accept(c)).unwrap_or(text.len());
+    &text[..len]
+}
+
+// Advances past every character before byte offset `end`
+fn skip(chars: &mut Peekable<CharIndices>, end: usize) {
+    while chars.next_if(|(j, _)| *j < end).is_some() {}
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, &'static str> {
+    let mut tokens = vec![];
+    let mut chars = expr.char_indices().peekable();
+    while let Some((i, ch)) = chars.next() {
+        let token = match ch {
+            ' ' => continue,
+            '.' => Token::Dot,
+            '|' => Token::Pipe,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '[' => {
+                let end = expr[i..]
+                    .find(']')
+                    .ok_or("It hurt itself in its confusion!")?
+                    + i;
+                let inside = expr[i + 1..end].trim();
+                skip(&mut chars, end + 1);
+                match inside {
+                    "" => Token::Iterate,
+                    _ => match inside.parse() {
+                        Ok(index) => Token::Index(index),
+                        Err(_) => return Err("It hurt itself in its confusion!"),
+                    },
+                }
+            }
+            '=' | '!' | '<' | '>' => {
+                let or_equal = chars.next_if(|(_, next)| *next == '=').is_some();
+                match (ch, or_equal) {
+                    ('=', true) => Token::Op(Op::Eq),
+                    ('!', true) => Token::Op(Op::Ne),
+                    ('<', false) => Token::Op(Op::Lt),
+                    ('<', true) => Token::Op(Op::Le),
+                    ('>', false) => Token::Op(Op::Gt),
+                    ('>', true) => Token::Op(Op::Ge),
+                    _ => return Err("It hurt itself in its confusion!"),
+                }
+            }
+            '"' => {
+                let end = expr[i + 1..].find('"').ok_or("Target is immune!")? + i + 1;
+                skip(&mut chars, end + 1);
+                Token::Literal(expr[i..=end].to_string())
+            }
+            _ if ch.is_ascii_digit() || ch == '-' => {
+                let word = word(&expr[i..], |c| c.is_ascii_digit() || "-+.eE".contains(c));
+                skip(&mut chars, i + word.len());
+                Token::Literal(word.to_string())
+            }
+            _ if ch.is_alphabetic() || ch == '_' => {
+                let word = word(&expr[i..], |c| c.is_alphanumeric() || c == '_');
+                skip(&mut chars, i + word.len());
+                Token::Ident(word.to_string())
+            }
+            _ => return Err("It hurt itself in its confusion!"),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn pipe(&mut self) -> Result<Filter, &'static str> {
+        let mut filter = self.comparison()?;
+        while self.peek() == Some(&Token::Pipe) {
+            self.pos += 1;
+            filter = Filter::Pipe(Box::new(filter), Box::new(self.comparison()?));
+        }
+        Ok(filter)
+    }
+
+    fn comparison(&mut self) -> Result<Filter, &'static str> {
+        let left = self.postfix()?;
+        let Some(Token::Op(op)) = self.peek().cloned() else {
+            return Ok(left);
+        };
+        self.pos += 1;
+        let right = self.postfix()?;
+        Ok(Filter::Compare(Box::new(left), op, Box::new(right)))
+    }
+
+    fn postfix(&mut self) -> Result<Filter, &'static str> {
+        let mut filter = self.primary()?;
+        loop {
+            let step = match self.peek() {
+                Some(Token::Dot) => match self.tokens.get(self.pos + 1) {
+                    Some(Token::Ident(key)) => {
+                        self.pos += 1;
+                        Filter::Field(key.clone())
+                    }
+                    _ => return Err("It hurt itself in its confusion!"),
+                },
+                Some(Token::Index(index)) => Filter::Index(*index),
+                Some(Token::Iterate) => Filter::Iterate,
+                _ => return Ok(filter),
+            };
+            self.pos += 1;
+            filter = Filter::Pipe(Box::new(filter), Box::new(step));
+        }
+    }
+
+    fn primary(&mut self) -> Result<Filter, &'static str> {
+        match self.next() {
+            Some(Token::Dot) => match self.peek() {
+                Some(Token::Ident(key)) => {
+                    let key = key.clone();
+                    self.pos += 1;
+                    Ok(Filter::Field(key))
+                }
+                _ => Ok(Filter::Identity),
+            },
+            Some(Token::Literal(literal)) => Ok(Filter::Literal(parse_literal(&literal))),
+            Some(Token::Ident(word)) => match word.as_str() {
+                "keys" => Ok(Filter::Keys),
+                "length" => Ok(Filter::Length),
+                "type" => Ok(Filter::Type),
+                "select" => {
+                    if self.next() != Some(Token::Open) {
+                        return Err("It hurt itself in its confusion!");
+                    }
+                    let condition = self.pipe()?;
+                    if self.next() != Some(Token::Close) {
+                        return Err("It hurt itself in its confusion!");
+                    }
+                    Ok(Filter::Select(Box::new(condition)))
+                }
+                // SuperEffective, NotVeryEffective and MissingNo
+                _ => match parse_literal(&word) {
+                    BsonValue::BString(_) => Err("It hurt itself in its confusion!"),
+                    literal => Ok(Filter::Literal(literal)),
+                },
+            },
+            Some(Token::Open) => {
+                let filter = self.pipe()?;
+                if self.next() != Some(Token::Close) {
+                    return Err("It hurt itself in its confusion!");
+                }
+                Ok(filter)
+            }
+            _ => Err("It hurt itself in its confusion!"),
+        }
+    }
+}
+
+fn compare(left: &BsonValue, right: &BsonValue) -> Option<Ordering> {
+    if left.is_number() && right.is_number() {
+        return left.compare_numbers(right);
+    }
+    match (left, right) {
+        (BsonValue::BString(a), BsonValue::BString(b)) => Some(a.cmp(b)),
+        _ if left == right => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+fn run<'a>(filter: &Filter, input: &BsonValue<'a>) -> Result<Vec<BsonValue<'a>>, &'static str> {
+    let outputs = match filter {
+        Filter::Identity => vec![input.clone()],
+        Filter::Field(key) => match input {
+            BsonValue::Map(m) => vec![m.get(key.as_str()).cloned().unwrap_or(BsonValue::Null(()))],
+            BsonValue::Null(()) => vec![BsonValue::Null(())],
+            _ => return Err("Target is immune!"),
+        },
+        Filter::Index(index) => match input {
+            BsonValue::Array(arr) => vec![arr.get(*index).cloned().unwrap_or(BsonValue::Null(()))],
+            BsonValue::Null(()) => vec![BsonValue::Null(())],
+            _ => return Err("Target is immune!"),
+        },
+        Filter::Iterate => match input {
+            BsonValue::Array(arr) => arr.clone(),
+            BsonValue::Map(m) => m.values().cloned().collect(),
+            _ => return Err("Target is immune!"),
+        },
+        Filter::Literal(value) => vec![value.clone()],
+        Filter::Keys => match input {
+            BsonValue::Map(m) => {
+                let keys = m
+                    .keys()
+                    .map(|key| BsonValue::BString(Cow::Owned(key.to_string())));
+                vec![BsonValue::Array(keys.collect())]
+            }
+            BsonValue::Array(arr) => {
+                let indices = (0..arr.len()).map(|i| BsonValue::Number(i as f64));
+                vec![BsonValue::Array(indices.collect())]
+            }
+            _ => return Err("Target is immune!"),
+        },
+        Filter::Length => {
+            let length = match input {
+                BsonValue::Map(m) => m.len(),
+                BsonValue::Array(arr) => arr.len(),
+                BsonValue::BString(s) => s.chars().count(),
+                BsonValue::Null(()) => 0,
+                _ => return Err("Target is immune!"),
+            };
+            vec![BsonValue::Number(length as f64)]
+        }
+        Filter::Type => {
+            let name = crate::schema::type_name(input);
+            vec![BsonValue::BString(Cow::Borrowed(name))]
+        }
+        Filter::Select(condition) => {
+            if run(condition, input)?.contains(&BsonValue::Bool(true)) {
+                vec![input.clone()]
+            } else {
+                vec![]
+            }
+        }
+        Filter::Pipe(first, second) => {
+            let mut outputs = vec![];
+            for value in run(first, input)? {
+                outputs.extend(run(second, &value)?);
+            }
+            outputs
+        }
+        Filter::Compare(left, op, right) => {
+            let mut outputs = vec![];
+            for a in run(left, input)? {
+                for b in run(right, input)? {
+                    let result = match (op, compare(&a, &b)) {
+                        (Op::Eq, _) => a == b,
+                        (Op::Ne, _) => a != b,
+                        (Op::Lt, Some(order)) => order == Ordering::Less,
+                        (Op::Le, Some(order)) => order != Ordering::Greater,
+                        (Op::Gt, Some(order)) => order == Ordering::Greater,
+                        (Op::Ge, Some(order)) => order != Ordering::Less,
+                        // Values of different types never order
+                        (_, None) => false,
+                    };
+                    outputs.push(BsonValue::Bool(result));
+                }
+            }
+            outputs
+        }
+    };
+    Ok(outputs)
+}
+
+impl<'a> BsonValue<'a> {
+    /// Runs a jq-style filter such as `.database.pool | keys` or
+    /// `.whitelist[] | select(. == "Mom")` and returns every value it
+    /// produces. Supported: `.`, `.key`, `[n]`, `[]`, `|`, parentheses,
+    /// `keys`, `length`, `type`, `select(...)` and the comparisons `==`,
+    /// `!=`, `<`, `<=`, `>`, `>=` against BSON literals. A missing key
+    /// gives `MissingNo`, as in jq.
+    pub fn query(&self, expr: &str) -> Result<Vec<BsonValue<'a>>, &'static str> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?,
+            pos: 0,
+        };
+        let filter = parser.pipe()?;
+        if parser.pos < parser.tokens.len() {
+            return Err("It hurt itself in its confusion!");
+        }
+        run(&filter, self)
+    }
+}
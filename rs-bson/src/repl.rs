@@ -0,0 +1,142 @@
+use std::io::{self, BufRead, Write};
+
+use crate::emit::{emit, fragment};
+use crate::env::parse_literal;
+use crate::parser::{BsonValue, to_pointer};
+use crate::patch::Patch;
+use crate::schema::{self, Schema, type_name};
+
+const HELP: &str = "\
+get <path>          print the value at a dotted path (`.` for the whole document)
+type <path>         print the type of the value at a path
+keys [path]         list the keys of a section
+set <path> <value>  change a value for this session, e.g. set database.port 5433
+unset <path>        remove a key for this session
+validate            check the session against the schema and BSON's own limits
+reset               drop every change made this session
+help                show this list
+quit                leave";
+
+/// Interactive session over one document: look values up, try edits and
+/// re-validate, without touching the file. A bare path works like `get`.
+pub struct Repl {
+    original: BsonValue<'static>,
+    value: BsonValue<'static>,
+    schema: Option<Schema>,
+}
+
+// `.` and the empty string both name the whole document
+fn path(arg: &str) -> &str {
+    arg.strip_prefix('.').unwrap_or(arg)
+}
+
+fn show(value: &BsonValue) -> String {
+    fragment(value).unwrap_or_else(|err| format!("error: {err}"))
+}
+
+impl Repl {
+    pub fn new(value: BsonValue<'static>, schema: Option<Schema>) -> Repl {
+        Repl {
+            original: value.clone(),
+            value,
+            schema,
+        }
+    }
+
+    /// Runs one command and returns what it prints, `None` to quit.
+    pub fn eval(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        let lookup = |path: &str| {
+            self.value
+                .get(path)
+                .ok_or_else(|| format!("error: no value at {path}"))
+        };
+        let output = match command {
+            "" => String::new(),
+            "quit" | "exit" => return None,
+            "help" => HELP.to_string(),
+            "type" => match lookup(path(rest)) {
+                Ok(value) => type_name(value).to_string(),
+                Err(err) => err,
+            },
+            "keys" => match lookup(path(rest)) {
+                Ok(BsonValue::Map(m)) => {
+                    m.keys().map(|k| k.as_ref()).collect::<Vec<_>>().join("\n")
+                }
+                Ok(_) => String::from("error: not a section"),
+                Err(err) => err,
+            },
+            "set" => {
+                let Some((target, literal)) = rest.split_once(' ') else {
+                    return Some(String::from("usage: set <path> <value>"));
+                };
+                let patch =
+                    Patch::new().add(&to_pointer(path(target)), parse_literal(literal.trim()));
+                match self.value.apply_patch(&patch) {
+                    Ok(()) => String::new(),
+                    Err(err) => format!("error: {err}"),
+                }
+            }
+            "unset" => match self
+                .value
+                .apply_patch(&Patch::new().remove(&to_pointer(path(rest))))
+            {
+                Ok(()) => String::new(),
+                Err(err) => format!("error: {err}"),
+            },
+            "reset" => {
+                self.value = self.original.clone();
+                String::new()
+            }
+            "validate" => self.validate(),
+            "get" => match lookup(path(rest)) {
+                Ok(value) => show(value),
+                Err(err) => err,
+            },
+            _ if rest.is_empty() => match lookup(path(command)) {
+                Ok(value) => show(value),
+                Err(err) => err,
+            },
+            _ => format!("error: unknown command {command}, try help"),
+        };
+        Some(output)
+    }
+
+    fn validate(&self) -> String {
+        if let Err(err) = emit(&self.value) {
+            return format!("error: {err}");
+        }
+        let Some(schema) = &self.schema else {
+            return String::from("ok");
+        };
+        let violations = schema::validate_value(&self.value, schema);
+        if violations.is_empty() {
+            return String::from("ok");
+        }
+        let lines: Vec<String> = violations
+            .iter()
+            .map(|v| format!("{}: {}: {}", v.severity, v.path, v.message))
+            .collect();
+        lines.join("\n")
+    }
+
+    /// Reads commands from `reader` until `quit` or the end of input,
+    /// writing a `bulba> ` prompt before each one.
+    pub fn run(&mut self, reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        write!(writer, "bulba> ")?;
+        writer.flush()?;
+        for line in reader.lines() {
+            let Some(output) = self.eval(&line?) else {
+                return Ok(());
+            };
+            if !output.is_empty() {
+                writeln!(writer, "{output}")?;
+            }
+            write!(writer, "bulba> ")?;
+            writer.flush()?;
+        }
+        writeln!(writer)
+    }
+}
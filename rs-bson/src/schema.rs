@@ -0,0 +1,1082 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+
+use regex::Regex;
+
+use crate::document::Document;
+use crate::json::JsonValue;
+use crate::lexer;
+use crate::parser::{BsonValue, ParseError, join_path};
+use crate::visit::{Visit, walk_array, walk_value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    String,
+    Number,
+    Int,
+    /// A `d` suffixed date, or a string spelling one
+    Date,
+    Bool,
+    Null,
+    Array,
+    Map,
+    Any,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Type::String => "string",
+            Type::Number => "number",
+            Type::Int => "int",
+            Type::Date => "date",
+            Type::Bool => "bool",
+            Type::Null => "null",
+            Type::Array => "array",
+            Type::Map => "map",
+            Type::Any => "any",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Type {
+    fn from_name(name: &str) -> Result<Type, &'static str> {
+        match name {
+            "string" => Ok(Type::String),
+            "number" => Ok(Type::Number),
+            "int" => Ok(Type::Int),
+            "date" => Ok(Type::Date),
+            "bool" => Ok(Type::Bool),
+            "null" => Ok(Type::Null),
+            "array" => Ok(Type::Array),
+            "map" => Ok(Type::Map),
+            "any" => Ok(Type::Any),
+            _ => Err("It hurt itself in its confusion!"),
+        }
+    }
+
+    fn matches(&self, value: &BsonValue) -> bool {
+        match (self, value) {
+            (Type::Any, _) => true,
+            (
+                Type::String,
+                BsonValue::BString(_)
+                | BsonValue::Secret(_)
+                | BsonValue::Regex(_)
+                | BsonValue::Encrypted(_)
+                | BsonValue::Date(_),
+            ) => true,
+            (Type::Number, value) if value.is_number() => true,
+            // A value pinned to a float is no integer, `2f` included
+            (Type::Int, BsonValue::Float(_)) => false,
+            (Type::Int, value) if value.is_number() => {
+                value.as_f64().is_some_and(|n| n.fract() == 0.0)
+            }
+            (Type::Date, BsonValue::Date(_)) => true,
+            (Type::Date, BsonValue::BString(s)) => lexer::is_date(s),
+            (Type::Bool, BsonValue::Bool(_)) => true,
+            (Type::Null, BsonValue::Null(())) => true,
+            (Type::Array, BsonValue::Array(_)) => true,
+            (Type::Map, BsonValue::Map(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Shorthand for `Schema::from(ty).range(range)`.
+    pub fn range<T: Into<f64> + Copy>(self, range: impl RangeBounds<T>) -> Schema {
+        Schema::from(self).range(range)
+    }
+}
+
+/// The name of the type of `value`, as the validator reports it.
+pub fn type_name(value: &BsonValue) -> &'static str {
+    match value {
+        BsonValue::BString(_) => "string",
+        BsonValue::Bool(_) => "bool",
+        BsonValue::Null(()) => "null",
+        BsonValue::Secret(_) => "secret",
+        BsonValue::Regex(_) => "regex",
+        BsonValue::Encrypted(_) => "encrypted",
+        BsonValue::Array(_) => "array",
+        BsonValue::Map(_) => "map",
+        BsonValue::Date(_) => "date",
+        // Every kind of number
+        _ => "number",
+    }
+}
+
+/// A check spanning several keys of a section. Paths are relative to the
+/// section whose schema holds the rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    /// `path` must be present whenever the value at `when` equals `equals`,
+    /// e.g. `tls.cert` when `tls.enabled` is `SuperEffective`
+    RequiredIf {
+        path: String,
+        when: String,
+        equals: BsonValue<'static>,
+    },
+    /// The number at `lower` must not exceed the number at `upper`
+    LessOrEqual { lower: String, upper: String },
+}
+
+impl Rule {
+    pub fn required_if(path: &str, when: &str, equals: BsonValue<'static>) -> Rule {
+        Rule::RequiredIf {
+            path: path.to_string(),
+            when: when.to_string(),
+            equals,
+        }
+    }
+
+    pub fn less_or_equal(lower: &str, upper: &str) -> Rule {
+        Rule::LessOrEqual {
+            lower: lower.to_string(),
+            upper: upper.to_string(),
+        }
+    }
+}
+
+/// A compiled string pattern, compared by its source.
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern(Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// Expected shape of a value. Sections list their keys in `fields`; keys
+/// named in `required` must be present, the others may be absent or `MissingNo`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub(crate) ty: Type,
+    pub(crate) doc: Option<String>,
+    pub(crate) one_of: Vec<BsonValue<'static>>,
+    pub(crate) default: Option<BsonValue<'static>>,
+    // Replacement hint, empty when there is none
+    pub(crate) deprecated: Option<String>,
+    pub(crate) min: Bound<f64>,
+    pub(crate) max: Bound<f64>,
+    pub(crate) pattern: Option<Pattern>,
+    pub(crate) min_len: Option<usize>,
+    pub(crate) max_len: Option<usize>,
+    pub(crate) items: Option<Box<Schema>>,
+    pub(crate) fields: BTreeMap<String, Schema>,
+    pub(crate) required: BTreeSet<String>,
+    pub(crate) deny_unknown: bool,
+    pub(crate) rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    /// Worth fixing, but the document is still usable, e.g. a deprecated key
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub path: String,
+    pub line: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+    /// Other paths a cross-field rule looked at to reach its verdict
+    pub related: Vec<String>,
+}
+
+impl Violation {
+    fn new(path: &str, message: String) -> Violation {
+        Violation {
+            path: path.to_string(),
+            line: None,
+            severity: Severity::Error,
+            message,
+            related: vec![],
+        }
+    }
+
+    fn warning(path: &str, message: String) -> Violation {
+        Violation {
+            severity: Severity::Warning,
+            ..Violation::new(path, message)
+        }
+    }
+}
+
+impl Schema {
+    fn of(ty: Type) -> Schema {
+        Schema {
+            ty,
+            doc: None,
+            one_of: vec![],
+            default: None,
+            deprecated: None,
+            min: Bound::Unbounded,
+            max: Bound::Unbounded,
+            pattern: None,
+            min_len: None,
+            max_len: None,
+            items: None,
+            fields: BTreeMap::new(),
+            required: BTreeSet::new(),
+            deny_unknown: false,
+            rules: vec![],
+        }
+    }
+
+    /// A section. Declare its keys with `key` and `required`.
+    pub fn map() -> Schema {
+        Schema::of(Type::Map)
+    }
+
+    /// A Razor Leaf list whose every element matches `items`.
+    pub fn array(items: impl Into<Schema>) -> Schema {
+        let mut schema = Schema::of(Type::Array);
+        schema.items = Some(Box::new(items.into()));
+        schema
+    }
+
+    /// Declares an optional key of a section.
+    pub fn key(mut self, name: &str, schema: impl Into<Schema>) -> Schema {
+        self.fields.insert(name.to_string(), schema.into());
+        self
+    }
+
+    /// Marks a key as required. It does not have to be declared with `key`,
+    /// in which case any value is accepted.
+    pub fn required(mut self, name: &str) -> Schema {
+        self.required.insert(name.to_string());
+        self
+    }
+
+    /// Reports keys of this section that the schema does not declare.
+    pub fn deny_unknown_fields(mut self) -> Schema {
+        self.deny_unknown = true;
+        self
+    }
+
+    /// Adds a cross-field rule, with paths relative to this section.
+    pub fn rule(mut self, rule: Rule) -> Schema {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Describes the key for people writing documents, e.g. in editor hovers.
+    pub fn doc(mut self, text: &str) -> Schema {
+        self.doc = Some(text.to_string());
+        self
+    }
+
+    pub fn one_of(mut self, allowed: Vec<BsonValue<'static>>) -> Schema {
+        self.one_of = allowed;
+        self
+    }
+
+    /// Value `validate_and_fill` puts in place of the key when it is missing.
+    pub fn default(mut self, value: BsonValue<'static>) -> Schema {
+        self.default = Some(value);
+        self
+    }
+
+    /// Marks the key as deprecated. Using it is reported as a warning which
+    /// names `replacement`, if one is given.
+    pub fn deprecated(mut self, replacement: &str) -> Schema {
+        self.deprecated = Some(replacement.to_string());
+        self
+    }
+
+    /// Bounds a number, e.g. `Type::Int.range(1..=65535)`.
+    pub fn range<T: Into<f64> + Copy>(mut self, range: impl RangeBounds<T>) -> Schema {
+        self.min = range.start_bound().map(|bound| (*bound).into());
+        self.max = range.end_bound().map(|bound| (*bound).into());
+        self
+    }
+
+    /// Requires strings to contain a match of `pattern`; anchor it with
+    /// `^...$` to match the whole string.
+    pub fn pattern(mut self, pattern: Regex) -> Schema {
+        self.pattern = Some(Pattern(pattern));
+        self
+    }
+
+    /// Bounds the length of a string in characters.
+    pub fn length(mut self, range: impl RangeBounds<usize>) -> Schema {
+        self.min_len = match range.start_bound() {
+            Bound::Included(min) => Some(*min),
+            Bound::Excluded(min) => Some(min + 1),
+            Bound::Unbounded => None,
+        };
+        self.max_len = match range.end_bound() {
+            Bound::Included(max) => Some(*max),
+            Bound::Excluded(max) => Some(max.saturating_sub(1)),
+            Bound::Unbounded => None,
+        };
+        self
+    }
+
+    /// Reads a `.bulbaschema` file. The schema is itself a BSON document
+    /// shaped like the documents it describes: every key holds a type
+    /// descriptor such as `"int"`, `"string?"` or `"array<string>"`, and
+    /// every section describes the section of the same name. A trailing `?`
+    /// on a type marks the key optional, `_optional ~> SuperEffective` inside
+    /// a section the section. Comments directly above a key become its
+    /// documentation.
+    pub fn parse(input: &str) -> Result<Schema, ParseError> {
+        let doc = Document::parse(input)?;
+        let mut schema = section_schema(&doc.value()?, "").map_err(|(path, message)| {
+            // Blame the key whose descriptor or rule is wrong
+            let line = doc.line_of(&path).unwrap_or(1);
+            let text = input.lines().nth(line - 1).unwrap_or_default();
+            ParseError::at(message, (line, text.len() - text.trim_start().len() + 1))
+        })?;
+        for (path, text) in doc.comments() {
+            if let Some(field) = schema.field_mut(&path) {
+                field.doc = Some(text);
+            }
+        }
+        Ok(schema)
+    }
+
+    /// Reads a standard JSON Schema. Strings, numbers, booleans and null map
+    /// onto their BSON counterparts, objects onto sections and arrays onto
+    /// Razor Leaf lists. `type`, `properties`, `required`, `items` and `enum`
+    /// are understood, `description` becomes the documentation of the key;
+    /// other keywords that only annotate, like `title`, are skipped.
+    pub fn from_json_schema(input: &str) -> Result<Schema, &'static str> {
+        json_schema(&JsonValue::parse(input)?)
+    }
+
+    fn field_mut(&mut self, path: &str) -> Option<&mut Schema> {
+        path.split('.')
+            .try_fold(self, |schema, key| schema.fields.get_mut(key))
+    }
+}
+
+impl From<Type> for Schema {
+    fn from(ty: Type) -> Schema {
+        Schema::of(ty)
+    }
+}
+
+fn json_type(name: &str) -> Result<Type, &'static str> {
+    match name {
+        "integer" => Ok(Type::Int),
+        "boolean" => Ok(Type::Bool),
+        "object" => Ok(Type::Map),
+        "string" | "number" | "null" | "array" => Type::from_name(name),
+        _ => Err("It hurt itself in its confusion!"),
+    }
+}
+
+fn json_schema(json: &JsonValue) -> Result<Schema, &'static str> {
+    let ty = match json.get("type") {
+        Some(JsonValue::String(name)) => json_type(name)?,
+        // Only the common `["<type>", "null"]` spelling of nullable values
+        // narrows the type, every other union accepts anything
+        Some(JsonValue::Array(names)) => {
+            let names: Vec<&str> = names
+                .iter()
+                .filter_map(JsonValue::as_str)
+                .filter(|name| *name != "null")
+                .collect();
+            match names[..] {
+                [name] => json_type(name)?,
+                _ => Type::Any,
+            }
+        }
+        Some(_) => return Err("It hurt itself in its confusion!"),
+        None if json.get("properties").is_some() => Type::Map,
+        None => Type::Any,
+    };
+    let ty = match json.get("format").and_then(JsonValue::as_str) {
+        Some("date" | "date-time") if ty == Type::String => Type::Date,
+        _ => ty,
+    };
+
+    let mut schema = Schema::of(ty);
+    if let Some(JsonValue::Array(allowed)) = json.get("enum") {
+        schema.one_of = allowed.iter().map(BsonValue::from).collect();
+    }
+    schema.default = json.get("default").map(BsonValue::from);
+    schema.doc = json
+        .get("description")
+        .and_then(JsonValue::as_str)
+        .map(str::to_string);
+    if json.get("deprecated") == Some(&JsonValue::Bool(true)) {
+        schema.deprecated = Some(String::new());
+    }
+    let number = |keyword: &str| match json.get(keyword) {
+        Some(JsonValue::Number(n)) => Some(*n),
+        _ => None,
+    };
+    if let Some(min) = number("minimum") {
+        schema.min = Bound::Included(min);
+    }
+    if let Some(min) = number("exclusiveMinimum") {
+        schema.min = Bound::Excluded(min);
+    }
+    if let Some(max) = number("maximum") {
+        schema.max = Bound::Included(max);
+    }
+    if let Some(max) = number("exclusiveMaximum") {
+        schema.max = Bound::Excluded(max);
+    }
+    schema.min_len = number("minLength").map(|n| n as usize);
+    schema.max_len = number("maxLength").map(|n| n as usize);
+    if let Some(pattern) = json.get("pattern").and_then(JsonValue::as_str) {
+        let pattern = Regex::new(pattern).map_err(|_| "It hurt itself in its confusion!")?;
+        schema.pattern = Some(Pattern(pattern));
+    }
+    if let Some(items) = json.get("items") {
+        schema.items = Some(Box::new(json_schema(items)?));
+    }
+    if let Some(JsonValue::Object(properties)) = json.get("properties") {
+        for (key, property) in properties {
+            schema.fields.insert(key.clone(), json_schema(property)?);
+        }
+    }
+    schema.deny_unknown = json.get("additionalProperties") == Some(&JsonValue::Bool(false));
+    if let Some(JsonValue::Array(required)) = json.get("required") {
+        for key in required {
+            let key = key.as_str().ok_or("It hurt itself in its confusion!")?;
+            schema.required.insert(key.to_string());
+        }
+    }
+    Ok(schema)
+}
+
+fn guess_literal(literal: &str) -> BsonValue<'static> {
+    match literal {
+        lexer::TRUE => BsonValue::Bool(true),
+        lexer::FALSE => BsonValue::Bool(false),
+        lexer::NULL => BsonValue::Null(()),
+        _ => match literal.parse::<f64>() {
+            Ok(n) => BsonValue::Number(n),
+            Err(_) => {
+                let unquoted = literal
+                    .strip_prefix('"')
+                    .and_then(|rest| rest.strip_suffix('"'))
+                    .unwrap_or(literal);
+                BsonValue::BString(Cow::Owned(unquoted.to_string()))
+            }
+        },
+    }
+}
+
+// Rule: `<path> required_if <path>=<literal>` or `<path> <= <path>`
+fn parse_rule(rule: &BsonValue) -> Result<Rule, &'static str> {
+    let BsonValue::BString(rule) = rule else {
+        return Err("It hurt itself in its confusion!");
+    };
+    let parts: Vec<&str> = rule.split_whitespace().collect();
+    match parts[..] {
+        [lower, "<=", upper] => Ok(Rule::less_or_equal(lower, upper)),
+        [path, "required_if", condition] => {
+            let (when, literal) = condition
+                .split_once('=')
+                .ok_or("It hurt itself in its confusion!")?;
+            Ok(Rule::required_if(path, when, guess_literal(literal)))
+        }
+        _ => Err("It hurt itself in its confusion!"),
+    }
+}
+
+// Marks the section it is in optional in a `.bulbaschema`
+pub(crate) const OPTIONAL: &str = "_optional";
+
+// Errors come with the path of the key they are about
+fn section_schema(shape: &BsonValue, path: &str) -> Result<Schema, (String, &'static str)> {
+    let mut schema = Schema::of(Type::Map);
+    if let BsonValue::Map(m) = shape {
+        for (key, value) in m.iter() {
+            let key_path = join_path(path, key);
+            let at = |message| (key_path.clone(), message);
+            // Cross-field rules of the section, one per key
+            if key == "_rules"
+                && let BsonValue::Map(rules) = value
+            {
+                for (name, rule) in rules.iter() {
+                    let rule = parse_rule(rule);
+                    schema
+                        .rules
+                        .push(rule.map_err(|message| (join_path(&key_path, name), message))?);
+                }
+                continue;
+            }
+            // Read by the section around it
+            if key == OPTIONAL {
+                continue;
+            }
+            let (field, optional) = match value {
+                BsonValue::BString(descriptor) => parse_descriptor(descriptor).map_err(at)?,
+                BsonValue::Map(section) => {
+                    let optional = match section.get(OPTIONAL) {
+                        None => false,
+                        Some(BsonValue::Bool(optional)) => *optional,
+                        Some(_) => {
+                            let marker = join_path(&key_path, OPTIONAL);
+                            return Err((marker, "It hurt itself in its confusion!"));
+                        }
+                    };
+                    (section_schema(value, &key_path)?, optional)
+                }
+                _ => return Err(at("It hurt itself in its confusion!")),
+            };
+            if !optional {
+                schema.required.insert(key.to_string());
+            }
+            schema.fields.insert(key.to_string(), field);
+        }
+    }
+    Ok(schema)
+}
+
+fn parse_type(spec: &str) -> Result<Schema, &'static str> {
+    if let Some(item) = spec
+        .strip_prefix("array<")
+        .and_then(|rest| rest.strip_suffix('>'))
+    {
+        let mut schema = Schema::of(Type::Array);
+        schema.items = Some(Box::new(parse_type(item)?));
+        return Ok(schema);
+    }
+    Ok(Schema::of(Type::from_name(spec)?))
+}
+
+fn parse_literal(ty: Type, literal: &str) -> Result<BsonValue<'static>, &'static str> {
+    match ty {
+        Type::Number | Type::Int => literal
+            .parse::<f64>()
+            .map(BsonValue::Number)
+            .map_err(|_| "It hurt itself in its confusion!"),
+        Type::Bool => match literal {
+            lexer::TRUE => Ok(BsonValue::Bool(true)),
+            lexer::FALSE => Ok(BsonValue::Bool(false)),
+            _ => Err("It hurt itself in its confusion!"),
+        },
+        _ => Ok(BsonValue::BString(literal.to_string().into())),
+    }
+}
+
+fn parse_number(literal: &str) -> Result<f64, &'static str> {
+    literal
+        .parse::<f64>()
+        .map_err(|_| "It hurt itself in its confusion!")
+}
+
+fn parse_length(literal: &str) -> Result<usize, &'static str> {
+    literal
+        .parse::<usize>()
+        .map_err(|_| "It hurt itself in its confusion!")
+}
+
+// Descriptor: `<type>[?] [constraint=value ...]`, where constraints are
+// `one_of=a|b|c`, `default=`, `min=`, `max=`, `min_len=`, `max_len=`, `pattern=`
+// and `deprecated` or `deprecated=<replacement>`
+fn parse_descriptor(descriptor: &str) -> Result<(Schema, bool), &'static str> {
+    let mut parts = descriptor.split_whitespace();
+    let spec = parts.next().ok_or("It hurt itself in its confusion!")?;
+    let optional = spec.ends_with('?');
+    let mut schema = parse_type(spec.strip_suffix('?').unwrap_or(spec))?;
+
+    for constraint in parts {
+        if constraint == "deprecated" {
+            schema.deprecated = Some(String::new());
+            continue;
+        }
+        let (name, value) = constraint
+            .split_once('=')
+            .ok_or("It hurt itself in its confusion!")?;
+        match name {
+            "one_of" => {
+                for literal in value.split('|') {
+                    schema.one_of.push(parse_literal(schema.ty, literal)?);
+                }
+            }
+            "default" => schema.default = Some(parse_literal(schema.ty, value)?),
+            "deprecated" => schema.deprecated = Some(value.to_string()),
+            "min" => schema.min = Bound::Included(parse_number(value)?),
+            "max" => schema.max = Bound::Included(parse_number(value)?),
+            "min_len" => schema.min_len = Some(parse_length(value)?),
+            "max_len" => schema.max_len = Some(parse_length(value)?),
+            "pattern" => {
+                let pattern = Regex::new(value).map_err(|_| "It hurt itself in its confusion!")?;
+                schema.pattern = Some(Pattern(pattern));
+            }
+            _ => return Err("It hurt itself in its confusion!"),
+        }
+    }
+    Ok((schema, optional))
+}
+
+fn describe(value: &BsonValue) -> String {
+    match value {
+        BsonValue::BString(s) => format!("\"{s}\""),
+        BsonValue::Bool(true) => lexer::TRUE.to_string(),
+        BsonValue::Bool(false) => lexer::FALSE.to_string(),
+        BsonValue::Null(()) => lexer::NULL.to_string(),
+        BsonValue::Secret(s) => format!("{}{s}{}", lexer::SECRET_OPEN, lexer::SECRET_CLOSE),
+        BsonValue::Regex(s) => format!("{0}{s}{0}", lexer::REGEX_DELIMITER),
+        BsonValue::Encrypted(s) => {
+            format!("{}{s}{}", lexer::ENCRYPTED_OPEN, lexer::ENCRYPTED_CLOSE)
+        }
+        other => other.to_string().trim().to_string(),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn unknown_key(key: &str, schema: &Schema) -> String {
+    let suggestion = schema
+        .fields
+        .keys()
+        .map(|known| (edit_distance(key, known), known))
+        .filter(|(distance, _)| *distance <= 2)
+        .min();
+    match suggestion {
+        Some((_, known)) => format!("Unknown key, did you mean {known}?"),
+        None => String::from("Unknown key"),
+    }
+}
+
+fn check_rule(section: &BsonValue, rule: &Rule, path: &str, violations: &mut Vec<Violation>) {
+    match rule {
+        Rule::RequiredIf {
+            path: required,
+            when,
+            equals,
+        } => {
+            let present = !matches!(section.get(required), None | Some(BsonValue::Null(())));
+            if section
+                .get(when)
+                .is_some_and(|value| value.same_value(equals))
+                && !present
+            {
+                violations.push(Violation {
+                    related: vec![join_path(path, when)],
+                    ..Violation::new(
+                        &join_path(path, required),
+                        format!("Required when {when} is {}", describe(equals)),
+                    )
+                });
+            }
+        }
+        Rule::LessOrEqual { lower, upper } => {
+            if let (Some(low), Some(high)) = (
+                section.get(lower).and_then(BsonValue::as_f64),
+                section.get(upper).and_then(BsonValue::as_f64),
+            ) && low > high
+            {
+                violations.push(Violation {
+                    related: vec![join_path(path, upper)],
+                    ..Violation::new(
+                        &join_path(path, lower),
+                        format!("{low} is greater than {upper} ({high})"),
+                    )
+                });
+            }
+        }
+    }
+}
+
+// Checks every value against the schema of its key. Only keys the schema
+// declares are visited, each with its own schema at hand.
+struct Check<'s, 'v> {
+    schema: &'s Schema,
+    strict: bool,
+    violations: &'v mut Vec<Violation>,
+}
+
+impl Visit for Check<'_, '_> {
+    fn visit_value(&mut self, path: &str, value: &BsonValue) {
+        let schema = self.schema;
+        match schema.deprecated.as_deref() {
+            Some("") => self
+                .violations
+                .push(Violation::warning(path, String::from("Key is deprecated"))),
+            Some(replacement) => self.violations.push(Violation::warning(
+                path,
+                format!("Key is deprecated, use {replacement} instead"),
+            )),
+            None => {}
+        }
+        if !schema.ty.matches(value) {
+            self.violations.push(Violation::new(
+                path,
+                format!(
+                    "Target is immune! Expected {}, found {}",
+                    schema.ty,
+                    type_name(value)
+                ),
+            ));
+            return;
+        }
+        if !schema.one_of.is_empty()
+            && !schema
+                .one_of
+                .iter()
+                .any(|allowed| allowed.same_value(value))
+        {
+            let allowed: Vec<String> = schema.one_of.iter().map(describe).collect();
+            self.violations.push(Violation::new(
+                path,
+                format!("{} is not one of {}", describe(value), allowed.join(", ")),
+            ));
+        }
+
+        if let Some(n) = &value.as_f64() {
+            let message = match (schema.min, schema.max) {
+                (Bound::Included(min), _) if *n < min => {
+                    Some(format!("{n} is below the minimum of {min}"))
+                }
+                (Bound::Excluded(min), _) if *n <= min => {
+                    Some(format!("{n} must be greater than {min}"))
+                }
+                (_, Bound::Included(max)) if *n > max => {
+                    Some(format!("{n} is above the maximum of {max}"))
+                }
+                (_, Bound::Excluded(max)) if *n >= max => {
+                    Some(format!("{n} must be less than {max}"))
+                }
+                _ => None,
+            };
+            if let Some(message) = message {
+                self.violations.push(Violation::new(path, message));
+            }
+        }
+
+        walk_value(self, path, value);
+
+        if let BsonValue::Map(m) = value {
+            for rule in &schema.rules {
+                check_rule(value, rule, path, self.violations);
+            }
+
+            // A plain `map` with nothing declared stays free-form
+            let declared = !schema.fields.is_empty() || !schema.required.is_empty();
+            if schema.deny_unknown || (self.strict && declared) {
+                for key in m.keys() {
+                    if !schema.fields.contains_key(key.as_ref())
+                        && !schema.required.contains(key.as_ref())
+                    {
+                        self.violations.push(Violation::new(
+                            &join_path(path, key),
+                            unknown_key(key, schema),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_string(&mut self, path: &str, s: &str) {
+        let schema = self.schema;
+        let len = s.chars().count();
+        if let Some(min_len) = schema.min_len.filter(|min_len| len < *min_len) {
+            self.violations.push(Violation::new(
+                path,
+                format!("\"{s}\" is shorter than {min_len} characters"),
+            ));
+        }
+        if let Some(max_len) = schema.max_len.filter(|max_len| len > *max_len) {
+            self.violations.push(Violation::new(
+                path,
+                format!("\"{s}\" is longer than {max_len} characters"),
+            ));
+        }
+        if let Some(Pattern(pattern)) = &schema.pattern
+            && !pattern.is_match(s)
+        {
+            self.violations.push(Violation::new(
+                path,
+                format!("\"{s}\" does not match /{}/", pattern.as_str()),
+            ));
+        }
+    }
+
+    // Lengths and patterns are for strings alone
+    fn visit_date(&mut self, _path: &str, _value: &str) {}
+
+    fn visit_array(&mut self, path: &str, items: &[BsonValue]) {
+        let schema = self.schema;
+        if let Some(item) = &schema.items {
+            self.schema = item;
+            walk_array(self, path, items);
+            self.schema = schema;
+        }
+    }
+
+    fn visit_map(&mut self, path: &str, m: &BTreeMap<Cow<'_, str>, BsonValue<'_>>) {
+        let schema = self.schema;
+        let keys: BTreeSet<&String> = schema.fields.keys().chain(&schema.required).collect();
+        for key in keys {
+            let field_path = join_path(path, key);
+            match (m.get(key.as_str()), schema.fields.get(key)) {
+                (None, _) | (Some(BsonValue::Null(())), _) if schema.required.contains(key) => {
+                    self.violations.push(Violation::new(
+                        &field_path,
+                        String::from("Required key is missing"),
+                    ));
+                }
+                (Some(BsonValue::Null(())), _) => {}
+                (Some(field_value), Some(field)) => {
+                    self.schema = field;
+                    self.visit_value(&field_path, field_value);
+                    self.schema = schema;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn check(
+    value: &BsonValue,
+    schema: &Schema,
+    path: &str,
+    strict: bool,
+    violations: &mut Vec<Violation>,
+) {
+    let mut check = Check {
+        schema,
+        strict,
+        violations,
+    };
+    check.visit_value(path, value);
+}
+
+/// Checks a value against the schema. Violations carry paths but no line
+/// numbers, use `validate` on a `Document` for those.
+pub fn validate_value(value: &BsonValue, schema: &Schema) -> Vec<Violation> {
+    let mut violations = vec![];
+    check(value, schema, "", false, &mut violations);
+    violations
+}
+
+/// Outcome of `validate_and_fill`: the violations left after defaults were
+/// filled in, and the paths of every key that was synthesized from a default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filled {
+    pub violations: Vec<Violation>,
+    pub synthesized: Vec<String>,
+}
+
+fn has_defaults(schema: &Schema) -> bool {
+    schema
+        .fields
+        .values()
+        .any(|field| field.default.is_some() || has_defaults(field))
+}
+
+fn fill(value: &mut BsonValue, schema: &Schema, path: &str, synthesized: &mut Vec<String>) {
+    let BsonValue::Map(m) = value else {
+        return;
+    };
+    for (key, field) in &schema.fields {
+        let field_path = join_path(path, key);
+        match (m.get_mut(key.as_str()), &field.default) {
+            (None | Some(BsonValue::Null(())), Some(default)) => {
+                m.insert(Cow::Owned(key.clone()), default.clone());
+                synthesized.push(field_path);
+            }
+            (Some(child), _) => fill(child, field, &field_path, synthesized),
+            // Sections missing entirely are grown when something inside
+            // them has a default
+            (None, None) if field.ty == Type::Map && has_defaults(field) => {
+                let mut section = BsonValue::Map(BTreeMap::new());
+                fill(&mut section, field, &field_path, synthesized);
+                m.insert(Cow::Owned(key.clone()), section);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Inserts the schema's defaults for every missing key, then validates the
+/// result. Defaulted keys are therefore never reported as missing.
+pub fn validate_and_fill(value: &mut BsonValue, schema: &Schema) -> Filled {
+    let mut synthesized = vec![];
+    fill(value, schema, "", &mut synthesized);
+    Filled {
+        violations: validate_value(value, schema),
+        synthesized,
+    }
+}
+
+fn locate(doc: &Document, violations: &mut [Violation]) {
+    for violation in violations.iter_mut() {
+        let mut path = violation.path.as_str();
+        while violation.line.is_none() && !path.is_empty() {
+            violation.line = doc.line_of(path);
+            path = path
+                .rsplit_once('.')
+                .map(|(parent, _)| parent)
+                .unwrap_or("");
+        }
+    }
+}
+
+/// Checks a document against the schema. Each violation points at the line
+/// of the offending key, or of the closest enclosing section when the key
+/// is missing altogether.
+pub fn validate(doc: &Document, schema: &Schema) -> Vec<Violation> {
+    let value = match doc.value() {
+        Ok(value) => value,
+        Err(err) => return vec![Violation::new("", err.to_string())],
+    };
+    let mut violations = validate_value(&value, schema);
+    locate(doc, &mut violations);
+    violations
+}
+
+/// A domain-specific check run on the value at a key path, such as "the
+/// port must be free". The error becomes the message of a violation.
+pub trait Hook {
+    fn check(&self, value: &BsonValue) -> Result<(), String>;
+}
+
+impl<F: Fn(&BsonValue) -> Result<(), String>> Hook for F {
+    fn check(&self, value: &BsonValue) -> Result<(), String> {
+        self(value)
+    }
+}
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let mut pattern_segments = pattern.split('.');
+    let mut path_segments = path.split('.');
+    loop {
+        match (pattern_segments.next(), path_segments.next()) {
+            (None, None) => return true,
+            (Some(expected), Some(actual)) if expected == "*" || expected == actual => {}
+            _ => return false,
+        }
+    }
+}
+
+// Runs every hook whose path matches on the value at that path
+struct Hooks<'h, 's, 'v> {
+    hooks: &'h [(String, Box<dyn Hook + 's>)],
+    violations: &'v mut Vec<Violation>,
+}
+
+impl Visit for Hooks<'_, '_, '_> {
+    fn visit_value(&mut self, path: &str, value: &BsonValue) {
+        for (pattern, hook) in self.hooks {
+            if path_matches(pattern, path)
+                && let Err(message) = hook.check(value)
+            {
+                self.violations.push(Violation::new(path, message));
+            }
+        }
+        walk_value(self, path, value);
+    }
+}
+
+/// A schema together with hooks registered on key paths. A `*` segment in a
+/// hook path matches any key or array index, e.g. `whitelist.*`.
+type CrossCheck<'s> = Box<dyn Fn(&[Option<&BsonValue>]) -> Result<(), String> + 's>;
+
+pub struct Validator<'s> {
+    schema: &'s Schema,
+    strict: bool,
+    hooks: Vec<(String, Box<dyn Hook + 's>)>,
+    cross_checks: Vec<(Vec<String>, CrossCheck<'s>)>,
+}
+
+impl<'s> Validator<'s> {
+    pub fn new(schema: &'s Schema) -> Validator<'s> {
+        Validator {
+            schema,
+            strict: false,
+            hooks: vec![],
+            cross_checks: vec![],
+        }
+    }
+
+    /// In strict mode every section the schema describes is closed: keys it
+    /// does not declare are reported, catching typos like `max_conections`.
+    pub fn strict(mut self, strict: bool) -> Validator<'s> {
+        self.strict = strict;
+        self
+    }
+
+    pub fn hook(mut self, path: &str, hook: impl Hook + 's) -> Validator<'s> {
+        self.hooks.push((path.to_string(), Box::new(hook)));
+        self
+    }
+
+    /// Registers a check over several paths from the document root. It gets
+    /// the values in the same order, `None` for absent keys. A failure is
+    /// reported on the first path and names the others as related.
+    pub fn cross_check(
+        mut self,
+        paths: &[&str],
+        check: impl Fn(&[Option<&BsonValue>]) -> Result<(), String> + 's,
+    ) -> Validator<'s> {
+        let paths = paths.iter().map(|path| path.to_string()).collect();
+        self.cross_checks.push((paths, Box::new(check)));
+        self
+    }
+
+    fn run_hooks(&self, value: &BsonValue, violations: &mut Vec<Violation>) {
+        value.visit(&mut Hooks {
+            hooks: &self.hooks,
+            violations,
+        });
+    }
+
+    /// Schema violations first, then whatever the hooks report.
+    pub fn validate_value(&self, value: &BsonValue) -> Vec<Violation> {
+        let mut violations = vec![];
+        check(value, self.schema, "", self.strict, &mut violations);
+        self.run_hooks(value, &mut violations);
+        for (paths, check) in &self.cross_checks {
+            let values: Vec<Option<&BsonValue>> =
+                paths.iter().map(|path| value.get(path)).collect();
+            if let Err(message) = check(&values) {
+                violations.push(Violation {
+                    related: paths[1..].to_vec(),
+                    ..Violation::new(&paths[0], message)
+                });
+            }
+        }
+        violations
+    }
+
+    pub fn validate(&self, doc: &Document) -> Vec<Violation> {
+        let value = match doc.value() {
+            Ok(value) => value,
+            Err(err) => return vec![Violation::new("", err.to_string())],
+        };
+        let mut violations = self.validate_value(&value);
+        locate(doc, &mut violations);
+        violations
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+use crate::parser::BsonValue;
+use crate::visit::{VisitMut, walk_value_mut};
+
+/// Looks up the value behind a `secret("...")` reference.
+pub trait SecretResolver {
+    fn resolve(&self, reference: &str) -> Result<String, String>;
+}
+
+impl<F: Fn(&str) -> Result<String, String>> SecretResolver for F {
+    fn resolve(&self, reference: &str) -> Result<String, String> {
+        self(reference)
+    }
+}
+
+/// Resolves references by their scheme, the part before the first `:`.
+/// `env:NAME` reads an environment variable and `file:PATH` the contents of
+/// a file, without its final newline. Other schemes, like a `vault:` store,
+/// are plugged in with `scheme`.
+#[derive(Default)]
+pub struct Secrets {
+    schemes: BTreeMap<String, Box<dyn SecretResolver>>,
+}
+
+impl Secrets {
+    pub fn new() -> Secrets {
+        Secrets::default()
+    }
+
+    /// Routes references starting with `<name>:` to `resolver`, which gets
+    /// the rest of the reference.
+    pub fn scheme(mut self, name: &str, resolver: impl SecretResolver + 'static) -> Secrets {
+        self.schemes.insert(name.to_string(), Box::new(resolver));
+        self
+    }
+}
+
+impl SecretResolver for Secrets {
+    fn resolve(&self, reference: &str) -> Result<String, String> {
+        let (scheme, location) = reference
+            .split_once(':')
+            .ok_or_else(|| format!("No scheme in secret reference {reference}"))?;
+        if let Some(resolver) = self.schemes.get(scheme) {
+            return resolver.resolve(location);
+        }
+        match scheme {
+            "env" => env::var(location).map_err(|err| format!("{location}: {err}")),
+            "file" => fs::read_to_string(location)
+                .map(|contents| contents.strip_suffix('\n').unwrap_or(&contents).to_string())
+                .map_err(|err| format!("{location}: {err}")),
+            _ => Err(format!("Unknown secret scheme {scheme}")),
+        }
+    }
+}
+
+/// A reference that could not be resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecretError {
+    pub path: String,
+    pub reference: String,
+    pub message: String,
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Error for SecretError {}
+
+// Swaps references for their values until the first one fails
+struct Resolve<'r> {
+    resolver: &'r dyn SecretResolver,
+    error: Option<SecretError>,
+}
+
+impl VisitMut for Resolve<'_> {
+    fn visit_value_mut(&mut self, path: &str, value: &mut BsonValue) {
+        if self.error.is_some() {
+            return;
+        }
+        let BsonValue::Secret(reference) = value else {
+            return walk_value_mut(self, path, value);
+        };
+        match self.resolver.resolve(reference) {
+            Ok(secret) => *value = BsonValue::BString(secret.into()),
+            Err(message) => {
+                self.error = Some(SecretError {
+                    path: path.to_string(),
+                    reference: reference.to_string(),
+                    message,
+                })
+            }
+        }
+    }
+}
+
+impl BsonValue<'_> {
+    /// Replaces every `secret("...")` reference with the string `resolver`
+    /// returns for it. Stops at the first reference that fails.
+    pub fn resolve_secrets(&mut self, resolver: &dyn SecretResolver) -> Result<(), SecretError> {
+        let mut resolve = Resolve {
+            resolver,
+            error: None,
+        };
+        self.visit_mut(&mut resolve);
+        resolve.error.map_or(Ok(()), Err)
+    }
+}
@@ -0,0 +1,592 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "decimal")]
+use rust_decimal::prelude::ToPrimitive;
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::de::ConvertError;
+use crate::parser::BsonValue;
+
+impl ser::Error for ConvertError {
+    fn custom<T: std::fmt::Display>(message: T) -> ConvertError {
+        ConvertError::new("", message.to_string())
+    }
+}
+
+/// Writes any `Serialize` type as a document value, the way `de::from_value`
+/// reads it back: `None` and `()` become `MissingNo`, structs and maps
+/// sections, unit variants strings and other variants a section with the
+/// variant as its only key. Integers `f64` would round become an `Integer`.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<BsonValue<'static>, ConvertError> {
+    value.serialize(Serializer)
+}
+
+// Integers `f64` would round stay exact, as the parser keeps them
+fn integer(n: i128) -> BsonValue<'static> {
+    match n as f64 as i128 == n {
+        true => BsonValue::Number(n as f64),
+        false => BsonValue::Integer(n),
+    }
+}
+
+fn variant(name: &'static str, value: BsonValue<'static>) -> BsonValue<'static> {
+    BsonValue::Map(BTreeMap::from([(Cow::Borrowed(name), value)]))
+}
+
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = BsonValue<'static>;
+    type Error = ConvertError;
+    type SerializeSeq = Items;
+    type SerializeTuple = Items;
+    type SerializeTupleStruct = Items;
+    type SerializeTupleVariant = Items;
+    type SerializeMap = Entries;
+    type SerializeStruct = Entries;
+    type SerializeStructVariant = Entries;
+
+    fn serialize_bool(self, b: bool) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::Bool(b))
+    }
+
+    fn serialize_i8(self, n: i8) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(integer(n.into()))
+    }
+
+    fn serialize_i16(self, n: i16) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(integer(n.into()))
+    }
+
+    fn serialize_i32(self, n: i32) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(integer(n.into()))
+    }
+
+    fn serialize_i64(self, n: i64) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(integer(n.into()))
+    }
+
+    fn serialize_i128(self, n: i128) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(integer(n))
+    }
+
+    fn serialize_u8(self, n: u8) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(integer(n.into()))
+    }
+
+    fn serialize_u16(self, n: u16) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(integer(n.into()))
+    }
+
+    fn serialize_u32(self, n: u32) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(integer(n.into()))
+    }
+
+    fn serialize_u64(self, n: u64) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(integer(n.into()))
+    }
+
+    fn serialize_u128(self, n: u128) -> Result<BsonValue<'static>, ConvertError> {
+        i128::try_from(n)
+            .map(integer)
+            .map_err(|_| ser::Error::custom("integer too large"))
+    }
+
+    fn serialize_f32(self, n: f32) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::Number(n.into()))
+    }
+
+    fn serialize_f64(self, n: f64) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::Number(n))
+    }
+
+    fn serialize_char(self, c: char) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::from(c.to_string()))
+    }
+
+    fn serialize_str(self, s: &str) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::from(s.to_string()))
+    }
+
+    fn serialize_bytes(self, bytes: &[u8]) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::Array(
+            bytes
+                .iter()
+                .map(|b| BsonValue::Number(f64::from(*b)))
+                .collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::Null(()))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<BsonValue<'static>, ConvertError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::Null(()))
+    }
+
+    fn serialize_unit_struct(
+        self,
+        _name: &'static str,
+    ) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::Null(()))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(BsonValue::from(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<BsonValue<'static>, ConvertError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<BsonValue<'static>, ConvertError> {
+        Ok(variant(name, to_value(value)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Items, ConvertError> {
+        Ok(Items {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Items, ConvertError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Items, ConvertError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Items, ConvertError> {
+        Ok(Items {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Entries, ConvertError> {
+        Ok(Entries {
+            variant: None,
+            entries: BTreeMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Entries, ConvertError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Entries, ConvertError> {
+        Ok(Entries {
+            variant: Some(variant),
+            entries: BTreeMap::new(),
+            key: None,
+        })
+    }
+}
+
+struct Items {
+    variant: Option<&'static str>,
+    items: Vec<BsonValue<'static>>,
+}
+
+impl Items {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        let index = self.items.len().to_string();
+        self.items
+            .push(to_value(value).map_err(|err| err.within(&index))?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<BsonValue<'static>, ConvertError> {
+        let items = BsonValue::Array(self.items);
+        match self.variant {
+            Some(name) => Ok(variant(name, items)),
+            None => Ok(items),
+        }
+    }
+}
+
+impl ser::SerializeSeq for Items {
+    type Ok = BsonValue<'static>;
+    type Error = ConvertError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<BsonValue<'static>, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Items {
+    type Ok = BsonValue<'static>;
+    type Error = ConvertError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<BsonValue<'static>, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Items {
+    type Ok = BsonValue<'static>;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<BsonValue<'static>, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Items {
+    type Ok = BsonValue<'static>;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<BsonValue<'static>, ConvertError> {
+        self.finish()
+    }
+}
+
+struct Entries {
+    variant: Option<&'static str>,
+    entries: BTreeMap<Cow<'static, str>, BsonValue<'static>>,
+    // The key handed over last, waiting for its value
+    key: Option<String>,
+}
+
+impl Entries {
+    fn insert<T: Serialize + ?Sized>(
+        &mut self,
+        key: String,
+        value: &T,
+    ) -> Result<(), ConvertError> {
+        let value = to_value(value).map_err(|err| err.within(&key))?;
+        self.entries.insert(Cow::Owned(key), value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<BsonValue<'static>, ConvertError> {
+        let entries = BsonValue::Map(self.entries);
+        match self.variant {
+            Some(name) => Ok(variant(name, entries)),
+            None => Ok(entries),
+        }
+    }
+}
+
+impl ser::SerializeMap for Entries {
+    type Ok = BsonValue<'static>;
+    type Error = ConvertError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ConvertError> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        match self.key.take() {
+            Some(key) => self.insert(key, value),
+            None => Err(ser::Error::custom("value given before its key")),
+        }
+    }
+
+    fn end(self) -> Result<BsonValue<'static>, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for Entries {
+    type Ok = BsonValue<'static>;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ConvertError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<BsonValue<'static>, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Entries {
+    type Ok = BsonValue<'static>;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ConvertError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<BsonValue<'static>, ConvertError> {
+        self.finish()
+    }
+}
+
+// Keys are strings; numbers and characters are written out as one
+struct KeySerializer;
+
+impl KeySerializer {
+    fn refuse() -> ConvertError {
+        ser::Error::custom("keys must be strings")
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = ConvertError;
+    type SerializeSeq = Impossible<String, ConvertError>;
+    type SerializeTuple = Impossible<String, ConvertError>;
+    type SerializeTupleStruct = Impossible<String, ConvertError>;
+    type SerializeTupleVariant = Impossible<String, ConvertError>;
+    type SerializeMap = Impossible<String, ConvertError>;
+    type SerializeStruct = Impossible<String, ConvertError>;
+    type SerializeStructVariant = Impossible<String, ConvertError>;
+
+    fn serialize_str(self, s: &str) -> Result<String, ConvertError> {
+        Ok(s.to_string())
+    }
+
+    fn serialize_char(self, c: char) -> Result<String, ConvertError> {
+        Ok(c.to_string())
+    }
+
+    fn serialize_i8(self, n: i8) -> Result<String, ConvertError> {
+        Ok(n.to_string())
+    }
+
+    fn serialize_i16(self, n: i16) -> Result<String, ConvertError> {
+        Ok(n.to_string())
+    }
+
+    fn serialize_i32(self, n: i32) -> Result<String, ConvertError> {
+        Ok(n.to_string())
+    }
+
+    fn serialize_i64(self, n: i64) -> Result<String, ConvertError> {
+        Ok(n.to_string())
+    }
+
+    fn serialize_u8(self, n: u8) -> Result<String, ConvertError> {
+        Ok(n.to_string())
+    }
+
+    fn serialize_u16(self, n: u16) -> Result<String, ConvertError> {
+        Ok(n.to_string())
+    }
+
+    fn serialize_u32(self, n: u32) -> Result<String, ConvertError> {
+        Ok(n.to_string())
+    }
+
+    fn serialize_u64(self, n: u64) -> Result<String, ConvertError> {
+        Ok(n.to_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<String, ConvertError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, ConvertError> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<String, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<String, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<String, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<String, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_none(self) -> Result<String, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<String, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_unit(self) -> Result<String, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<String, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<String, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, ConvertError> {
+        Err(KeySerializer::refuse())
+    }
+}
+
+/// A document written to any format, such as JSON through `serde_json`, the
+/// way `de::from_value` hands it to a `Deserialize` type: `MissingNo` as
+/// unit and whole numbers as integers. Secret references and encrypted
+/// values have to be resolved first.
+impl Serialize for BsonValue<'_> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            BsonValue::BString(s) | BsonValue::Regex(s) | BsonValue::Date(s) => {
+                serializer.serialize_str(s)
+            }
+            BsonValue::Number(n)
+                if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 =>
+            {
+                serializer.serialize_i64(*n as i64)
+            }
+            BsonValue::Number(n) | BsonValue::Float(n) => serializer.serialize_f64(*n),
+            BsonValue::Integer(n) => match (i64::try_from(*n), u64::try_from(*n)) {
+                (Ok(n), _) => serializer.serialize_i64(n),
+                (_, Ok(n)) => serializer.serialize_u64(n),
+                _ => serializer.serialize_i128(*n),
+            },
+            // Whole decimals go as integers too, the others as the nearest
+            // `f64`
+            #[cfg(feature = "decimal")]
+            BsonValue::Decimal(d) => match (d.fract().is_zero(), d.to_i128(), d.to_f64()) {
+                (true, Some(n), _) => BsonValue::Integer(n).serialize(serializer),
+                (_, _, Some(n)) => serializer.serialize_f64(n),
+                _ => serializer.serialize_str(&d.to_string()),
+            },
+            BsonValue::Bool(b) => serializer.serialize_bool(*b),
+            BsonValue::Null(()) => serializer.serialize_unit(),
+            BsonValue::Array(items) => serializer.collect_seq(items),
+            BsonValue::Map(m) => serializer.collect_map(m),
+            BsonValue::Secret(reference) => Err(ser::Error::custom(format!(
+                "Unresolved secret reference {reference}"
+            ))),
+            BsonValue::Encrypted(_) => Err(ser::Error::custom("Encrypted values need a key")),
+        }
+    }
+}
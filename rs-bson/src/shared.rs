@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::lexer;
+use crate::load::{self, LoadError};
+use crate::parser::{self, BsonValue, ParseOptions};
+
+/// A parsed config to hand to every thread of a server. Cloning copies a
+/// pointer rather than the tree and nothing can change the value once it
+/// is built; reads go through `Deref` to the `BsonValue`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedDocument(Arc<BsonValue<'static>>);
+
+impl SharedDocument {
+    pub fn parse(input: &str) -> Result<SharedDocument, &'static str> {
+        let tokens = lexer::lex_str(input)?;
+        Ok(SharedDocument::from(parser::parse(&tokens)?.into_owned()))
+    }
+
+    /// Reads and parses a file the way `load::read` does, compressed or not.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<SharedDocument, LoadError> {
+        let input = load::read(path.as_ref())?;
+        let value = parser::parse_str(&input, &ParseOptions::default())?;
+        Ok(SharedDocument::from(value))
+    }
+
+    /// Whether both handles point at the same parsed tree.
+    pub fn ptr_eq(a: &SharedDocument, b: &SharedDocument) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+}
+
+impl From<BsonValue<'static>> for SharedDocument {
+    fn from(value: BsonValue<'static>) -> SharedDocument {
+        SharedDocument(Arc::new(value))
+    }
+}
+
+impl Deref for SharedDocument {
+    type Target = BsonValue<'static>;
+
+    fn deref(&self) -> &BsonValue<'static> {
+        &self.0
+    }
+}
+
+/// A document whose clones share every subtree neither side has changed,
+/// for keeping many variants of one large config, e.g. one per tenant.
+/// Sections and lists sit behind an `Arc`; `set` and `remove` copy only
+/// the sections and lists on the way to the key they change.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedValue {
+    Scalar(BsonValue<'static>),
+    Map(Arc<BTreeMap<String, SharedValue>>),
+    Array(Arc<Vec<SharedValue>>),
+}
+
+impl From<BsonValue<'static>> for SharedValue {
+    fn from(value: BsonValue<'static>) -> SharedValue {
+        let value = match value.into_map() {
+            Ok(m) => {
+                return SharedValue::Map(Arc::new(
+                    m.into_iter()
+                        .map(|(key, child)| (key.into_owned(), child.into()))
+                        .collect(),
+                ));
+            }
+            Err(value) => value,
+        };
+        match value.into_array() {
+            Ok(items) => {
+                SharedValue::Array(Arc::new(items.into_iter().map(SharedValue::from).collect()))
+            }
+            Err(scalar) => SharedValue::Scalar(scalar),
+        }
+    }
+}
+
+impl SharedValue {
+    /// Looks up a dotted path like `BsonValue::get`.
+    pub fn get(&self, path: &str) -> Option<&SharedValue> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.split('.')
+            .try_fold(self, |value, segment| match value {
+                SharedValue::Map(m) => m.get(segment),
+                SharedValue::Array(items) => items.get(segment.parse::<usize>().ok()?),
+                SharedValue::Scalar(_) => None,
+            })
+    }
+
+    // Unshares every section and list on the way to `path`
+    fn get_mut(&mut self, path: &str) -> Option<&mut SharedValue> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.split('.')
+            .try_fold(self, |value, segment| match value {
+                SharedValue::Map(m) => Arc::make_mut(m).get_mut(segment),
+                SharedValue::Array(items) => {
+                    Arc::make_mut(items).get_mut(segment.parse::<usize>().ok()?)
+                }
+                SharedValue::Scalar(_) => None,
+            })
+    }
+
+    /// Sets the value at `path`, adding the key when its section exists
+    /// but lacks it. The attack missed! when the section or list element
+    /// is not there.
+    pub fn set(
+        &mut self,
+        path: &str,
+        value: impl Into<BsonValue<'static>>,
+    ) -> Result<(), &'static str> {
+        let value = SharedValue::from(value.into());
+        if path.is_empty() {
+            *self = value;
+            return Ok(());
+        }
+        let (parent, key) = path.rsplit_once('.').unwrap_or(("", path));
+        // Looked up first so a missed attack copies nothing
+        let index = key.parse::<usize>().ok();
+        match self.get(parent) {
+            Some(SharedValue::Map(_)) => {}
+            Some(SharedValue::Array(items)) if index.is_some_and(|i| i < items.len()) => {}
+            _ => return Err("The attack missed!"),
+        }
+        match self.get_mut(parent) {
+            Some(SharedValue::Map(m)) => {
+                Arc::make_mut(m).insert(key.to_string(), value);
+            }
+            Some(SharedValue::Array(items)) => {
+                Arc::make_mut(items)[index.unwrap_or_default()] = value;
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Takes the key at `path` out of its section, `None` when it is not
+    /// set. List elements cannot be removed this way.
+    pub fn remove(&mut self, path: &str) -> Option<BsonValue<'static>> {
+        let (parent, key) = path.rsplit_once('.').unwrap_or(("", path));
+        match self.get(parent) {
+            Some(SharedValue::Map(m)) if m.contains_key(key) => {}
+            _ => return None,
+        }
+        match self.get_mut(parent)? {
+            SharedValue::Map(m) => Arc::make_mut(m).remove(key).map(|value| value.to_value()),
+            _ => None,
+        }
+    }
+
+    /// Builds a plain `BsonValue` out of the tree, copying everything.
+    pub fn to_value(&self) -> BsonValue<'static> {
+        match self {
+            SharedValue::Scalar(value) => value.clone(),
+            SharedValue::Map(m) => BsonValue::Map(
+                m.iter()
+                    .map(|(key, child)| (key.clone().into(), child.to_value()))
+                    .collect(),
+            ),
+            SharedValue::Array(items) => {
+                BsonValue::Array(items.iter().map(SharedValue::to_value).collect())
+            }
+        }
+    }
+
+    /// Whether both are the same section or list in memory rather than
+    /// equal copies. Always false for scalars.
+    pub fn ptr_eq(a: &SharedValue, b: &SharedValue) -> bool {
+        match (a, b) {
+            (SharedValue::Map(a), SharedValue::Map(b)) => Arc::ptr_eq(a, b),
+            (SharedValue::Array(a), SharedValue::Array(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
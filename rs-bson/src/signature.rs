@@ -0,0 +1,193 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "hmac")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "hmac")]
+use sha2::Sha256;
+
+#[cfg(feature = "hmac")]
+use crate::checksum;
+use crate::lexer;
+use crate::load::{self, FileError, LoadError};
+use crate::parser::{self, BsonValue, ParseOptions};
+
+/// Checks a detached signature over the bytes of a document as they were
+/// shipped, before anything reads them.
+pub trait Verifier {
+    fn verify(&self, document: &[u8], signature: &[u8]) -> Result<(), String>;
+}
+
+impl<F: Fn(&[u8], &[u8]) -> Result<(), String>> Verifier for F {
+    fn verify(&self, document: &[u8], signature: &[u8]) -> Result<(), String> {
+        self(document, signature)
+    }
+}
+
+/// Runs a shell command that exits with 0 for a good signature, such as
+/// `minisign -Vm "$1" -x "$2" -p key.pub` or `gpg --verify "$2" "$1"`.
+/// `$1` is a copy of the document and `$2` one of the signature.
+#[derive(Debug, Clone)]
+pub struct CommandVerifier {
+    command: String,
+}
+
+impl CommandVerifier {
+    pub fn new(command: &str) -> CommandVerifier {
+        CommandVerifier {
+            command: command.to_string(),
+        }
+    }
+
+    /// The command in `BULBA_VERIFY_COMMAND`, if it is set.
+    pub fn from_env() -> Option<CommandVerifier> {
+        env::var("BULBA_VERIFY_COMMAND")
+            .ok()
+            .map(|command| CommandVerifier::new(&command))
+    }
+}
+
+// A directory for one check that nobody else can have prepared: made
+// afresh rather than reused when it exists, and closed to other users, so
+// the files verified are the ones written
+fn private_dir() -> io::Result<PathBuf> {
+    static CHECKS: AtomicUsize = AtomicUsize::new(0);
+    let mut result = Err(io::Error::from(io::ErrorKind::AlreadyExists));
+    for _ in 0..16 {
+        let check = CHECKS.fetch_add(1, Ordering::Relaxed);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.subsec_nanos());
+        let name = format!("rs-bson-verify-{}-{check}-{nanos:x}", process::id());
+        let dir = env::temp_dir().join(name);
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        builder.mode(0o700);
+        result = builder.create(&dir).map(|_| dir);
+        match &result {
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            _ => break,
+        }
+    }
+    result
+}
+
+// Writes a file that must not exist yet, never following a link
+fn write_new(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)?.write_all(bytes)
+}
+
+impl Verifier for CommandVerifier {
+    fn verify(&self, document: &[u8], signature: &[u8]) -> Result<(), String> {
+        let fail = |err: &dyn std::fmt::Display| format!("{}: {err}", self.command);
+        let dir = private_dir().map_err(|err| fail(&err))?;
+        let (doc_path, sig_path) = (dir.join("document"), dir.join("signature"));
+        let status = write_new(&doc_path, document)
+            .and_then(|_| write_new(&sig_path, signature))
+            .and_then(|_| {
+                Command::new("sh")
+                    .args(["-c", &self.command, "sh"])
+                    .arg(&doc_path)
+                    .arg(&sig_path)
+                    .status()
+            });
+        let _ = fs::remove_dir_all(&dir);
+        match status.map_err(|err| fail(&err))? {
+            status if status.success() => Ok(()),
+            status => Err(fail(&status)),
+        }
+    }
+}
+
+/// Signatures that are the hex HMAC-SHA256 of the document under a shared
+/// key, for deployments that already hand out a secret to every host.
+#[cfg(feature = "hmac")]
+#[derive(Debug, Clone)]
+pub struct HmacVerifier {
+    key: Vec<u8>,
+}
+
+#[cfg(feature = "hmac")]
+impl HmacVerifier {
+    pub fn new(key: &[u8]) -> HmacVerifier {
+        HmacVerifier { key: key.to_vec() }
+    }
+
+    fn mac(&self, document: &[u8]) -> Hmac<Sha256> {
+        // HMAC takes a key of any length
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).unwrap();
+        mac.update(document);
+        mac
+    }
+
+    /// The signature to ship along with `document`.
+    pub fn sign(&self, document: &[u8]) -> String {
+        checksum::hex(&self.mac(document).finalize().into_bytes())
+    }
+}
+
+// The bytes of an even number of hex digits
+#[cfg(feature = "hmac")]
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(feature = "hmac")]
+impl Verifier for HmacVerifier {
+    fn verify(&self, document: &[u8], signature: &[u8]) -> Result<(), String> {
+        let mismatch = || String::from("Signature does not match");
+        let tag = unhex(String::from_utf8_lossy(signature).trim()).ok_or_else(mismatch)?;
+        // Compares in constant time
+        self.mac(document)
+            .verify_slice(&tag)
+            .map_err(|_| mismatch())
+    }
+}
+
+/// Verifies `document` against `signature` and only then parses it.
+pub fn parse_verified(
+    document: &[u8],
+    signature: &[u8],
+    verifier: &dyn Verifier,
+    options: &ParseOptions,
+) -> Result<BsonValue<'static>, LoadError> {
+    verifier
+        .verify(document, signature)
+        .map_err(LoadError::Signature)?;
+    let tokens = lexer::lex_bytes(document, options)?;
+    let value = parser::parse_with(&tokens, options)?;
+    Ok(value.into_owned())
+}
+
+/// The text of the file at `path` once `verifier` accepted the signature
+/// in `signature_path`. The signature covers the file as stored, so a
+/// compressed file is decompressed only after the check, see `load::read`.
+pub fn read_verified(
+    path: &Path,
+    signature_path: &Path,
+    verifier: &dyn Verifier,
+) -> Result<String, LoadError> {
+    let read = |path: &Path| fs::read(path).map_err(|err| LoadError::Io(FileError::new(path, err)));
+    let (document, signature) = (read(path)?, read(signature_path)?);
+    verifier
+        .verify(&document, &signature)
+        .map_err(LoadError::Signature)?;
+    load::decode(path, document)
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::lexer::Token;
+use crate::parser::{self, BsonValue, ParseError, ParseOptions, join_path};
+
+/// Where a value was written: its line and the bytes of that line it
+/// covers. A value continued over several lines counts as on the first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Span {
+    pub line: usize,
+    pub columns: Range<usize>,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, cols {}-{}",
+            self.line,
+            self.columns.start + 1,
+            self.columns.end
+        )
+    }
+}
+
+// Span of every value by dotted path, as the parser records them
+pub(crate) type Spans = BTreeMap<String, Span>;
+
+/// A document tree whose every node knows where it was written, see
+/// `parse_spanned`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned {
+    pub span: Span,
+    pub node: SpannedNode,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpannedNode {
+    /// Anything but a section or a list
+    Scalar(BsonValue<'static>),
+    Array(Vec<Spanned>),
+    Map(BTreeMap<String, Spanned>),
+}
+
+impl Spanned {
+    /// The node at a dotted path, with list elements addressed by index as
+    /// in `BsonValue::get`. The empty path is the node itself.
+    pub fn get(&self, path: &str) -> Option<&Spanned> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        let (first, rest) = path.split_once('.').unwrap_or((path, ""));
+        match &self.node {
+            SpannedNode::Map(m) => m.get(first)?.get(rest),
+            SpannedNode::Array(items) => items.get(first.parse::<usize>().ok()?)?.get(rest),
+            SpannedNode::Scalar(_) => None,
+        }
+    }
+
+    /// The value without its spans.
+    pub fn to_value(&self) -> BsonValue<'static> {
+        match &self.node {
+            SpannedNode::Scalar(value) => value.clone(),
+            SpannedNode::Array(items) => {
+                BsonValue::Array(items.iter().map(Spanned::to_value).collect())
+            }
+            SpannedNode::Map(m) => BsonValue::Map(
+                m.iter()
+                    .map(|(key, node)| (key.clone().into(), node.to_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// Depth is bounded by `ParseOptions::max_depth`
+fn attach(value: BsonValue<'static>, path: String, spans: &mut Spans) -> Spanned {
+    let span = spans.remove(&path).unwrap_or_default();
+    let node = match value.into_array() {
+        Ok(items) => SpannedNode::Array(
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| attach(item, join_path(&path, &i.to_string()), spans))
+                .collect(),
+        ),
+        Err(value) => match value.into_map() {
+            Ok(m) => SpannedNode::Map(
+                m.into_iter()
+                    .map(|(key, child)| {
+                        let child = attach(child, join_path(&path, &key), spans);
+                        (key.into_owned(), child)
+                    })
+                    .collect(),
+            ),
+            Err(scalar) => SpannedNode::Scalar(scalar),
+        },
+    };
+    Spanned { span, node }
+}
+
+/// `parser::parse_with` for tools that point at the text, such as "value
+/// at line 12, cols 20-25 exceeds maximum". Sections span their header,
+/// values span their literal, and the root spans the cry.
+pub fn parse_spanned(tokens: &[Token], options: &ParseOptions) -> Result<Spanned, ParseError> {
+    let mut spans = Spans::new();
+    let value = parser::parse_recording(tokens, options, &mut spans)?.into_owned();
+    Ok(attach(value, String::new(), &mut spans))
+}
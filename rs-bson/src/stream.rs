@@ -0,0 +1,60 @@
+use std::io::BufRead;
+
+use crate::lexer::{self, Token, TokenType};
+use crate::parser::{self, BsonValue, ParseError, ParseOptions};
+
+/// Reads a stream of documents one at a time, so an append-only log of
+/// snapshots never has to be held in memory all at once. Documents split
+/// where `parser::parse_all` splits them, at every cry the lexer reads.
+pub struct Documents<R: BufRead> {
+    tokens: lexer::Tokens<R>,
+    options: ParseOptions,
+    // Header of the next document, already read while finishing the last one
+    next_header: Option<Token>,
+}
+
+impl<R: BufRead> Documents<R> {
+    pub fn new(reader: R) -> Documents<R> {
+        Documents::with_options(reader, &ParseOptions::default())
+    }
+
+    /// `new` with the rules of `options`, such as a header of your own.
+    pub fn with_options(reader: R, options: &ParseOptions) -> Documents<R> {
+        Documents {
+            tokens: lexer::Tokens::new(reader, options),
+            options: options.clone(),
+            next_header: None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Documents<R> {
+    type Item = Result<BsonValue<'static>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = match self.next_header.take() {
+            Some(header) => header,
+            None => match self.tokens.next()? {
+                // A stream without a single cry holds no documents
+                Ok(token) if token.ttype == TokenType::Eof => return None,
+                Ok(token) => token,
+                Err(err) => return Some(Err(err)),
+            },
+        };
+
+        let mut tokens = vec![first];
+        for token in self.tokens.by_ref() {
+            match token {
+                Ok(token) if token.ttype == TokenType::Header => {
+                    self.next_header = Some(token);
+                    break;
+                }
+                Ok(token) => tokens.push(token),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        let document = parser::parse_with(&tokens, &self.options).map(BsonValue::into_owned);
+        Some(document)
+    }
+}
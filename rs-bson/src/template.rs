@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::emit::fragment;
+use crate::parser::BsonValue;
+
+/// Placeholders `render` found no value for, each named once in the order
+/// they first appear.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unresolved(pub Vec<String>);
+
+impl fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<String> = self.0.iter().map(|name| format!("${{{name}}}")).collect();
+        write!(f, "Wild placeholders appeared: {}", names.join(", "))
+    }
+}
+
+impl Error for Unresolved {}
+
+/// Replaces every `${name}` in `input` with `vars[name]`. The text is
+/// substituted as it is, so a placeholder may stand for a whole value, as
+/// in `port ~> ${port}`, or for part of a string. Surrounding spaces in a
+/// name are ignored.
+pub fn render(input: &str, vars: &BTreeMap<String, String>) -> Result<String, Unresolved> {
+    let mut output = String::with_capacity(input.len());
+    let mut missing: Vec<String> = vec![];
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        output += &rest[..start];
+        let name = rest[start + 2..start + 2 + len].trim();
+        match vars.get(name) {
+            Some(value) => output += value,
+            None if !missing.iter().any(|m| m == name) => missing.push(name.to_string()),
+            None => {}
+        }
+        rest = &rest[start + 3 + len..];
+    }
+    output += rest;
+    if !missing.is_empty() {
+        return Err(Unresolved(missing));
+    }
+    Ok(output)
+}
+
+/// The variables a document of values defines for `render`, one per dotted
+/// path such as `database.host`. Strings stand for their text, which the
+/// template quotes itself where it needs to, and everything else for its
+/// BSON spelling. Lists stay whole; values BSON cannot spell, such as a
+/// list element holding a comma, are left out.
+pub fn vars_from(value: &BsonValue) -> BTreeMap<String, String> {
+    value
+        .flatten_with(false)
+        .into_iter()
+        .filter_map(|(path, value)| {
+            let text = match &value {
+                // An empty section holds no value
+                BsonValue::Map(_) => return None,
+                BsonValue::BString(s) => s.to_string(),
+                _ => fragment(&value).ok()?,
+            };
+            Some((path, text))
+        })
+        .collect()
+}
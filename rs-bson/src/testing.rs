@@ -0,0 +1,214 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diff::diff;
+use crate::emit::{emit, fragment};
+use crate::format::format;
+use crate::lexer;
+use crate::parser::{self, BsonValue, ParseError, ParseOptions, join_path};
+use crate::patch::Operation;
+
+/// Set to anything but `0` to write snapshots instead of comparing them.
+pub const UPDATE_VAR: &str = "BULBA_UPDATE_SNAPSHOTS";
+
+fn updating() -> bool {
+    env::var(UPDATE_VAR).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+// One `path: literal` line per scalar, so a whole section reads as a list
+fn leaves(value: &BsonValue, path: &str, lines: &mut Vec<String>) {
+    match value {
+        BsonValue::Map(m) if !m.is_empty() => {
+            for (key, child) in m {
+                leaves(child, &join_path(path, key), lines);
+            }
+        }
+        BsonValue::Map(_) => lines.push(format!("{path}: (empty section)")),
+        _ => lines.push(format!("{path}: {}", fragment(value).unwrap_or_default())),
+    }
+}
+
+fn report(expected: &BsonValue, actual: &BsonValue) -> Vec<String> {
+    let mut changes: Vec<_> = diff(expected, actual)
+        .operations
+        .into_iter()
+        .filter_map(|operation| match operation {
+            Operation::Remove { path } => Some((path, None)),
+            Operation::Add { path, value } | Operation::Replace { path, value } => {
+                Some((path, Some(value)))
+            }
+            Operation::Move { .. } => None,
+        })
+        .collect();
+    // In key order rather than removals first
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut lines = vec![];
+    for (pointer, added) in &changes {
+        // Listed by dotted path, the way the snapshot reads
+        let path = parser::pointer_segments(pointer)
+            .unwrap_or_default()
+            .join(".");
+        let (mut old, mut new) = (vec![], vec![]);
+        if let Some(removed) = expected.pointer(pointer) {
+            leaves(removed, &path, &mut old);
+        }
+        if let Some(added) = added {
+            leaves(added, &path, &mut new);
+        }
+        lines.extend(old.iter().map(|line| format!("  - {line}")));
+        lines.extend(new.iter().map(|line| format!("  + {line}")));
+    }
+    lines
+}
+
+/// Compares `value` with the BSON snapshot at `path`. The comparison is
+/// structural, so the snapshot may be reformatted or commented freely. A
+/// mismatch lists every differing key, `-` for the snapshot and `+` for the
+/// value. With `BULBA_UPDATE_SNAPSHOTS=1` the snapshot is written instead.
+pub fn check_snapshot(value: &BsonValue, path: impl AsRef<Path>) -> Result<(), String> {
+    let path = path.as_ref();
+    let hint = format!("rerun with {UPDATE_VAR}=1 to accept the new value");
+    if updating() {
+        let text = emit(value).map_err(|err| format!("{}: {err}", path.display()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", path.display()))?;
+        }
+        return fs::write(path, text).map_err(|err| format!("{}: {err}", path.display()));
+    }
+    let Ok(text) = fs::read_to_string(path) else {
+        return Err(format!(
+            "snapshot {} does not exist, {hint}",
+            path.display()
+        ));
+    };
+    let tokens = lexer::lex_str(&text).map_err(|err| format!("{}: {err}", path.display()))?;
+    let expected = parser::parse(&tokens).map_err(|err| format!("{}: {err}", path.display()))?;
+    if expected == *value {
+        return Ok(());
+    }
+    let lines = report(&expected, value);
+    Err(format!(
+        "snapshot {} does not match:\n{}\n{hint}",
+        path.display(),
+        lines.join("\n")
+    ))
+}
+
+/// Panics with the difference when `value` does not match the snapshot at
+/// `path`, see `check_snapshot`.
+#[track_caller]
+pub fn assert_snapshot(value: &BsonValue, path: impl AsRef<Path>) {
+    if let Err(message) = check_snapshot(value, path) {
+        panic!("{message}");
+    }
+}
+
+/// A fixture `check_corpus` refused, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorpusFailure {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for CorpusFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl Error for CorpusFailure {}
+
+fn read(text: &str) -> Result<BsonValue<'static>, ParseError> {
+    parser::parse_str(text, &ParseOptions::default())
+}
+
+// Everything `check_corpus` asks of one fixture
+fn check_fixture(path: &Path) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let expected = fs::read_to_string(path.with_extension("err")).ok();
+    let value = match (read(&text), expected.as_deref().map(str::trim)) {
+        (Ok(value), None) => value,
+        (Err(err), Some(expected)) if err.message() == expected => return Ok(()),
+        (Err(err), Some(expected)) => {
+            return Err(format!("fails with {}, not {expected}", err.message()));
+        }
+        (Ok(_), Some(expected)) => return Err(format!("parses, but should fail with {expected}")),
+        (Err(err), None) => return Err(format!("does not parse: {err}")),
+    };
+
+    let emitted = emit(&value).map_err(|err| format!("does not emit: {err}"))?;
+    let again = read(&emitted).map_err(|err| format!("emitted text does not parse: {err}"))?;
+    if again != value {
+        let lines = report(&value, &again);
+        return Err(format!("emit and parse change it:\n{}", lines.join("\n")));
+    }
+    if emit(&again).as_ref() != Ok(&emitted) {
+        return Err(String::from("emitting it twice gives two texts"));
+    }
+
+    let formatted = format(&text).map_err(|err| format!("does not format: {err}"))?;
+    if format(&formatted).as_ref() != Ok(&formatted) {
+        return Err(String::from("formatting it twice gives two texts"));
+    }
+    if read(&formatted).as_ref() != Ok(&value) {
+        return Err(String::from("formatting changes what it means"));
+    }
+    Ok(())
+}
+
+fn fixtures(dir: &Path, found: &mut Vec<PathBuf>) -> Result<(), CorpusFailure> {
+    let fail = |err: std::io::Error| CorpusFailure {
+        path: dir.to_path_buf(),
+        message: err.to_string(),
+    };
+    for entry in fs::read_dir(dir).map_err(fail)? {
+        let path = entry.map_err(fail)?.path();
+        if path.is_dir() {
+            fixtures(&path, found)?;
+        } else if path.extension().is_some_and(|ext| ext == "bson") {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Checks every `.bson` file under `dir`, recursively. A fixture must parse,
+/// read back the same after `emit`, emit the same text twice, and format to
+/// a fixpoint that still means the same. A fixture with an `.err` file next
+/// to it, such as `bad_tab.err` for `bad_tab.bson`, must instead fail with
+/// the message in it. Returns how many fixtures passed, or every failure.
+pub fn check_corpus(dir: impl AsRef<Path>) -> Result<usize, Vec<CorpusFailure>> {
+    let mut paths = vec![];
+    fixtures(dir.as_ref(), &mut paths).map_err(|failure| vec![failure])?;
+    paths.sort();
+    let failures: Vec<CorpusFailure> = paths
+        .iter()
+        .filter_map(|path| {
+            check_fixture(path).err().map(|message| CorpusFailure {
+                path: path.clone(),
+                message,
+            })
+        })
+        .collect();
+    match failures.is_empty() {
+        true => Ok(paths.len()),
+        false => Err(failures),
+    }
+}
+
+/// Panics with every failing fixture under `dir`, see `check_corpus`, and
+/// returns how many there are otherwise.
+#[track_caller]
+pub fn assert_corpus(dir: impl AsRef<Path>) -> usize {
+    match check_corpus(dir) {
+        Ok(count) => count,
+        Err(failures) => {
+            let lines: Vec<String> = failures.iter().map(|failure| failure.to_string()).collect();
+            panic!("{} fixtures failed:\n{}", failures.len(), lines.join("\n"));
+        }
+    }
+}
@@ -0,0 +1,33 @@
+// What reading a config costs, for the observability stack of a service.
+// Lexing and parsing each run in a `tracing` span of target `rs_bson`, which
+// notes what they produced once they are done.
+
+use tracing::field::Empty;
+use tracing::span::EnteredSpan;
+
+use crate::lexer::Token;
+use crate::parser::BsonValue;
+
+pub(crate) fn lexing() -> EnteredSpan {
+    tracing::debug_span!(target: "rs_bson", "lex", tokens = Empty).entered()
+}
+
+pub(crate) fn lexed(span: &EnteredSpan, tokens: &[Token]) {
+    span.record("tokens", tokens.len());
+}
+
+// `size` as in `BsonValue::deep_size`
+pub(crate) fn parsing() -> EnteredSpan {
+    tracing::debug_span!(target: "rs_bson", "parse", leaves = Empty, depth = Empty, size = Empty)
+        .entered()
+}
+
+// The stats are only worked out when someone listens
+pub(crate) fn parsed(span: &EnteredSpan, value: &BsonValue) {
+    if span.is_disabled() {
+        return;
+    }
+    span.record("leaves", value.leaf_count());
+    span.record("depth", value.max_depth());
+    span.record("size", value.deep_size());
+}
@@ -0,0 +1,209 @@
+// One traversal for every tool that walks a document. A visitor overrides
+// the methods for the values it cares about; the defaults keep walking.
+// Overriding `visit_value` sees every value before it is dispatched, and
+// calling the matching `walk_*` function from an override continues below it.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+
+use crate::parser::{BsonValue, join_path};
+
+/// Walks a document by reference. Paths are dotted, with list elements
+/// addressed by index as in `whitelist.0`; the root has the empty path.
+pub trait Visit {
+    fn visit_value(&mut self, path: &str, value: &BsonValue) {
+        walk_value(self, path, value);
+    }
+
+    fn visit_map(&mut self, path: &str, map: &BTreeMap<Cow<'_, str>, BsonValue<'_>>) {
+        walk_map(self, path, map);
+    }
+
+    fn visit_array(&mut self, path: &str, items: &[BsonValue]) {
+        walk_array(self, path, items);
+    }
+
+    fn visit_string(&mut self, _path: &str, _value: &str) {}
+
+    /// A `d` suffixed date, by default visited as a string
+    fn visit_date(&mut self, path: &str, value: &str) {
+        self.visit_string(path, value);
+    }
+
+    /// Plain numbers and `f` suffixed floats
+    fn visit_number(&mut self, _path: &str, _value: f64) {}
+
+    fn visit_integer(&mut self, _path: &str, _value: i128) {}
+
+    /// An exact number, see `ParseOptions::exact_numbers`
+    #[cfg(feature = "decimal")]
+    fn visit_decimal(&mut self, _path: &str, _value: &Decimal) {}
+
+    fn visit_bool(&mut self, _path: &str, _value: bool) {}
+
+    fn visit_null(&mut self, _path: &str) {}
+
+    fn visit_secret(&mut self, _path: &str, _reference: &str) {}
+
+    fn visit_regex(&mut self, _path: &str, _pattern: &str) {}
+
+    fn visit_encrypted(&mut self, _path: &str, _ciphertext: &str) {}
+}
+
+/// Calls the `Visit` method matching the type of `value`.
+pub fn walk_value<V: Visit + ?Sized>(visitor: &mut V, path: &str, value: &BsonValue) {
+    match value {
+        BsonValue::Map(m) => visitor.visit_map(path, m),
+        BsonValue::Array(items) => visitor.visit_array(path, items),
+        BsonValue::BString(s) => visitor.visit_string(path, s),
+        BsonValue::Number(n) | BsonValue::Float(n) => visitor.visit_number(path, *n),
+        BsonValue::Date(d) => visitor.visit_date(path, d),
+        BsonValue::Integer(n) => visitor.visit_integer(path, *n),
+        #[cfg(feature = "decimal")]
+        BsonValue::Decimal(d) => visitor.visit_decimal(path, d),
+        BsonValue::Bool(b) => visitor.visit_bool(path, *b),
+        BsonValue::Null(()) => visitor.visit_null(path),
+        BsonValue::Secret(s) => visitor.visit_secret(path, s),
+        BsonValue::Regex(r) => visitor.visit_regex(path, r),
+        BsonValue::Encrypted(c) => visitor.visit_encrypted(path, c),
+    }
+}
+
+/// Visits every value of a section, in key order.
+pub fn walk_map<V: Visit + ?Sized>(
+    visitor: &mut V,
+    path: &str,
+    map: &BTreeMap<Cow<'_, str>, BsonValue<'_>>,
+) {
+    for (key, child) in map {
+        visitor.visit_value(&join_path(path, key), child);
+    }
+}
+
+/// Visits every element of a Razor Leaf list.
+pub fn walk_array<V: Visit + ?Sized>(visitor: &mut V, path: &str, items: &[BsonValue]) {
+    for (i, item) in items.iter().enumerate() {
+        visitor.visit_value(&join_path(path, &i.to_string()), item);
+    }
+}
+
+/// `Visit` with mutable access. To swap a value for one of another type,
+/// override `visit_value_mut` and assign to it.
+pub trait VisitMut {
+    fn visit_value_mut(&mut self, path: &str, value: &mut BsonValue) {
+        walk_value_mut(self, path, value);
+    }
+
+    fn visit_map_mut(&mut self, path: &str, map: &mut BTreeMap<Cow<'_, str>, BsonValue<'_>>) {
+        walk_map_mut(self, path, map);
+    }
+
+    fn visit_array_mut(&mut self, path: &str, items: &mut Vec<BsonValue>) {
+        walk_array_mut(self, path, items);
+    }
+
+    fn visit_string_mut(&mut self, _path: &str, _value: &mut Cow<'_, str>) {}
+
+    /// A `d` suffixed date, by default visited as a string
+    fn visit_date_mut(&mut self, path: &str, value: &mut Cow<'_, str>) {
+        self.visit_string_mut(path, value);
+    }
+
+    fn visit_number_mut(&mut self, _path: &str, _value: &mut f64) {}
+
+    fn visit_integer_mut(&mut self, _path: &str, _value: &mut i128) {}
+
+    /// An exact number, see `ParseOptions::exact_numbers`
+    #[cfg(feature = "decimal")]
+    fn visit_decimal_mut(&mut self, _path: &str, _value: &mut Decimal) {}
+
+    fn visit_bool_mut(&mut self, _path: &str, _value: &mut bool) {}
+
+    fn visit_null_mut(&mut self, _path: &str) {}
+
+    fn visit_secret_mut(&mut self, _path: &str, _reference: &mut Cow<'_, str>) {}
+
+    fn visit_regex_mut(&mut self, _path: &str, _pattern: &mut Cow<'_, str>) {}
+
+    fn visit_encrypted_mut(&mut self, _path: &str, _ciphertext: &mut Cow<'_, str>) {}
+}
+
+/// Calls the `VisitMut` method matching the type of `value`.
+pub fn walk_value_mut<V: VisitMut + ?Sized>(visitor: &mut V, path: &str, value: &mut BsonValue) {
+    match value {
+        BsonValue::Map(m) => visitor.visit_map_mut(path, m),
+        BsonValue::Array(items) => visitor.visit_array_mut(path, items),
+        BsonValue::BString(s) => visitor.visit_string_mut(path, s),
+        BsonValue::Number(n) | BsonValue::Float(n) => visitor.visit_number_mut(path, n),
+        BsonValue::Date(d) => visitor.visit_date_mut(path, d),
+        BsonValue::Integer(n) => visitor.visit_integer_mut(path, n),
+        #[cfg(feature = "decimal")]
+        BsonValue::Decimal(d) => visitor.visit_decimal_mut(path, d),
+        BsonValue::Bool(b) => visitor.visit_bool_mut(path, b),
+        BsonValue::Null(()) => visitor.visit_null_mut(path),
+        BsonValue::Secret(s) => visitor.visit_secret_mut(path, s),
+        BsonValue::Regex(r) => visitor.visit_regex_mut(path, r),
+        BsonValue::Encrypted(c) => visitor.visit_encrypted_mut(path, c),
+    }
+}
+
+/// Visits every value of a section, in key order.
+pub fn walk_map_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    path: &str,
+    map: &mut BTreeMap<Cow<'_, str>, BsonValue<'_>>,
+) {
+    for (key, child) in map.iter_mut() {
+        visitor.visit_value_mut(&join_path(path, key), child);
+    }
+}
+
+/// Visits every element of a Razor Leaf list.
+pub fn walk_array_mut<V: VisitMut + ?Sized>(
+    visitor: &mut V,
+    path: &str,
+    items: &mut Vec<BsonValue>,
+) {
+    for (i, item) in items.iter_mut().enumerate() {
+        visitor.visit_value_mut(&join_path(path, &i.to_string()), item);
+    }
+}
+
+impl BsonValue<'_> {
+    /// Walks the whole document with `visitor`, starting at the root.
+    pub fn visit(&self, visitor: &mut (impl Visit + ?Sized)) {
+        visitor.visit_value("", self);
+    }
+
+    /// Walks the whole document with `visitor`, which may change it.
+    pub fn visit_mut(&mut self, visitor: &mut (impl VisitMut + ?Sized)) {
+        visitor.visit_value_mut("", self);
+    }
+
+    /// A copy of the document with `f` applied to every value, see
+    /// `transform_mut`.
+    pub fn transform(&self, f: impl FnMut(&str, &mut BsonValue)) -> BsonValue<'static> {
+        let mut copy = self.clone().into_owned();
+        copy.transform_mut(f);
+        copy
+    }
+
+    /// Calls `f` with the path of every value, root included, and lets it
+    /// rewrite the value in place. Sections and lists come after their
+    /// children, so `f` sees them already transformed.
+    pub fn transform_mut(&mut self, f: impl FnMut(&str, &mut BsonValue)) {
+        self.visit_mut(&mut Transform(f));
+    }
+}
+
+struct Transform<F>(F);
+
+impl<F: FnMut(&str, &mut BsonValue)> VisitMut for Transform<F> {
+    fn visit_value_mut(&mut self, path: &str, value: &mut BsonValue) {
+        walk_value_mut(self, path, value);
+        (self.0)(path, value);
+    }
+}
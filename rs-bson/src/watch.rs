@@ -0,0 +1,207 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use notify::event::{AccessKind, AccessMode, EventKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode};
+use serde::de::DeserializeOwned;
+
+use crate::describe::Describe;
+use crate::diff::{self, ChangedPaths};
+use crate::document::Document;
+use crate::load::{self, FileError, LoadError};
+use crate::parser::BsonValue;
+use crate::schema;
+use crate::secret::Secrets;
+
+type Callback<T> = Box<dyn Fn(&T) + Send>;
+type Subscriber<T> = (String, Box<dyn Fn(&T, &ChangedPaths) + Send>);
+
+/// Keeps a typed config in sync with its file. The file system tells a
+/// background thread about every change and, once the file has stopped
+/// changing for the debounce interval, it loads the file again. Only a
+/// version that parses, validates and deserializes replaces the current
+/// one, so a half-written save never reaches the program; it goes to the
+/// `on_error` callback instead.
+pub struct Watcher<T> {
+    current: Arc<ArcSwap<T>>,
+    // Dropping it ends the events, and with them the thread
+    events: Option<RecommendedWatcher>,
+    handle: Option<JoinHandle<()>>,
+}
+
+pub struct WatcherBuilder<T> {
+    path: PathBuf,
+    debounce: Duration,
+    on_change: Option<Callback<T>>,
+    on_error: Option<Callback<LoadError>>,
+    subscribers: Vec<Subscriber<T>>,
+}
+
+// `load::from_file`, also handing back the document before conversion to
+// diff the next version against. Secret references are compared as
+// written.
+fn load<T: Describe + DeserializeOwned>(path: &Path) -> Result<(T, BsonValue<'static>), LoadError> {
+    let doc = Document::parse(&load::read(path)?)?;
+    let violations = schema::validate(&doc, &T::schema());
+    let value = doc.value()?;
+    Ok((
+        load::convert(value.clone(), violations, &Secrets::new())?,
+        value,
+    ))
+}
+
+// Whether `event` may have changed the file called `name`. Reading it, as
+// every reload does, does not.
+fn touches(event: &Event, name: &OsString) -> bool {
+    let writes = match event.kind {
+        EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+        EventKind::Access(_) => false,
+        _ => true,
+    };
+    writes
+        && event
+            .paths
+            .iter()
+            .any(|path| path.file_name() == Some(name.as_os_str()))
+}
+
+impl<T: Describe + DeserializeOwned + Send + Sync + 'static> Watcher<T> {
+    pub fn builder(path: impl Into<PathBuf>) -> WatcherBuilder<T> {
+        WatcherBuilder {
+            path: path.into(),
+            debounce: Duration::from_millis(50),
+            on_change: None,
+            on_error: None,
+            subscribers: vec![],
+        }
+    }
+
+    /// The latest good version of the config.
+    pub fn get(&self) -> Arc<T> {
+        self.current.load_full()
+    }
+
+    /// Where every new version is stored, for code that reads the config
+    /// on each request: `load` on it takes no lock.
+    pub fn shared(&self) -> Arc<ArcSwap<T>> {
+        self.current.clone()
+    }
+}
+
+impl<T: Describe + DeserializeOwned + Send + Sync + 'static> WatcherBuilder<T> {
+    /// How long the file has to stay untouched before it is reloaded, so an
+    /// editor writing in several steps triggers one reload.
+    pub fn debounce(mut self, debounce: Duration) -> WatcherBuilder<T> {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Called with every new version after it has replaced the old one.
+    pub fn on_change(mut self, callback: impl Fn(&T) + Send + 'static) -> WatcherBuilder<T> {
+        self.on_change = Some(Box::new(callback));
+        self
+    }
+
+    /// Called with every new version that changed anything at the JSON
+    /// Pointer `prefix` or below, along with every path that changed, so an
+    /// application can e.g. rebuild its database pool only when `/database`
+    /// did. See `ChangedPaths::touches`; the empty prefix subscribes to any
+    /// change.
+    pub fn subscribe(
+        mut self,
+        prefix: &str,
+        callback: impl Fn(&T, &ChangedPaths) + Send + 'static,
+    ) -> WatcherBuilder<T> {
+        self.subscribers
+            .push((prefix.to_string(), Box::new(callback)));
+        self
+    }
+
+    /// Called for every reload that failed; the previous version stays.
+    pub fn on_error(mut self, callback: impl Fn(&LoadError) + Send + 'static) -> WatcherBuilder<T> {
+        self.on_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Loads the file once and starts watching it. The first load has to
+    /// succeed, there is nothing to fall back on yet.
+    pub fn start(self) -> Result<Watcher<T>, LoadError> {
+        let (first, mut document) = load::<T>(&self.path)?;
+        let current = Arc::new(ArcSwap::from_pointee(first));
+
+        // Editors often save by renaming a new file over the old one, which
+        // a watch on the file itself would not outlive
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = self.path.file_name().unwrap_or_default().to_os_string();
+        let fail = |err: notify::Error| LoadError::Io(FileError::new(&self.path, err));
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(fail)?;
+        notify::Watcher::watch(&mut watcher, &dir, RecursiveMode::NonRecursive).map_err(fail)?;
+
+        let shared = current.clone();
+        let handle = thread::spawn(move || {
+            let report = |err: LoadError| {
+                if let Some(callback) = &self.on_error {
+                    callback(&err);
+                }
+            };
+            while let Ok(event) = events.recv() {
+                match event {
+                    Ok(event) if touches(&event, &name) => {}
+                    Ok(_) => continue,
+                    Err(err) => {
+                        report(LoadError::Io(FileError::new(&self.path, err)));
+                        continue;
+                    }
+                }
+                // Let the burst of events of one save settle first
+                loop {
+                    match events.recv_timeout(self.debounce) {
+                        Ok(_) => continue,
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                match load::<T>(&self.path) {
+                    Ok((value, new_document)) => {
+                        let value = Arc::new(value);
+                        shared.store(value.clone());
+                        if let Some(callback) = &self.on_change {
+                            callback(&value);
+                        }
+                        let changed = diff::changed_paths(&document, &new_document);
+                        document = new_document;
+                        for (prefix, callback) in &self.subscribers {
+                            if changed.touches(prefix) {
+                                callback(&value, &changed);
+                            }
+                        }
+                    }
+                    Err(err) => report(err),
+                }
+            }
+        });
+        Ok(Watcher {
+            current,
+            events: Some(watcher),
+            handle: Some(handle),
+        })
+    }
+}
+
+impl<T> Drop for Watcher<T> {
+    fn drop(&mut self) {
+        self.events.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
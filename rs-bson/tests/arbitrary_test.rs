@@ -0,0 +1,50 @@
+#![cfg(feature = "arbitrary")]
+
+use arbitrary::{Arbitrary, Unstructured};
+
+use rs_bson::emit;
+use rs_bson::format;
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+
+#[cfg(test)]
+pub mod arbitrary_tests {
+    use crate::*;
+
+    // Bytes from a small xorshift generator, so every run checks the same
+    // values
+    fn bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn parse(input: &str) -> BsonValue<'static> {
+        let tokens = lexer::lex_str(input).unwrap();
+        parser::parse(&tokens).unwrap().into_owned()
+    }
+
+    #[test]
+    fn arbitrary_values_roundtrip() {
+        for seed in 1..300 {
+            let data = bytes(seed, 4096);
+            let value = BsonValue::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            let text = emit(&value).unwrap();
+            assert_eq!(parse(&text), value, "{text}");
+            assert_eq!(emit(&parse(&text)), Ok(text.clone()));
+            assert_eq!(format::format(&text), Ok(text));
+        }
+    }
+
+    #[test]
+    fn running_out_of_bytes_still_gives_a_document() {
+        let value = BsonValue::arbitrary(&mut Unstructured::new(&[])).unwrap();
+        assert_eq!(emit(&value), Ok(String::from("BULBA!\n")));
+    }
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "checksum")]
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use rs_bson::checksum;
+use rs_bson::document::{ConflictPolicy, Document};
+use rs_bson::emit;
+use rs_bson::format;
+use rs_bson::lexer::{self, CORRUPTED};
+use rs_bson::parser::{self, BsonValue, ParseOptions};
+
+#[cfg(test)]
+pub mod checksum_tests {
+    use crate::*;
+
+    #[test]
+    fn digest_reads_lines() {
+        assert_eq!(
+            checksum::digest(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        let digest = checksum::digest("abc\nd\n");
+        assert_eq!(
+            digest,
+            "f1282259787bfe9fdf238df6902c274868c76f7682758c9ec0fbe5fe47c82677"
+        );
+        assert_eq!(checksum::digest("abc\r\nd"), digest);
+    }
+
+    #[test]
+    fn sealed_documents_verify() {
+        let value = BsonValue::Map(BTreeMap::from([(
+            Cow::Borrowed("trainer"),
+            BsonValue::BString("Ash".into()),
+        )]));
+        let sealed = emit::emit_sealed(&value).unwrap();
+        assert_eq!(
+            sealed,
+            format!(
+                "BULBA!\ntrainer ~> \"Ash\"\nzZz bulba-sha256: {}\n",
+                checksum::digest("BULBA!\ntrainer ~> \"Ash\"\n")
+            )
+        );
+        let parse = |input: &str, options: &ParseOptions| {
+            lexer::lex_str_with(input, options)
+                .and_then(|tokens| parser::parse(&tokens).map(|v| v.into_owned()))
+        };
+        let strict = ParseOptions::new().require_checksum();
+        assert_eq!(parse(&sealed, &strict), Ok(value.clone()));
+        assert_eq!(
+            parse(&sealed.replace('\n', "\r\n"), &strict),
+            Ok(value.clone())
+        );
+        assert_eq!(
+            parse(&(sealed.clone() + "\n\n"), &strict),
+            Ok(value.clone())
+        );
+
+        let tampered = sealed.replace("Ash", "Gary");
+        assert_eq!(
+            parse(&tampered, &ParseOptions::new())
+                .unwrap_err()
+                .message(),
+            CORRUPTED
+        );
+        let appended = sealed.clone() + "badges ~> 8\n";
+        assert_eq!(
+            parse(&appended, &ParseOptions::new())
+                .unwrap_err()
+                .message(),
+            CORRUPTED
+        );
+        let truncated = "BULBA!\ntrainer ~> \"Ash\"\n";
+        assert_eq!(parse(truncated, &ParseOptions::new()), Ok(value.clone()));
+        assert_eq!(parse(truncated, &strict).unwrap_err().message(), CORRUPTED);
+
+        let stream = lexer::Tokens::new(tampered.as_bytes(), &ParseOptions::new());
+        assert_eq!(
+            parser::parse_stream(stream, &ParseOptions::new())
+                .unwrap_err()
+                .message(),
+            CORRUPTED
+        );
+    }
+
+    #[test]
+    fn format_reseals() {
+        let sealed = checksum::seal("BULBA!\ntrainer~>\"Ash\"\n");
+        assert!(lexer::is_sealed(&sealed));
+        let formatted = format::format(&sealed).unwrap();
+        assert_eq!(formatted, checksum::seal("BULBA!\ntrainer ~> \"Ash\"\n"));
+        assert_eq!(checksum::seal(&formatted), formatted);
+        assert!(lexer::lex_str(&formatted).is_ok());
+    }
+
+    #[test]
+    fn document_edits_reseal() {
+        let sealed = checksum::seal("BULBA!\nzebra ~> 1\nalpha ~> 2 zZz first\n");
+        let mut doc = Document::parse(&sealed).unwrap();
+        assert_eq!(doc.to_string(), sealed);
+
+        doc.sort_keys();
+        doc.rename_path("zebra", "zubat").unwrap();
+        let extra = Document::parse(&checksum::seal("BULBA!\nbeta ~> 3\n")).unwrap();
+        doc.extend_from(&extra, ConflictPolicy::Error).unwrap();
+        let expected = "BULBA!\nalpha ~> 2 zZz first\nzubat ~> 1\nbeta ~> 3\n";
+        assert_eq!(doc.to_string(), checksum::seal(expected));
+        assert_eq!(
+            doc.value().unwrap().get("beta"),
+            Some(&BsonValue::Number(3.0))
+        );
+
+        // Documents that were not sealed stay that way
+        let mut doc = Document::parse("BULBA!\nb ~> 1\na ~> 2\n").unwrap();
+        doc.sort_keys();
+        assert_eq!(doc.to_string(), "BULBA!\na ~> 2\nb ~> 1\n");
+    }
+}
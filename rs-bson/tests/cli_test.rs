@@ -0,0 +1,259 @@
+#![cfg(feature = "cli")]
+
+use std::process::Command;
+
+#[cfg(test)]
+pub mod cli_tests {
+    use crate::*;
+
+    fn rs_bson(args: &[&str]) -> (i32, String) {
+        let output = Command::new(env!("CARGO_BIN_EXE_rs-bson"))
+            .args(args)
+            .output()
+            .unwrap();
+        (
+            output.status.code().unwrap(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    }
+
+    #[test]
+    fn lint_warns_on_deprecated_keys() {
+        let (code, stdout) = rs_bson(&[
+            "lint",
+            "tests/test_data/main_input.bson",
+            "--schema",
+            "tests/test_data/deprecated.bulbaschema",
+        ]);
+        assert_eq!(code, 0);
+        assert_eq!(
+            stdout,
+            "tests/test_data/main_input.bson:5: warning: version: Key is deprecated
+tests/test_data/main_input.bson:8: warning: zapdos: Key is deprecated, use legendary_birds instead
+"
+        );
+    }
+
+    #[test]
+    fn lint_fails_on_errors() {
+        let (code, stdout) = rs_bson(&["lint", "tests/test_data/invalid_charizard.bson"]);
+        assert_eq!(code, 1);
+        assert_eq!(
+            stdout,
+            "tests/test_data/invalid_charizard.bson:2: error: It burns the bulb\n"
+        );
+    }
+
+    #[test]
+    fn to_env_flattens_keys() {
+        let (code, stdout) = rs_bson(&[
+            "to-env",
+            "tests/test_data/main_input.bson",
+            "--prefix",
+            "APP_",
+            "--format",
+            "dotenv",
+        ]);
+        assert_eq!(code, 0);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[4], "APP_DATABASE_POOL_MAX_CONNECTIONS=100");
+        assert_eq!(lines[6], "APP_IS_PRODUCTION=NotVeryEffective");
+        assert_eq!(
+            lines[9],
+            r#"APP_WHITELIST=<| "Prof_Oak", "Mom", "Nurse_Joy" |>"#
+        );
+        assert_eq!(lines[10], "APP_ZAPDOS=Legendary bird");
+    }
+
+    #[test]
+    fn from_env_nests_variables() {
+        let output = Command::new(env!("CARGO_BIN_EXE_rs-bson"))
+            .args(["from-env", "--prefix", "BULBA_CLI_"])
+            .env("BULBA_CLI_DATABASE__HOST", "127.0.0.1")
+            .env("BULBA_CLI_DATABASE__PORT", "5432")
+            .env("BULBA_CLI_DEBUG", "SuperEffective")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "BULBA!
+(o) database (o)
+    host ~> \"127.0.0.1\"
+    port ~> 5432
+debug ~> SuperEffective
+"
+        );
+        assert_eq!(rs_bson(&["from-env"]).0, 2);
+    }
+
+    #[test]
+    fn keys_lists_paths() {
+        let (code, stdout) = rs_bson(&["keys", "tests/test_data/main_input.bson"]);
+        assert_eq!(code, 0);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(
+            lines[..4],
+            ["app_name", "database", "database.host", "database.pool"]
+        );
+        assert_eq!(lines.len(), 14);
+
+        let (_, stdout) = rs_bson(&["keys", "tests/test_data/main_input.bson", "--types"]);
+        assert!(stdout.contains("\ndatabase.pool.max_connections: number\n"));
+        assert!(stdout.contains("\nwhitelist: array\n"));
+    }
+
+    #[test]
+    fn grep_finds_values_and_keys() {
+        let (code, stdout) = rs_bson(&["grep", "^(Mom|127)", "tests/test_data/main_input.bson"]);
+        assert_eq!(code, 0);
+        assert_eq!(
+            stdout,
+            "tests/test_data/main_input.bson:database.host: \"127.0.0.1\"
+tests/test_data/main_input.bson:whitelist.1: \"Mom\"
+"
+        );
+
+        let (code, stdout) = rs_bson(&[
+            "grep",
+            "^pool$",
+            "tests/test_data/main_input.bson",
+            "--keys",
+        ]);
+        assert_eq!(code, 0);
+        assert_eq!(
+            stdout,
+            "tests/test_data/main_input.bson:database.pool: (section)\n"
+        );
+        assert_eq!(
+            rs_bson(&["grep", "Mewtwo", "tests/test_data/main_input.bson"]).0,
+            1
+        );
+    }
+
+    #[test]
+    fn tree_draws_sections() {
+        let (code, stdout) = rs_bson(&["tree", "tests/test_data/main_input.bson"]);
+        assert_eq!(code, 0);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines[0], "tests/test_data/main_input.bson");
+        assert_eq!(lines[4], "│   └── pool [map]");
+        assert_eq!(
+            lines[6],
+            "│       │   ├── panic_on_fail [bool] SuperEffective"
+        );
+        assert_eq!(lines[14], "└── zapdos [string] \"Legendary bird\"");
+
+        let (_, stdout) = rs_bson(&["tree", "tests/test_data/main_input.bson", "--ascii"]);
+        assert!(
+            stdout
+                .contains("\n|-- whitelist [array 3] <| \"Prof_Oak\", \"Mom\", \"Nurse_Joy\" |>\n")
+        );
+    }
+
+    #[test]
+    fn tokens_dump_stream() {
+        let (code, stdout) = rs_bson(&["tokens", "tests/test_data/invalid_type.bson"]);
+        assert_eq!(code, 1);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines[1], "1     0     0..6     Header       \"BULBA!\"");
+        assert_eq!(
+            lines.last(),
+            Some(&"2     0     4..6     VineWhip     \"\"")
+        );
+
+        let (code, stdout) = rs_bson(&["tokens", "tests/test_data/valid.bson", "--json"]);
+        assert_eq!(code, 0);
+        assert!(stdout.starts_with(
+            r#"[{"level":0,"line":1,"literal":"BULBA!","span":[0,6],"type":"Header"},"#
+        ));
+        assert!(stdout.trim_end().ends_with(r#""type":"Eof"}]"#));
+    }
+
+    #[test]
+    fn minify_strips_layout() {
+        let (code, stdout) = rs_bson(&["minify", "tests/test_data/main_input.bson"]);
+        assert_eq!(code, 0);
+        assert!(!stdout.contains("zZz") && !stdout.contains("\n\n"));
+        assert!(stdout.contains("\nwhitelist~><|\"Prof_Oak\",\"Mom\",\"Nurse_Joy\"|>\n"));
+        let original = std::fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        assert!(stdout.len() < original.len());
+    }
+
+    #[test]
+    fn to_env_prints_exports() {
+        let (code, stdout) = rs_bson(&["to-env", "tests/test_data/main_input.bson"]);
+        assert_eq!(code, 0);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines[4], "export DATABASE_POOL_MAX_CONNECTIONS='100'");
+        assert_eq!(
+            lines[9],
+            r#"export WHITELIST='<| "Prof_Oak", "Mom", "Nurse_Joy" |>'"#
+        );
+        assert_eq!(lines[10], "export ZAPDOS='Legendary bird'");
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_files() {
+        let rot13 = "tr 'A-Za-z' 'N-ZA-Mn-za-m'";
+        let run = |args: &[&str]| {
+            let output = Command::new(env!("CARGO_BIN_EXE_rs-bson"))
+                .args(args)
+                .env("BULBA_ENCRYPT_COMMAND", rot13)
+                .env("BULBA_DECRYPT_COMMAND", rot13)
+                .output()
+                .unwrap();
+            (
+                output.status.code().unwrap(),
+                String::from_utf8(output.stdout).unwrap(),
+            )
+        };
+        let (code, stdout) = run(&["encrypt", "tests/test_data/lint.bson"]);
+        assert_eq!(code, 0);
+        assert!(stdout.contains("\npassword ~~~~> ENC[Y3Z4bnB1aDEyMw==]\n"));
+        assert!(stdout.contains("\ntrainer ~~~~~> \"Ash\"\n"));
+        assert!(stdout.contains("ENC[bm9w] zZz bulba-allow(no_plaintext_secrets)"));
+
+        let file = std::env::temp_dir().join(format!("rs-bson-crypt-{}.bson", std::process::id()));
+        std::fs::write(&file, &stdout).unwrap();
+        let (code, decrypted) = run(&["decrypt", file.to_str().unwrap()]);
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(code, 0);
+        let original = std::fs::read_to_string("tests/test_data/lint.bson").unwrap();
+        assert_eq!(decrypted, original);
+
+        let (code, stdout) = run(&["encrypt", "tests/test_data/lint.bson", "--path", "trainer"]);
+        assert_eq!(code, 0);
+        assert!(stdout.contains("\ntrainer ~~~~~> ENC[TmZ1]\n"));
+        assert!(stdout.contains("\npassword ~~~~> \"pikachu123\"\n"));
+    }
+
+    #[test]
+    fn verify_checks_signatures() {
+        use rs_bson::signature::HmacVerifier;
+
+        let dir = std::env::temp_dir().join(format!("rs-bson-cli-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (doc, key) = (dir.join("app.bson"), dir.join("key"));
+        let input = std::fs::read("tests/test_data/main_input.bson").unwrap();
+        std::fs::write(&doc, &input).unwrap();
+        std::fs::write(&key, "pallet-town\n").unwrap();
+        let signature = HmacVerifier::new(b"pallet-town").sign(&input);
+        std::fs::write(dir.join("app.bson.sig"), signature).unwrap();
+
+        let doc = doc.to_str().unwrap();
+        let key = key.to_str().unwrap();
+        let (code, stdout) = rs_bson(&["verify", doc, "--hmac-key", key]);
+        assert_eq!((code, stdout), (0, format!("{doc}: OK\n")));
+
+        std::fs::write(doc, [&input[..], b"zZz tampered\n"].concat()).unwrap();
+        let (code, stdout) = rs_bson(&["verify", doc, "--hmac-key", key]);
+        assert_eq!(code, 1);
+        assert_eq!(
+            stdout,
+            format!("{doc}: Bad signature: Signature does not match\n")
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "schema")]
+
+use std::fs;
+
+use rs_bson::completion::{self, Completion};
+use rs_bson::parser::BsonValue;
+use rs_bson::schema::{Schema, Type};
+
+#[cfg(test)]
+pub mod completion_tests {
+    use crate::*;
+
+    #[test]
+    fn completions_from_schema_file() {
+        let input = fs::read_to_string("tests/test_data/main_input.bulbaschema").unwrap();
+        let schema = Schema::parse(&input).unwrap();
+        let completions = completion::completions(&schema);
+
+        let paths: Vec<&str> = completions.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths.len(), 14);
+        assert_eq!(
+            paths[..4],
+            ["app_name", "database", "database.host", "database.pool"]
+        );
+        assert_eq!(completions[0].doc, None);
+        assert_eq!(
+            completions[6],
+            Completion {
+                path: "database.pool.KERNEL_FLAGS.retry_strategy".to_string(),
+                ty: "string".to_string(),
+                required: true,
+                doc: Some("What to do after a failed connection".to_string()),
+                allowed: vec![
+                    BsonValue::BString("SolarBeam".into()),
+                    BsonValue::BString("Tackle".into()),
+                ],
+                default: None,
+                deprecated: None,
+            }
+        );
+        assert_eq!(completions[13].ty, "string");
+        assert!(!completions[13].required);
+    }
+
+    #[test]
+    fn manifest_as_json() {
+        let schema = Schema::map().required("servers").key(
+            "servers",
+            Schema::array(
+                Schema::map()
+                    .key("host", Schema::from(Type::String).doc("Where to connect"))
+                    .key(
+                        "port",
+                        Type::Int.range(1..=65535).default(BsonValue::Number(80.0)),
+                    ),
+            ),
+        );
+        assert_eq!(
+            completion::manifest(&schema).to_string(),
+            concat!(
+                r#"{"keys":[{"path":"servers","required":true,"type":"array<map>"},"#,
+                r#"{"doc":"Where to connect","path":"servers.*.host","required":false,"type":"string"},"#,
+                r#"{"default":80,"path":"servers.*.port","required":false,"type":"int"}]}"#
+            )
+        );
+
+        let json =
+            r#"{"properties": {"port": {"type": "integer", "description": "Port to listen on"}}}"#;
+        let schema = Schema::from_json_schema(json).unwrap();
+        assert_eq!(
+            completion::completions(&schema)[0].doc.as_deref(),
+            Some("Port to listen on")
+        );
+    }
+}
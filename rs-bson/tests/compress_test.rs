@@ -0,0 +1,117 @@
+#![cfg(all(feature = "gzip", feature = "zstd", feature = "schema"))]
+
+use std::fs;
+use std::path::Path;
+
+use rs_bson::compress::{self, Compression};
+use rs_bson::layers::Layers;
+use rs_bson::load::{self, FileError, LoadError};
+use rs_bson::parser::{ParseError, ParseOptions};
+
+#[cfg(test)]
+pub mod compress_tests {
+    use crate::*;
+
+    #[test]
+    fn read_compressed_files() {
+        let plain = fs::read_to_string("tests/test_data/valid.bson").unwrap();
+        for path in [
+            "tests/test_data/valid.bson.gz",
+            "tests/test_data/valid.bson.zst",
+        ] {
+            assert_eq!(load::read(Path::new(path)).unwrap(), plain, "{path}");
+        }
+
+        let config = Layers::new()
+            .file("tests/test_data/valid.bson.zst")
+            .load()
+            .unwrap();
+        assert!(config.get("database.pool.max_connections").is_some());
+    }
+
+    #[test]
+    fn detect_by_content() {
+        let gzip = fs::read("tests/test_data/valid.bson.gz").unwrap();
+        let zstd = fs::read("tests/test_data/valid.bson.zst").unwrap();
+        assert_eq!(compress::detect(&gzip), Compression::Gzip);
+        assert_eq!(compress::detect(&zstd), Compression::Zstd);
+        assert_eq!(compress::detect(b"BULBA!\n"), Compression::None);
+
+        // A damaged archive is an error, never garbage text
+        let mut damaged = zstd.clone();
+        damaged.truncate(zstd.len() / 2);
+        assert_eq!(
+            compress::decompress(&damaged),
+            Err("It hurt itself in its confusion!")
+        );
+        let mut damaged = gzip.clone();
+        let last = damaged.len() - 9;
+        damaged[last] ^= 1;
+        assert!(compress::decompress(&damaged).is_err());
+    }
+
+    #[test]
+    fn refuse_oversized_output() {
+        // One RLE block may not claim more than 128 KiB
+        let frame = |blocks: &[(u32, u32)]| {
+            let mut frame = vec![0x28, 0xb5, 0x2f, 0xfd, 0x20, 0];
+            for (i, (kind, size)) in blocks.iter().enumerate() {
+                let last = u32::from(i + 1 == blocks.len());
+                frame.extend_from_slice(&(last | kind << 1 | size << 3).to_le_bytes()[..3]);
+                frame.extend(std::iter::repeat_n(
+                    b'a',
+                    if *kind == 1 { 1 } else { *size as usize },
+                ));
+            }
+            frame
+        };
+        let bomb = frame(&[(1, 2_000_000)]);
+        assert_eq!(
+            compress::decompress(&bomb),
+            Err("It hurt itself in its confusion!")
+        );
+        assert_eq!(
+            compress::decompress(&frame(&[(0, (128 << 10) + 1)])),
+            Err("It hurt itself in its confusion!")
+        );
+        let full = frame(&[(1, 128 << 10), (1, 128 << 10)]);
+        assert_eq!(compress::decompress(&full).unwrap().len(), 256 << 10);
+
+        // Each format stops at the limit of the options
+        let plain = fs::read("tests/test_data/valid.bson").unwrap();
+        for path in [
+            "tests/test_data/valid.bson.gz",
+            "tests/test_data/valid.bson.zst",
+        ] {
+            let data = fs::read(path).unwrap();
+            let options = ParseOptions::new().max_decompressed(plain.len());
+            assert_eq!(compress::decompress_with(&data, &options).unwrap(), plain);
+            let options = ParseOptions::new().max_decompressed(plain.len() - 1);
+            assert_eq!(
+                compress::decompress_with(&data, &options),
+                Err(compress::TOO_LARGE),
+                "{path}"
+            );
+        }
+        let options = ParseOptions::new().max_decompressed(200 << 10);
+        assert_eq!(
+            compress::decompress_with(&full, &options),
+            Err(compress::TOO_LARGE)
+        );
+    }
+
+    #[test]
+    fn report_the_path() {
+        let dir = std::env::temp_dir().join("bulba_compress_test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("broken.bson.gz");
+        fs::write(&path, [0x1f, 0x8b, 8, 0]).unwrap();
+        assert_eq!(
+            load::read(&path),
+            Err(LoadError::Io(FileError::new(
+                &path,
+                ParseError::Document("It hurt itself in its confusion!")
+            )))
+        );
+    }
+}
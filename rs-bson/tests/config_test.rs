@@ -0,0 +1,70 @@
+#![cfg(feature = "config")]
+
+use config::{Config, File};
+use serde::Deserialize;
+
+use rs_bson::config::Bson;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Database {
+    host: String,
+    port: u16,
+    pool: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Settings {
+    app_name: String,
+    ratio: f64,
+    database: Database,
+    whitelist: Vec<String>,
+}
+
+#[cfg(test)]
+pub mod config_tests {
+    use crate::*;
+
+    #[test]
+    fn load_a_bulba_file() {
+        let settings = Config::builder()
+            .set_default("database.pool", 8)
+            .unwrap()
+            .add_source(File::new("tests/test_data/app.bulba", Bson))
+            .set_override("ratio", 0.5)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(settings.get_string("app_name").unwrap(), "Pokedex_API");
+        let settings: Settings = settings.try_deserialize().unwrap();
+        assert_eq!(settings.ratio, 0.5);
+        assert_eq!(
+            settings.database,
+            Database {
+                host: String::from("db.internal"),
+                port: 6543,
+                pool: 8,
+            }
+        );
+        assert_eq!(settings.whitelist, vec!["Prof_Oak", "Mom"]);
+    }
+
+    #[test]
+    fn find_the_file_by_extension() {
+        let settings = Config::builder()
+            .add_source(File::new("tests/test_data/app", Bson))
+            .build()
+            .unwrap();
+        assert_eq!(settings.get_float("ratio").unwrap(), 0.75);
+        assert_eq!(settings.get_int("database.port").unwrap(), 6543);
+
+        let invalid = Config::builder()
+            .add_source(File::from_str("BULBA!\nCharizard ~> 6\n", Bson))
+            .build();
+        assert!(
+            invalid
+                .unwrap_err()
+                .to_string()
+                .contains("It burns the bulb")
+        );
+    }
+}
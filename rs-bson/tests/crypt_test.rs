@@ -0,0 +1,139 @@
+#![cfg(all(feature = "crypt", feature = "schema"))]
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use rs_bson::crypt::CommandKeys;
+use rs_bson::crypt::{self, CryptError, KeyProvider};
+use rs_bson::layers::Layers;
+use rs_bson::lexer;
+use rs_bson::lint;
+use rs_bson::parser::{self, BsonValue};
+
+#[cfg(test)]
+pub mod crypt_tests {
+    use crate::*;
+
+    // Reverses the text, which is all a test needs from a cipher
+    struct Mirror;
+
+    impl KeyProvider for Mirror {
+        fn encrypt(&self, plaintext: &str) -> Result<String, String> {
+            Ok(plaintext.chars().rev().collect())
+        }
+
+        fn decrypt(&self, ciphertext: &str) -> Result<String, String> {
+            match ciphertext {
+                "" => Err(String::from("Wrong key")),
+                _ => Ok(ciphertext.chars().rev().collect()),
+            }
+        }
+    }
+
+    const INPUT: &str = "BULBA!
+trainer ~~~~~> \"Ash\"
+zZz rotate yearly
+password ~~~~> \"pikachu123\"
+(o) database (o)
+    api_token ~~~> \"abc\" zZz from the vault
+    port ~~~~> 5432
+";
+
+    #[test]
+    fn encrypt_and_decrypt_values() {
+        let tokens = lexer::lex_str("BULBA!\npassword ~~~> ENC[321uhcakip]\n").unwrap();
+        let mut value = parser::parse(&tokens).unwrap();
+        assert_eq!(
+            value.get("password"),
+            Some(&BsonValue::Encrypted("321uhcakip".into()))
+        );
+        value.decrypt(&Mirror).unwrap();
+        assert_eq!(
+            value.get("password"),
+            Some(&BsonValue::BString("pikachu123".into()))
+        );
+
+        let tokens = lexer::lex_str(INPUT).unwrap();
+        let mut value = parser::parse(&tokens).unwrap().into_owned();
+        assert_eq!(value.encrypt(&Mirror, lint::sensitive_key), Ok(2));
+        assert_eq!(
+            value.get("database.api_token"),
+            Some(&BsonValue::Encrypted("cba".into()))
+        );
+        assert_eq!(
+            value.get("trainer"),
+            Some(&BsonValue::BString("Ash".into()))
+        );
+
+        let mut broken = BsonValue::Map(BTreeMap::from([(
+            Cow::Borrowed("key"),
+            BsonValue::Encrypted("".into()),
+        )]));
+        assert_eq!(
+            broken.decrypt(&Mirror),
+            Err(CryptError {
+                path: String::from("key"),
+                message: String::from("Wrong key"),
+            })
+        );
+    }
+
+    #[test]
+    fn rewrite_text_in_place() {
+        let encrypted = crypt::encrypt_text(INPUT, &Mirror, lint::sensitive_key).unwrap();
+        assert_eq!(
+            encrypted,
+            "BULBA!
+trainer ~~~~~> \"Ash\"
+zZz rotate yearly
+password ~~~~> ENC[321uhcakip]
+(o) database (o)
+    api_token ~~~> ENC[cba] zZz from the vault
+    port ~~~~> 5432
+"
+        );
+        assert_eq!(
+            crypt::decrypt_text(&encrypted, &Mirror),
+            Ok(INPUT.to_string())
+        );
+
+        let only_port = crypt::encrypt_text(INPUT, &Mirror, |path| path == "database.port");
+        assert_eq!(only_port, Ok(INPUT.to_string()));
+    }
+
+    #[test]
+    fn command_keys_and_layers() {
+        let rot13 = "tr 'A-Za-z' 'N-ZA-Mn-za-m'";
+        let keys = CommandKeys::new(rot13, rot13);
+        let ciphertext = keys.encrypt("pikachu").unwrap();
+        assert_eq!(ciphertext, "Y3Z4bnB1aA==");
+        assert_eq!(keys.decrypt(&ciphertext), Ok(String::from("pikachu")));
+        assert!(keys.decrypt("not base64!").is_err());
+
+        let tokens = lexer::lex_str(&format!("BULBA!\npassword ~~~> ENC[{ciphertext}]\n")).unwrap();
+        let layers = Layers::new().value(parser::parse(&tokens).unwrap().into_owned());
+        let config = layers.load_decrypted(&keys).unwrap();
+        assert_eq!(
+            config.get("password"),
+            Some(&BsonValue::BString("pikachu".into()))
+        );
+    }
+
+    #[test]
+    fn command_keys_read_while_they_write() {
+        // Prints more than a pipe holds before reading its input
+        let chatty = "head -c 300000 /dev/zero; cat > /dev/null";
+        let keys = CommandKeys::new(chatty, chatty);
+        let ciphertext = keys.encrypt(&"a".repeat(300_000)).unwrap();
+        assert_eq!(ciphertext.len(), 400_000);
+        assert!(ciphertext.starts_with("AAAA"));
+
+        let failing = CommandKeys::new("cat > /dev/null; exit 3", "cat");
+        assert!(
+            failing
+                .encrypt("pikachu")
+                .unwrap_err()
+                .contains("exit status: 3")
+        );
+    }
+}
@@ -0,0 +1,203 @@
+#![cfg(feature = "serde")]
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use serde::Deserialize;
+
+use rs_bson::de::{self, ConvertError};
+use rs_bson::lexer;
+#[cfg(feature = "decimal")]
+use rs_bson::parser::ParseOptions;
+use rs_bson::parser::{self, BsonValue};
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Pool {
+    max_connections: u16,
+    timeout_ms: u64,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Database<'a> {
+    host: &'a str,
+    pool: Pool,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Level {
+    Debug,
+    Info,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Config<'a> {
+    app_name: String,
+    version: f64,
+    is_production: bool,
+    whitelist: Vec<String>,
+    #[serde(borrow)]
+    database: Database<'a>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Retry {
+    #[serde(deserialize_with = "de::nullable")]
+    strategy: Option<String>,
+    #[serde(default, deserialize_with = "de::nullable")]
+    backoff_ms: Option<u32>,
+}
+
+#[cfg(test)]
+pub mod de_tests {
+    use crate::*;
+
+    #[test]
+    fn read_a_document_into_a_struct() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let tokens = lexer::lex_str(&input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        let config: Config = de::from_value(&value).unwrap();
+        assert_eq!(config.app_name, "Pokedex_API");
+        assert_eq!(config.version, 1.5);
+        assert!(!config.is_production);
+        assert_eq!(config.whitelist, vec!["Prof_Oak", "Mom", "Nurse_Joy"]);
+        assert_eq!(config.database.host, "127.0.0.1");
+        assert_eq!(
+            config.database.pool,
+            Pool {
+                max_connections: 100,
+                timeout_ms: 5000
+            }
+        );
+    }
+
+    #[test]
+    fn numbers_fit_the_field() {
+        let input = "BULBA!\nport ~> 70000\nratio ~> 2\nid ~> 9007199254740993\nprice ~> 0.1\n";
+        let tokens = lexer::lex_str(input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        assert_eq!(de::from_value::<f32>(value.get("ratio").unwrap()), Ok(2.0));
+        assert_eq!(
+            de::from_value::<u64>(value.get("id").unwrap()),
+            Ok(9007199254740993)
+        );
+        assert_eq!(
+            de::from_value::<u16>(value.get("port").unwrap()),
+            Err(ConvertError::new(
+                "",
+                "invalid value: integer `70000`, expected u16"
+            ))
+        );
+        assert!(de::from_value::<u8>(value.get("price").unwrap()).is_err());
+        assert_eq!(
+            de::from_value::<String>(value.get("id").unwrap()),
+            Ok(String::from("9007199254740993"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "decimal")]
+    fn exact_numbers_fit_the_field() {
+        // A string keeps the digits exactly as written
+        let tokens = lexer::lex_str("BULBA!\nprice ~> 0.1\n").unwrap();
+        let options = ParseOptions::new().exact_numbers();
+        let value = parser::parse_with(&tokens, &options).unwrap();
+        assert_eq!(
+            de::from_value::<String>(value.get("price").unwrap()),
+            Ok(String::from("0.1"))
+        );
+        assert_eq!(de::from_value::<f64>(value.get("price").unwrap()), Ok(0.1));
+    }
+
+    #[test]
+    fn enums_and_maps() {
+        let input = "BULBA!\nlevel ~> \"info\"\n(o) limits (o)\n    cpu ~> 2\n    memory ~> 512\n";
+        let tokens = lexer::lex_str(input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        assert_eq!(
+            de::from_value::<Level>(value.get("level").unwrap()),
+            Ok(Level::Info)
+        );
+        let limits: BTreeMap<&str, u32> = de::from_value(value.get("limits").unwrap()).unwrap();
+        assert_eq!(limits, BTreeMap::from([("cpu", 2), ("memory", 512)]));
+        assert!(de::from_value::<Level>(value.get("limits").unwrap()).is_err());
+
+        assert_ne!(Level::Debug, Level::Info);
+    }
+
+    #[test]
+    fn errors_name_their_key() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let input = input.replace("5000", "\"5000\"");
+        let tokens = lexer::lex_str(&input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        let err = de::from_value::<Config>(&value).unwrap_err();
+        assert_eq!(err.path, "database.pool.timeout_ms");
+        assert_eq!(
+            err.to_string(),
+            "database.pool.timeout_ms: invalid type: string \"5000\", expected u64"
+        );
+
+        let whitelist = value.get("whitelist").unwrap();
+        let err = de::from_value::<Vec<u8>>(whitelist).unwrap_err();
+        assert_eq!(err.path, "0");
+    }
+
+    #[test]
+    fn missing_no_is_none_but_absent_is_missing() {
+        let retry = |input: &str| {
+            let tokens = lexer::lex_str(input).unwrap();
+            let value = parser::parse(&tokens).unwrap();
+            de::from_value::<Retry>(&value)
+        };
+        assert_eq!(
+            retry("BULBA!\nstrategy ~> MissingNo\nbackoff_ms ~> MissingNo\n"),
+            Ok(Retry {
+                strategy: None,
+                backoff_ms: None
+            })
+        );
+        assert_eq!(
+            retry("BULBA!\nstrategy ~> \"SolarBeam\"\nbackoff_ms ~> 250\n"),
+            Ok(Retry {
+                strategy: Some(String::from("SolarBeam")),
+                backoff_ms: Some(250)
+            })
+        );
+        // Only the key with a default may be left out
+        assert_eq!(
+            retry("BULBA!\nstrategy ~> MissingNo\n").map(|retry| retry.backoff_ms),
+            Ok(None)
+        );
+        assert_eq!(
+            retry("BULBA!\nbackoff_ms ~> 250\n"),
+            Err(ConvertError::new("", "missing field `strategy`"))
+        );
+    }
+
+    #[test]
+    fn unresolved_values_are_refused() {
+        let value = BsonValue::Secret("env:DB_PASSWORD".into());
+        assert_eq!(
+            de::from_value::<String>(&value),
+            Err(ConvertError::new(
+                "",
+                "invalid type: secret reference, expected a string"
+            ))
+        );
+        assert!(de::from_value::<String>(&BsonValue::Encrypted("abc".into())).is_err());
+    }
+
+    #[test]
+    fn values_read_back_into_values() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let tokens = lexer::lex_str(&input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        let copy: BsonValue<'static> = de::from_value(&value).unwrap();
+        assert_eq!(copy, value.clone().into_owned());
+
+        let wide = BsonValue::Integer(u64::MAX.into());
+        assert_eq!(de::from_value::<BsonValue>(&wide), Ok(wide.clone()));
+    }
+}
@@ -0,0 +1,190 @@
+#![cfg(feature = "schema")]
+
+use std::error::Error;
+use std::fs;
+use std::io;
+
+use serde::Deserialize;
+
+use rs_bson::bulba_schema;
+use rs_bson::de;
+use rs_bson::describe::Describe;
+use rs_bson::document::Document;
+use rs_bson::layers::Layers;
+use rs_bson::load::{self, FileError, LoadError};
+use rs_bson::parser::ParseError;
+use rs_bson::schema::{self, Schema, Type};
+
+bulba_schema! {
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct KernelFlags {
+        panic_on_fail: bool,
+        retry_strategy: Option<String>,
+    }
+}
+
+bulba_schema! {
+    #[derive(Deserialize)]
+    #[allow(dead_code, non_snake_case)]
+    struct Pool {
+        max_connections: u16,
+        timeout_ms: Option<u32>,
+        KERNEL_FLAGS: KernelFlags,
+    }
+}
+
+bulba_schema! {
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Database {
+        host: String,
+        pool: Pool,
+    }
+}
+
+bulba_schema! {
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Config {
+        app_name: String,
+        version: f64,
+        is_production: bool,
+        whitelist: Vec<String>,
+        database: Database,
+    }
+}
+
+#[cfg(test)]
+pub mod describe_tests {
+    use crate::*;
+
+    #[test]
+    fn derived_schema() {
+        let expected = Schema::map()
+            .key("panic_on_fail", Type::Bool)
+            .required("panic_on_fail")
+            .key("retry_strategy", Type::String);
+        assert_eq!(KernelFlags::schema(), expected);
+
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let doc = Document::parse(&input).unwrap();
+        assert_eq!(schema::validate(&doc, &Config::schema()), vec![]);
+
+        let input = fs::read_to_string("tests/test_data/invalid_schema.bson").unwrap();
+        let doc = Document::parse(&input).unwrap();
+        let paths: Vec<String> = schema::validate(&doc, &Config::schema())
+            .into_iter()
+            .map(|violation| violation.path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "app_name",
+                "database.host",
+                "database.pool.max_connections",
+                "whitelist.1",
+            ]
+        );
+    }
+
+    #[test]
+    fn load_typed_config() {
+        let config: Config = load::from_file("tests/test_data/main_input.bson").unwrap();
+        assert_eq!(config.app_name, "Pokedex_API");
+        assert_eq!(config.whitelist, vec!["Prof_Oak", "Mom", "Nurse_Joy"]);
+        assert_eq!(config.database.pool.max_connections, 100);
+        assert_eq!(config.database.pool.timeout_ms, Some(5000));
+        assert_eq!(
+            config.database.pool.KERNEL_FLAGS.retry_strategy.as_deref(),
+            Some("SolarBeam")
+        );
+
+        let err = load::from_file::<Config>("tests/test_data/invalid_schema.bson").err();
+        let Some(LoadError::Invalid(violations)) = &err else {
+            panic!("expected violations, got {err:?}");
+        };
+        assert_eq!(violations.len(), 4);
+        assert_eq!(
+            load::from_file::<Config>("tests/test_data/invalid_charizard.bson").err(),
+            Some(LoadError::Parse(ParseError::At {
+                message: "It burns the bulb",
+                line: 2,
+                column: 1,
+            }))
+        );
+    }
+
+    #[test]
+    fn load_with_env_overrides() {
+        // Only this test reads BULBA_ variables
+        unsafe { std::env::set_var("BULBA_DATABASE_POOL_MAX_CONNECTIONS", "300") };
+        let config: Config = rs_bson::load("tests/test_data/main_input.bson").unwrap();
+        assert_eq!(config.database.pool.max_connections, 300);
+        assert_eq!(config.database.host, "127.0.0.1");
+
+        let err = Layers::new()
+            .file("tests/test_data/invalid_schema.bson")
+            .load_as::<Config>()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "app_name: Target is immune! Expected string, found number
+database.host: Required key is missing
+database.pool.max_connections: Target is immune! Expected int, found number
+whitelist.1: Target is immune! Expected string, found number"
+        );
+        assert_eq!(
+            rs_bson::load::<Config>("tests/test_data/nowhere.bson").err(),
+            Some(LoadError::Io(FileError::new(
+                "tests/test_data/nowhere.bson",
+                io::Error::from_raw_os_error(2)
+            )))
+        );
+    }
+
+    #[test]
+    fn errors_chain_to_their_source() {
+        fn load_port(path: &str) -> Result<Config, Box<dyn Error + Send + Sync>> {
+            Ok(load::from_file::<Config>(path)?)
+        }
+
+        let err = load_port("tests/test_data/nowhere.bson").err().unwrap();
+        let file = err.source().unwrap();
+        assert!(file.is::<FileError>());
+        let io = file.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(io.kind(), io::ErrorKind::NotFound);
+
+        let err = load_port("tests/test_data/invalid_charizard.bson")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.source()
+                .unwrap()
+                .downcast_ref::<ParseError>()
+                .map(ParseError::message),
+            Some("It burns the bulb")
+        );
+        assert!(
+            load_port("tests/test_data/invalid_schema.bson")
+                .err()
+                .unwrap()
+                .source()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn missing_no_and_absent_keys() {
+        let flags = |input: &str| {
+            let value = Document::parse(input).unwrap().value().unwrap();
+            de::from_value::<KernelFlags>(&value).map(|flags| flags.retry_strategy)
+        };
+        let set = "BULBA!\npanic_on_fail ~> SuperEffective\nretry_strategy ~> MissingNo\n";
+        assert_eq!(flags(set), Ok(None));
+        assert_eq!(flags("BULBA!\npanic_on_fail ~> SuperEffective\n"), Ok(None));
+        assert!(flags("BULBA!\npanic_on_fail ~> MissingNo\n").is_err());
+        assert!(flags("BULBA!\nretry_strategy ~> \"SolarBeam\"\n").is_err());
+    }
+}
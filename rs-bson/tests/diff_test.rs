@@ -0,0 +1,99 @@
+use std::fs::File;
+use std::path::Path;
+
+use rs_bson::diff::changed_paths;
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::patch::Operation;
+
+#[cfg(test)]
+pub mod diff_tests {
+    use crate::*;
+
+    #[test]
+    fn diff_round_trips_through_patch() {
+        let valid = File::open(Path::new("tests/test_data/valid.bson")).unwrap();
+        let old_tokens = lexer::lex(valid).unwrap();
+        let old = parser::parse(&old_tokens).unwrap();
+        let main = File::open(Path::new("tests/test_data/main_input.bson")).unwrap();
+        let new_tokens = lexer::lex(main).unwrap();
+        let new = parser::parse(&new_tokens).unwrap();
+
+        let patch = rs_bson::diff(&old, &new);
+        assert_eq!(
+            patch.operations,
+            vec![
+                Operation::Add {
+                    path: "/database/pool/KERNEL_FLAGS/retry_strategy".to_string(),
+                    value: BsonValue::BString("SolarBeam".into()),
+                },
+                Operation::Add {
+                    path: "/database/pool/timeout_ms".to_string(),
+                    value: BsonValue::Number(5000.0),
+                },
+                Operation::Replace {
+                    path: "/whitelist".to_string(),
+                    value: new.get("whitelist").unwrap().clone(),
+                },
+                Operation::Add {
+                    path: "/zapdos".to_string(),
+                    value: BsonValue::BString("Legendary bird".into()),
+                },
+            ]
+        );
+
+        let mut patched = old.clone();
+        patched.apply_patch(&patch).unwrap();
+        assert_eq!(patched, new);
+        assert!(rs_bson::diff(&new, &patched).operations.is_empty());
+    }
+
+    #[test]
+    fn changed_paths_answer_prefixes() {
+        let parse = |path: &str| {
+            let tokens = lexer::lex(File::open(Path::new(path)).unwrap()).unwrap();
+            parser::parse(&tokens).unwrap().into_owned()
+        };
+        let old = parse("tests/test_data/valid.bson");
+        let new = parse("tests/test_data/main_input.bson");
+        let changed = changed_paths(&old, &new);
+        assert_eq!(
+            changed.iter().collect::<Vec<_>>(),
+            [
+                "/database/pool/KERNEL_FLAGS/retry_strategy",
+                "/database/pool/timeout_ms",
+                "/whitelist",
+                "/zapdos",
+            ]
+        );
+        assert!(changed.touches("/database"));
+        assert!(changed.touches("/database/pool"));
+        assert!(changed.touches("/whitelist/0"));
+        assert!(changed.touches(""));
+        assert!(!changed.touches("/database/host"));
+        assert!(!changed.touches("/data"));
+        assert!(changed_paths(&new, &new).is_empty());
+        assert!(!changed_paths(&new, &new).touches(""));
+    }
+
+    #[test]
+    fn diff_reaches_keys_with_dots() {
+        let key = |name: &str, value| {
+            BsonValue::Map([(name.to_string().into(), value)].into_iter().collect())
+        };
+        let old = key("profile.production", key("port", BsonValue::Number(80.0)));
+        let new = key("profile.production", key("port", BsonValue::Number(443.0)));
+
+        let patch = rs_bson::diff(&old, &new);
+        assert_eq!(
+            patch.operations,
+            vec![Operation::Replace {
+                path: "/profile.production/port".to_string(),
+                value: BsonValue::Number(443.0),
+            }]
+        );
+        let mut patched = old.clone();
+        patched.apply_patch(&patch).unwrap();
+        assert_eq!(patched, new);
+    }
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "schema")]
+
+use std::fs;
+
+use rs_bson::docs;
+use rs_bson::document::Document;
+use rs_bson::schema::Schema;
+
+#[cfg(test)]
+pub mod docs_tests {
+    use crate::*;
+
+    #[test]
+    fn docs_from_schema() {
+        let schema = fs::read_to_string("tests/test_data/deprecated.bulbaschema").unwrap();
+        let schema = Schema::parse(&schema).unwrap();
+        let markdown = docs::markdown(&schema);
+        assert!(markdown.starts_with(
+            "# Configuration reference\n\n| Key | Type | Required | Default | Description |\n"
+        ));
+        assert!(
+            markdown
+                .contains("| `zapdos` | string | no |  | Deprecated, use `legendary_birds`. |\n")
+        );
+        assert!(markdown.contains("| `whitelist` | array<string> | yes |  |  |\n"));
+
+        let html = docs::html(&schema);
+        assert!(html.contains(
+            "<tr><td><code>whitelist</code></td><td>array&lt;string&gt;</td><td>yes</td><td></td><td></td></tr>"
+        ));
+    }
+
+    #[test]
+    fn docs_from_example() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let schema = docs::from_example(&Document::parse(&input).unwrap()).unwrap();
+        let markdown = docs::markdown(&schema);
+        assert!(
+            markdown.contains(
+                "| `app_name` | string | no | `\"Pokedex_API\"` | Basic Configuration. |\n"
+            )
+        );
+        assert!(markdown.contains("| `database.pool.max_connections` | int | no | `100` |  |\n"));
+        assert!(markdown.contains(
+            "| `whitelist` | array<string> | no | `<\\| \"Prof_Oak\", \"Mom\", \"Nurse_Joy\" \\|>` | Allowed Users List. |\n"
+        ));
+    }
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "doctor")]
+
+use std::fs;
+
+use rs_bson::doctor;
+
+#[cfg(test)]
+pub mod doctor_tests {
+    use crate::*;
+
+    #[test]
+    fn diagnose_common_mistakes() {
+        let input = fs::read_to_string("tests/test_data/doctor.bson").unwrap();
+        let found: Vec<(usize, String)> = doctor::examine(&input)
+            .into_iter()
+            .map(|d| (d.line, d.suggestion))
+            .collect();
+        let expected = [
+            (2, "Write debug ~> SuperEffective"),
+            (3, "Drop the quotes: port ~> 8080"),
+            (4, "Quote it: mode ~> \"SolarBeam\""),
+            (5, "Spell it another way, the line ends at the first zZz"),
+            (7, "Indent by a multiple of 4, e.g. 4"),
+            (9, "Save the file with LF line endings"),
+        ];
+        let expected: Vec<(usize, String)> = expected
+            .iter()
+            .map(|(line, s)| (*line, s.to_string()))
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn find_keys_differing_by_case() {
+        let found = doctor::examine("BULBA!\n(o) db (o)\n    host ~> \"a\"\n    Host ~> \"b\"\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].line, 4);
+        assert_eq!(found[0].message, "`Host` differs from `host` only by case");
+        assert!(
+            doctor::examine(&fs::read_to_string("tests/test_data/valid.bson").unwrap()).is_empty()
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use std::fs;
+
+use rs_bson::document::{ConflictPolicy, Document};
+use rs_bson::lexer;
+use rs_bson::parser;
+
+#[cfg(test)]
+pub mod document_tests {
+    use crate::*;
+
+    #[test]
+    fn round_trip_is_lossless() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let doc = Document::parse(&input).unwrap();
+        assert_eq!(doc.to_string(), input);
+    }
+
+    #[test]
+    fn sort_keys_keeps_comments_attached() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let mut doc = Document::parse(&input).unwrap();
+        doc.sort_keys();
+        let expected = "BULBA!
+
+zZz Basic Configuration
+app_name ~~~~~~> \"Pokedex_API\"
+
+zZz Database Connection (Level 1)
+(o) database (o)
+    host ~~~~> \"127.0.0.1\"
+
+    zZz Connection Pool Settings (Level 2)
+    (O) pool (O)
+
+        zZz Critical Kernel flags (Level 3)
+        (@) KERNEL_FLAGS (@)
+            panic_on_fail ~~~~> SuperEffective
+            retry_strategy ~~~> \"SolarBeam\"
+        max_connections ~~~~> 100
+        timeout_ms      ~~~~> 5000
+is_production ~> NotVeryEffective
+missing_data ~~> MissingNo
+version  ~~~~~~> 1.5
+
+zZz Allowed Users List
+whitelist ~~~~> <| \"Prof_Oak\", \"Mom\", \"Nurse_Joy\" |>
+zapdos ~~~~~~~~> \"Legendary bird\"
+";
+        assert_eq!(doc.to_string(), expected);
+
+        let before = lexer::lex_str(&input).unwrap();
+        let after = lexer::lex_str(expected).unwrap();
+        assert_eq!(parser::parse(&before), parser::parse(&after));
+    }
+
+    #[test]
+    fn sort_keys_keeps_closing_comment_in_section() {
+        let input = "BULBA!
+zebra ~> 1
+(o) alpha (o)
+    b ~> 2
+    a ~> 1
+    zZz end of alpha
+";
+        let mut doc = Document::parse(input).unwrap();
+        doc.sort_keys();
+        let expected = "BULBA!
+(o) alpha (o)
+    a ~> 1
+    b ~> 2
+    zZz end of alpha
+zebra ~> 1
+";
+        assert_eq!(doc.to_string(), expected);
+    }
+
+    #[test]
+    fn sort_keys_keeps_continued_values_whole() {
+        let input = "BULBA!
+zebra ~> 1
+motto ~> \"Gotta catch \\
+    'em all\"
+(o) alpha (o)
+    b ~> <| 1, \\
+        2 |>
+    a ~> 1
+";
+        let mut doc = Document::parse(input).unwrap();
+        assert_eq!(doc.line_of("alpha.a"), Some(8));
+        let value = doc.value().unwrap();
+        doc.sort_keys();
+        let expected = "BULBA!
+(o) alpha (o)
+    a ~> 1
+    b ~> <| 1, \\
+        2 |>
+motto ~> \"Gotta catch \\
+    'em all\"
+zebra ~> 1
+";
+        assert_eq!(doc.to_string(), expected);
+        assert_eq!(doc.value(), Ok(value));
+        assert_eq!(doc.line_of("zebra"), Some(8));
+    }
+
+    #[test]
+    fn sort_keys_leaves_conditional_sections_in_place() {
+        let input = "BULBA!
+zebra ~> 1
+log_level ~> \"debug\"
+(o) when env == \"production\" (o)
+    log_level ~> \"warn\"
+    burst ~> 2
+cache ~> 3
+alpha ~> 4
+";
+        let mut doc = Document::parse(input).unwrap();
+        doc.sort_keys();
+        let expected = "BULBA!
+log_level ~> \"debug\"
+zebra ~> 1
+(o) when env == \"production\" (o)
+    burst ~> 2
+    log_level ~> \"warn\"
+alpha ~> 4
+cache ~> 3
+";
+        assert_eq!(doc.to_string(), expected);
+    }
+
+    #[test]
+    fn rename_path_rewrites_key_line() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let mut doc = Document::parse(&input).unwrap();
+        let touched = doc
+            .rename_path("database.pool.max_connections", "max_conns")
+            .unwrap();
+        assert_eq!(touched, vec![16]);
+        assert_eq!(doc.line_of("database.pool.max_conns"), Some(16));
+        assert_eq!(doc.line_of("database.pool.max_connections"), None);
+        assert!(doc.to_string().contains("        max_conns ~~~~> 100\n"));
+
+        assert_eq!(
+            doc.rename_path("whitelist", "Charizard"),
+            Err("It burns the bulb")
+        );
+        assert_eq!(
+            doc.rename_path("database.nope", "yes"),
+            Err("But it failed!")
+        );
+        assert_eq!(
+            doc.rename_path("database", "app_name"),
+            Err("But it failed!")
+        );
+        for bad in ["9lives", "max-conns", "pool.size", ""] {
+            assert_eq!(
+                doc.rename_path("whitelist", bad),
+                Err("It hurt itself in its confusion!")
+            );
+        }
+    }
+
+    #[test]
+    fn rename_path_on_dotted_keys() {
+        let input = "BULBA!
+db.host ~> \"localhost\"
+db.port ~> 5432
+(o) db (o)
+    user ~> \"ash\"
+(o) profile.test (o)
+    db.pool.size ~> 4
+";
+        let mut doc = Document::parse(input).unwrap();
+        assert_eq!(doc.line_of("db.port"), Some(3));
+        assert_eq!(doc.line_of("profile.test.db.pool"), Some(7));
+
+        assert_eq!(doc.rename_path("db.host", "hostname"), Ok(vec![2]));
+        assert_eq!(doc.rename_path("db.port", "user"), Err("But it failed!"));
+        assert_eq!(doc.rename_path("db", "database"), Ok(vec![2, 3, 4]));
+        assert_eq!(
+            doc.rename_path("profile.test.db.pool", "connections"),
+            Ok(vec![7])
+        );
+        let expected = "BULBA!
+database.hostname ~> \"localhost\"
+database.port ~> 5432
+(o) database (o)
+    user ~> \"ash\"
+(o) profile.test (o)
+    db.connections.size ~> 4
+";
+        assert_eq!(doc.to_string(), expected);
+    }
+
+    #[test]
+    fn rename_path_in_repeated_sections() {
+        let input = "BULBA!
+(o) srv[] (o)
+    zZz the first one
+    x ~> 1
+(o) srv[] (o)
+    x ~> 2
+";
+        let mut doc = Document::parse(input).unwrap();
+        assert_eq!(doc.line_of("srv.1.x"), Some(6));
+        assert_eq!(doc.line_of("srv.2.x"), None);
+        assert_eq!(doc.comments().get("srv.0.x").unwrap(), "the first one");
+
+        assert_eq!(doc.rename_path("srv.1.x", "y"), Ok(vec![6]));
+        assert_eq!(doc.rename_path("srv.x", "y"), Err("But it failed!"));
+        assert_eq!(doc.rename_path("srv.0", "y"), Err("But it failed!"));
+        assert_eq!(doc.rename_path("srv", "server"), Ok(vec![2, 5]));
+        let expected = "BULBA!
+(o) server[] (o)
+    zZz the first one
+    x ~> 1
+(o) server[] (o)
+    y ~> 2
+";
+        assert_eq!(doc.to_string(), expected);
+        assert_eq!(
+            doc.value().unwrap().get("server.1.y"),
+            Some(&parser::BsonValue::Number(2.0))
+        );
+    }
+
+    #[test]
+    fn rename_path_keeps_expressions_pointing_somewhere() {
+        let input = "BULBA!
+port ~> 8080
+timeout ~> 30 * 1000
+retry ~> timeout * 2
+(o) db (o)
+    port ~> 5432
+    url ~> \"host:\" + port
+";
+        let options = parser::ParseOptions::new().expressions();
+        let mut doc = Document::parse_with(input, &options).unwrap();
+        assert_eq!(doc.rename_path("timeout", "wait"), Err("But it failed!"));
+        assert_eq!(doc.rename_path("db.port", "p"), Err("But it failed!"));
+        assert_eq!(doc.rename_path("db", "database"), Err("But it failed!"));
+        // `url` reads the port of its own section
+        assert_eq!(doc.rename_path("port", "http_port"), Ok(vec![2]));
+        assert_eq!(doc.rename_path("retry", "retry_after"), Ok(vec![4]));
+        assert_eq!(
+            doc.value().unwrap().get("db.url"),
+            Some(&parser::BsonValue::from("host:5432"))
+        );
+    }
+
+    #[test]
+    fn extend_from_appends_entries() {
+        let base = "BULBA!
+name ~> \"Bulbasaur\"
+level ~> 5
+zZz end of base
+";
+        let fragment = "BULBA!
+zZz From the level tool
+level ~> 16
+(o) evolution (o)
+    name ~> \"Ivysaur\"
+";
+        let other = Document::parse(fragment).unwrap();
+
+        let mut doc = Document::parse(base).unwrap();
+        assert_eq!(
+            doc.extend_from(&other, ConflictPolicy::Error),
+            Err("But it failed!")
+        );
+        assert_eq!(doc.to_string(), base);
+
+        doc.extend_from(&other, ConflictPolicy::KeepExisting)
+            .unwrap();
+        let expected = "BULBA!
+name ~> \"Bulbasaur\"
+level ~> 5
+zZz end of base
+(o) evolution (o)
+    name ~> \"Ivysaur\"
+";
+        assert_eq!(doc.to_string(), expected);
+
+        let mut doc = Document::parse(base).unwrap();
+        doc.extend_from(&other, ConflictPolicy::Overwrite).unwrap();
+        let expected = "BULBA!
+name ~> \"Bulbasaur\"
+zZz From the level tool
+level ~> 16
+zZz end of base
+(o) evolution (o)
+    name ~> \"Ivysaur\"
+";
+        assert_eq!(doc.to_string(), expected);
+    }
+
+    #[test]
+    fn extend_from_compares_resolved_paths() {
+        let base = "BULBA!\ndatabase.host ~> \"x\"\ndatabase.port ~> 5432\nname ~> \"Bulbasaur\"\n";
+        let other = Document::parse("BULBA!\ndatabase ~> 1\n").unwrap();
+
+        let mut doc = Document::parse(base).unwrap();
+        assert_eq!(
+            doc.extend_from(&other, ConflictPolicy::Error),
+            Err("But it failed!")
+        );
+        doc.extend_from(&other, ConflictPolicy::KeepExisting)
+            .unwrap();
+        assert_eq!(doc.to_string(), base);
+
+        doc.extend_from(&other, ConflictPolicy::Overwrite).unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "BULBA!\ndatabase ~> 1\nname ~> \"Bulbasaur\"\n"
+        );
+
+        let mut doc = Document::parse(base).unwrap();
+        let other = Document::parse("BULBA!\ndatabase.user ~> \"ash\"\n").unwrap();
+        doc.extend_from(&other, ConflictPolicy::Error).unwrap();
+        assert_eq!(
+            doc.value().unwrap().get("database.user"),
+            Some(&parser::BsonValue::BString("ash".into()))
+        );
+
+        let options = parser::ParseOptions::new().allow("Charizard");
+        let other = Document::parse_with("BULBA!\nCharizard ~> 6\n", &options).unwrap();
+        let before = doc.to_string();
+        assert_eq!(
+            doc.extend_from(&other, ConflictPolicy::Error),
+            Err("It burns the bulb")
+        );
+        assert_eq!(doc.to_string(), before);
+    }
+
+    #[test]
+    fn parse_with_keeps_options() {
+        let input = "BULBA!\nCharizard ~> 6\n";
+        let options = parser::ParseOptions::new().allow("Charizard");
+        assert_eq!(
+            Document::parse(input).unwrap_err().message(),
+            "It burns the bulb"
+        );
+        let mut doc = Document::parse_with(input, &options).unwrap();
+        assert_eq!(
+            doc.value().unwrap().get("Charizard"),
+            Some(&parser::BsonValue::Number(6.0))
+        );
+        doc.rename_path("Charizard", "Mega_Charizard").unwrap();
+        assert!(doc.value().unwrap().get("Mega_Charizard").is_some());
+
+        let options = parser::ParseOptions::new().reserve("Mewtwo");
+        let mut doc = Document::parse_with("BULBA!\nMew ~> 151\n", &options).unwrap();
+        assert_eq!(doc.rename_path("Mew", "Mewtwo"), Err("It burns the bulb"));
+    }
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "schema")]
+
+use rs_bson::env;
+use rs_bson::layers::Layers;
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue, PathConflict};
+
+#[cfg(test)]
+pub mod env_tests {
+    use crate::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn from_vars_nests_on_separator() {
+        let value = env::from_vars(
+            "APP_",
+            "__",
+            vars(&[
+                ("APP_DATABASE__HOST", "db.internal"),
+                ("APP_DATABASE__POOL__MAX_CONNECTIONS", "100"),
+                ("APP_IS_PRODUCTION", "SuperEffective"),
+                ("APP_WHITELIST", "<| \"Mom\" |>"),
+                ("APP_BROKEN____KEY", "skipped"),
+                ("OTHER_HOST", "ignored"),
+            ]),
+        )
+        .unwrap();
+        let tokens = lexer::lex_str(
+            "BULBA!
+is_production ~> SuperEffective
+whitelist ~> <| \"Mom\" |>
+(o) database (o)
+    host ~> \"db.internal\"
+    (O) pool (O)
+        max_connections ~> 100
+",
+        )
+        .unwrap();
+        assert_eq!(value, parser::parse(&tokens).unwrap());
+
+        let value = env::from_vars("APP_", ".", vars(&[("APP_DATABASE.HOST", "x")])).unwrap();
+        assert_eq!(
+            value.get("database.host"),
+            Some(&BsonValue::BString("x".into()))
+        );
+
+        let value = env::from_vars(
+            "APP_",
+            "__",
+            vars(&[("APP_HOSTS__0", "\"a\""), ("APP_HOSTS__1", "\"b\"")]),
+        )
+        .unwrap();
+        assert_eq!(value.get("hosts.1"), Some(&BsonValue::BString("b".into())));
+
+        let clash = vars(&[("APP_DATABASE", "1"), ("APP_DATABASE__HOST", "x")]);
+        assert_eq!(
+            env::from_vars("APP_", "__", clash),
+            Err(PathConflict(String::from("database")))
+        );
+    }
+
+    #[test]
+    fn env_map_round_trips_through_layers() {
+        let defaults = Layers::new()
+            .file("tests/test_data/main_input.bson")
+            .load()
+            .unwrap();
+        // Only this test reads variables with this prefix
+        for (name, value) in defaults.to_env_map("ROUND_TRIP_") {
+            unsafe { std::env::set_var(name, value) };
+        }
+        let loaded = Layers::new()
+            .value(defaults.clone())
+            .env_prefix("ROUND_TRIP_")
+            .load()
+            .unwrap();
+        assert_eq!(loaded, defaults);
+    }
+
+    #[test]
+    fn env_map_leaves_out_what_bson_cannot_spell() {
+        let list = |items: &[&'static str]| {
+            BsonValue::Array(items.iter().map(|item| BsonValue::from(*item)).collect())
+        };
+        // The lexer refuses a comma inside a list element, so it can only be built
+        let value = BsonValue::Map(
+            [
+                ("plain".into(), list(&["a", "b"])),
+                ("split".into(), list(&["a,b"])),
+            ]
+            .into(),
+        );
+        let vars = value.to_env_map("APP_");
+        assert_eq!(vars.get("APP_PLAIN").unwrap(), "<| \"a\", \"b\" |>");
+        assert!(!vars.contains_key("APP_SPLIT"));
+        let back = env::from_vars("APP_", "__", vars).unwrap();
+        assert_eq!(back.get("plain"), value.get("plain"));
+    }
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "figment")]
+
+use figment::providers::{Env, Serialized};
+use figment::{Figment, Jail, Profile};
+use serde::{Deserialize, Serialize};
+
+use rs_bson::figment::Bson;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Database {
+    host: String,
+    port: u16,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    app_name: String,
+    workers: u32,
+    database: Database,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            app_name: String::from("Pokedex"),
+            workers: 4,
+            database: Database {
+                host: String::from("localhost"),
+                port: 5432,
+            },
+        }
+    }
+}
+
+const APP: &str = "BULBA!
+app_name ~> \"Pokedex_API\"
+(o) database (o)
+    host ~> \"db.internal\"
+(o) profile.test (o)
+    workers ~> 1
+";
+
+#[cfg(test)]
+pub mod figment_tests {
+    use crate::*;
+
+    #[test]
+    // The closure returns figment's own error type
+    #[allow(clippy::result_large_err)]
+    fn layers_over_defaults_and_under_env() {
+        Jail::expect_with(|jail| {
+            jail.create_file("App.bson", APP)?;
+            jail.set_env("APP_DATABASE", "{port=6543}");
+
+            let figment = Figment::from(Serialized::defaults(Config::default()))
+                .merge(Bson::file("App.bson"))
+                .merge(Env::prefixed("APP_"));
+            let config: Config = figment.extract()?;
+            assert_eq!(
+                config,
+                Config {
+                    app_name: String::from("Pokedex_API"),
+                    workers: 4,
+                    database: Database {
+                        host: String::from("db.internal"),
+                        port: 6543,
+                    },
+                }
+            );
+            let source = figment.find_metadata("app_name").unwrap();
+            assert_eq!(source.name, "BSON file");
+
+            let config: Config = figment.select("test").extract()?;
+            assert_eq!(config.workers, 1);
+            assert_eq!(config.database.host, "db.internal");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn profiles_and_missing_files() {
+        let data = figment::Provider::data(&Bson::string(APP)).unwrap();
+        let profiles: Vec<&Profile> = data.keys().collect();
+        assert_eq!(profiles, vec![&Profile::Default, &Profile::new("test")]);
+
+        let figment = Figment::from(Serialized::defaults(Config::default()))
+            .merge(Bson::file("tests/test_data/nowhere.bson"));
+        assert_eq!(figment.extract::<Config>().unwrap(), Config::default());
+        let required = figment.merge(Bson::file("tests/test_data/nowhere.bson").required(true));
+        assert!(required.extract::<Config>().is_err());
+
+        let invalid = Figment::new().merge(Bson::string("BULBA!\nCharizard ~> 6\n"));
+        let err = invalid.extract::<Config>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "It burns the bulb (line 2, column 1) in BSON source string"
+        );
+    }
+}
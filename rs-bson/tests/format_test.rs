@@ -0,0 +1,115 @@
+use std::fs;
+#[cfg(feature = "schema")]
+use std::path::Path;
+
+use rs_bson::format::{self, FormatOptions, Newline};
+
+#[cfg(test)]
+pub mod format_tests {
+    use crate::*;
+
+    #[test]
+    fn format_canonical_layout() {
+        let input = "BULBA!\n\n\nname~>\"Bulbasaur\"   \nlevel ~~~~~~~~~~> 5 zZz Caught today\n\
+(o)   moves   (o)\n    first    ~> <|\"Tackle\",\"Growl\"|>\n\n\n    zZz Learned at 7\n    \
+leech_seed ~~~> SuperEffective\n\n\n";
+        let expected = "BULBA!
+
+name ~~> \"Bulbasaur\"
+level ~> 5 zZz Caught today
+(o) moves (o)
+    first ~> <| \"Tackle\", \"Growl\" |>
+
+    zZz Learned at 7
+    leech_seed ~> SuperEffective
+";
+        assert_eq!(format::format(input), Ok(expected.to_string()));
+        assert_eq!(format::format(expected), Ok(expected.to_string()));
+
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let formatted = format::format(&input).unwrap();
+        assert_eq!(format::format(&formatted), Ok(formatted));
+        assert_eq!(
+            format::format("BULBA!\n\tkey ~> 1\n"),
+            Err("Poison Type: Tab character detected")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "schema")]
+    fn format_with_discovered_options() {
+        let options =
+            FormatOptions::discover(Path::new("tests/test_data/fmt/nested/party.bson")).unwrap();
+        assert_eq!(
+            options,
+            FormatOptions {
+                align_arrows: false,
+                min_arrow: 2,
+                max_blank_lines: 0,
+                blank_before_sections: true,
+                ..FormatOptions::default()
+            }
+        );
+
+        let input = "BULBA!
+
+name ~> \"Bulbasaur\"
+level ~> 5
+
+
+zZz Moves
+(o) moves (o)
+    first ~> \"Tackle\"
+";
+        let expected = "BULBA!
+name ~~> \"Bulbasaur\"
+level ~~> 5
+
+zZz Moves
+(o) moves (o)
+    first ~~> \"Tackle\"
+";
+        assert_eq!(
+            format::format_with(input, &options),
+            Ok(expected.to_string())
+        );
+
+        assert_eq!(
+            FormatOptions::discover(Path::new("Cargo.toml")),
+            Ok(FormatOptions::default())
+        );
+        assert!(
+            FormatOptions::parse(
+                "BULBA!
+indent_width ~> 2
+"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn format_newline_style() {
+        let crlf = fs::read_to_string("tests/test_data/valid_crlf.bson").unwrap();
+        let lf = fs::read_to_string("tests/test_data/valid.bson").unwrap();
+        assert_eq!(format::format(&crlf), format::format(&lf));
+
+        let windows = FormatOptions {
+            newline: Newline::CrLf,
+            ..FormatOptions::default()
+        };
+        let text = format::format_with(&lf, &windows).unwrap();
+        assert_eq!(text.replace("\r\n", "\n"), format::format(&lf).unwrap());
+        assert_eq!(text.matches('\n').count(), text.matches("\r\n").count());
+
+        let tokens = rs_bson::lexer::lex_str(&lf).unwrap();
+        let value = rs_bson::parser::parse(&tokens).unwrap();
+        let emitted = rs_bson::emit::emit_with(&value, &windows).unwrap();
+        assert_eq!(
+            emitted,
+            rs_bson::emit(&value).unwrap().replace('\n', "\r\n")
+        );
+        #[cfg(feature = "schema")]
+        assert!(FormatOptions::parse("BULBA!\nnewline ~> \"cr\"\n").is_err());
+    }
+}
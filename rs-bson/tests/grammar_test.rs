@@ -0,0 +1,46 @@
+#![cfg(all(feature = "json", feature = "regex"))]
+
+use regex::Regex;
+
+use rs_bson::grammar;
+use rs_bson::json::JsonValue;
+
+#[cfg(test)]
+pub mod grammar_tests {
+    use crate::*;
+
+    fn pattern(grammar: &JsonValue, rule: &str, field: &str) -> Regex {
+        let repository = grammar.get("repository").unwrap();
+        let source = repository.get(rule).unwrap().get(field).unwrap();
+        Regex::new(source.as_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn textmate_grammar_follows_lexer() {
+        let grammar = grammar::textmate();
+        assert_eq!(
+            grammar.get("scopeName").and_then(JsonValue::as_str),
+            Some("source.bson")
+        );
+
+        let section = pattern(&grammar, "section", "match");
+        let captures = section.captures("    (O) pool (O)").unwrap();
+        assert_eq!(&captures[2], "pool");
+
+        let pair = pattern(&grammar, "pair", "begin");
+        let captures = pair.captures("        timeout_ms      ~~~~> 5000").unwrap();
+        assert_eq!((&captures[1], &captures[2]), ("timeout_ms", "~~~~>"));
+        assert!(!pair.is_match("9lives ~> 9"));
+
+        let constant = pattern(&grammar, "constant", "match");
+        assert!(constant.is_match("SuperEffective") && constant.is_match("MissingNo"));
+        let secret = pattern(&grammar, "secret", "match");
+        assert_eq!(
+            &secret.captures(r#"secret("env:DB_PASS")"#).unwrap()[2],
+            "env:DB_PASS"
+        );
+        assert!(pattern(&grammar, "array", "begin").is_match("<| 1, 2 |>"));
+        assert!(pattern(&grammar, "comment", "match").is_match("zZz napping"));
+        assert!(pattern(&grammar, "header", "match").is_match("BULBA!"));
+    }
+}
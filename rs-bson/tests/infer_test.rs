@@ -0,0 +1,59 @@
+#![cfg(feature = "schema")]
+
+use std::fs;
+
+use rs_bson::infer;
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::schema::{self, Schema};
+
+#[cfg(test)]
+pub mod infer_tests {
+    use crate::*;
+
+    fn parse(input: &str) -> BsonValue<'static> {
+        let tokens = lexer::lex_str(input).unwrap();
+        parser::parse(&tokens).unwrap().into_owned()
+    }
+
+    #[test]
+    fn infer_from_examples() {
+        let examples: Vec<BsonValue> = ["main_input.bson", "valid.bson"]
+            .iter()
+            .map(|file| parse(&fs::read_to_string(format!("tests/test_data/{file}")).unwrap()))
+            .collect();
+        let text = infer::to_bulbaschema(&infer::infer(&examples));
+        assert!(text.contains("missing_data ~~> \"any?\"\n"));
+        assert!(text.contains("        timeout_ms ~~~~~~> \"int?\"\n"));
+
+        let written = Schema::parse(&text).unwrap();
+        for example in &examples {
+            assert_eq!(schema::validate_value(example, &written), vec![]);
+        }
+        assert!(!schema::validate_value(&parse("BULBA!\nversion ~> \"1\"\n"), &written).is_empty());
+    }
+
+    #[test]
+    fn mix_types_across_examples() {
+        let examples = [
+            parse("BULBA!\nratio ~> 1\nid ~> 5\n(o) cache (o)\n    ttl ~> 60\n"),
+            parse("BULBA!\nratio ~> 0.5\nid ~> \"five\"\nports ~> <| 80, 443 |>\n"),
+        ];
+        let text = infer::to_bulbaschema(&infer::infer(&examples));
+        assert_eq!(
+            text,
+            "BULBA!
+id ~~~~> \"any\"
+ports ~> \"array<int>?\"
+ratio ~> \"number\"
+(o) cache (o)
+    _optional ~> SuperEffective
+    ttl ~~~~~~~> \"int\"
+"
+        );
+        let written = Schema::parse(&text).unwrap();
+        for example in &examples {
+            assert_eq!(schema::validate_value(example, &written), vec![]);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "schema")]
+
+use std::fs;
+
+use rs_bson::js;
+
+#[cfg(test)]
+pub mod js_tests {
+    use crate::*;
+
+    #[test]
+    fn js_parse_and_format() {
+        assert_eq!(
+            js::parse("BULBA!\nname ~> \"Bulbasaur\"\nlevel ~> 5\n").to_string(),
+            r#"{"ok":true,"value":{"level":5,"name":"Bulbasaur"}}"#
+        );
+        assert_eq!(
+            js::parse("BULBA!\nname ~> \"Bulbasaur\"\nlevel ~> five\n").to_string(),
+            r#"{"error":"Target is immune!","line":3,"ok":false}"#
+        );
+        assert_eq!(
+            js::format("BULBA!\nname ~~~> \"Bulbasaur\"\n").to_string(),
+            r#"{"ok":true,"text":"BULBA!\nname ~> \"Bulbasaur\"\n"}"#
+        );
+    }
+
+    #[test]
+    fn js_validate() {
+        let schema = fs::read_to_string("tests/test_data/main_input.bulbaschema").unwrap();
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        assert_eq!(
+            js::validate(&input, &schema).to_string(),
+            r#"{"diagnostics":[]}"#
+        );
+
+        let input = input.replace("max_connections ~~~~> 100", "max_connections ~~~~> 1.5");
+        assert_eq!(
+            js::validate(&input, &schema).to_string(),
+            r#"{"diagnostics":[{"line":16,"message":"Target is immune! Expected int, found number","path":"database.pool.max_connections","severity":"error"}]}"#
+        );
+        assert_eq!(
+            js::validate("BULBA\n", &schema).to_string(),
+            r#"{"diagnostics":[{"line":1,"message":"Status: Fainted","path":null,"severity":"error"}]}"#
+        );
+    }
+}
@@ -0,0 +1,146 @@
+#![cfg(feature = "schema")]
+
+use std::path::PathBuf;
+
+use rs_bson::layers::{Layers, Origin};
+use rs_bson::load::LoadError;
+use rs_bson::parser::{BsonValue, ParseOptions};
+
+#[cfg(test)]
+pub mod layers_tests {
+    use crate::*;
+
+    #[test]
+    fn later_layers_win() {
+        // Only this test reads variables with this prefix
+        unsafe {
+            std::env::set_var("LAYERS_TEST_DATABASE_POOL_TIMEOUT_MS", "250");
+            std::env::set_var("LAYERS_TEST_APP_NAME", "Pokedex_Staging");
+        }
+        let config = Layers::new()
+            .file("tests/test_data/main_input.bson")
+            .optional_file("tests/test_data/does_not_exist.bson")
+            .file("tests/test_data/prod_override.bson")
+            .env_prefix("LAYERS_TEST_")
+            .load()
+            .unwrap();
+
+        let get = |path: &str| config.get(path).unwrap();
+        assert_eq!(
+            get("app_name"),
+            &BsonValue::BString("Pokedex_Staging".into())
+        );
+        assert_eq!(get("is_production"), &BsonValue::Bool(true));
+        assert_eq!(get("database.host"), &BsonValue::BString("10.0.0.1".into()));
+        assert_eq!(
+            get("database.pool.max_connections"),
+            &BsonValue::Number(500.0)
+        );
+        assert_eq!(get("database.pool.timeout_ms"), &BsonValue::Number(250.0));
+        assert_eq!(
+            get("database.pool.KERNEL_FLAGS.retry_strategy"),
+            &BsonValue::BString("SolarBeam".into())
+        );
+
+        let missing = Layers::new().file("tests/test_data/does_not_exist.bson");
+        assert!(matches!(missing.load(), Err(LoadError::Io(_))));
+    }
+
+    #[test]
+    fn select_profile_overlays_base() {
+        let layers = Layers::new().file("tests/test_data/profiles.bson");
+        assert_eq!(
+            layers.load().unwrap().profiles(),
+            vec!["production", "test"]
+        );
+
+        let production = layers.clone().profile("production").load().unwrap();
+        assert_eq!(
+            production.get("app_name"),
+            Some(&BsonValue::BString("Pokedex_API".into()))
+        );
+        assert_eq!(
+            production.get("database.port"),
+            Some(&BsonValue::Number(5432.0))
+        );
+        assert_eq!(
+            production.get("database.host"),
+            Some(&BsonValue::BString("db.pokedex.internal".into()))
+        );
+        assert!(production.profiles().is_empty());
+
+        let test = layers.clone().profile("test").load().unwrap();
+        assert_eq!(
+            test.get("app_name"),
+            Some(&BsonValue::BString("Pokedex_Test".into()))
+        );
+        assert!(matches!(
+            layers.profile("staging").load(),
+            Err(LoadError::Profile(name)) if name == "staging"
+        ));
+    }
+
+    #[test]
+    fn explain_where_values_came_from() {
+        // Only this test reads variables with this prefix
+        unsafe {
+            std::env::set_var("EXPLAIN_TEST_APP_NAME", "Pokedex_Staging");
+        }
+        let layers = Layers::new()
+            .file("tests/test_data/main_input.bson")
+            .file("tests/test_data/prod_override.bson")
+            .value(BsonValue::Map(Default::default()))
+            .env_prefix("EXPLAIN_TEST_");
+        let file = |path: &str, line| Origin::File {
+            path: PathBuf::from(path),
+            line: Some(line),
+        };
+        assert_eq!(
+            layers.explain_path("database.host").unwrap(),
+            Some(file("tests/test_data/prod_override.bson", 6))
+        );
+        assert_eq!(
+            layers
+                .explain_path("database.pool.KERNEL_FLAGS.retry_strategy")
+                .unwrap(),
+            Some(file("tests/test_data/main_input.bson", 22))
+        );
+        let app_name = layers.explain_path("app_name").unwrap().unwrap();
+        assert_eq!(
+            app_name,
+            Origin::Env {
+                name: "EXPLAIN_TEST_APP_NAME".to_string()
+            }
+        );
+        assert_eq!(
+            app_name.to_string(),
+            "environment variable EXPLAIN_TEST_APP_NAME"
+        );
+        assert_eq!(layers.explain_path("database.nope").unwrap(), None);
+
+        let production = Layers::new()
+            .file("tests/test_data/profiles.bson")
+            .profile("production");
+        let origins = production.explain().unwrap();
+        assert_eq!(
+            origins["database.host"].to_string(),
+            "tests/test_data/profiles.bson:10"
+        );
+        assert_eq!(
+            origins["database.port"],
+            file("tests/test_data/profiles.bson", 5)
+        );
+        assert!(origins.keys().all(|path| !path.starts_with("profile")));
+    }
+
+    #[test]
+    fn options_reach_file_layers() {
+        let layers = Layers::new().file("tests/test_data/invalid_charizard.bson");
+        assert!(matches!(layers.load(), Err(LoadError::Parse(_))));
+        let config = layers
+            .options(ParseOptions::new().allow("Charizard"))
+            .load()
+            .unwrap();
+        assert!(config.get("Charizard").is_some());
+    }
+}
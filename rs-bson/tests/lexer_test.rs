@@ -1,7 +1,8 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::path::Path;
 
 use rs_bson::lexer;
+use rs_bson::parser::{self, ParseOptions};
 
 #[cfg(test)]
 pub mod parser_tests {
@@ -11,27 +12,401 @@ pub mod parser_tests {
     fn fail_invalid_header() {
         let input = Path::new("tests/test_data/invalid_header.bson");
         let file = File::open(input).unwrap();
-        assert_eq!(lexer::lex(file), Err("Status: Fainted"));
+        assert_eq!(lexer::lex(file).unwrap_err().message(), "Status: Fainted");
     }
 
     #[test]
     fn fail_tab_character() {
         let input = Path::new("tests/test_data/invalid_tab_character.bson");
         let file = File::open(input).unwrap();
-        assert_eq!(lexer::lex(file), Err("Poison Type: Tab character detected"));
+        assert_eq!(
+            lexer::lex(file).unwrap_err().message(),
+            "Poison Type: Tab character detected"
+        );
     }
 
     #[test]
     fn fail_wrong_indentation() {
         let input = Path::new("tests/test_data/invalid_wrong_indentation.bson");
         let file = File::open(input).unwrap();
-        assert_eq!(lexer::lex(file), Err("The attack missed!"));
+        assert_eq!(
+            lexer::lex(file).unwrap_err().message(),
+            "The attack missed!"
+        );
     }
 
     #[test]
     fn fail_invalid_type() {
         let input = Path::new("tests/test_data/invalid_type.bson");
         let file = File::open(input).unwrap();
-        assert_eq!(lexer::lex(file), Err("Target is immune!"));
+        assert_eq!(lexer::lex(file).unwrap_err().message(), "Target is immune!");
+    }
+
+    #[test]
+    fn fail_without_panicking() {
+        // Inputs the fuzz targets turned up
+        let cases = [
+            ("BULBA!\n(o) (o)\n", "It hurt itself in its confusion!"),
+            ("BULBA!\n(o) a ~> (o)\n", "It hurt itself in its confusion!"),
+            ("BULBA!\nkey ~> \"\n", "Target is immune!"),
+            ("BULBA!\nkey ~> <|>\n", "Target is immune!"),
+            ("BULBA!\nkey ~> NaN\n", "Target is immune!"),
+            ("BULBA!\nkey ~> 1e999\n", "Target is immune!"),
+        ];
+        for (input, err) in cases {
+            assert_eq!(
+                lexer::lex_str(input).unwrap_err().message(),
+                err,
+                "{input:?}"
+            );
+        }
+        let tokens = lexer::lex_str("BULBA!\n(o)   spaced   (o)\n").unwrap();
+        assert_eq!(tokens[3].literal, "spaced");
+    }
+
+    #[test]
+    fn lex_line_by_line() {
+        let input = fs::read_to_string("tests/test_data/valid.bson").unwrap();
+        let mut lexer = lexer::Lexer::new();
+        for line in input.lines() {
+            lexer.push_line(line).unwrap();
+        }
+        assert_eq!(lexer.finish().unwrap(), lexer::lex_str(&input).unwrap());
+
+        let mut lexer = lexer::Lexer::new();
+        assert_eq!(lexer.push_line("IVYSAUR!"), Err("Status: Fainted"));
+    }
+
+    #[test]
+    fn lenient_tabs() {
+        let input = fs::read_to_string("tests/test_data/tabs.bson").unwrap();
+        assert_eq!(
+            lexer::lex_str(&input).unwrap_err().message(),
+            "Poison Type: Tab character detected"
+        );
+
+        let options = ParseOptions::new().lenient_tabs(4);
+        let (tokens, warnings) = lexer::lex_str_with_warnings(&input, &options).unwrap();
+        let parsed = parser::parse_with(&tokens, &options).unwrap();
+        assert!(parsed.get("database.host").is_some());
+        assert_eq!(
+            warnings,
+            vec![lexer::Warning {
+                line: 3,
+                message: String::from("Poison Type: Tab character read as 4 spaces"),
+            }]
+        );
+
+        // Two spaces per tab leaves the indentation off the grid
+        let options = ParseOptions::new().lenient_tabs(2);
+        assert_eq!(
+            lexer::lex_str_with(&input, &options).unwrap_err().message(),
+            "The attack missed!"
+        );
+    }
+
+    #[test]
+    fn skip_bom_and_refuse_utf16() {
+        let bom = File::open(Path::new("tests/test_data/bom.bson")).unwrap();
+        let tokens = lexer::lex(bom).unwrap();
+        assert_eq!(tokens[0].literal, "BULBA!");
+        assert!(lexer::lex_str("\u{feff}BULBA!\n").is_ok());
+
+        let utf16 = File::open(Path::new("tests/test_data/utf16.bson")).unwrap();
+        assert_eq!(
+            lexer::lex(utf16).unwrap_err().message(),
+            "Wrong region: BSON is UTF-8 only"
+        );
+        let bytes = fs::read("tests/test_data/utf16.bson").unwrap();
+        assert_eq!(
+            lexer::check_encoding(&bytes[2..]),
+            Err("Wrong region: BSON is UTF-8 only")
+        );
+    }
+
+    #[test]
+    fn crlf_lexes_like_lf() {
+        let lf = lexer::lex(File::open("tests/test_data/valid.bson").unwrap()).unwrap();
+        let crlf = lexer::lex(File::open("tests/test_data/valid_crlf.bson").unwrap()).unwrap();
+        assert_eq!(crlf, lf);
+
+        let mut lexer = lexer::Lexer::new();
+        lexer.push_line("BULBA!\r").unwrap();
+        lexer.push_line("name ~> \"Bulbasaur\"   \r").unwrap();
+        assert_eq!(
+            lexer.finish().unwrap(),
+            lexer::lex_str("BULBA!\nname ~> \"Bulbasaur\"\n").unwrap()
+        );
+    }
+
+    #[test]
+    fn token_spans() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let lines: Vec<&str> = input.lines().collect();
+        for token in lexer::lex_str(&input).unwrap() {
+            let text = lines
+                .get(token.line - 1)
+                .map_or("", |line| &line[token.span.clone()]);
+            let expected = match token.ttype {
+                lexer::TokenType::TString => format!("\"{}\"", token.literal),
+                lexer::TokenType::Bool if token.literal == "true" => String::from(lexer::TRUE),
+                lexer::TokenType::Bool => String::from(lexer::FALSE),
+                lexer::TokenType::Null => String::from(lexer::NULL),
+                lexer::TokenType::ArrayStart => String::from(lexer::ARRAY_OPEN),
+                lexer::TokenType::ArrayEnd => String::from(lexer::ARRAY_CLOSE),
+                lexer::TokenType::Comma => String::from(","),
+                lexer::TokenType::SectionOpen | lexer::TokenType::SectionClose => {
+                    String::from(lexer::STAGES[token.level - 1])
+                }
+                lexer::TokenType::Indent => " ".repeat(token.level * 4),
+                lexer::TokenType::VineWhip => "~".repeat(text.len().max(2) - 1) + ">",
+                lexer::TokenType::Eof => String::new(),
+                _ => token.literal.clone(),
+            };
+            assert_eq!(text, expected, "{token:?}");
+        }
+    }
+
+    #[test]
+    fn single_quoted_strings() {
+        let input = "BULBA!\nmotto ~> 'Gotta catch \"em\" all'\nlist ~> <| 'a', \"b\" |>\n";
+        let tokens = lexer::lex_str(input).unwrap();
+        let strings: Vec<&str> = tokens
+            .iter()
+            .filter(|token| token.ttype == lexer::TokenType::TString)
+            .map(|token| token.literal.as_str())
+            .collect();
+        assert_eq!(strings, vec!["Gotta catch \"em\" all", "a", "b"]);
+
+        let value = parser::parse(&tokens).unwrap();
+        let text = rs_bson::emit(&value).unwrap();
+        assert!(text.contains("motto ~> 'Gotta catch \"em\" all'"), "{text}");
+        assert!(text.contains("<| \"a\", \"b\" |>"), "{text}");
+        assert!(lexer::lex_str("BULBA!\nbroken ~> 'open\"\n").is_err());
+    }
+
+    #[test]
+    fn extra_keyword_spellings() {
+        let input = "BULBA!\nenabled ~> true\nflags ~> <| false, null, SuperEffective |>\n";
+        assert_eq!(
+            lexer::lex_str(input).unwrap_err().message(),
+            "Target is immune!"
+        );
+
+        let options = ParseOptions::new()
+            .json_keywords()
+            .keyword("nada", lexer::NULL);
+        let tokens = lexer::lex_str_with(input, &options).unwrap();
+        let value = parser::parse_with(&tokens, &options).unwrap();
+        let text = rs_bson::emit(&value).unwrap();
+        assert!(text.contains("enabled ~> SuperEffective"), "{text}");
+        assert!(
+            text.contains("<| NotVeryEffective, MissingNo, SuperEffective |>"),
+            "{text}"
+        );
+        let nada = lexer::lex_str_with("BULBA!\ngone ~> nada\n", &options).unwrap();
+        assert_eq!(nada[4].ttype, lexer::TokenType::Null);
+    }
+
+    #[test]
+    fn type_suffixes() {
+        use parser::BsonValue;
+        let input = "BULBA!\nport ~> 8080i\nratio ~> 1.5f\nreleased ~> \"2024-02-29\"d\nat ~> '2024-01-01T12:30:00+02:00'd\n";
+        let options = ParseOptions::new();
+        let tokens = lexer::lex_str_with(input, &options).unwrap();
+        let value = parser::parse_with(&tokens, &options).unwrap();
+        assert_eq!(value.get("port"), Some(&BsonValue::Integer(8080)));
+        assert_eq!(value.get("ratio"), Some(&BsonValue::Float(1.5)));
+        assert_eq!(
+            value.get("released"),
+            Some(&BsonValue::Date("2024-02-29".into()))
+        );
+        assert_eq!(
+            value.get("at"),
+            Some(&BsonValue::Date("2024-01-01T12:30:00+02:00".into()))
+        );
+
+        // Emitting keeps every suffix
+        let emitted = rs_bson::emit::emit(&value).unwrap();
+        for typed in ["> 8080i\n", "> 1.5f\n", "> \"2024-02-29\"d\n"] {
+            assert!(emitted.contains(typed), "{emitted}");
+        }
+        let tokens = lexer::lex_str(&emitted).unwrap();
+        assert_eq!(parser::parse(&tokens), Ok(value));
+
+        // A token the lexer would never hand over is refused, not a panic
+        let mut forged = lexer::lex_str("BULBA!\nratio ~> 1.5f\n").unwrap();
+        forged[4].literal = String::from("onef");
+        assert_eq!(
+            parser::parse(&forged).unwrap_err().message(),
+            "Target is immune!"
+        );
+
+        for bad in [
+            "1.5i",
+            "\"2023-02-29\"d",
+            "\"2024-13-01\"d",
+            "\"soon\"d",
+            "fi",
+        ] {
+            let input = format!("BULBA!\nvalue ~> {bad}\n");
+            assert_eq!(
+                lexer::lex_str(&input).unwrap_err().message(),
+                "Target is immune!",
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn dates_keys_and_conditions() {
+        for good in [
+            "2024-01-01",
+            "2024-01-01T23:59:60Z",
+            "2024-01-01T00:00:00.125-05:30",
+            "2024-01-01T00:00:00",
+        ] {
+            let input = format!("BULBA!\nat ~> \"{good}\"d\n");
+            assert!(lexer::lex_str(&input).is_ok(), "{good}");
+        }
+        for bad in [
+            "2024-1-01",
+            "2024-01-01T24:00:00Z",
+            "2024-01-01T00:00:00.Z",
+            "2024-01-01T00:00:00+24:00",
+            "2024-01-01 00:00:00",
+            "２０２４-01-01",
+        ] {
+            let input = format!("BULBA!\nat ~> \"{bad}\"d\n");
+            assert_eq!(
+                lexer::lex_str(&input).unwrap_err().message(),
+                "Target is immune!",
+                "{bad}"
+            );
+        }
+
+        assert!(lexer::is_dotted_key("database.pool_2"));
+        for bad in ["", "2fast", "a..b", "a.", "kéy"] {
+            assert!(!lexer::is_dotted_key(bad), "{bad}");
+        }
+        assert_eq!(lexer::split_pair("a.b~~> 1"), Some((0..3, 3..6)));
+        assert_eq!(lexer::split_pair("a - ~> 1"), None);
+        assert_eq!(lexer::split_pair("a ~~ 1"), None);
+
+        assert_eq!(
+            lexer::condition("when env  ==\t'prod'"),
+            Some(("env", true, "prod"))
+        );
+        assert_eq!(
+            lexer::condition("when region != \"\""),
+            Some(("region", false, ""))
+        );
+        for bad in [
+            "whenenv == 'x'",
+            "when 1env == 'x'",
+            "when env == 'x\"",
+            "when env = 'x'",
+        ] {
+            assert_eq!(lexer::condition(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn continue_long_lines() {
+        let input = "BULBA!
+motto ~> \"Gotta catch \\
+    them all\"
+(o) team (o)
+    members ~> <| \"Bulbasaur\", \\
+                  \"Ivysaur\" |> zZz more to come
+    lead ~> \"Ash\"
+";
+        let tokens = lexer::lex_str(input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        let expected = lexer::lex_str(
+            "BULBA!
+motto ~> \"Gotta catch them all\"
+(o) team (o)
+    members ~> <| \"Bulbasaur\", \"Ivysaur\" |>
+    lead ~> \"Ash\"
+",
+        )
+        .unwrap();
+        assert_eq!(value, parser::parse(&expected).unwrap());
+        let lead = tokens.iter().find(|token| token.literal == "lead").unwrap();
+        assert_eq!(lead.line, 7);
+
+        assert_eq!(
+            lexer::lex_str("BULBA!\nname ~> \"a\" \\\n")
+                .unwrap_err()
+                .message(),
+            "It hurt itself in its confusion!"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn regex_literals() {
+        use parser::BsonValue;
+        let input =
+            "BULBA!\npattern ~> /^poke[0-9]+$/\nrules ~> <| /a+/, \"b\" |>\nbroken ~> /[/\n";
+        let tokens = lexer::lex_str(input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        let pattern = value.get("pattern").unwrap();
+        assert_eq!(pattern, &BsonValue::Regex("^poke[0-9]+$".into()));
+        assert!(pattern.to_regex().unwrap().is_match("poke151"));
+        assert_eq!(value.get("rules.0"), Some(&BsonValue::Regex("a+".into())));
+        assert!(value.get("broken").unwrap().to_regex().is_none());
+        let text = rs_bson::emit(&value).unwrap();
+        assert!(text.contains("pattern ~> /^poke[0-9]+$/"), "{text}");
+
+        let options = ParseOptions::new().check_regexes();
+        let tokens = lexer::lex_str_with(input, &options).unwrap();
+        assert_eq!(
+            parser::parse_with(&tokens, &options).unwrap_err().message(),
+            "Target is immune!"
+        );
+    }
+
+    #[test]
+    fn lex_bytes_with_invalid_utf8() {
+        use parser::{BsonValue, Utf8Policy};
+        let input = b"BULBA!\nblob ~> \"ab\xff\xfecd\"\nnote ~> 'x' zZz caf\xe9\nname ~> \"ok\"\n";
+        let strict = ParseOptions::new();
+        assert_eq!(
+            lexer::lex_bytes(input, &strict).unwrap_err().message(),
+            "It hurt itself in its confusion!"
+        );
+        let text = "BULBA!\nname ~> \"ok\"\n";
+        assert_eq!(
+            lexer::lex_bytes(text.as_bytes(), &strict),
+            lexer::lex_str(text)
+        );
+
+        let value = |policy| {
+            let options = ParseOptions::new().invalid_utf8(policy);
+            let tokens = lexer::lex_bytes(input, &options).unwrap();
+            parser::parse(&tokens).unwrap().into_owned()
+        };
+        let replaced = value(Utf8Policy::Replace);
+        assert_eq!(
+            replaced.get("blob"),
+            Some(&BsonValue::from("ab\u{fffd}\u{fffd}cd"))
+        );
+        assert_eq!(replaced.get("name"), Some(&BsonValue::from("ok")));
+        let passed = value(Utf8Policy::PassThrough);
+        assert_eq!(
+            passed.get("blob"),
+            Some(&BsonValue::from("ab\u{ff}\u{fe}cd"))
+        );
+
+        // Only string values may hold them
+        let options = ParseOptions::new().invalid_utf8(Utf8Policy::Replace);
+        assert_eq!(
+            lexer::lex_bytes(b"BULBA!\nke\xffy ~> 1\n", &options)
+                .unwrap_err()
+                .message(),
+            "It hurt itself in its confusion!"
+        );
     }
 }
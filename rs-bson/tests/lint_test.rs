@@ -0,0 +1,99 @@
+#![cfg(feature = "schema")]
+
+use std::fs;
+
+use rs_bson::document::Document;
+use rs_bson::lexer;
+use rs_bson::lint::{Linter, Rule};
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::schema::Severity;
+
+#[cfg(test)]
+pub mod lint_tests {
+    use crate::*;
+
+    struct NoCharmander;
+
+    impl Rule for NoCharmander {
+        fn name(&self) -> &str {
+            "no_charmander"
+        }
+
+        fn severity(&self) -> Severity {
+            Severity::Error
+        }
+
+        fn check(&self, _path: &str, value: &BsonValue) -> Result<(), String> {
+            match value {
+                BsonValue::BString(s) if s == "Charmander" => Err(String::from("Too hot")),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn parse(input: &str) -> BsonValue<'static> {
+        let tokens = lexer::lex_str(input).unwrap();
+        parser::parse(&tokens).unwrap().into_owned()
+    }
+
+    fn load_doc() -> Document {
+        Document::parse(&fs::read_to_string("tests/test_data/lint.bson").unwrap()).unwrap()
+    }
+
+    fn summary(linter: &Linter) -> Vec<(String, Option<usize>, Severity, String)> {
+        let violations = linter.lint(&load_doc());
+        violations
+            .into_iter()
+            .map(|v| (v.path, v.line, v.severity, v.message))
+            .collect()
+    }
+
+    #[test]
+    fn lint_builtin_rules_and_suppression() {
+        assert_eq!(
+            summary(&Linter::default()),
+            vec![(
+                String::from("password"),
+                Some(5),
+                Severity::Warning,
+                String::from("Plaintext credential, use secret(\"...\") [no_plaintext_secrets]")
+            )]
+        );
+        assert_eq!(summary(&Linter::new()), vec![]);
+    }
+
+    #[test]
+    fn lint_custom_rule() {
+        let linter = Linter::new().rule(NoCharmander);
+        assert_eq!(
+            summary(&linter),
+            vec![(
+                String::from("party.1"),
+                Some(8),
+                Severity::Error,
+                String::from("Too hot [no_charmander]")
+            )]
+        );
+        assert_eq!(
+            summary(&linter.severity("no_charmander", Some(Severity::Warning)))[0].2,
+            Severity::Warning
+        );
+    }
+
+    #[test]
+    fn lint_severity_config() {
+        let config = "BULBA!\n(o) rules (o)\n    no_plaintext_secrets ~> \"error\"\n    empty_section ~~~~~~~~> \"off\"\n";
+        let config = parse(config);
+        let linter = Linter::default().configure(&config).unwrap();
+        let violations = summary(&linter);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].2, Severity::Error);
+
+        let config = "BULBA!\n(o) rules (o)\n    empty_section ~> \"loud\"\n";
+        let config = parse(config);
+        assert_eq!(
+            Linter::default().configure(&config).err(),
+            Some("Target is immune!")
+        );
+    }
+}
@@ -0,0 +1,178 @@
+#![cfg(feature = "schema")]
+
+use std::fs;
+
+use rs_bson::json::JsonValue;
+use rs_bson::lsp::Server;
+use rs_bson::schema::Schema;
+
+#[cfg(test)]
+pub mod lsp_tests {
+    use crate::*;
+
+    fn frame(message: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{message}", message.len())
+    }
+
+    // Runs a session and returns every message the server sent
+    fn session(schema: Option<Schema>, messages: &[String]) -> Vec<JsonValue> {
+        let input: String = messages.iter().map(|message| frame(message)).collect();
+        let mut output = vec![];
+        Server::new(schema)
+            .run(input.as_bytes(), &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        output
+            .split("Content-Length: ")
+            .skip(1)
+            .map(|framed| JsonValue::parse(framed.split_once("\r\n\r\n").unwrap().1).unwrap())
+            .collect()
+    }
+
+    fn open(text: &str) -> String {
+        let text = JsonValue::String(text.to_string());
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///a.bson","text":{text}}}}}}}"#
+        )
+    }
+
+    fn request(id: usize, method: &str, params: &str) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":{id},"method":"{method}","params":{{"textDocument":{{"uri":"file:///a.bson"}}{params}}}}}"#
+        )
+    }
+
+    #[test]
+    fn diagnostics_hover_and_symbols() {
+        let schema =
+            Schema::parse(&fs::read_to_string("tests/test_data/main_input.bulbaschema").unwrap())
+                .unwrap();
+        let text = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let messages = session(
+            Some(schema),
+            &[
+                request(1, "initialize", ""),
+                open(&text),
+                request(
+                    2,
+                    "textDocument/hover",
+                    r#","position":{"line":21,"character":14}"#,
+                ),
+                request(3, "textDocument/documentSymbol", ""),
+                request(4, "textDocument/definition", ""),
+                r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+            ],
+        );
+        assert_eq!(messages.len(), 5);
+
+        let capabilities = messages[0]
+            .get("result")
+            .unwrap()
+            .get("capabilities")
+            .unwrap();
+        assert_eq!(
+            capabilities.get("hoverProvider"),
+            Some(&JsonValue::Bool(true))
+        );
+        // main_input.bson follows its schema
+        assert_eq!(
+            messages[1].get("params").unwrap().get("diagnostics"),
+            Some(&JsonValue::Array(vec![]))
+        );
+        let hover = messages[2].get("result").unwrap().get("contents").unwrap();
+        assert_eq!(
+            hover.get("value").and_then(JsonValue::as_str),
+            Some(
+                "`database.pool.KERNEL_FLAGS.retry_strategy`: string\n\nExpected `string`\n\n\
+What to do after a failed connection"
+            )
+        );
+
+        let Some(JsonValue::Array(symbols)) = messages[3].get("result") else {
+            panic!("expected symbols");
+        };
+        let names: Vec<&str> = symbols
+            .iter()
+            .filter_map(|symbol| symbol.get("name").and_then(JsonValue::as_str))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "app_name",
+                "version",
+                "is_production",
+                "missing_data",
+                "zapdos",
+                "database",
+                "whitelist"
+            ]
+        );
+        let database = symbols[5].get("range").unwrap();
+        assert_eq!(
+            database.get("start").unwrap().get("line"),
+            Some(&JsonValue::Number(10.0))
+        );
+        assert_eq!(
+            database.get("end").unwrap().get("line"),
+            Some(&JsonValue::Number(21.0))
+        );
+
+        assert!(messages[4].get("error").is_some());
+    }
+
+    #[test]
+    fn syntax_errors_and_formatting() {
+        let messages = session(
+            None,
+            &[
+                open("BULBA!\nname ~> \"Bulbasaur\"\n(o) Charizard (o)\n"),
+                request(1, "textDocument/formatting", ""),
+            ],
+        );
+        let Some(JsonValue::Array(diagnostics)) =
+            messages[0].get("params").unwrap().get("diagnostics")
+        else {
+            panic!("expected diagnostics");
+        };
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].get("message").and_then(JsonValue::as_str),
+            Some("It burns the bulb")
+        );
+        let start = diagnostics[0].get("range").unwrap().get("start").unwrap();
+        assert_eq!(start.get("line"), Some(&JsonValue::Number(2.0)));
+        assert_eq!(messages[1].get("result"), Some(&JsonValue::Array(vec![])));
+
+        let messages = session(
+            None,
+            &[
+                open("BULBA!\nname~>\"Bulbasaur\"\n"),
+                request(1, "textDocument/formatting", ""),
+            ],
+        );
+        let Some(JsonValue::Array(edits)) = messages[1].get("result") else {
+            panic!("expected edits");
+        };
+        assert_eq!(
+            edits[0].get("newText").and_then(JsonValue::as_str),
+            Some("BULBA!\nname ~> \"Bulbasaur\"\n")
+        );
+    }
+
+    #[test]
+    fn violations_point_at_the_value() {
+        use rs_bson::schema::Type;
+        let schema = Schema::map().key("port", Type::Int.range(1..=65535));
+        let messages = session(Some(schema), &[open("BULBA!\nport ~~~> 99999\n")]);
+        let Some(JsonValue::Array(diagnostics)) =
+            messages[0].get("params").unwrap().get("diagnostics")
+        else {
+            panic!("expected diagnostics");
+        };
+        let range = diagnostics[0].get("range").unwrap();
+        let at = |end: &str, field: &str| range.get(end).unwrap().get(field).cloned();
+        assert_eq!(at("start", "line"), Some(JsonValue::Number(1.0)));
+        assert_eq!(at("start", "character"), Some(JsonValue::Number(10.0)));
+        assert_eq!(at("end", "character"), Some(JsonValue::Number(15.0)));
+    }
+}
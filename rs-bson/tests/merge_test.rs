@@ -0,0 +1,121 @@
+use rs_bson::lexer;
+use rs_bson::merge::Conflict;
+use rs_bson::parser::{self, BsonValue};
+
+#[cfg(test)]
+pub mod merge_tests {
+    use crate::*;
+
+    const BASE: &str = "BULBA!
+name ~> \"Bulbasaur\"
+level ~> 5
+(o) moves (o)
+    first ~> \"Tackle\"
+";
+    const OURS: &str = "BULBA!
+name ~> \"Bulbasaur\"
+level ~> 6
+(o) moves (o)
+    first ~> \"Tackle\"
+    second ~> \"Growl\"
+";
+    const THEIRS: &str = "BULBA!
+name ~> \"Ivysaur\"
+level ~> 7
+(o) moves (o)
+    first ~> \"Vine Whip\"
+";
+
+    #[test]
+    fn merge3_combines_and_reports_conflicts() {
+        let base_tokens = lexer::lex_str(BASE).unwrap();
+        let our_tokens = lexer::lex_str(OURS).unwrap();
+        let their_tokens = lexer::lex_str(THEIRS).unwrap();
+        let base = parser::parse(&base_tokens).unwrap();
+        let ours = parser::parse(&our_tokens).unwrap();
+        let theirs = parser::parse(&their_tokens).unwrap();
+
+        let merged = rs_bson::merge3(&base, &ours, &theirs);
+        let expected = "level: 6
+moves:
+    first: Vine Whip
+    second: Growl
+name: Ivysaur
+";
+        assert_eq!(merged.value.to_string(), expected);
+        assert_eq!(
+            merged.conflicts,
+            vec![Conflict {
+                path: "level".to_string(),
+                base: Some(BsonValue::Number(5.0)),
+                ours: Some(BsonValue::Number(6.0)),
+                theirs: Some(BsonValue::Number(7.0)),
+            }]
+        );
+    }
+
+    #[test]
+    fn merge3_takes_removals_from_either_side() {
+        let base = parse("BULBA!\na ~> 1\nb ~> 2\nc ~> 3\ne ~> 5\n");
+        let ours = parse("BULBA!\nb ~> 20\nc ~> 3\n");
+        let theirs = parse("BULBA!\na ~> 1\nd ~> 4\ne ~> 50\n");
+
+        let merged = rs_bson::merge3(&base, &ours, &theirs);
+        assert_eq!(merged.value, parse("BULBA!\nb ~> 20\nd ~> 4\n"));
+        assert_eq!(
+            merged.conflicts,
+            vec![
+                Conflict {
+                    path: "b".to_string(),
+                    base: Some(BsonValue::Number(2.0)),
+                    ours: Some(BsonValue::Number(20.0)),
+                    theirs: None,
+                },
+                Conflict {
+                    path: "e".to_string(),
+                    base: Some(BsonValue::Number(5.0)),
+                    ours: None,
+                    theirs: Some(BsonValue::Number(50.0)),
+                },
+            ]
+        );
+    }
+
+    fn parse(input: &str) -> BsonValue<'static> {
+        let tokens = lexer::lex_str(input).unwrap();
+        parser::parse(&tokens).unwrap().into_owned()
+    }
+
+    #[test]
+    fn with_defaults_fills_missing_keys() {
+        let defaults = parse(
+            "BULBA!
+name ~> \"Bulbasaur\"
+level ~> 5
+held_item ~> \"Oran Berry\"
+(o) moves (o)
+    first ~> \"Tackle\"
+    second ~> \"Growl\"
+",
+        );
+        let value = parse(
+            "BULBA!
+level ~> 7
+held_item ~> MissingNo
+(o) moves (o)
+    first ~> \"Vine Whip\"
+",
+        );
+        let expected = parse(
+            "BULBA!
+name ~> \"Bulbasaur\"
+level ~> 7
+held_item ~> MissingNo
+(o) moves (o)
+    first ~> \"Vine Whip\"
+    second ~> \"Growl\"
+",
+        );
+        assert_eq!(value.with_defaults(&defaults), expected);
+    }
+}
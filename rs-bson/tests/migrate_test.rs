@@ -0,0 +1,86 @@
+use rs_bson::lexer;
+use rs_bson::migrate::{Migration, Migrations};
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::patch::Patch;
+
+#[cfg(test)]
+pub mod migrate_tests {
+    use crate::*;
+
+    // v1 -> v2: `db_host` moved into the database section
+    fn nest_host(mut value: BsonValue<'static>) -> BsonValue<'static> {
+        let patch = Patch::new()
+            .add("/database", BsonValue::Map(Default::default()))
+            .move_to("/db_host", "/database/host");
+        value.apply_patch(&patch).unwrap();
+        value
+    }
+
+    // v2 -> v3: timeouts are now in milliseconds
+    fn timeout_to_ms(mut value: BsonValue<'static>) -> BsonValue<'static> {
+        if let Some(BsonValue::Number(secs)) = value.get_mut("database.timeout") {
+            *secs *= 1000.0;
+        }
+        value
+    }
+
+    fn parse(input: &str) -> BsonValue<'static> {
+        let tokens = lexer::lex_str(input).unwrap();
+        parser::parse(&tokens).unwrap().into_owned()
+    }
+
+    #[test]
+    fn migrate_to_latest_runs_every_step() {
+        let migrations = Migrations::new()
+            .register(Migration {
+                from: 2,
+                to: 3,
+                transform: timeout_to_ms,
+            })
+            .register(Migration {
+                from: 1,
+                to: 2,
+                transform: nest_host,
+            });
+        assert_eq!(migrations.latest(), 3);
+
+        let old = parse("BULBA!\ndb_host ~> \"127.0.0.1\"\n");
+        let expected = parse(
+            "BULBA!
+version ~> 3
+(o) database (o)
+    host ~> \"127.0.0.1\"
+",
+        );
+        assert_eq!(migrations.migrate_to_latest(old), Ok(expected.clone()));
+        assert_eq!(migrations.migrate_to_latest(expected.clone()), Ok(expected));
+
+        let v2 = parse(
+            "BULBA!
+version ~> 2
+(o) database (o)
+    timeout ~> 5
+",
+        );
+        let migrated = migrations.migrate_to_latest(v2).unwrap();
+        assert_eq!(
+            migrated.get("database.timeout"),
+            Some(&BsonValue::Number(5000.0))
+        );
+
+        let future = parse("BULBA!\nversion ~> 4\n");
+        assert_eq!(
+            migrations.migrate_to_latest(future),
+            Err("Not enough badges!")
+        );
+        let gap = Migrations::new().register(Migration {
+            from: 2,
+            to: 3,
+            transform: timeout_to_ms,
+        });
+        assert_eq!(
+            gap.migrate_to_latest(parse("BULBA!\n")),
+            Err("But it failed!")
+        );
+    }
+}
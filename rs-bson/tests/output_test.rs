@@ -0,0 +1,63 @@
+#![cfg(feature = "schema")]
+
+use rs_bson::lexer::Warning;
+use rs_bson::output::{self, ParseStats};
+use rs_bson::parser::{BsonValue, EmptyPolicy, ParseOptions};
+
+#[cfg(test)]
+pub mod output_tests {
+    use crate::*;
+
+    #[test]
+    fn keep_parse_metadata() {
+        let options = ParseOptions::new();
+        let out = output::parse_file_output("tests/test_data/valid.bson.gz", &options).unwrap();
+        assert_eq!(
+            out.source_name.as_deref(),
+            Some("tests/test_data/valid.bson.gz")
+        );
+        assert_eq!(out.header_version.as_deref(), Some("BULBA!"));
+        assert_eq!(out.stats.leaves, out.value.leaf_count());
+        assert!(out.stats.tokens > out.stats.lines);
+        assert!(out.warnings.is_empty());
+
+        let options = ParseOptions::new()
+            .accept_header("BULBA2!")
+            .lenient_tabs(4)
+            .header_only(EmptyPolicy::EmptyDocument);
+        let out = output::parse_output("BULBA2!\nname ~>\t1\n", &options).unwrap();
+        assert_eq!(out.source_name, None);
+        assert_eq!(out.header_version.as_deref(), Some("BULBA2!"));
+        assert_eq!(out.value.get("name"), Some(&BsonValue::Number(1.0)));
+        assert_eq!(
+            out.stats,
+            ParseStats {
+                bytes: 18,
+                lines: 2,
+                tokens: 6,
+                leaves: 1,
+                depth: 0,
+                size: out.value.deep_size(),
+            }
+        );
+        assert_eq!(
+            out.warnings,
+            vec![Warning {
+                line: 2,
+                message: String::from("Poison Type: Tab character read as 4 spaces"),
+            }]
+        );
+
+        let empty = ParseOptions::new().empty_file(EmptyPolicy::EmptyDocument);
+        assert_eq!(
+            output::parse_output("", &empty).unwrap().header_version,
+            None
+        );
+        assert_eq!(
+            output::parse_output("IVYSAUR!\n", &ParseOptions::new())
+                .unwrap_err()
+                .message(),
+            "Status: Fainted"
+        );
+    }
+}
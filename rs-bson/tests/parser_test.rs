@@ -1,4 +1,4 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::path::Path;
 
 use rs_bson::lexer;
@@ -36,7 +36,10 @@ whitelist:
         let input = Path::new("tests/test_data/invalid_charizard.bson");
         let file = File::open(input).unwrap();
         let tokens = lexer::lex(file).unwrap();
-        assert_eq!(parser::parse(&tokens), Err("It burns the bulb"));
+        assert_eq!(
+            parser::parse(&tokens).unwrap_err().message(),
+            "It burns the bulb"
+        );
     }
 
     #[test]
@@ -44,6 +47,744 @@ whitelist:
         let input = Path::new("tests/test_data/invalid_nesting.bson");
         let file = File::open(input).unwrap();
         let tokens = lexer::lex(file).unwrap();
-        assert_eq!(parser::parse(&tokens), Err("Not enough badges!"));
+        assert_eq!(
+            parser::parse(&tokens).unwrap_err().message(),
+            "Not enough badges!"
+        );
+    }
+
+    #[test]
+    fn get_ignoring_case() {
+        let input = "BULBA!\nHost ~> \"a\"\nhost ~> \"b\"\n(o) Database (o)\n    Port ~> 5432\n";
+        let tokens = lexer::lex_str(input).unwrap();
+        let parsed = parser::parse(&tokens).unwrap();
+        let port = parser::BsonValue::Number(5432.0);
+        assert_eq!(parsed.get_ci("DATABASE.port"), Some(&port));
+        assert_eq!(parsed.get("DATABASE.port"), None);
+        // The exact spelling wins over other casings
+        let text = |s: &'static str| parser::BsonValue::BString(s.into());
+        assert_eq!(parsed.get_ci("host"), Some(&text("b")));
+        assert_eq!(parsed.get_ci("Host"), Some(&text("a")));
+        assert!(parsed.to_string().contains("Database:"));
+    }
+
+    #[test]
+    fn tell_missing_no_from_absent() {
+        let input = Path::new("tests/test_data/valid.bson");
+        let tokens = lexer::lex(File::open(input).unwrap()).unwrap();
+        let parsed = parser::parse(&tokens).unwrap();
+        let null = parser::BsonValue::Null(());
+
+        assert!(parsed.is_set("missing_data") && parsed.is_null("missing_data"));
+        assert!(!parsed.is_set("not_there") && !parsed.is_null("not_there"));
+        assert_eq!(parsed.get("missing_data"), Some(&null));
+        assert_eq!(parsed.get("not_there"), None);
+        assert_eq!(parsed.get_or_null("not_there"), &null);
+        assert_eq!(parsed.get_non_null("missing_data"), None);
+        assert!(parsed.get_non_null("version").is_some());
+    }
+
+    #[test]
+    fn configure_reserved_keys() {
+        let input = Path::new("tests/test_data/invalid_charizard.bson");
+        let tokens = lexer::lex(File::open(input).unwrap()).unwrap();
+        let options = parser::ParseOptions::new().allow("Charizard");
+        assert!(parser::parse_with(&tokens, &options).is_ok());
+
+        let tokens = lexer::lex_str("BULBA!\n(o) Mewtwo (o)\n    level ~> 70\n").unwrap();
+        let options = parser::ParseOptions::new().reserve("Mewtwo");
+        assert_eq!(
+            parser::parse_with(&tokens, &options).unwrap_err().message(),
+            "It burns the bulb"
+        );
+        assert!(parser::parse(&tokens).is_ok());
+    }
+
+    #[test]
+    fn custom_header() {
+        let input = "IVYSAUR!\nlevel ~> 16\n";
+        let options = parser::ParseOptions::new().header("IVYSAUR!");
+        let tokens = lexer::lex_str_with(input, &options).unwrap();
+        assert!(parser::parse_with(&tokens, &options).is_ok());
+        assert_eq!(
+            lexer::lex_str(input).unwrap_err().message(),
+            "Status: Fainted"
+        );
+        assert_eq!(
+            lexer::lex_str_with("BULBA!\n", &options)
+                .unwrap_err()
+                .message(),
+            "Status: Fainted"
+        );
+
+        let both = parser::ParseOptions::new().accept_header("IVYSAUR!");
+        assert!(lexer::lex_str_with(input, &both).is_ok());
+        assert!(lexer::lex_str_with("BULBA!\n", &both).is_ok());
+    }
+
+    #[test]
+    fn limit_nesting_depth() {
+        let input = Path::new("tests/test_data/valid.bson");
+        let tokens = lexer::lex(File::open(input).unwrap()).unwrap();
+        let shallow = parser::ParseOptions::new().max_depth(2);
+        assert_eq!(
+            parser::parse_with(&tokens, &shallow).unwrap_err().message(),
+            "Not enough badges!"
+        );
+        assert!(parser::parse_with(&tokens, &parser::ParseOptions::new().max_depth(3)).is_ok());
+
+        // Lists nested far deeper than any lexer output must not exhaust the stack
+        let tokens = lexer::lex_str("BULBA!\nk ~> <| |>\n").unwrap();
+        let open = tokens
+            .iter()
+            .find(|t| t.ttype == lexer::TokenType::ArrayStart);
+        let mut hostile = tokens[..4].to_vec();
+        hostile.extend(std::iter::repeat_n(open.unwrap().clone(), 100_000));
+        assert_eq!(
+            parser::parse(&hostile).unwrap_err().message(),
+            "Not enough badges!"
+        );
+
+        // Past the limit, neither parsing, printing, copying nor dropping recurses
+        let close = tokens
+            .iter()
+            .find(|t| t.ttype == lexer::TokenType::ArrayEnd);
+        hostile.extend(std::iter::repeat_n(close.unwrap().clone(), 100_000));
+        hostile.push(tokens.last().unwrap().clone());
+        let deep = parser::ParseOptions::new().max_depth(200_000);
+        let value = parser::parse_with(&hostile, &deep).unwrap();
+        assert_eq!(value.max_depth(), 100_000);
+        assert_eq!(value.to_string().matches('-').count(), 99_999);
+        let copy = value.clone();
+        assert_eq!(copy, value);
+        assert_eq!(copy.into_owned().max_depth(), 100_000);
+    }
+
+    #[test]
+    #[cfg(feature = "decimal")]
+    fn keep_exact_numbers() {
+        let input = "BULBA!\nprice ~> 0.10\nid ~> 12345678901234567891\n";
+        let exact = parser::ParseOptions::new().exact_numbers();
+        let tokens = lexer::lex_str_with(input, &exact).unwrap();
+        let parsed = parser::parse_with(&tokens, &exact).unwrap();
+        let price = parsed.get("price").unwrap();
+        assert_eq!(
+            price,
+            &parser::BsonValue::Decimal(rust_decimal::Decimal::new(10, 2))
+        );
+        assert_eq!(price.as_f64(), Some(0.1));
+        let emitted = rs_bson::emit::emit(&parsed).unwrap();
+        assert!(emitted.contains("> 0.10\n") && emitted.contains("> 12345678901234567891\n"));
+
+        let float = lexer::lex_str_with("BULBA!\nratio ~> 0.10f\n", &exact).unwrap();
+        let float = parser::parse_with(&float, &exact).unwrap();
+        assert_eq!(float.get("ratio"), Some(&parser::BsonValue::Float(0.1)));
+
+        let rounded = parser::parse(&tokens).unwrap();
+        assert_eq!(rounded.get("price"), Some(&parser::BsonValue::Number(0.1)));
+    }
+
+    #[test]
+    #[cfg(feature = "decimal")]
+    fn exact_arithmetic_and_comparison() {
+        use parser::{BsonValue, ParseOptions};
+        use rust_decimal::Decimal;
+        let input = "BULBA!\na ~> 0.1\nb ~> 0.2\nsum ~> a + b\nhalf ~> -b / 2 * 3\n";
+        let options = ParseOptions::new().exact_numbers().expressions();
+        let tokens = lexer::lex_str_with(input, &options).unwrap();
+        let value = parser::parse_with(&tokens, &options).unwrap();
+        assert_eq!(
+            value.get("sum"),
+            Some(&BsonValue::Decimal(Decimal::new(3, 1)))
+        );
+        assert_eq!(
+            value.get("half"),
+            Some(&BsonValue::Decimal(Decimal::new(-3, 1)))
+        );
+        assert_eq!(
+            value.get("sum").unwrap().as_decimal(),
+            BsonValue::Number(0.3).as_decimal()
+        );
+        assert_eq!(
+            BsonValue::Integer(9007199254740993).as_decimal(),
+            Some(Decimal::from(9007199254740993i64))
+        );
+
+        let zero = "BULBA!\na ~> 0.1\nb ~> a / 0\n";
+        let tokens = lexer::lex_str_with(zero, &options).unwrap();
+        assert_eq!(
+            parser::parse_with(&tokens, &options).unwrap_err().message(),
+            "Target is immune!"
+        );
+    }
+
+    #[test]
+    fn keep_wide_integers() {
+        let input = "BULBA!\nid ~> 9007199254740993\nbig ~> 1e300\nsmall ~> 42\n";
+        let tokens = lexer::lex_str(input).unwrap();
+        let parsed = parser::parse(&tokens).unwrap();
+        assert_eq!(
+            parsed.get("id"),
+            Some(&parser::BsonValue::Integer(9007199254740993))
+        );
+        assert_eq!(parsed.get("big"), Some(&parser::BsonValue::Number(1e300)));
+        assert_eq!(parsed.get("small"), Some(&parser::BsonValue::Number(42.0)));
+        let emitted = rs_bson::emit::emit(&parsed).unwrap();
+        assert!(emitted.contains("> 9007199254740993i\n"));
+    }
+
+    #[test]
+    fn coerce_sloppy_values() {
+        let input = "BULBA!\nflag ~> \" Yes \"\noff ~> 0\nlimit ~> \"100\"\nratio ~> 0.5\nbig ~> \"9007199254740993\"\nname ~> \"ash\"\n";
+        let tokens = lexer::lex_str(input).unwrap();
+        let parsed = parser::parse(&tokens).unwrap();
+        let get = |key| parsed.get(key).unwrap();
+        assert_eq!(get("flag").as_bool_lenient(), Some(true));
+        assert_eq!(get("off").as_bool_lenient(), Some(false));
+        assert_eq!(get("ratio").as_bool_lenient(), None);
+        assert_eq!(get("limit").as_f64_lenient(), Some(100.0));
+        assert_eq!(get("limit").as_i64_lenient(), Some(100));
+        assert_eq!(get("ratio").as_i64_lenient(), None);
+        assert_eq!(get("big").as_i64_lenient(), Some(9007199254740993));
+        assert_eq!(get("name").as_f64_lenient(), None);
+        assert_eq!(get("limit").as_f64(), None);
+    }
+
+    #[test]
+    fn choose_what_empty_files_mean() {
+        use parser::{EmptyPolicy, ParseOptions};
+        let empty = lexer::lex_str("").unwrap();
+        let header_only = lexer::lex_str("BULBA!\nzZz nothing yet\n\n").unwrap();
+        let no_keys = parser::BsonValue::Map(Default::default());
+
+        assert_eq!(
+            parser::parse(&empty).unwrap_err().message(),
+            "Status: Fainted"
+        );
+        assert_eq!(parser::parse(&header_only), Ok(no_keys.clone()));
+
+        let lenient = ParseOptions::new().empty_file(EmptyPolicy::EmptyDocument);
+        assert_eq!(parser::parse_with(&empty, &lenient), Ok(no_keys));
+        let strict = ParseOptions::new().header_only(EmptyPolicy::Error);
+        assert_eq!(
+            parser::parse_with(&header_only, &strict)
+                .unwrap_err()
+                .message(),
+            "Status: Fainted"
+        );
+    }
+
+    #[test]
+    fn flatten_to_dotted_keys() {
+        let file = File::open("tests/test_data/valid.bson").unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let flat = parser::parse(&tokens).unwrap().flatten();
+        let keys: Vec<&str> = flat.keys().map(String::as_str).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "app_name",
+                "database.host",
+                "database.pool.KERNEL_FLAGS.panic_on_fail",
+                "database.pool.max_connections",
+                "is_production",
+                "missing_data",
+                "version",
+                "whitelist.0",
+                "whitelist.1",
+            ]
+        );
+        assert_eq!(
+            flat["database.pool.max_connections"],
+            parser::BsonValue::Number(100.0)
+        );
+        assert_eq!(
+            flat["whitelist.0"],
+            parser::BsonValue::BString("Prof_Oak".into())
+        );
+    }
+
+    #[test]
+    fn unflatten_dotted_keys() {
+        use parser::{BsonValue, PathConflict};
+        let file = File::open("tests/test_data/valid.bson").unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        assert_eq!(BsonValue::unflatten(value.flatten()), Ok(value));
+
+        let clash = [
+            (String::from("database.host"), BsonValue::Number(1.0)),
+            (String::from("database.host.port"), BsonValue::Number(2.0)),
+        ];
+        assert_eq!(
+            BsonValue::unflatten(clash),
+            Err(PathConflict(String::from("database.host")))
+        );
+    }
+
+    #[test]
+    fn address_with_json_pointers() {
+        use parser::BsonValue;
+        let file = File::open("tests/test_data/valid.bson").unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let mut value = parser::parse(&tokens).unwrap();
+
+        let path = "database.pool.max_connections";
+        assert_eq!(parser::to_pointer(path), "/database/pool/max_connections");
+        assert_eq!(value.pointer(&parser::to_pointer(path)), value.get(path));
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(
+            value.pointer("/whitelist/1"),
+            Some(&BsonValue::BString("Mom".into()))
+        );
+        for bad in ["database", "/whitelist/01", "/whitelist/-", "/database/~2"] {
+            assert_eq!(value.pointer(bad), None);
+        }
+
+        let BsonValue::Map(m) = &mut value else {
+            unreachable!()
+        };
+        m.insert("a/b~c.d".into(), BsonValue::Bool(true));
+        assert_eq!(value.pointer("/a~1b~0c.d"), Some(&BsonValue::Bool(true)));
+        *value.pointer_mut("/a~1b~0c.d").unwrap() = BsonValue::Bool(false);
+        assert_eq!(value.pointer("/a~1b~0c.d"), Some(&BsonValue::Bool(false)));
+    }
+
+    #[test]
+    fn take_inner_values() {
+        use parser::BsonValue;
+        let file = File::open("tests/test_data/valid.bson").unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let mut map = parser::parse(&tokens).unwrap().into_map().unwrap();
+
+        let whitelist = map.remove("whitelist").unwrap().into_array().unwrap();
+        assert_eq!(whitelist.len(), 2);
+        let name = map.remove("app_name").unwrap().into_string().unwrap();
+        assert_eq!(name, "Pokedex_API");
+
+        let version = map.remove("version").unwrap();
+        assert_eq!(version.clone().into_map(), Err(version.clone()));
+        assert_eq!(version.into_string(), Err(BsonValue::Number(1.5)));
+    }
+
+    #[test]
+    fn build_with_entries() {
+        use parser::BsonValue;
+        let file = File::open("tests/test_data/valid.bson").unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let mut value = parser::parse(&tokens).unwrap();
+
+        let database = value.entry("database").or_insert_map();
+        database.entry("host").or_insert("localhost");
+        database.entry("port").or_insert(5432);
+        value
+            .entry("version")
+            .and_modify(|version| *version = BsonValue::from(2.0))
+            .or_insert(1.0);
+        value.entry("whitelist").or_insert_array();
+        value
+            .entry("cache")
+            .or_insert_map()
+            .entry("ttl")
+            .or_insert(60);
+
+        assert_eq!(
+            value.get("database.host"),
+            Some(&BsonValue::from("127.0.0.1"))
+        );
+        assert_eq!(value.get("database.port"), Some(&BsonValue::Number(5432.0)));
+        assert_eq!(value.get("version"), Some(&BsonValue::Number(2.0)));
+        assert_eq!(value.get("whitelist.1"), Some(&BsonValue::from("Mom")));
+        assert_eq!(value.get("cache.ttl"), Some(&BsonValue::Number(60.0)));
+    }
+
+    #[test]
+    fn edit_lists() {
+        use parser::BsonValue;
+        let file = File::open("tests/test_data/valid.bson").unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let mut value = parser::parse(&tokens).unwrap();
+
+        let whitelist = value.get_mut("whitelist").unwrap();
+        whitelist.push("Nurse_Joy").unwrap();
+        whitelist.extend(["Brock", "Misty"]).unwrap();
+        whitelist.insert(0, "Ash").unwrap();
+        assert_eq!(whitelist.remove(2), Ok(BsonValue::from("Mom")));
+        whitelist
+            .retain(|name| name != &BsonValue::from("Brock"))
+            .unwrap();
+        whitelist
+            .sort_by(|a, b| format!("{a}").cmp(&format!("{b}")))
+            .unwrap();
+        let names: Vec<String> = whitelist
+            .clone()
+            .into_array()
+            .unwrap()
+            .into_iter()
+            .map(|name| name.into_string().unwrap().into_owned())
+            .collect();
+        assert_eq!(names, vec!["Ash", "Misty", "Nurse_Joy", "Prof_Oak"]);
+
+        assert_eq!(whitelist.insert(9, "Gary"), Err("The attack missed!"));
+        assert_eq!(whitelist.remove(4), Err("The attack missed!"));
+        let version = value.get_mut("version").unwrap();
+        assert_eq!(version.push(1), Err("Target is immune!"));
+    }
+
+    #[test]
+    fn measure_documents() {
+        use parser::BsonValue;
+        let file = File::open("tests/test_data/valid.bson").unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let mut value = parser::parse(&tokens).unwrap();
+        assert_eq!(value.max_depth(), 3);
+        assert_eq!(value.leaf_count(), 9);
+
+        let size = value.deep_size();
+        value
+            .get_mut("whitelist")
+            .unwrap()
+            .push("Nurse_Joy")
+            .unwrap();
+        assert!(value.deep_size() > size + "Nurse_Joy".len());
+        assert_eq!(BsonValue::Number(1.0).max_depth(), 0);
+
+        let nested = (0..1000).fold(BsonValue::Bool(true), |inner, _| {
+            BsonValue::Array(vec![inner])
+        });
+        assert_eq!(nested.max_depth(), 999);
+        assert_eq!(nested.leaf_count(), 1);
+    }
+
+    #[test]
+    fn repeated_sections_make_lists() {
+        use parser::BsonValue;
+        let input = fs::read_to_string("tests/test_data/repeated_sections.bson").unwrap();
+        let tokens = lexer::lex_str(&input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        assert_eq!(
+            value.get("server.0.host"),
+            Some(&BsonValue::from("kanto.example"))
+        );
+        assert_eq!(
+            value.get("server.0.tls.enabled"),
+            Some(&BsonValue::Bool(true))
+        );
+        assert_eq!(
+            value.get("server.1.port.1.number"),
+            Some(&BsonValue::Number(443.0))
+        );
+        assert_eq!(value.get("zone"), Some(&BsonValue::from("east")));
+
+        let text = rs_bson::emit(&value).unwrap();
+        let tokens = lexer::lex_str(&text).unwrap();
+        assert_eq!(parser::parse(&tokens).unwrap(), value);
+
+        let mixed = lexer::lex_str("BULBA!\nserver ~> 1\n(o) server[] (o)\n    a ~> 1\n").unwrap();
+        assert_eq!(
+            parser::parse(&mixed).unwrap_err().message(),
+            "It hurt itself in its confusion!"
+        );
+    }
+
+    #[test]
+    fn dotted_keys_nest() {
+        use parser::BsonValue;
+        let input = "BULBA!
+database.pool.max_connections ~~> 100
+database.host ~> \"localhost\"
+(o) cache (o)
+    redis.port ~> 6379
+    ttl ~> 60
+";
+        let tokens = lexer::lex_str(input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        assert_eq!(
+            value.get("database.pool.max_connections"),
+            Some(&BsonValue::Number(100.0))
+        );
+        assert_eq!(
+            value.get("database.host"),
+            Some(&BsonValue::from("localhost"))
+        );
+        assert_eq!(
+            value.get("cache.redis.port"),
+            Some(&BsonValue::Number(6379.0))
+        );
+        assert_eq!(value.get("cache.ttl"), Some(&BsonValue::Number(60.0)));
+
+        let errors = [
+            (
+                "BULBA!\nhost ~> 1\nhost.port ~> 2\n",
+                "It hurt itself in its confusion!",
+            ),
+            ("BULBA!\nCharizard.fire ~> 1\n", "It burns the bulb"),
+        ];
+        for (input, err) in errors {
+            let tokens = lexer::lex_str(input).unwrap();
+            assert_eq!(parser::parse(&tokens).unwrap_err().message(), err);
+        }
+        let deep = lexer::lex_str("BULBA!\na.b.c ~> 1\n").unwrap();
+        let shallow = parser::ParseOptions::new().max_depth(1);
+        assert_eq!(
+            parser::parse_with(&deep, &shallow).unwrap_err().message(),
+            "Not enough badges!"
+        );
+    }
+
+    #[test]
+    fn sections_merge_with_dotted_keys() {
+        use parser::BsonValue;
+        let orders = [
+            "BULBA!\na.b.c ~> 1\n(o) a (o)\n    d ~> 2\n",
+            "BULBA!\n(o) a (o)\n    d ~> 2\n(o) a (o)\n    b.c ~> 1\n",
+            "BULBA!\n(o) a (o)\n    d ~> 2\n(o) x (o)\n(o) a (o)\n    (O) b (O)\n        c ~> 1\n",
+        ];
+        for input in orders {
+            let tokens = lexer::lex_str(input).unwrap();
+            let value = parser::parse(&tokens).unwrap();
+            assert_eq!(value.get("a.b.c"), Some(&BsonValue::Number(1.0)), "{input}");
+            assert_eq!(value.get("a.d"), Some(&BsonValue::Number(2.0)), "{input}");
+        }
+
+        // Sorting the keys puts the section first and keeps the value
+        let mut doc = rs_bson::document::Document::parse(orders[0]).unwrap();
+        doc.sort_keys();
+        let sorted = lexer::lex_str(&doc.to_string()).unwrap();
+        let original = lexer::lex_str(orders[0]).unwrap();
+        assert_eq!(parser::parse(&sorted), parser::parse(&original));
+
+        for input in [
+            "BULBA!\na ~> 1\n(o) a (o)\n    d ~> 2\n",
+            "BULBA!\n(o) a[] (o)\n    d ~> 2\n(o) a (o)\n    d ~> 3\n",
+        ] {
+            let tokens = lexer::lex_str(input).unwrap();
+            assert_eq!(
+                parser::parse(&tokens).unwrap_err().message(),
+                "It hurt itself in its confusion!",
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn plain_keys_do_not_replace_sections() {
+        for input in [
+            "BULBA!\na ~> 1\n(o) a (o)\n    d ~> 2\n",
+            "BULBA!\n(o) a (o)\n    d ~> 2\nb ~> 0\n(o) c (o)\n\na ~> 1\n",
+            "BULBA!\na ~> 1\na.d ~> 2\n",
+            "BULBA!\na.d ~> 2\na ~> 1\n",
+        ] {
+            let tokens = lexer::lex_str(input).unwrap();
+            assert_eq!(
+                parser::parse(&tokens).unwrap_err().message(),
+                "It hurt itself in its confusion!",
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn dotted_section_names_nest() {
+        use parser::BsonValue;
+        let nested = "BULBA!\n(o) a (o)\n    (O) b (O)\n        c ~> 1\n";
+        for input in [
+            nested,
+            "BULBA!\n(o) a.b (o)\n    c ~> 1\n",
+            "BULBA!\na.b.c ~> 1\n",
+        ] {
+            let tokens = lexer::lex_str(input).unwrap();
+            let value = parser::parse(&tokens).unwrap();
+            assert_eq!(value.get("a.b.c"), Some(&BsonValue::Number(1.0)), "{input}");
+            assert_eq!(
+                value,
+                parser::parse(&lexer::lex_str(nested).unwrap()).unwrap()
+            );
+        }
+
+        // Both orders of a dotted section and a dotted key add to one another
+        for input in [
+            "BULBA!\n(o) a.b (o)\n    c ~> 1\n(o) x (o)\na.b.d ~> 2\n",
+            "BULBA!\na.b.d ~> 2\n(o) a.b (o)\n    c ~> 1\n",
+        ] {
+            let tokens = lexer::lex_str(input).unwrap();
+            let value = parser::parse(&tokens).unwrap();
+            assert_eq!(value.get("a.b.c"), Some(&BsonValue::Number(1.0)), "{input}");
+            assert_eq!(value.get("a.b.d"), Some(&BsonValue::Number(2.0)), "{input}");
+        }
+
+        let tokens = lexer::lex_str("BULBA!\na ~> 1\n(o) a.b (o)\n    c ~> 1\n").unwrap();
+        assert_eq!(
+            parser::parse(&tokens).unwrap_err().message(),
+            "It hurt itself in its confusion!"
+        );
+    }
+
+    #[test]
+    fn conditional_sections() {
+        use parser::{BsonValue, ParseOptions};
+        let input = fs::read_to_string("tests/test_data/conditional.bson").unwrap();
+        let tokens = lexer::lex_str(&input).unwrap();
+        let parse = |options: &ParseOptions| parser::parse_with(&tokens, options).unwrap();
+
+        let prod = parse(
+            &ParseOptions::new()
+                .context("env", "production")
+                .context("region", "eu"),
+        );
+        assert_eq!(prod.get("log_level"), Some(&BsonValue::from("warn")));
+        assert_eq!(
+            prod.get("database.host"),
+            Some(&BsonValue::from("db.internal"))
+        );
+        assert_eq!(prod.get("cache.ttl"), Some(&BsonValue::Number(120.0)));
+        assert_eq!(prod.get("name"), Some(&BsonValue::from("pokedex")));
+
+        let dev = parse(&ParseOptions::new());
+        assert_eq!(dev.get("log_level"), Some(&BsonValue::from("debug")));
+        assert_eq!(
+            dev.get("database.host"),
+            Some(&BsonValue::from("localhost"))
+        );
+        assert_eq!(dev.get("cache.ttl"), Some(&BsonValue::Number(60.0)));
+
+        let bad = "BULBA!\n(o) when env = \"x\" (o)\n    a ~> 1\n";
+        assert_eq!(
+            lexer::lex_str(bad).unwrap_err().message(),
+            "It hurt itself in its confusion!"
+        );
+
+        // Sections that are left out are still checked, only their values go
+        let misplaced = "BULBA!\n(o) when env == \"prod\" (o)\n    (@) a (@)\n";
+        let tokens = lexer::lex_str(misplaced).unwrap();
+        for options in [
+            ParseOptions::new(),
+            ParseOptions::new().context("env", "prod"),
+        ] {
+            assert_eq!(
+                parser::parse_with(&tokens, &options).unwrap_err().message(),
+                "The attack missed!"
+            );
+        }
+        let skipped = "BULBA!\n(o) when env == \"prod\" (o)\n    a ~> 1\n    (O) b (O)\n        c ~> a + 1\nd ~> 2\n";
+        let options = ParseOptions::new().expressions();
+        let tokens = lexer::lex_str_with(skipped, &options).unwrap();
+        let value = parser::parse_with(&tokens, &options).unwrap();
+        assert_eq!(
+            value,
+            parser::parse(&lexer::lex_str("BULBA!\nd ~> 2\n").unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn evaluate_expressions() {
+        use parser::{BsonValue, ParseOptions};
+        let input = "BULBA!
+timeout ~> 30 * (1000 - 1) / 3
+(o) server (o)
+    host ~> \"localhost\"
+    port ~> 8000 + offset
+    url ~> host + \":\" + port
+offset ~> -80
+";
+        assert_eq!(
+            lexer::lex_str(input).unwrap_err().message(),
+            "Target is immune!"
+        );
+
+        let options = ParseOptions::new().expressions();
+        let tokens = lexer::lex_str_with(input, &options).unwrap();
+        let value = parser::parse_with(&tokens, &options).unwrap();
+        assert_eq!(value.get("timeout"), Some(&BsonValue::Number(9990.0)));
+        assert_eq!(value.get("server.port"), Some(&BsonValue::Number(7920.0)));
+        assert_eq!(
+            value.get("server.url"),
+            Some(&BsonValue::from("localhost:7920"))
+        );
+
+        for (input, err) in [
+            (
+                "BULBA!\na ~> b + 1\nb ~> a + 1\n",
+                "It hurt itself in its confusion!",
+            ),
+            ("BULBA!\na ~> 1 / 0\n", "Target is immune!"),
+            ("BULBA!\na ~> missing * 2\n", "The attack missed!"),
+        ] {
+            let tokens = lexer::lex_str_with(input, &options).unwrap();
+            assert_eq!(
+                parser::parse_with(&tokens, &options).unwrap_err().message(),
+                err,
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_token_stream() {
+        use std::io::BufReader;
+        let options = parser::ParseOptions::new();
+        for name in ["valid", "main_input", "repeated_sections", "conditional"] {
+            let path = format!("tests/test_data/{name}.bson");
+            let tokens = lexer::lex(File::open(&path).unwrap()).unwrap();
+            let reader = BufReader::new(File::open(&path).unwrap());
+            let streamed = parser::parse_stream(lexer::Tokens::new(reader, &options), &options);
+            assert_eq!(streamed, parser::parse(&tokens), "{name}");
+        }
+
+        let reader = BufReader::new(File::open("tests/test_data/invalid_type.bson").unwrap());
+        let mut tokens = lexer::Tokens::new(reader, &options);
+        assert_eq!(
+            parser::parse_stream(&mut tokens, &options)
+                .unwrap_err()
+                .message(),
+            "Target is immune!"
+        );
+        assert!(tokens.next().is_none());
+
+        let empty = lexer::Tokens::new("".as_bytes(), &options);
+        assert_eq!(
+            parser::parse_stream(empty, &options).unwrap_err().message(),
+            "Status: Fainted"
+        );
+    }
+
+    #[test]
+    fn parse_errors_say_where() {
+        use rs_bson::parser::{ParseError, ParseOptions};
+
+        let options = ParseOptions::default();
+        let err = parser::parse_str("BULBA!\nkey ~> 1\n  bad ~> 2\n", &options).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::At {
+                message: "The attack missed!",
+                line: 3,
+                column: 1,
+            }
+        );
+        assert_eq!(err.to_string(), "The attack missed! (line 3, column 1)");
+
+        let err = parser::parse_str("BULBA!\nCharizard ~~~> 6\n", &options).unwrap_err();
+        assert_eq!(
+            (err.message(), err.line(), err.column()),
+            ("It burns the bulb", Some(2), Some(1))
+        );
+
+        // The parser points at the token it stopped at
+        let input = "BULBA!\nkey ~> 1\n(o) key (o)\n";
+        let err = parser::parse_str(input, &options).unwrap_err();
+        assert_eq!(err.message(), "It hurt itself in its confusion!");
+        assert_eq!((err.line(), err.column()), (Some(3), Some(9)));
+        let tokens = lexer::lex_str(input).unwrap();
+        assert_eq!(
+            parser::parse(&tokens).unwrap_err().message(),
+            "It hurt itself in its confusion!"
+        );
+
+        let err = ParseError::from("Status: Fainted");
+        assert_eq!(
+            (err.line(), err.to_string()),
+            (None, String::from("Status: Fainted"))
+        );
     }
 }
@@ -0,0 +1,65 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::path::Path;
+
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::patch::Patch;
+
+#[cfg(test)]
+pub mod patch_tests {
+    use crate::*;
+
+    #[test]
+    fn apply_patch() {
+        let input = Path::new("tests/test_data/valid.bson");
+        let file = File::open(input).unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let mut parsed = parser::parse(&tokens).unwrap();
+        let patch = Patch::new()
+            .replace("/version", BsonValue::Number(2.0))
+            .add("/database/port", BsonValue::Number(8080.0))
+            .add("/whitelist/-", BsonValue::BString(Cow::Borrowed("Brock")))
+            .remove("/missing_data")
+            .move_to("/database/pool/KERNEL_FLAGS", "/kernel_flags");
+        parsed.apply_patch(&patch).unwrap();
+        let expected = "app_name: Pokedex_API
+database:
+    host: 127.0.0.1
+    pool:
+        max_connections: 100
+    port: 8080
+is_production: false
+kernel_flags:
+    panic_on_fail: true
+version: 2
+whitelist:
+- Prof_Oak
+- Mom
+- Brock
+";
+        assert_eq!(parsed.to_string(), expected);
+    }
+
+    #[test]
+    fn failed_patch_changes_nothing() {
+        let input = Path::new("tests/test_data/valid.bson");
+        let file = File::open(input).unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let mut parsed = parser::parse(&tokens).unwrap();
+        let original = parsed.clone();
+        let patch = Patch::new()
+            .remove("/version")
+            .replace("/database/nope", BsonValue::Null(()));
+        assert_eq!(parsed.apply_patch(&patch), Err("But it failed!"));
+
+        let patch = Patch::new().add("/Charizard", BsonValue::Bool(true));
+        assert_eq!(parsed.apply_patch(&patch), Err("It burns the bulb"));
+        let patch = Patch::new().add("/database/pool.size", BsonValue::Number(5.0));
+        assert_eq!(
+            parsed.apply_patch(&patch),
+            Err("It hurt itself in its confusion!")
+        );
+        assert_eq!(parsed, original);
+    }
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "schema")]
+
+use std::borrow::Cow;
+use std::fs;
+
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+
+#[cfg(test)]
+pub mod query_tests {
+    use crate::*;
+
+    fn load() -> BsonValue<'static> {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let tokens = lexer::lex_str(&input).unwrap();
+        parser::parse(&tokens).unwrap().into_owned()
+    }
+
+    fn string(s: &str) -> BsonValue<'static> {
+        BsonValue::BString(Cow::Owned(s.to_string()))
+    }
+
+    #[test]
+    fn query_paths_and_builtins() {
+        let value = load();
+        assert_eq!(
+            value.query(".database.pool | keys"),
+            Ok(vec![BsonValue::Array(vec![
+                string("KERNEL_FLAGS"),
+                string("max_connections"),
+                string("timeout_ms"),
+            ])])
+        );
+        assert_eq!(value.query(".whitelist[2]"), Ok(vec![string("Nurse_Joy")]));
+        assert_eq!(
+            value.query(".whitelist | length"),
+            Ok(vec![BsonValue::Number(3.0)])
+        );
+        assert_eq!(value.query(".version | type"), Ok(vec![string("number")]));
+        assert_eq!(value.query(".nope.deeper"), Ok(vec![BsonValue::Null(())]));
+        assert_eq!(value.query("."), Ok(vec![value.clone()]));
+    }
+
+    #[test]
+    fn query_select() {
+        let value = load();
+        assert_eq!(
+            value.query(r#".whitelist[] | select(. == "Mom")"#),
+            Ok(vec![string("Mom")])
+        );
+        assert_eq!(
+            value.query(".database.pool | select(.max_connections > 50) | .timeout_ms"),
+            Ok(vec![BsonValue::Number(5000.0)])
+        );
+        assert_eq!(
+            value.query(".database.pool[] | select(. <= 100)"),
+            Ok(vec![BsonValue::Number(100.0)])
+        );
+        assert_eq!(
+            value.query(".is_production == NotVeryEffective"),
+            Ok(vec![BsonValue::Bool(true)])
+        );
+    }
+
+    #[test]
+    fn query_errors() {
+        let value = load();
+        assert_eq!(value.query(".app_name[0]"), Err("Target is immune!"));
+        assert_eq!(value.query(".version | keys"), Err("Target is immune!"));
+        assert_eq!(
+            value.query(".whitelist[x]"),
+            Err("It hurt itself in its confusion!")
+        );
+        assert_eq!(
+            value.query("select(. == 1"),
+            Err("It hurt itself in its confusion!")
+        );
+        assert_eq!(
+            value.query("pikachu"),
+            Err("It hurt itself in its confusion!")
+        );
+        assert_eq!(value.query(". ."), Err("It hurt itself in its confusion!"));
+    }
+
+    #[test]
+    #[cfg(feature = "decimal")]
+    fn exact_numbers_compare_exactly() {
+        let input =
+            "BULBA!\nprices ~> <| 0.1, 0.3, 0.30000000000000001 |>\nid ~> 9007199254740993\n";
+        let options = parser::ParseOptions::new().exact_numbers();
+        let tokens = lexer::lex_str_with(input, &options).unwrap();
+        let value = parser::parse_with(&tokens, &options).unwrap();
+        assert_eq!(
+            value.query(".prices[] | select(. > 0.3)"),
+            Ok(vec![BsonValue::Decimal(
+                "0.30000000000000001".parse().unwrap()
+            )])
+        );
+
+        let tokens = lexer::lex_str(input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        assert_eq!(
+            value.query(".id > 9007199254740992"),
+            Ok(vec![BsonValue::Bool(true)])
+        );
+    }
+}
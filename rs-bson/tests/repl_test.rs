@@ -0,0 +1,65 @@
+#![cfg(feature = "schema")]
+
+use std::fs;
+use std::io::Cursor;
+
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::repl::Repl;
+use rs_bson::schema::Schema;
+
+#[cfg(test)]
+pub mod repl_tests {
+    use crate::*;
+
+    fn session() -> Repl {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let tokens = lexer::lex_str(&input).unwrap();
+        let value: BsonValue<'static> = parser::parse(&tokens).unwrap().into_owned();
+        let schema = fs::read_to_string("tests/test_data/main_input.bulbaschema").unwrap();
+        Repl::new(value, Some(Schema::parse(&schema).unwrap()))
+    }
+
+    fn eval(repl: &mut Repl, line: &str) -> String {
+        repl.eval(line).unwrap()
+    }
+
+    #[test]
+    fn repl_queries_and_edits() {
+        let mut repl = session();
+        assert_eq!(eval(&mut repl, "database.host"), "\"127.0.0.1\"");
+        assert_eq!(eval(&mut repl, "type .whitelist"), "array");
+        assert_eq!(eval(&mut repl, "keys database"), "host\npool");
+        assert_eq!(
+            eval(&mut repl, "get database.pool.KERNEL_FLAGS"),
+            "panic_on_fail ~~> SuperEffective\nretry_strategy ~> \"SolarBeam\""
+        );
+        assert_eq!(eval(&mut repl, "get nope"), "error: no value at nope");
+
+        assert_eq!(eval(&mut repl, "validate"), "ok");
+        assert_eq!(
+            eval(&mut repl, "set database.pool.max_connections \"many\""),
+            ""
+        );
+        assert_eq!(
+            eval(&mut repl, "validate"),
+            "error: database.pool.max_connections: Target is immune! Expected int, found string"
+        );
+        assert_eq!(eval(&mut repl, "unset app_name"), "");
+        assert_eq!(eval(&mut repl, "app_name"), "error: no value at app_name");
+        assert_eq!(eval(&mut repl, "reset"), "");
+        assert_eq!(eval(&mut repl, "validate"), "ok");
+        assert_eq!(repl.eval("quit"), None);
+    }
+
+    #[test]
+    fn repl_runs_over_streams() {
+        let mut output = vec![];
+        let input = Cursor::new("version\nset version 2\nversion\n");
+        session().run(input, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "bulba> 1.5\nbulba> bulba> 2\nbulba> \n"
+        );
+    }
+}
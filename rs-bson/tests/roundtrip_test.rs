@@ -0,0 +1,183 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+
+use rs_bson::emit;
+use rs_bson::format;
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+
+#[cfg(test)]
+pub mod roundtrip_tests {
+    use crate::*;
+
+    // Small xorshift generator, so every run checks the same values
+    struct Gen(u64);
+
+    impl Gen {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> usize {
+            (self.next() % n) as usize
+        }
+
+        fn text(&mut self, alphabet: &[&str], max: u64) -> String {
+            (0..self.below(max))
+                .map(|_| alphabet[self.below(alphabet.len() as u64)])
+                .collect()
+        }
+
+        fn key(&mut self) -> String {
+            let first = ["a", "Z", "_", "pool"];
+            let rest = ["a", "B", "0", "9", "_", "ash"];
+            first[self.below(4)].to_string() + &self.text(&rest, 6)
+        }
+
+        fn scalar(&mut self, in_array: bool) -> BsonValue<'static> {
+            // Quotes, arrows, leaves and keywords are all fine inside a string
+            let mut alphabet = vec![
+                "a",
+                " ",
+                "\"",
+                "'",
+                "~>",
+                "<|",
+                "|>",
+                "(o)",
+                "é",
+                "MissingNo",
+                ".",
+            ];
+            if !in_array {
+                alphabet.push(",");
+            }
+            match self.below(9) {
+                0 => BsonValue::BString(Cow::Owned(self.text(&alphabet, 12))),
+                1 => BsonValue::Number(self.next() as f64 / 1024.0 - 1e15),
+                2 => BsonValue::Number(self.below(2000) as f64 - 1000.0),
+                3 => BsonValue::Bool(self.below(2) == 0),
+                4 => BsonValue::Null(()),
+                5 => BsonValue::Secret(Cow::Owned(format!("env:{}", self.key()))),
+                6 => BsonValue::Regex(Cow::Owned(format!("^{}[0-9]+$", self.key()))),
+                7 => BsonValue::Encrypted(Cow::Owned(format!("{}+/==", self.key()))),
+                _ if in_array => BsonValue::Array(vec![]),
+                _ => BsonValue::Array((0..self.below(4)).map(|_| self.scalar(true)).collect()),
+            }
+        }
+
+        fn map(&mut self, level: usize) -> BsonValue<'static> {
+            let mut m = BTreeMap::new();
+            for _ in 0..self.below(6) {
+                let value = if level < 3 && self.below(4) == 0 {
+                    self.map(level + 1)
+                } else {
+                    self.scalar(false)
+                };
+                m.insert(Cow::Owned(self.key()), value);
+            }
+            BsonValue::Map(m)
+        }
+    }
+
+    fn parse(input: &str) -> BsonValue<'static> {
+        let tokens = lexer::lex_str(input).unwrap();
+        parser::parse(&tokens).unwrap().into_owned()
+    }
+
+    #[test]
+    fn roundtrip_generated_values() {
+        let mut generator = Gen(0x5eed_b01b);
+        for _ in 0..500 {
+            let value = generator.map(0);
+            let text = emit(&value).unwrap();
+            assert_eq!(parse(&text), value, "{text}");
+            assert_eq!(emit(&parse(&text)), Ok(text.clone()));
+            assert_eq!(format::format(&text), Ok(text));
+            let compact = emit::emit_compact(&value).unwrap();
+            assert_eq!(parse(&compact), value, "{compact}");
+        }
+    }
+
+    #[test]
+    fn custom_key_order() {
+        let value = parse(
+            "BULBA!\nport ~> 80\nversion ~> 2\n(o) app (o)\n    name ~> \"x\"\n    id ~> 1\napp_name ~> \"dex\"\n",
+        );
+        let text = emit::emit_ordered(&value, &["app_name", "version", "name"]).unwrap();
+        let keys: Vec<&str> = text
+            .lines()
+            .map(|line| line.split(' ').find(|w| !w.is_empty()).unwrap())
+            .collect();
+        assert_eq!(
+            keys,
+            ["BULBA!", "app_name", "version", "(o)", "name", "id", "port"]
+        );
+        assert_eq!(parse(&text), value);
+
+        let reversed = emit::emit_by(&value, |a, b| b.cmp(a)).unwrap();
+        assert!(reversed.find("version") < reversed.find("port"));
+    }
+
+    #[test]
+    fn roundtrip_fixtures() {
+        for file in [
+            "main_input.bson",
+            "valid.bson",
+            "profiles.bson",
+            "lint.bson",
+        ] {
+            let input = fs::read_to_string(format!("tests/test_data/{file}")).unwrap();
+            let text = emit(&parse(&input)).unwrap();
+            assert_eq!(parse(&text), parse(&input), "{file}");
+            assert_eq!(emit(&parse(&text)), Ok(text));
+        }
+    }
+
+    #[test]
+    fn emit_refuses_what_bson_cannot_spell() {
+        let value =
+            parse("BULBA!\n(o) a (o)\n    (O) b (O)\n        (@) c (@)\n            d ~> 1\n");
+        let mut deeper = value.clone();
+        let BsonValue::Map(c) = deeper.get_mut("a.b.c").unwrap() else {
+            unreachable!()
+        };
+        c.insert(Cow::Borrowed("e"), BsonValue::Map(BTreeMap::new()));
+        assert_eq!(emit(&deeper), Err("Not enough badges!"));
+
+        let cases = [
+            ("text", BsonValue::BString(Cow::Borrowed("zZz napping"))),
+            ("text", BsonValue::BString(Cow::Borrowed("two\nlines"))),
+            ("level", BsonValue::Number(f64::NAN)),
+            (
+                "party",
+                BsonValue::Array(vec![BsonValue::BString(Cow::Borrowed("a,b"))]),
+            ),
+            (
+                "party",
+                BsonValue::Array(vec![
+                    BsonValue::Map(BTreeMap::new()),
+                    BsonValue::Number(1.0),
+                ]),
+            ),
+        ];
+        for (key, value) in cases {
+            let doc = BsonValue::Map(BTreeMap::from([(Cow::Borrowed(key), value)]));
+            assert_eq!(emit(&doc), Err("Target is immune!"));
+        }
+        let doc = BsonValue::Map(BTreeMap::from([(
+            Cow::Borrowed("bad key"),
+            BsonValue::Null(()),
+        )]));
+        assert_eq!(emit(&doc), Err("It hurt itself in its confusion!"));
+        let doc = BsonValue::Map(BTreeMap::from([(
+            Cow::Borrowed("Charizard"),
+            BsonValue::Null(()),
+        )]));
+        assert_eq!(emit(&doc), Err("It burns the bulb"));
+    }
+}
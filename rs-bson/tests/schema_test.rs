@@ -0,0 +1,497 @@
+#![cfg(feature = "schema")]
+
+use std::fs;
+
+use rs_bson::document::Document;
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue, ParseError};
+use rs_bson::schema::{self, Rule, Schema, Severity, Type, Validator, Violation};
+
+#[cfg(test)]
+pub mod schema_tests {
+    use crate::*;
+
+    fn load_schema() -> Schema {
+        let input = fs::read_to_string("tests/test_data/main_input.bulbaschema").unwrap();
+        Schema::parse(&input).unwrap()
+    }
+
+    fn violation(path: &str, line: usize, message: &str) -> Violation {
+        Violation {
+            path: path.to_string(),
+            line: Some(line),
+            severity: Severity::Error,
+            message: message.to_string(),
+            related: vec![],
+        }
+    }
+
+    #[test]
+    fn validate_valid_document() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let doc = Document::parse(&input).unwrap();
+        assert_eq!(schema::validate(&doc, &load_schema()), vec![]);
+    }
+
+    #[test]
+    fn validate_reports_paths_and_lines() {
+        let input = fs::read_to_string("tests/test_data/invalid_schema.bson").unwrap();
+        let doc = Document::parse(&input).unwrap();
+        assert_eq!(
+            schema::validate(&doc, &load_schema()),
+            vec![
+                violation(
+                    "app_name",
+                    2,
+                    "Target is immune! Expected string, found number"
+                ),
+                violation("database.host", 6, "Required key is missing"),
+                violation(
+                    "database.pool.KERNEL_FLAGS.retry_strategy",
+                    11,
+                    "\"Splash\" is not one of \"SolarBeam\", \"Tackle\""
+                ),
+                violation(
+                    "database.pool.max_connections",
+                    8,
+                    "Target is immune! Expected int, found number"
+                ),
+                violation(
+                    "whitelist.1",
+                    5,
+                    "Target is immune! Expected string, found number"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn fail_unknown_type() {
+        let input = "BULBA!\nport ~> \"integer\"\n";
+        assert_eq!(
+            Schema::parse(input),
+            Err(ParseError::At {
+                message: "It hurt itself in its confusion!",
+                line: 2,
+                column: 1
+            })
+        );
+
+        let input = "BULBA!\n(o) tls (o)\n    _optional ~> 1\n";
+        let err = Schema::parse(input).unwrap_err();
+        assert_eq!((err.line(), err.column()), (Some(3), Some(5)));
+    }
+
+    #[test]
+    fn optional_sections() {
+        let input = fs::read_to_string("tests/test_data/optional_section.bulbaschema").unwrap();
+        let schema = Schema::parse(&input).unwrap();
+        let check = |input: &str| {
+            let doc = Document::parse(input).unwrap();
+            schema::validate(&doc, &schema)
+                .iter()
+                .map(|violation| violation.path.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(check("BULBA!\nport ~> 443\n"), Vec::<String>::new());
+        assert_eq!(
+            check("BULBA!\nport ~> 443\n(o) tls (o)\n    cert ~> \"cert.pem\"\n"),
+            vec!["tls.key"]
+        );
+    }
+
+    #[test]
+    fn validate_against_json_schema() {
+        let input = fs::read_to_string("tests/test_data/main_input.schema.json").unwrap();
+        let json_schema = Schema::from_json_schema(&input).unwrap();
+
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let doc = Document::parse(&input).unwrap();
+        assert_eq!(schema::validate(&doc, &json_schema), vec![]);
+
+        let input = fs::read_to_string("tests/test_data/invalid_schema.bson").unwrap();
+        let doc = Document::parse(&input).unwrap();
+        let paths: Vec<String> = schema::validate(&doc, &json_schema)
+            .into_iter()
+            .map(|violation| violation.path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "app_name",
+                "database.host",
+                "database.pool.KERNEL_FLAGS.retry_strategy",
+                "database.pool.max_connections",
+                "whitelist.1",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_against_built_schema() {
+        let built = Schema::map()
+            .key("port", Type::Int.range(1..=65535))
+            .key("ratio", Type::Number.range(0..1))
+            .key("tags", Schema::array(Type::String))
+            .required("host");
+        let input = "BULBA!
+port ~> 70000
+ratio ~> 1
+tags ~> <| \"a\", SuperEffective |>
+";
+        let tokens = lexer::lex_str(input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        let messages: Vec<(String, String)> = schema::validate_value(&value, &built)
+            .into_iter()
+            .map(|violation| (violation.path, violation.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("host".to_string(), "Required key is missing".to_string()),
+                (
+                    "port".to_string(),
+                    "70000 is above the maximum of 65535".to_string()
+                ),
+                ("ratio".to_string(), "1 must be less than 1".to_string()),
+                (
+                    "tags.1".to_string(),
+                    "Target is immune! Expected string, found bool".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_type_suffixes() {
+        let built = Schema::map()
+            .key("port", Type::Int)
+            .key("ratio", Type::Int)
+            .key("released", Type::Date)
+            .key("planned", Type::Date)
+            .key("label", Type::Date);
+        let input = "BULBA!
+port ~> 8080i
+ratio ~> 2f
+released ~> \"2024-02-29\"d
+planned ~> \"2025-06-01\"
+label ~> \"soon\"
+";
+        let tokens = lexer::lex_str(input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        let messages: Vec<(String, String)> = schema::validate_value(&value, &built)
+            .into_iter()
+            .map(|violation| (violation.path, violation.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    "label".to_string(),
+                    "Target is immune! Expected date, found string".to_string()
+                ),
+                (
+                    "ratio".to_string(),
+                    "Target is immune! Expected int, found number".to_string()
+                ),
+            ]
+        );
+        let parsed = Schema::parse("BULBA!\nreleased ~> \"date\"\nplanned ~> \"date\"\n").unwrap();
+        assert_eq!(schema::validate_value(&value, &parsed), vec![]);
+    }
+
+    #[test]
+    fn numbers_match_by_value() {
+        let parsed = Schema::parse(
+            "BULBA!
+port ~> \"int one_of=80|443\"
+tls ~> \"bool?\"
+cert ~> \"string?\"
+(o) _rules (o)
+    cert ~> \"cert required_if port=443\"
+",
+        )
+        .unwrap();
+        let validate = |input: &str| {
+            let tokens = lexer::lex_str(input).unwrap();
+            let value = parser::parse(&tokens).unwrap();
+            schema::validate_value(&value, &parsed)
+                .into_iter()
+                .map(|violation| violation.message)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(validate("BULBA!\nport ~> 80i\n"), Vec::<String>::new());
+        assert_eq!(
+            validate("BULBA!\nport ~> 443i\n"),
+            vec!["Required when port is 443"]
+        );
+        assert_eq!(
+            validate("BULBA!\nport ~> 8080i\n"),
+            vec!["8080 is not one of 80, 443"]
+        );
+
+        #[cfg(feature = "decimal")]
+        {
+            let options = parser::ParseOptions::new().exact_numbers();
+            let tokens = lexer::lex_str("BULBA!\nport ~> 443\n").unwrap();
+            let value = parser::parse_with(&tokens, &options).unwrap();
+            assert_eq!(
+                schema::validate_value(&value, &parsed)
+                    .into_iter()
+                    .map(|violation| violation.message)
+                    .collect::<Vec<String>>(),
+                vec!["Required when port is 443"]
+            );
+        }
+    }
+
+    #[test]
+    fn validate_constraints() {
+        let dsl = Schema::parse(
+            "BULBA!
+port ~> \"int min=1 max=65535\"
+name ~> \"string min_len=3 max_len=8 pattern=^[a-z]+$\"
+env ~> \"string one_of=dev|prod\"
+",
+        )
+        .unwrap();
+        let json = Schema::from_json_schema(
+            r#"{
+                "type": "object",
+                "properties": {
+                    "port": { "type": "integer", "minimum": 1, "maximum": 65535 },
+                    "name": { "type": "string", "minLength": 3, "maxLength": 8, "pattern": "^[a-z]+$" },
+                    "env": { "enum": ["dev", "prod"] }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let input = "BULBA!
+port ~> 0
+name ~> \"Bulbasaur\"
+env ~> \"staging\"
+";
+        let tokens = lexer::lex_str(input).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        let expected = vec![
+            (
+                "env".to_string(),
+                "\"staging\" is not one of \"dev\", \"prod\"".to_string(),
+            ),
+            (
+                "name".to_string(),
+                "\"Bulbasaur\" is longer than 8 characters".to_string(),
+            ),
+            (
+                "name".to_string(),
+                "\"Bulbasaur\" does not match /^[a-z]+$/".to_string(),
+            ),
+            (
+                "port".to_string(),
+                "0 is below the minimum of 1".to_string(),
+            ),
+        ];
+        for built in [dsl, json] {
+            let messages: Vec<(String, String)> = schema::validate_value(&value, &built)
+                .into_iter()
+                .map(|violation| (violation.path, violation.message))
+                .collect();
+            assert_eq!(messages, expected);
+        }
+    }
+
+    #[test]
+    fn validate_with_hooks() {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let doc = Document::parse(&input).unwrap();
+        let schema = load_schema();
+        let banned = "Nurse_Joy";
+        let validator = Validator::new(&schema)
+            .hook("database.host", |value: &BsonValue| match value {
+                BsonValue::BString(host) if host == "127.0.0.1" => {
+                    Err(String::from("Loopback hosts are not allowed"))
+                }
+                _ => Ok(()),
+            })
+            .hook("whitelist.*", |value: &BsonValue| match value {
+                BsonValue::BString(name) if name == banned => Err(format!("{name} is banned")),
+                _ => Ok(()),
+            });
+        assert_eq!(
+            validator.validate(&doc),
+            vec![
+                violation("database.host", 12, "Loopback hosts are not allowed"),
+                violation("whitelist.2", 25, "Nurse_Joy is banned"),
+            ]
+        );
+    }
+
+    #[test]
+    fn fill_defaults() {
+        let dsl = Schema::parse(
+            "BULBA!
+name ~> \"string\"
+level ~> \"int default=5\"
+(o) database (o)
+    port ~> \"int default=8080\"
+    host ~> \"string\"
+",
+        )
+        .unwrap();
+        let tokens = lexer::lex_str("BULBA!\nname ~> \"Bulbasaur\"\n").unwrap();
+        let mut value = parser::parse(&tokens).unwrap();
+        let filled = schema::validate_and_fill(&mut value, &dsl);
+        assert_eq!(
+            filled.synthesized,
+            vec!["database.port".to_string(), "level".to_string()]
+        );
+        let paths: Vec<String> = filled.violations.into_iter().map(|v| v.path).collect();
+        assert_eq!(paths, vec!["database.host".to_string()]);
+        assert_eq!(
+            value.to_string(),
+            "database:\n    port: 8080\nlevel: 5\nname: Bulbasaur\n"
+        );
+    }
+
+    #[test]
+    fn warn_on_deprecated_keys() {
+        let dsl = Schema::parse(
+            "BULBA!
+port ~> \"int?\"
+(o) database (o)
+    hostname ~> \"string? deprecated=database.host\"
+    host ~> \"string?\"
+",
+        )
+        .unwrap();
+        let doc = Document::parse("BULBA!\n(o) database (o)\n    hostname ~> \"db\"\n").unwrap();
+        assert_eq!(
+            schema::validate(&doc, &dsl),
+            vec![Violation {
+                path: "database.hostname".to_string(),
+                line: Some(3),
+                severity: Severity::Warning,
+                message: "Key is deprecated, use database.host instead".to_string(),
+                related: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn strict_mode_flags_unknown_keys() {
+        let input = "BULBA!
+app_name ~> \"Pokedex_API\"
+(o) database (o)
+    host ~> \"127.0.0.1\"
+    max_conections ~> 100
+    extras ~> 1
+";
+        let doc = Document::parse(input).unwrap();
+        let built = Schema::map().key("app_name", Type::String).key(
+            "database",
+            Schema::map()
+                .key("host", Type::String)
+                .key("max_connections", Type::Int),
+        );
+        assert_eq!(schema::validate(&doc, &built), vec![]);
+
+        let messages: Vec<(String, String)> = Validator::new(&built)
+            .strict(true)
+            .validate(&doc)
+            .into_iter()
+            .map(|violation| (violation.path, violation.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("database.extras".to_string(), "Unknown key".to_string()),
+                (
+                    "database.max_conections".to_string(),
+                    "Unknown key, did you mean max_connections?".to_string()
+                ),
+            ]
+        );
+
+        let closed = Schema::map()
+            .key("app_name", Type::String)
+            .deny_unknown_fields();
+        let paths: Vec<String> = schema::validate(&doc, &closed)
+            .into_iter()
+            .map(|violation| violation.path)
+            .collect();
+        assert_eq!(paths, vec!["database".to_string()]);
+    }
+
+    #[test]
+    fn cross_field_rules() {
+        let dsl = Schema::parse(
+            "BULBA!
+(o) tls (o)
+    enabled ~> \"bool\"
+    cert ~> \"string?\"
+    (O) _rules (O)
+        cert_with_tls ~> \"cert required_if enabled=SuperEffective\"
+(o) pool (o)
+    min ~> \"int\"
+    max ~> \"int\"
+    (O) _rules (O)
+        bounds ~> \"min <= max\"
+",
+        )
+        .unwrap();
+        let built = Schema::map()
+            .key(
+                "tls",
+                Schema::map()
+                    .key("enabled", Type::Bool)
+                    .key("cert", Type::String)
+                    .rule(Rule::required_if("cert", "enabled", BsonValue::Bool(true))),
+            )
+            .key(
+                "pool",
+                Schema::map()
+                    .key("min", Type::Int)
+                    .key("max", Type::Int)
+                    .rule(Rule::less_or_equal("min", "max")),
+            );
+        let input = "BULBA!
+(o) tls (o)
+    enabled ~> SuperEffective
+(o) pool (o)
+    min ~> 10
+    max ~> 5
+";
+        let doc = Document::parse(input).unwrap();
+        let expected = vec![
+            Violation {
+                related: vec!["pool.max".to_string()],
+                ..violation("pool.min", 5, "10 is greater than max (5)")
+            },
+            Violation {
+                related: vec!["tls.enabled".to_string()],
+                ..violation("tls.cert", 2, "Required when enabled is SuperEffective")
+            },
+        ];
+        assert_eq!(schema::validate(&doc, &dsl), expected);
+        assert_eq!(schema::validate(&doc, &built), expected);
+
+        let validator = Validator::new(&built).cross_check(
+            &["pool.max", "tls.enabled"],
+            |values: &[Option<&BsonValue>]| match values {
+                [Some(BsonValue::Number(max)), Some(BsonValue::Bool(true))] if *max < 8.0 => {
+                    Err(String::from("TLS needs at least 8 connections"))
+                }
+                _ => Ok(()),
+            },
+        );
+        let last = validator.validate(&doc).pop().unwrap();
+        assert_eq!(
+            last,
+            Violation {
+                related: vec!["tls.enabled".to_string()],
+                ..violation("pool.max", 6, "TLS needs at least 8 connections")
+            }
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::fs;
+
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::secret::{SecretError, Secrets};
+
+#[cfg(test)]
+pub mod secret_tests {
+    use crate::*;
+
+    #[test]
+    fn resolve_secret_references() {
+        let key_file = std::env::temp_dir().join(format!("rs-bson-secret-{}", std::process::id()));
+        fs::write(&key_file, "-----BEGIN KEY-----\n").unwrap();
+        // Only this test reads this variable
+        unsafe { std::env::set_var("SECRET_TEST_DB_PASS", "hunter2") };
+
+        let input = format!(
+            "BULBA!
+(o) database (o)
+    password ~~~> secret(\"env:SECRET_TEST_DB_PASS\")
+    api_key ~~~~> secret(\"vault:kv/app#api_key\")
+    tls_key ~~~~> secret(\"file:{}\")
+",
+            key_file.display()
+        );
+        let tokens = lexer::lex_str(&input).unwrap();
+        let mut value = parser::parse(&tokens).unwrap();
+        assert_eq!(
+            value.get("database.password"),
+            Some(&BsonValue::Secret("env:SECRET_TEST_DB_PASS".into()))
+        );
+
+        let secrets = Secrets::new().scheme("vault", |location: &str| match location {
+            "kv/app#api_key" => Ok(String::from("pikachu")),
+            _ => Err(format!("{location} not found")),
+        });
+        value.resolve_secrets(&secrets).unwrap();
+        let get = |path: &str| value.get(path).unwrap().clone();
+        assert_eq!(
+            get("database.password"),
+            BsonValue::BString("hunter2".into())
+        );
+        assert_eq!(
+            get("database.api_key"),
+            BsonValue::BString("pikachu".into())
+        );
+        assert_eq!(
+            get("database.tls_key"),
+            BsonValue::BString("-----BEGIN KEY-----".into())
+        );
+        fs::remove_file(&key_file).unwrap();
+
+        let tokens = lexer::lex_str("BULBA!\ntoken ~> secret(\"vault:kv/missing\")\n").unwrap();
+        let mut value = parser::parse(&tokens).unwrap();
+        assert_eq!(
+            value.resolve_secrets(&secrets),
+            Err(SecretError {
+                path: "token".to_string(),
+                reference: "vault:kv/missing".to_string(),
+                message: "kv/missing not found".to_string(),
+            })
+        );
+    }
+}
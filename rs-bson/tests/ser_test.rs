@@ -0,0 +1,109 @@
+#![cfg(feature = "serde")]
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use rs_bson::de;
+use rs_bson::emit;
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::ser;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Pool {
+    max_connections: u16,
+    timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Level {
+    Debug,
+    Info,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    app_name: String,
+    level: Level,
+    whitelist: Vec<String>,
+    retry_strategy: Option<String>,
+    pool: Pool,
+}
+
+#[cfg(test)]
+pub mod ser_tests {
+    use crate::*;
+
+    fn parse(input: &str) -> BsonValue<'static> {
+        let tokens = lexer::lex_str(input).unwrap();
+        parser::parse(&tokens).unwrap().into_owned()
+    }
+
+    #[test]
+    fn structs_become_documents() {
+        let config = Config {
+            app_name: String::from("Pokedex_API"),
+            level: Level::Info,
+            whitelist: vec![String::from("Prof_Oak"), String::from("Mom")],
+            retry_strategy: None,
+            pool: Pool {
+                max_connections: 100,
+                timeout_ms: Some(5000),
+            },
+        };
+        let value = ser::to_value(&config).unwrap();
+        assert_eq!(value.get("retry_strategy"), Some(&BsonValue::Null(())));
+        assert_eq!(value.get("level"), Some(&BsonValue::from("info")));
+        assert_eq!(
+            value.get("pool.timeout_ms"),
+            Some(&BsonValue::Number(5000.0))
+        );
+        let text = emit(&value).unwrap();
+        assert!(text.contains("retry_strategy ~> MissingNo"), "{text}");
+        assert_eq!(de::from_value::<Config>(&parse(&text)), Ok(config));
+        assert_ne!(Level::Debug, Level::Info);
+    }
+
+    #[test]
+    fn numbers_keep_their_digits() {
+        assert_eq!(ser::to_value(&8080u16), Ok(BsonValue::Number(8080.0)));
+        assert_eq!(
+            ser::to_value(&u64::MAX),
+            Ok(BsonValue::Integer(u64::MAX.into()))
+        );
+        assert_eq!(
+            ser::to_value(&(1.5, -3i8)),
+            Ok(BsonValue::Array(vec![
+                BsonValue::Number(1.5),
+                BsonValue::Number(-3.0)
+            ]))
+        );
+    }
+
+    #[test]
+    fn keys_have_to_be_strings() {
+        let ports = BTreeMap::from([(1u8, "ssh"), (80, "http")]);
+        let value = ser::to_value(&ports).unwrap();
+        assert_eq!(value.get("80"), Some(&BsonValue::from("http")));
+
+        let flags = BTreeMap::from([(true, 1)]);
+        let err = ser::to_value(&flags).unwrap_err();
+        assert_eq!(err.to_string(), "keys must be strings");
+    }
+
+    #[test]
+    fn documents_serialize_like_any_type() {
+        let value = parse(
+            "BULBA!\nname ~> \"Pokedex\"\nport ~> 8080\nratio ~> 0.5\nid ~> 18446744073709551615\nretry ~> MissingNo\n(o) pool (o)\n    size ~> 4\n",
+        );
+        assert_eq!(ser::to_value(&value), Ok(value.clone()));
+        assert_eq!(de::from_value::<BsonValue>(&value), Ok(value));
+
+        let secret = BsonValue::Secret(Cow::Borrowed("env:DB_PASSWORD"));
+        let err = ser::to_value(&secret).unwrap_err();
+        assert_eq!(err.message, "Unresolved secret reference env:DB_PASSWORD");
+    }
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "schema")]
+
+use std::thread;
+
+use rs_bson::SharedDocument;
+use rs_bson::parser::BsonValue;
+use rs_bson::shared::SharedValue;
+
+#[cfg(test)]
+pub mod shared_tests {
+    use crate::*;
+
+    #[test]
+    fn share_across_threads() {
+        let doc = SharedDocument::from_file("tests/test_data/valid.bson").unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let doc = doc.clone();
+                thread::spawn(move || doc.get("database.pool.max_connections").cloned())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Some(BsonValue::Number(100.0)));
+        }
+
+        let copy = doc.clone();
+        assert!(SharedDocument::ptr_eq(&doc, &copy));
+        let reparsed = SharedDocument::from_file("tests/test_data/valid.bson").unwrap();
+        assert_eq!(doc, reparsed);
+        assert!(!SharedDocument::ptr_eq(&doc, &reparsed));
+    }
+
+    #[test]
+    fn tenants_share_unchanged_sections() {
+        let doc = SharedDocument::from_file("tests/test_data/valid.bson").unwrap();
+        let base = SharedValue::from(BsonValue::clone(&doc));
+        let mut tenant = base.clone();
+        tenant.set("database.pool.max_connections", 10).unwrap();
+        tenant.set("database.region", "Kanto").unwrap();
+        assert_eq!(tenant.remove("missing_data"), Some(BsonValue::Null(())));
+
+        let untouched = |doc: &SharedValue, path| doc.get(path).unwrap().clone();
+        assert!(SharedValue::ptr_eq(
+            &untouched(&base, "whitelist"),
+            &untouched(&tenant, "whitelist")
+        ));
+        assert!(SharedValue::ptr_eq(
+            &untouched(&base, "database.pool.KERNEL_FLAGS"),
+            &untouched(&tenant, "database.pool.KERNEL_FLAGS")
+        ));
+        assert!(!SharedValue::ptr_eq(
+            &untouched(&base, "database"),
+            &untouched(&tenant, "database")
+        ));
+
+        let base_value = base.to_value();
+        let tenant_value = tenant.to_value();
+        assert_eq!(
+            base_value.get("database.pool.max_connections"),
+            Some(&BsonValue::Number(100.0))
+        );
+        assert_eq!(
+            tenant_value.get("database.pool.max_connections"),
+            Some(&BsonValue::Number(10.0))
+        );
+        assert_eq!(
+            tenant_value.get("database.region"),
+            Some(&BsonValue::from("Kanto"))
+        );
+        assert!(tenant_value.get("missing_data").is_none());
+
+        assert_eq!(
+            tenant.set("database.pool.nope.deeper", 1),
+            Err("The attack missed!")
+        );
+        assert_eq!(tenant.set("whitelist.5", "Gary"), Err("The attack missed!"));
+    }
+}
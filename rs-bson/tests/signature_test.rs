@@ -0,0 +1,84 @@
+#![cfg(feature = "signature")]
+
+use std::fs;
+
+use rs_bson::load::LoadError;
+use rs_bson::parser::{BsonValue, ParseOptions};
+use rs_bson::signature::{self, CommandVerifier};
+#[cfg(feature = "hmac")]
+use rs_bson::signature::{HmacVerifier, Verifier};
+
+#[cfg(test)]
+pub mod signature_tests {
+    use crate::*;
+
+    const DOC: &[u8] = b"BULBA!\ntrainer ~> \"Ash\"\n";
+
+    #[test]
+    #[cfg(feature = "hmac")]
+    fn hmac_signatures() {
+        // RFC 4231, test case 2
+        let jefe = HmacVerifier::new(b"Jefe");
+        assert_eq!(
+            jefe.sign(b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let long_key = HmacVerifier::new(&[0xaa; 131]);
+        assert_eq!(
+            long_key.sign(b"Test Using Larger Than Block-Size Key - Hash Key First"),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+
+        let signature = jefe.sign(DOC) + "\n";
+        assert_eq!(jefe.verify(DOC, signature.as_bytes()), Ok(()));
+        assert!(
+            jefe.verify(b"BULBA!\ntrainer ~> \"Gary\"\n", signature.as_bytes())
+                .is_err()
+        );
+        assert!(
+            HmacVerifier::new(b"Gary")
+                .verify(DOC, signature.as_bytes())
+                .is_err()
+        );
+        let upper = signature.trim().to_uppercase();
+        assert_eq!(jefe.verify(DOC, upper.as_bytes()), Ok(()));
+        for bad in ["", "5bdc", "not hex", &signature[..63]] {
+            assert!(jefe.verify(DOC, bad.as_bytes()).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn verify_before_parsing() {
+        let accept = |_: &[u8], signature: &[u8]| match signature {
+            b"good" => Ok(()),
+            _ => Err(String::from("Unknown signer")),
+        };
+        let options = ParseOptions::new();
+        let value = signature::parse_verified(DOC, b"good", &accept, &options).unwrap();
+        assert_eq!(
+            value.get("trainer"),
+            Some(&BsonValue::BString("Ash".into()))
+        );
+        assert_eq!(
+            signature::parse_verified(b"not even BSON", b"bad", &accept, &options),
+            Err(LoadError::Signature(String::from("Unknown signer")))
+        );
+
+        let dir = std::env::temp_dir().join(format!("rs-bson-signature-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (doc, sig) = (dir.join("app.bson"), dir.join("app.bson.sig"));
+        fs::write(&doc, DOC).unwrap();
+        fs::write(&sig, "good").unwrap();
+        let command = CommandVerifier::new(r#"test -s "$1" && test "$(cat "$2")" = good"#);
+        assert_eq!(
+            signature::read_verified(&doc, &sig, &command),
+            Ok(String::from_utf8(DOC.to_vec()).unwrap())
+        );
+        fs::write(&sig, "forged").unwrap();
+        assert!(matches!(
+            signature::read_verified(&doc, &sig, &command),
+            Err(LoadError::Signature(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
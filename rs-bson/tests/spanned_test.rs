@@ -0,0 +1,44 @@
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue, ParseOptions};
+use rs_bson::spanned::{self, Span, SpannedNode};
+
+#[cfg(test)]
+pub mod spanned_tests {
+    use crate::*;
+
+    #[test]
+    fn point_at_every_value() {
+        let input = "BULBA!
+(o) database (o)
+    port ~~> 5432
+    hosts ~> <| \"a\", 22 |>
+pool.max ~> 100
+(o) servers[] (o)
+    name ~> 'first'
+";
+        let tokens = lexer::lex_str(input).unwrap();
+        let options = ParseOptions::new();
+        let tree = spanned::parse_spanned(&tokens, &options).unwrap();
+        assert_eq!(tree.to_value(), parser::parse(&tokens).unwrap());
+
+        let span = |path: &str| tree.get(path).unwrap().span.clone();
+        let at = |line, columns| Span { line, columns };
+        assert_eq!(span(""), at(1, 0..6));
+        assert_eq!(span("database"), at(2, 0..16));
+        assert_eq!(span("database.port"), at(3, 13..17));
+        assert_eq!(span("database.port").to_string(), "line 3, cols 14-17");
+        assert_eq!(span("database.hosts"), at(4, 13..26));
+        assert_eq!(span("database.hosts.0"), at(4, 16..19));
+        assert_eq!(span("database.hosts.1"), at(4, 21..23));
+        assert_eq!(span("pool"), at(5, 0..8));
+        assert_eq!(span("pool.max"), at(5, 12..15));
+        assert_eq!(span("servers"), at(6, 0..17));
+        assert_eq!(span("servers.0"), at(6, 0..17));
+        assert_eq!(span("servers.0.name"), at(7, 12..19));
+        assert!(matches!(
+            tree.get("servers.0.name").unwrap().node,
+            SpannedNode::Scalar(BsonValue::BString(_))
+        ));
+        assert!(tree.get("database.missing").is_none());
+    }
+}
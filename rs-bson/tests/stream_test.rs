@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+#[cfg(feature = "checksum")]
+use rs_bson::checksum;
+use rs_bson::lexer;
+use rs_bson::parser::{self, ParseOptions};
+use rs_bson::stream::Documents;
+
+#[cfg(test)]
+pub mod stream_tests {
+    use crate::*;
+
+    #[test]
+    fn parse_all_documents() {
+        let input = Path::new("tests/test_data/multi_document.bson");
+        let file = File::open(input).unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let documents = parser::parse_all(&tokens).unwrap();
+        let rendered: Vec<String> = documents.iter().map(|doc| doc.to_string()).collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "level: 5\n",
+                "evolution:\n    name: Ivysaur\nlevel: 16\n",
+                "level: 32\n"
+            ]
+        );
+
+        assert_eq!(
+            parser::parse(&tokens).unwrap_err().message(),
+            "It hurt itself in its confusion!"
+        );
+    }
+
+    #[test]
+    fn stream_documents() {
+        let input = Path::new("tests/test_data/multi_document.bson");
+        let file = File::open(input).unwrap();
+        let tokens = lexer::lex(file).unwrap();
+        let expected = parser::parse_all(&tokens).unwrap();
+
+        let file = File::open(input).unwrap();
+        let streamed: Result<Vec<_>, _> = Documents::new(BufReader::new(file)).collect();
+        assert_eq!(streamed.unwrap(), expected);
+    }
+
+    #[test]
+    fn stream_fails_on_invalid_header() {
+        let input = Path::new("tests/test_data/invalid_header.bson");
+        let file = File::open(input).unwrap();
+        let mut documents = Documents::new(BufReader::new(file));
+        assert_eq!(
+            documents.next().unwrap().unwrap_err().message(),
+            "Status: Fainted"
+        );
+    }
+
+    #[test]
+    fn stream_splits_like_parse_all() {
+        let options = ParseOptions::new().header("IVY!").reserve("Mew");
+        let input = "IVY!\nlevel ~> 5\nIVY! zZz again\nlevel ~> 16\n";
+        let tokens = lexer::lex_str_with(input, &options).unwrap();
+        let expected = parser::parse_all_with(&tokens, &options).unwrap();
+        assert_eq!(expected.len(), 2);
+        let streamed: Result<Vec<_>, _> =
+            Documents::with_options(input.as_bytes(), &options).collect();
+        assert_eq!(streamed.unwrap(), expected);
+
+        // Not a cry under these options, so the line fails as a key would
+        let input = "IVY!\nlevel ~> 5\nBULBA!\n";
+        let mut documents = Documents::with_options(input.as_bytes(), &options);
+        assert_eq!(
+            documents.next().unwrap().unwrap_err().message(),
+            "It hurt itself in its confusion!"
+        );
+
+        let input = "IVY!\nMew ~> 151\n";
+        let mut documents = Documents::with_options(input.as_bytes(), &options);
+        assert_eq!(
+            documents.next().unwrap().unwrap_err().message(),
+            "It burns the bulb"
+        );
+        assert_eq!(Documents::new(&b""[..]).next(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "checksum")]
+    fn stream_checks_footer_over_every_document() {
+        let input = checksum::seal("BULBA!\nlevel ~> 5\nBULBA!\nlevel ~> 16\n");
+        let tokens = lexer::lex_str(&input).unwrap();
+        let expected = parser::parse_all(&tokens).unwrap();
+        let streamed: Result<Vec<_>, _> = Documents::new(input.as_bytes()).collect();
+        assert_eq!(streamed.unwrap(), expected);
+
+        let tampered = input.replace("16", "17");
+        assert_eq!(
+            lexer::lex_str(&tampered).map(|_| ()).unwrap_err().message(),
+            lexer::CORRUPTED
+        );
+        let streamed: Result<Vec<_>, _> = Documents::new(tampered.as_bytes()).collect();
+        assert_eq!(streamed.unwrap_err().message(), lexer::CORRUPTED);
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::template::{self, Unresolved};
+
+#[cfg(test)]
+pub mod template_tests {
+    use crate::*;
+
+    #[test]
+    fn render_placeholders() {
+        let input = fs::read_to_string("tests/test_data/template.bson").unwrap();
+        let vars_file = fs::read_to_string("tests/test_data/template_vars.bson").unwrap();
+        let tokens = lexer::lex_str(&vars_file).unwrap();
+        let mut vars = template::vars_from(&parser::parse(&tokens).unwrap());
+        assert_eq!(vars["database.port"], "5432");
+        assert_eq!(vars["replicas"], r#"<| "a", "b" |>"#);
+
+        assert_eq!(
+            template::render(&input, &vars),
+            Err(Unresolved(vec![String::from("region")]))
+        );
+        vars.insert(String::from("region"), String::from("eu-west-1"));
+        let rendered = template::render(&input, &vars).unwrap();
+        let tokens = lexer::lex_str(&rendered).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        assert_eq!(
+            value.get("app_name"),
+            Some(&BsonValue::BString("Pokedex_eu-west-1".into()))
+        );
+        assert_eq!(value.get("database.port"), Some(&BsonValue::Number(5432.0)));
+    }
+
+    #[test]
+    fn list_every_unresolved_placeholder() {
+        let err = template::render("${a} ${ b } ${a} ${c", &BTreeMap::new()).unwrap_err();
+        assert_eq!(err, Unresolved(vec![String::from("a"), String::from("b")]));
+        assert_eq!(err.to_string(), "Wild placeholders appeared: ${a}, ${b}");
+    }
+}
@@ -0,0 +1,83 @@
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::testing::{self, UPDATE_VAR};
+
+#[cfg(test)]
+pub mod testing_tests {
+    use crate::*;
+
+    fn load() -> BsonValue<'static> {
+        let input = fs::read_to_string("tests/test_data/main_input.bson").unwrap();
+        let tokens = lexer::lex_str(&input).unwrap();
+        parser::parse(&tokens).unwrap().into_owned()
+    }
+
+    #[test]
+    fn snapshot_compare_and_update() {
+        let value = load();
+        testing::assert_snapshot(&value, "tests/test_data/main_input.bson");
+
+        let mut changed = value.clone();
+        *changed.get_mut("database.host").unwrap() = BsonValue::BString(Cow::Borrowed("10.0.0.1"));
+        let BsonValue::Map(root) = &mut changed else {
+            unreachable!()
+        };
+        root.remove("zapdos");
+        assert_eq!(
+            testing::check_snapshot(&changed, "tests/test_data/main_input.bson"),
+            Err(format!(
+                "snapshot tests/test_data/main_input.bson does not match:
+  - database.host: \"127.0.0.1\"
+  + database.host: \"10.0.0.1\"
+  - zapdos: \"Legendary bird\"
+rerun with {UPDATE_VAR}=1 to accept the new value"
+            ))
+        );
+
+        let dir = env::temp_dir().join(format!("bulba-snapshots-{}", std::process::id()));
+        let snapshot = dir.join("nested/changed.bson");
+        assert!(
+            testing::check_snapshot(&changed, &snapshot)
+                .unwrap_err()
+                .contains("does not exist")
+        );
+        unsafe { env::set_var(UPDATE_VAR, "1") };
+        testing::assert_snapshot(&changed, &snapshot);
+        unsafe { env::remove_var(UPDATE_VAR) };
+        testing::assert_snapshot(&changed, &snapshot);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn golden_corpus() {
+        assert!(testing::assert_corpus("tests/test_data/corpus") >= 50);
+
+        let dir = env::temp_dir().join(format!("bulba-corpus-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("good.bson"), "BULBA!\nname ~> 1\n").unwrap();
+        fs::write(dir.join("nested/bad.bson"), "BULBA!\nname ~> Nope\n").unwrap();
+        fs::write(dir.join("wrong.bson"), "BULBA!\nname ~> 1\n").unwrap();
+        fs::write(dir.join("wrong.err"), "Status: Fainted\n").unwrap();
+        let failures = testing::check_corpus(&dir).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        let messages: Vec<String> = failures.iter().map(|failure| failure.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                format!(
+                    "{}: does not parse: Target is immune! (line 2, column 8)",
+                    dir.join("nested/bad.bson").display()
+                ),
+                format!(
+                    "{}: parses, but should fail with Status: Fainted",
+                    dir.join("wrong.bson").display()
+                ),
+            ]
+        );
+        assert!(testing::check_corpus(dir.join("missing")).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "trace")]
+
+use std::fmt::Debug;
+use std::sync::Mutex;
+
+use rs_bson::lexer;
+use rs_bson::parser;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+// Notes every span opened and every field recorded on one, as `name field=value`
+#[derive(Default)]
+struct Spans(Mutex<Vec<String>>);
+
+struct Fields<'s>(&'s mut Vec<String>, &'static str);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .push(format!("{} {}={value:?}", self.1, field.name()));
+    }
+}
+
+impl Subscriber for Spans {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut seen = self.0.lock().unwrap();
+        let metadata = span.metadata();
+        seen.push(format!("{} {}", metadata.target(), metadata.name()));
+        Id::from_u64(seen.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut seen = self.0.lock().unwrap();
+        let opened = &seen[span.into_u64() as usize - 1];
+        let name = if opened.ends_with(" lex") {
+            "lex"
+        } else {
+            "parse"
+        };
+        values.record(&mut Fields(&mut seen, name));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, _: &Event<'_>) {}
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[cfg(test)]
+pub mod trace_tests {
+    use crate::*;
+
+    #[test]
+    fn span_lex_and_parse() {
+        let spans = std::sync::Arc::new(Spans::default());
+        tracing::subscriber::with_default(spans.clone(), || {
+            let tokens = lexer::lex_str("BULBA!\n(o) pool (o)\n    max ~> 100\n").unwrap();
+            parser::parse(&tokens).unwrap();
+        });
+        let seen = spans.0.lock().unwrap();
+        assert_eq!(
+            seen[..5],
+            [
+                "rs_bson lex",
+                "lex tokens=10",
+                "rs_bson parse",
+                "parse leaves=1",
+                "parse depth=1",
+            ]
+        );
+        assert!(seen[5].starts_with("parse size="), "{seen:?}");
+    }
+}
@@ -0,0 +1,115 @@
+use std::borrow::Cow;
+
+use rs_bson::lexer;
+use rs_bson::parser::{self, BsonValue};
+use rs_bson::visit::{Visit, VisitMut};
+
+#[cfg(test)]
+pub mod visit_tests {
+    use crate::*;
+
+    const INPUT: &str = "BULBA!
+name ~> \"Bulbasaur\"
+(o) stats (o)
+    hp ~> 45
+    moves ~> <| \"Tackle\", \"Growl\" |>
+    shiny ~> SuperEffective
+";
+
+    #[derive(Default)]
+    struct Paths(Vec<String>);
+
+    impl Visit for Paths {
+        fn visit_string(&mut self, path: &str, value: &str) {
+            self.0.push(format!("{path}={value}"));
+        }
+
+        fn visit_number(&mut self, path: &str, value: f64) {
+            self.0.push(format!("{path}={value}"));
+        }
+    }
+
+    struct Shout;
+
+    impl VisitMut for Shout {
+        fn visit_string_mut(&mut self, _path: &str, value: &mut Cow<'_, str>) {
+            *value = Cow::Owned(value.to_uppercase());
+        }
+    }
+
+    #[test]
+    fn walk_every_value() {
+        let tokens = lexer::lex_str(INPUT).unwrap();
+        let mut value = parser::parse(&tokens).unwrap();
+
+        let mut paths = Paths::default();
+        value.visit(&mut paths);
+        assert_eq!(
+            paths.0,
+            vec![
+                "name=Bulbasaur",
+                "stats.hp=45",
+                "stats.moves.0=Tackle",
+                "stats.moves.1=Growl",
+            ]
+        );
+
+        value.visit_mut(&mut Shout);
+        assert_eq!(
+            value.get("stats.moves.1"),
+            Some(&BsonValue::BString("GROWL".into()))
+        );
+    }
+
+    #[test]
+    fn transform_values() {
+        let tokens = lexer::lex_str(INPUT).unwrap();
+        let value = parser::parse(&tokens).unwrap();
+
+        let lowered = value.transform(|path, value| {
+            if path.starts_with("stats.moves.")
+                && let BsonValue::BString(s) = value
+            {
+                *s = Cow::Owned(s.to_lowercase());
+            }
+        });
+        assert_eq!(
+            lowered.get("stats.moves.0"),
+            Some(&BsonValue::BString("tackle".into()))
+        );
+        assert_eq!(
+            value.get("stats.moves.0"),
+            Some(&BsonValue::BString("Tackle".into()))
+        );
+
+        let mut counted = value.clone();
+        counted.transform_mut(|_, value| {
+            if let BsonValue::Array(items) = value {
+                *value = BsonValue::Number(items.len() as f64);
+            }
+        });
+        assert_eq!(counted.get("stats.moves"), Some(&BsonValue::Number(2.0)));
+    }
+
+    #[derive(Default)]
+    struct Integers(Vec<(String, i128)>);
+
+    impl Visit for Integers {
+        fn visit_integer(&mut self, path: &str, value: i128) {
+            self.0.push((path.to_string(), value));
+        }
+
+        fn visit_number(&mut self, path: &str, _value: f64) {
+            panic!("{path} is not a plain number");
+        }
+    }
+
+    #[test]
+    fn integers_keep_every_digit() {
+        let tokens = lexer::lex_str("BULBA!\nid ~> 18446744073709551615i\n").unwrap();
+        let value = parser::parse(&tokens).unwrap();
+        let mut integers = Integers::default();
+        value.visit(&mut integers);
+        assert_eq!(integers.0, vec![(String::from("id"), 18446744073709551615)]);
+    }
+}
@@ -0,0 +1,111 @@
+#![cfg(feature = "watch")]
+
+use std::fs;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use rs_bson::bulba_schema;
+use rs_bson::load::LoadError;
+use rs_bson::parser::ParseError;
+use rs_bson::watch::Watcher;
+
+bulba_schema! {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Server {
+        host: String,
+        port: u16,
+    }
+}
+
+#[cfg(test)]
+pub mod watch_tests {
+    use crate::*;
+
+    #[test]
+    fn reloads_good_saves_and_reports_bad_ones() {
+        let path = std::env::temp_dir().join(format!("rs-bson-watch-{}.bson", std::process::id()));
+        fs::write(&path, "BULBA!\nhost ~> \"localhost\"\nport ~> 8080\n").unwrap();
+
+        let (changes, changed) = mpsc::channel();
+        let (errors, failed) = mpsc::channel();
+        let watcher = Watcher::<Server>::builder(&path)
+            .debounce(Duration::from_millis(20))
+            .on_change(move |server: &Server| changes.send(server.port).unwrap())
+            .on_error(move |err: &LoadError| errors.send(err.clone()).unwrap())
+            .start()
+            .unwrap();
+        assert_eq!(watcher.get().port, 8080);
+
+        // Saved halfway through an edit
+        fs::write(&path, "BULBA!\nhost ~> \"localhost\"\nport ~> \"80\n").unwrap();
+        let err = failed.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            err,
+            LoadError::Parse(ParseError::At {
+                message: "Target is immune!",
+                line: 3,
+                column: 8,
+            })
+        );
+        assert_eq!(watcher.get().port, 8080);
+
+        fs::write(&path, "BULBA!\nhost ~> \"localhost\"\nport ~> 9090\n").unwrap();
+        assert_eq!(changed.recv_timeout(Duration::from_secs(5)), Ok(9090));
+        assert_eq!(
+            *watcher.get(),
+            Server {
+                host: "localhost".to_string(),
+                port: 9090
+            }
+        );
+
+        // Saved the way most editors do, by renaming a new file over it
+        let shared = watcher.shared();
+        let fresh = path.with_extension("bson.new");
+        fs::write(&fresh, "BULBA!\nhost ~> \"localhost\"\nport ~> 7070\n").unwrap();
+        fs::rename(&fresh, &path).unwrap();
+        assert_eq!(changed.recv_timeout(Duration::from_secs(5)), Ok(7070));
+        assert_eq!(shared.load().port, 7070);
+
+        drop(watcher);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn subscribers_hear_about_their_paths() {
+        let path =
+            std::env::temp_dir().join(format!("rs-bson-subscribe-{}.bson", std::process::id()));
+        fs::write(&path, "BULBA!\nhost ~> \"localhost\"\nport ~> 8080\n").unwrap();
+
+        let (ports, port_changed) = mpsc::channel();
+        let (hosts, host_changed) = mpsc::channel();
+        let watcher = Watcher::<Server>::builder(&path)
+            .debounce(Duration::from_millis(20))
+            .subscribe("/port", move |server: &Server, changed| {
+                let paths: Vec<String> = changed.iter().map(String::from).collect();
+                ports.send((server.port, paths)).unwrap()
+            })
+            .subscribe("/host", move |server: &Server, _| {
+                hosts.send(server.host.clone()).unwrap()
+            })
+            .start()
+            .unwrap();
+
+        fs::write(&path, "BULBA!\nhost ~> \"localhost\"\nport ~> 9090\n").unwrap();
+        assert_eq!(
+            port_changed.recv_timeout(Duration::from_secs(5)),
+            Ok((9090, vec![String::from("/port")]))
+        );
+        fs::write(&path, "BULBA!\nhost ~> \"db.internal\"\nport ~> 9090\n").unwrap();
+        assert_eq!(
+            host_changed.recv_timeout(Duration::from_secs(5)),
+            Ok(String::from("db.internal"))
+        );
+        // The port subscriber stayed quiet for the host change
+        drop(watcher);
+        assert!(port_changed.try_recv().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use rs_bson::lexer::{Lexer, Token};
+use rs_bson::parser::{self, BsonValue};
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+
+/// Tokenizes a document from an async reader, the counterpart of
+/// `rs_bson::lexer::lex` for files and sockets owned by a tokio runtime.
+/// Lines go through the same `Lexer` as the blocking path.
+pub async fn lex_async(reader: impl AsyncBufRead + Unpin) -> Result<Vec<Token>, &'static str> {
+    let mut lines = reader.lines();
+    let mut lexer = Lexer::new();
+    // Anything but UTF-8 confuses the parser, see the spec
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|_| "It hurt itself in its confusion!")?
+    {
+        lexer.push_line(&line)?;
+    }
+    lexer.finish()
+}
+
+/// Reads and parses the document at `path` without blocking the runtime.
+pub async fn load_async(path: impl AsRef<Path>) -> Result<BsonValue<'static>, &'static str> {
+    let file = File::open(path)
+        .await
+        .map_err(|_| "It hurt itself in its confusion!")?;
+    let tokens = lex_async(BufReader::new(file)).await?;
+    Ok(parser::parse(&tokens)?.into_owned())
+}
+
@@ -0,0 +1,20 @@
+use bulba_tokio::*;
+use rs_bson::lexer;
+
+#[cfg(test)]
+pub mod tokio_tests {
+    use crate::*;
+
+    #[tokio::test]
+    async fn match_the_blocking_lexer() {
+        let input = "BULBA!\n(o) database (o)\n    port ~> 5432\n";
+        let tokens = lex_async(input.as_bytes()).await.unwrap();
+        assert_eq!(tokens, lexer::lex_str(input).unwrap());
+    }
+
+    #[tokio::test]
+    async fn load_a_file() {
+        let value = load_async("../tests/test_data/valid.bson").await.unwrap();
+        assert!(value.get("database.host").is_some());
+    }
+}
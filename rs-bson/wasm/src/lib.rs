@@ -0,0 +1,28 @@
+use js_sys::JSON;
+use rs_bson::js;
+use rs_bson::json::JsonValue;
+use wasm_bindgen::prelude::*;
+
+// Every result is built as JSON by rs_bson::js, which always serializes to
+// valid JSON, so JSON.parse cannot fail here
+fn to_js(value: JsonValue) -> JsValue {
+    JSON::parse(&value.to_string()).unwrap()
+}
+
+/// `{ok: true, value}` or `{ok: false, error, line}`
+#[wasm_bindgen]
+pub fn parse(input: &str) -> JsValue {
+    to_js(js::parse(input))
+}
+
+/// `{ok: true, text}` or `{ok: false, error, line}`
+#[wasm_bindgen]
+pub fn format(input: &str) -> JsValue {
+    to_js(js::format(input))
+}
+
+/// `{diagnostics: [{line, severity, path, message}]}`
+#[wasm_bindgen]
+pub fn validate(input: &str, schema: &str) -> JsValue {
+    to_js(js::validate(input, schema))
+}